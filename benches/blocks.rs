@@ -0,0 +1,49 @@
+// Block mode throughput against the size of rayon's thread pool, with a 2048-bit key.
+// Run with: cargo bench --bench blocks
+
+use naive_rabin_cryptosystem::blocks::{block_capacity, decrypt_blocks, encrypt_blocks};
+use naive_rabin_cryptosystem::keys::PrivateKey;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const KEY_BITS: usize = 1024;
+const BLOCKS: usize = 256;
+
+fn time<F: FnMut()>(mut operation: F) -> Duration {
+    let started = Instant::now();
+    operation();
+    started.elapsed()
+}
+
+fn main() {
+    // Two 1024-bit primes make the 2048-bit modulus
+    let key = PrivateKey::generate(KEY_BITS);
+    let public = key.public_key();
+    let message = vec![0xa5u8; block_capacity(key.n()).unwrap() * BLOCKS];
+    let ciphertexts = encrypt_blocks(&public, &message).unwrap();
+
+    let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    let mut threads: Vec<usize> = (0..).map(|shift| 1 << shift).take_while(|count| *count < cores).collect();
+    threads.push(cores);
+
+    println!("{} KiB in {} blocks, {} cores available", message.len() / 1024, BLOCKS, cores);
+    println!("{:>8}  {:>12}  {:>12}  {:>8}", "threads", "encrypt", "decrypt", "scaling");
+    let mut baseline = None;
+    for count in threads {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(count).build().unwrap();
+        let encrypt = time(|| {
+            pool.install(|| black_box(encrypt_blocks(&public, black_box(&message)).unwrap()));
+        });
+        let decrypt = time(|| {
+            pool.install(|| black_box(decrypt_blocks(&key, black_box(&ciphertexts)).unwrap()));
+        });
+        let baseline = *baseline.get_or_insert(decrypt);
+        println!(
+            "{:>8}  {:>12.2?}  {:>12.2?}  {:>7.2}x",
+            count,
+            encrypt,
+            decrypt,
+            baseline.as_secs_f64() / decrypt.as_secs_f64()
+        );
+    }
+}
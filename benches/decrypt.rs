@@ -0,0 +1,56 @@
+// Decryption latency for a 2048-bit key, with compute_candidates confined to one thread (the
+// old sequential behaviour) and with two threads for the parallel root computations, then
+// on one thread with and without the key's cached decryption parameters.
+// Run with: cargo bench --bench decrypt
+
+use naive_rabin_cryptosystem::keys::PrivateKey;
+use naive_rabin_cryptosystem::rabin::{compute_candidates, encrypt};
+use num_bigint::{BigInt, RandBigInt};
+use rand::thread_rng;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 20;
+
+fn time<F: FnMut()>(mut operation: F) -> Duration {
+    let started = Instant::now();
+    for _ in 0..ITERATIONS {
+        operation();
+    }
+    started.elapsed() / ITERATIONS
+}
+
+fn main() {
+    // Two 1024-bit primes make the 2048-bit modulus
+    let key = PrivateKey::generate(1024);
+    let message = thread_rng().gen_bigint_range(&BigInt::from(2), key.n());
+    let ciphertext = encrypt(&message, key.n()).unwrap();
+
+    let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    println!("{} cores available", cores);
+    let mut sequential = None;
+    for threads in [1, 2] {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let latency = time(|| {
+            pool.install(|| black_box(compute_candidates(black_box(&ciphertext), key.p(), key.q(), key.n()).unwrap()));
+        });
+        let sequential = *sequential.get_or_insert(latency);
+        println!(
+            "{} thread(s): {:>10.2?} per decryption ({:.2}x)",
+            threads,
+            latency,
+            sequential.as_secs_f64() / latency.as_secs_f64()
+        );
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let loaded = PrivateKey::from_der(&key.to_der()).unwrap();
+    let uncached = time(|| {
+        pool.install(|| black_box(loaded.decrypt(black_box(&ciphertext)).unwrap()));
+    });
+    let cached = time(|| {
+        pool.install(|| black_box(key.decrypt(black_box(&ciphertext)).unwrap()));
+    });
+    println!("uncached: {:>10.2?} per decryption", uncached);
+    println!("cached:   {:>10.2?} per decryption ({:.2}x)", cached, uncached.as_secs_f64() / cached.as_secs_f64());
+}
@@ -0,0 +1,48 @@
+// Bulk encryption under one key: rabin::encrypt (a product and a division per message) against
+// a Barrett context built once for the modulus, for a range of key sizes.
+// Run with: cargo bench --bench encrypt
+
+use naive_rabin_cryptosystem::barrett::Barrett;
+use naive_rabin_cryptosystem::rabin::encrypt;
+use num_bigint::{BigInt, BigUint, RandBigInt};
+use num_traits::One;
+use rand::thread_rng;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const MESSAGES: usize = 2000;
+
+fn time<F: FnMut()>(mut operation: F) -> Duration {
+    let started = Instant::now();
+    operation();
+    started.elapsed() / MESSAGES as u32
+}
+
+fn main() {
+    let mut rng = thread_rng();
+    println!("{:>6}  {:>12}  {:>12}  {:>8}", "bits", "encrypt", "barrett", "speedup");
+    for bits in [512u64, 1024, 2048, 4096] {
+        // Any odd modulus of the right size does; encryption never looks at the factors
+        let n = BigInt::from(rng.gen_biguint(bits) | (BigUint::one() << (bits - 1)) | BigUint::one());
+        let messages: Vec<BigInt> = (0..MESSAGES).map(|_| rng.gen_bigint_range(&BigInt::one(), &n)).collect();
+        let context = Barrett::new(n.magnitude()).unwrap();
+
+        let plain = time(|| {
+            for message in &messages {
+                black_box(encrypt(black_box(message), &n).unwrap());
+            }
+        });
+        let barrett = time(|| {
+            for message in &messages {
+                black_box(context.encrypt(black_box(message)));
+            }
+        });
+        println!(
+            "{:>6}  {:>12.2?}  {:>12.2?}  {:>7.2}x",
+            bits,
+            plain,
+            barrett,
+            plain.as_secs_f64() / barrett.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,43 @@
+// Time to find a 1024-bit prime ≡ 3 (mod 4): num-prime's generator with rejection of the
+// wrong congruence (how gen_prime used to work) against the sieved search.
+// Run with: cargo bench --bench keygen
+
+use naive_rabin_cryptosystem::rabin::gen_prime;
+use num_bigint::BigUint;
+use num_prime::{PrimalityTestConfig, RandPrime};
+use rand::thread_rng;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const BITS: usize = 1024;
+const PRIMES: u32 = 20;
+
+fn time<F: FnMut()>(mut operation: F) -> Duration {
+    let started = Instant::now();
+    for _ in 0..PRIMES {
+        operation();
+    }
+    started.elapsed() / PRIMES
+}
+
+fn rejection_sampling() -> BigUint {
+    let mut rng = thread_rng();
+    loop {
+        let prime: BigUint = rng.gen_prime_exact(BITS, Some(PrimalityTestConfig::strict()));
+        if &prime % 4u8 == BigUint::from(3u8) {
+            return prime;
+        }
+    }
+}
+
+fn main() {
+    let rejection = time(|| {
+        black_box(rejection_sampling());
+    });
+    let sieved = time(|| {
+        black_box(gen_prime(BITS));
+    });
+    println!("{}-bit primes, averaged over {}", BITS, PRIMES);
+    println!("rejection: {:>10.2?} per prime", rejection);
+    println!("sieved:    {:>10.2?} per prime ({:.2}x)", sieved, rejection.as_secs_f64() / sieved.as_secs_f64());
+}
@@ -0,0 +1,44 @@
+// Key generation time against the number of workers racing for primes: mean and slowest of
+// KEYS keys with 1024-bit primes. Extra workers only help when the pool has threads to run them
+// on, so the pool size is printed with the results.
+// Run with: cargo bench --bench keygen_workers
+
+use naive_rabin_cryptosystem::keygen::KeygenConfig;
+use naive_rabin_cryptosystem::keys::PrivateKey;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const BITS: usize = 1024;
+const KEYS: u32 = 20;
+
+fn time_keys(workers: usize) -> (Duration, Duration) {
+    let config = KeygenConfig::new(BITS).with_workers(workers);
+    let mut total = Duration::ZERO;
+    let mut slowest = Duration::ZERO;
+    for _ in 0..KEYS {
+        let started = Instant::now();
+        black_box(PrivateKey::generate_with(&config).unwrap());
+        let elapsed = started.elapsed();
+        total += elapsed;
+        slowest = slowest.max(elapsed);
+    }
+    (total / KEYS, slowest)
+}
+
+fn main() {
+    let threads = rayon::current_num_threads();
+    println!("{}-bit primes, {} keys per row, {} pool threads", BITS, KEYS, threads);
+    let mut baseline = None;
+    for workers in [2, 4, 8, 16] {
+        let (mean, slowest) = time_keys(workers);
+        // Two workers, one per prime, is how key generation worked before the race
+        let baseline = *baseline.get_or_insert(mean);
+        println!(
+            "{:>2} workers: {:>10.2?} mean, {:>10.2?} slowest ({:.2}x)",
+            workers,
+            mean,
+            slowest,
+            baseline.as_secs_f64() / mean.as_secs_f64()
+        );
+    }
+}
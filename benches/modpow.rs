@@ -0,0 +1,109 @@
+// Compares num-bigint's modpow with the crate's Montgomery exponentiation at RSA-like sizes.
+// Run with: cargo bench --bench modpow (add --features fast-math for a GMP comparison)
+
+use naive_rabin_cryptosystem::montgomery::{modpow, Montgomery};
+use num_bigint::{BigInt, BigUint, RandBigInt};
+use num_traits::{One, Zero};
+use rand::thread_rng;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ROUNDS: usize = 5;
+
+// Best of several rounds, which filters out most scheduler noise
+fn time<F: FnMut()>(iterations: u32, mut operation: F) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let started = Instant::now();
+            for _ in 0..iterations {
+                operation();
+            }
+            started.elapsed() / iterations
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let mut rng = thread_rng();
+    println!("{:>6}  {:>14}  {:>14}  {:>7}", "bits", "num-bigint", "montgomery", "speedup");
+    for bits in [1024u64, 2048, 3072, 4096] {
+        let modulus = rng.gen_biguint(bits) | BigUint::one() | (BigUint::one() << (bits - 1));
+        let base = rng.gen_biguint(bits) % &modulus;
+        let exponent = rng.gen_biguint(bits);
+        let context = Montgomery::new(&modulus).unwrap();
+        assert_eq!(context.pow(&base, &exponent), base.modpow(&exponent, &modulus));
+
+        let iterations = (1 << 23) / (bits * bits) as u32 + 1;
+        let generic = time(iterations, || {
+            black_box(black_box(&base).modpow(black_box(&exponent), black_box(&modulus)));
+        });
+        let montgomery = time(iterations, || {
+            black_box(context.pow(black_box(&base), black_box(&exponent)));
+        });
+        println!(
+            "{:>6}  {:>14.2?}  {:>14.2?}  {:>6.2}x",
+            bits,
+            generic,
+            montgomery,
+            generic.as_secs_f64() / montgomery.as_secs_f64()
+        );
+    }
+
+    // Short exponents against a 2048-bit modulus through the modpow wrapper, which sets up a
+    // Montgomery context on every call; this is where the setup could outweigh the windows
+    let modulus = BigInt::from(rng.gen_biguint(2048) | BigUint::one() | (BigUint::one() << 2047u32));
+    let base = rng.gen_bigint_range(&BigInt::zero(), &modulus);
+    println!();
+    println!("{:>6}  {:>14}  {:>14}  {:>7}", "exp", "num-bigint", "modpow", "speedup");
+    for exponent_bits in [2u64, 8, 16, 32, 64, 128] {
+        let exponent = BigInt::from(rng.gen_biguint(exponent_bits) | (BigUint::one() << (exponent_bits - 1)));
+        let generic = time(2000, || {
+            black_box(black_box(&base).modpow(black_box(&exponent), black_box(&modulus)));
+        });
+        let montgomery = time(2000, || {
+            black_box(modpow(black_box(&base), black_box(&exponent), black_box(&modulus)));
+        });
+        println!(
+            "{:>6}  {:>14.2?}  {:>14.2?}  {:>6.2}x",
+            exponent_bits,
+            generic,
+            montgomery,
+            generic.as_secs_f64() / montgomery.as_secs_f64()
+        );
+    }
+
+    #[cfg(feature = "fast-math")]
+    gmp_table(&mut rng);
+}
+
+// With --features fast-math: the portable path against GMP, both through the BigInt wrappers
+#[cfg(feature = "fast-math")]
+fn gmp_table(rng: &mut impl rand::Rng) {
+    use naive_rabin_cryptosystem::gmp;
+    use naive_rabin_cryptosystem::montgomery::modpow_portable;
+
+    println!();
+    println!("{:>6}  {:>14}  {:>14}  {:>7}", "bits", "portable", "gmp", "speedup");
+    for bits in [1024u64, 2048, 3072, 4096] {
+        let modulus = BigInt::from(rng.gen_biguint(bits) | BigUint::one() | (BigUint::one() << (bits - 1)));
+        let base = rng.gen_bigint_range(&BigInt::zero(), &modulus);
+        let exponent = BigInt::from(rng.gen_biguint(bits));
+        assert_eq!(gmp::modpow(&base, &exponent, &modulus), modpow_portable(&base, &exponent, &modulus));
+
+        let iterations = (1 << 23) / (bits * bits) as u32 + 1;
+        let portable = time(iterations, || {
+            black_box(modpow_portable(black_box(&base), black_box(&exponent), black_box(&modulus)));
+        });
+        let fast = time(iterations, || {
+            black_box(gmp::modpow(black_box(&base), black_box(&exponent), black_box(&modulus)));
+        });
+        println!(
+            "{:>6}  {:>14.2?}  {:>14.2?}  {:>6.2}x",
+            bits,
+            portable,
+            fast,
+            portable.as_secs_f64() / fast.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,44 @@
+// str2num on long texts: the divide-and-conquer conversion against Horner's rule over the whole
+// text, which the crate used before. Run with: cargo bench --bench str2num
+
+use naive_rabin_cryptosystem::encoding::{str2num, Alphabet, DEFAULT_SYMBOLS};
+use num_bigint::BigInt;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+fn time<F: FnMut()>(mut operation: F) -> Duration {
+    let started = Instant::now();
+    operation();
+    started.elapsed()
+}
+
+fn horner(text: &str, alphabet: &Alphabet) -> BigInt {
+    let base = BigInt::from(alphabet.len());
+    let mut num = BigInt::from(0);
+    for symbol in text.chars() {
+        num = num * &base + alphabet.value(symbol.encode_utf8(&mut [0; 4])).unwrap();
+    }
+    num
+}
+
+fn main() {
+    let alphabet = Alphabet::default_symbols();
+    println!("{:>10}  {:>12}  {:>12}  {:>8}", "symbols", "horner", "str2num", "speedup");
+    for kib in [1, 4, 16, 64] {
+        let text: String = DEFAULT_SYMBOLS.chars().cycle().take(kib * 1024).collect();
+        assert_eq!(horner(&text, alphabet), str2num(&text, alphabet).unwrap());
+        let naive = time(|| {
+            black_box(horner(black_box(&text), alphabet));
+        });
+        let fast = time(|| {
+            black_box(str2num(black_box(&text), alphabet).unwrap());
+        });
+        println!(
+            "{:>10}  {:>12.2?}  {:>12.2?}  {:>7.2}x",
+            text.len(),
+            naive,
+            fast,
+            naive.as_secs_f64() / fast.as_secs_f64()
+        );
+    }
+}
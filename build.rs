@@ -0,0 +1,31 @@
+// Generates the C header for src/ffi.rs into OUT_DIR when built with the ffi feature (a test in
+// ffi.rs keeps the committed include/rabin.h in step with it), and compiles proto/rabin.proto
+// into the gRPC messages and stubs for src/grpc.rs when built with the grpc feature
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=src/ffi.rs");
+        println!("cargo:rerun-if-changed=cbindgen.toml");
+        // Only ffi.rs: the rest of the crate has constants and GMP declarations C must not see
+        let config = cbindgen::Config::from_file("cbindgen.toml").expect("cbindgen.toml should parse");
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src("src/ffi.rs")
+            .generate()
+            .expect("cbindgen could not generate the C header")
+            .write_to_file(std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("rabin.h"));
+    }
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/rabin.proto");
+        // The build needs no protoc installed: PROTOC, if set, overrides the vendored one
+        if std::env::var_os("PROTOC").is_none() {
+            let protoc = protoc_bin_vendored::protoc_bin_path().expect("no vendored protoc for this platform");
+            std::env::set_var("PROTOC", protoc);
+        }
+        tonic_build::configure()
+            .compile_protos(&["proto/rabin.proto"], &["proto"])
+            .expect("proto/rabin.proto should compile");
+    }
+}
@@ -0,0 +1,244 @@
+#!/usr/bin/env python3
+# Reference vectors for the compat suite (src/compat.rs and `rabin selftest`), computed without
+# any of this crate's code: primality, square roots modulo a prime and the CRT come from sympy,
+# and the alphabet codec is written out below from its definition. Regenerate with
+#
+#     python3 compat/reference.py > compat/vectors.json
+#
+# The output is deterministic, so a diff of vectors.json shows exactly what changed. Numbers are
+# decimal strings, since most JSON readers cannot hold them otherwise.
+
+import json
+import random
+import sys
+
+import sympy
+from sympy.ntheory import sqrt_mod
+from sympy.ntheory.modular import crt
+
+DEFAULT_SYMBOLS = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz(.,;:!?)[<+-*/=>]@| "
+
+# --- keygen edge cases -----------------------------------------------------------------------
+
+# Validation as PrivateKey::validate defines it: primality of both factors, p ≡ q ≡ 3 (mod 4),
+# distinct factors, and optionally the bit length of each
+def violations(p, q, bits=None):
+    found = []
+    if not sympy.isprime(p):
+        found.append("p_not_prime")
+    if not sympy.isprime(q):
+        found.append("q_not_prime")
+    if p % 4 != 3:
+        found.append("p_not_three_mod_four")
+    if q % 4 != 3:
+        found.append("q_not_three_mod_four")
+    if p == q:
+        found.append("equal_primes")
+    if bits is not None and (p.bit_length() != bits or q.bit_length() != bits):
+        found.append("bit_length_mismatch")
+    return found
+
+
+def keygen_case(p, q, bits=None, note=""):
+    case = {"note": note, "p": str(p), "q": str(q)}
+    if bits is not None:
+        case["bits"] = bits
+    if p <= 1 or q <= 1:
+        case["error"] = "invalid_key"
+        return case
+    case["n"] = str(p * q)
+    case["violations"] = violations(p, q, bits)
+    return case
+
+
+def keygen_cases(rng):
+    mersenne_61 = 2**61 - 1
+    mersenne_127 = 2**127 - 1
+    cases = [
+        keygen_case(7, 11, note="smallest textbook key"),
+        keygen_case(3, 7, note="smallest primes that are 3 mod 4"),
+        keygen_case(mersenne_61, mersenne_127, note="Mersenne primes, both 3 mod 4"),
+        keygen_case(13, 11, note="p is 1 mod 4"),
+        keygen_case(11, 17, note="q is 1 mod 4"),
+        keygen_case(2, 7, note="p is the even prime"),
+        keygen_case(7, 7, note="equal primes"),
+        keygen_case(15, 13, note="composite p, q is 1 mod 4"),
+        keygen_case(2047, 11, note="2047 = 23 * 89, a strong pseudoprime to base 2"),
+        keygen_case(3215031751, 7, note="a strong pseudoprime to bases 2, 3, 5 and 7"),
+        keygen_case(561, 19, note="Carmichael number"),
+        keygen_case(4, 9, note="both composite, neither 3 mod 4"),
+        keygen_case(1, 7, note="p must exceed 1"),
+        keygen_case(7, 0, note="q must exceed 1"),
+        keygen_case(-7, 11, note="negative p"),
+    ]
+    for bits in (16, 64, 256):
+        p = next_three_mod_four(rng, bits)
+        q = next_three_mod_four(rng, bits)
+        cases.append(keygen_case(p, q, bits, note="random %d-bit primes" % bits))
+    p = next_three_mod_four(rng, 64)
+    q = next_three_mod_four(rng, 65)
+    cases.append(keygen_case(p, q, 64, note="q one bit too long"))
+    return cases
+
+
+def next_three_mod_four(rng, bits):
+    while True:
+        candidate = sympy.nextprime(rng.getrandbits(bits) | (1 << (bits - 1)))
+        if candidate % 4 == 3 and candidate.bit_length() == bits:
+            return candidate
+
+
+# --- CRT recombination ------------------------------------------------------------------------
+
+# Every square root of c modulo p * q, from the roots modulo each prime joined by the CRT
+def square_roots(c, p, q):
+    n = p * q
+    roots_p = sqrt_mod(c % p, p, all_roots=True)
+    roots_q = sqrt_mod(c % q, q, all_roots=True)
+    if not roots_p or not roots_q:
+        return None
+    roots = {int(crt([p, q], [rp, rq])[0]) % n for rp in roots_p for rq in roots_q}
+    return sorted(roots)
+
+
+def crt_case(p, q, c, note=""):
+    case = {"note": note, "p": str(p), "q": str(q), "c": str(c)}
+    if p == q:
+        case["error"] = "not_invertible"
+    elif c < 0 or c >= p * q:
+        case["error"] = "message_out_of_range"
+    else:
+        roots = square_roots(c, p, q)
+        if roots is None:
+            case["error"] = "not_quadratic_residue"
+        else:
+            case["roots"] = [str(root) for root in roots]
+    return case
+
+
+def crt_cases(rng):
+    cases = [
+        crt_case(7, 11, 23, note="23 = 10^2 mod 77"),
+        crt_case(7, 11, 4, note="small square with a trivial root"),
+        crt_case(7, 11, 0, note="zero, whose only root is zero"),
+        crt_case(7, 11, 1, note="the four roots of unity"),
+        crt_case(7, 11, 3, note="not a square modulo 7"),
+        crt_case(7, 11, 77, note="ciphertext equal to n"),
+        crt_case(7, 11, 49, note="shares the factor 7 with n"),
+        crt_case(13, 17, 16, note="both primes 1 mod 4 (Tonelli-Shanks)"),
+        crt_case(13, 11, 9, note="p is 1 mod 4"),
+        crt_case(7, 7, 4, note="equal primes have no CRT"),
+    ]
+    p = 2**61 - 1
+    q = 2**127 - 1
+    for _ in range(3):
+        m = rng.randrange(2, p * q)
+        cases.append(crt_case(p, q, m * m % (p * q), note="Mersenne key, random square"))
+    for bits in (128, 512):
+        p = next_three_mod_four(rng, bits)
+        q = next_three_mod_four(rng, bits)
+        m = rng.randrange(2, p * q)
+        cases.append(crt_case(p, q, m * m % (p * q), note="random %d-bit primes" % bits))
+        non_residue = next(c for c in iter(lambda: rng.randrange(2, p * q), None) if sympy.legendre_symbol(c % p, p) == -1)
+        cases.append(crt_case(p, q, non_residue, note="a non-residue modulo p"))
+    return cases
+
+
+# --- alphabet codec ---------------------------------------------------------------------------
+
+# Positional base-len(symbols), most significant symbol first; leading zero symbols are allowed
+def decode(text, symbols):
+    value = 0
+    for index, symbol in enumerate(text):
+        if symbol not in symbols:
+            return None, index
+        value = value * len(symbols) + symbols.index(symbol)
+    return value, None
+
+
+def encode(value, symbols):
+    if value == 0:
+        return symbols[0]
+    digits = []
+    while value:
+        value, digit = divmod(value, len(symbols))
+        digits.append(symbols[digit])
+    return "".join(reversed(digits))
+
+
+# Bijective numeration: symbol i stands for the digit i + 1, so there is no zero digit and
+# every number has exactly one spelling (the empty text for 0)
+def decode_bijective(text, symbols):
+    value = 0
+    for index, symbol in enumerate(text):
+        if symbol not in symbols:
+            return None, index
+        value = value * len(symbols) + symbols.index(symbol) + 1
+    return value, None
+
+
+def encode_bijective(value, symbols):
+    digits = []
+    while value:
+        value, digit = divmod(value - 1, len(symbols))
+        digits.append(symbols[digit])
+    return "".join(reversed(digits))
+
+
+def alphabet_case(text, symbols=None, bijective=False, note=""):
+    case = {"note": note, "text": text, "bijective": bijective}
+    if symbols is not None:
+        case["symbols"] = symbols
+    symbols = symbols or DEFAULT_SYMBOLS
+    value, bad_index = (decode_bijective if bijective else decode)(text, symbols)
+    if value is None:
+        case["error"] = "invalid_symbol"
+        case["index"] = bad_index
+        return case
+    case["number"] = str(value)
+    # The spelling encoding writes back, which drops leading zero symbols
+    case["encoded"] = (encode_bijective if bijective else encode)(value, symbols)
+    return case
+
+
+def alphabet_cases(rng):
+    greek = "αβγδεζηθ"
+    cases = [
+        alphabet_case("Hello, World!", note="mixed case and punctuation"),
+        alphabet_case("0", note="zero"),
+        alphabet_case("000A", note="leading zero symbols"),
+        alphabet_case(" ", note="the last symbol, space"),
+        alphabet_case("z" * 40, note="long run of one symbol"),
+        alphabet_case("#", note="not in the alphabet"),
+        alphabet_case("abc~def", note="invalid symbol mid-text"),
+        alphabet_case("", bijective=True, note="empty text is zero, bijectively"),
+        alphabet_case("0", bijective=True, note="the first symbol is one, bijectively"),
+        alphabet_case("00", bijective=True, note="leading first symbols count, bijectively"),
+        alphabet_case("Rabin 1979", bijective=True, note="bijective text"),
+        alphabet_case("01", symbols="01", note="binary"),
+        alphabet_case("1101", symbols="01", bijective=True, note="bijective binary"),
+        alphabet_case("βαδ", symbols=greek, note="non-ASCII symbols"),
+        alphabet_case("θθθ", symbols=greek, bijective=True, note="non-ASCII symbols, bijectively"),
+        alphabet_case("βxγ", symbols=greek, note="ASCII outside a non-ASCII alphabet"),
+    ]
+    for length in (10, 100, 300):
+        text = "".join(rng.choice(DEFAULT_SYMBOLS) for _ in range(length))
+        cases.append(alphabet_case(text, note="random text of %d symbols" % length))
+        cases.append(alphabet_case(text, bijective=True, note="random text of %d symbols, bijectively" % length))
+    return cases
+
+
+def main():
+    rng = random.Random(1979)
+    vectors = {
+        "generator": "compat/reference.py with sympy %s" % sympy.__version__,
+        "keygen": keygen_cases(rng),
+        "crt": crt_cases(rng),
+        "alphabet": alphabet_cases(rng),
+    }
+    json.dump(vectors, sys.stdout, indent=1, ensure_ascii=False)
+    sys.stdout.write("\n")
+
+
+if __name__ == "__main__":
+    main()
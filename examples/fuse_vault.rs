@@ -0,0 +1,591 @@
+// An encrypted directory through FUSE: every file in BACKING_DIR is stored encrypted to one
+// Rabin key, and MOUNTPOINT shows the same files in plaintext, encrypted and decrypted on the fly
+// as they are written and read.
+//
+// Run with (Linux, or macOS with macFUSE; fusermount must be on PATH):
+//     cargo run --features fuse-vault --example fuse_vault -- BACKING_DIR MOUNTPOINT --key KEY.pem
+//
+// KEY.pem is a PKCS#8 private key, such as ~/.rabin/keys/NAME.priv after `rabin keys create
+// NAME`; keys below 2048 bits need --insecure-demo. Unmount with `fusermount -u MOUNTPOINT` (umount on macOS) or Ctrl-C.
+//
+// Backing files are ordinary `rabin encrypt` output, so `rabin decrypt --key KEY.pem --in FILE`
+// recovers any of them without the mount: files below STREAM_THRESHOLD are sealed as one
+// envelope (DER), larger ones with the streaming format, and reads tell the two apart by the
+// stream magic as the CLI does. Names and sizes are not hidden.
+//
+// An open file is decrypted whole into memory, and re-encrypted when it is flushed or released
+// after a write, into a temporary file that is then renamed over the old one, so a crash leaves
+// either the old or the new contents. The vault is flat: subdirectories, links and special files
+// are not supported, and files in BACKING_DIR that do not decrypt fail to open with EIO.
+
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty,
+    ReplyEntry, ReplyOpen, ReplyWrite, Request, TimeOrNow, FUSE_ROOT_ID,
+};
+use libc::{EEXIST, EINVAL, EIO, ENOENT};
+use naive_rabin_cryptosystem::envelope::Envelope;
+use naive_rabin_cryptosystem::error::RabinError;
+use naive_rabin_cryptosystem::keys::{InsecureDemo, PrivateKey, PublicKey};
+use naive_rabin_cryptosystem::stream::{decrypt_stream, encrypt_stream, StreamConfig, STREAM_MAGIC};
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, Metadata, Permissions};
+use std::io::{self, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime};
+
+const USAGE: &str = "usage: fuse_vault BACKING_DIR MOUNTPOINT --key KEY.pem [--insecure-demo]";
+
+// Plaintexts of at least this many bytes are written as streams rather than envelopes
+const STREAM_THRESHOLD: usize = 64 * 1024;
+// How long the kernel may cache attributes and lookups
+const TTL: Duration = Duration::from_secs(1);
+// Suffix of the temporary files a save writes before renaming; readdir and lookup skip them
+const TEMP_SUFFIX: &str = ".vault-tmp";
+const BLOCK_SIZE: u32 = 4096;
+
+// The decrypted contents of an open file, shared by all of its handles
+struct OpenFile {
+    data: Vec<u8>,
+    dirty: bool,
+    handles: usize,
+}
+
+// Counts the bytes written to it, to size a stream without keeping its plaintext
+struct Counter(u64);
+
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Vault {
+    backing: PathBuf,
+    key: PrivateKey,
+    recipient: PublicKey,
+    config: StreamConfig,
+    // Inode numbers are handed out on first sight of a name and kept until it is unlinked
+    names: HashMap<u64, OsString>,
+    inodes: HashMap<OsString, u64>,
+    next_inode: u64,
+    open: HashMap<u64, OpenFile>,
+    // Plaintext sizes of closed files, valid while the backing file keeps its mtime
+    sizes: HashMap<u64, (SystemTime, u64)>,
+}
+
+fn io_errno(err: io::Error) -> i32 {
+    err.raw_os_error().unwrap_or(EIO)
+}
+
+impl Vault {
+    fn new(backing: PathBuf, key: PrivateKey) -> Self {
+        let recipient = key.public_key();
+        Vault {
+            backing,
+            key: key.precompute(),
+            recipient,
+            config: StreamConfig::default(),
+            names: HashMap::new(),
+            inodes: HashMap::new(),
+            next_inode: FUSE_ROOT_ID + 1,
+            open: HashMap::new(),
+            sizes: HashMap::new(),
+        }
+    }
+
+    fn path(&self, name: &OsStr) -> PathBuf {
+        self.backing.join(name)
+    }
+
+    fn backing_path(&self, ino: u64) -> Result<PathBuf, i32> {
+        self.names.get(&ino).map(|name| self.path(name)).ok_or(ENOENT)
+    }
+
+    fn is_temporary(name: &OsStr) -> bool {
+        name.to_string_lossy().ends_with(TEMP_SUFFIX)
+    }
+
+    fn inode(&mut self, name: &OsStr) -> u64 {
+        if let Some(&ino) = self.inodes.get(name) {
+            return ino;
+        }
+        let ino = self.next_inode;
+        self.next_inode += 1;
+        self.names.insert(ino, name.to_owned());
+        self.inodes.insert(name.to_owned(), ino);
+        ino
+    }
+
+    fn forget(&mut self, name: &OsStr) -> Option<u64> {
+        let ino = self.inodes.remove(name)?;
+        self.names.remove(&ino);
+        self.sizes.remove(&ino);
+        Some(ino)
+    }
+
+    // Encryption and decryption failures are logged here, since the caller only sees EIO
+    fn crypto_errno(&self, ino: u64, err: RabinError) -> i32 {
+        let name = self.names.get(&ino).map(|name| name.to_string_lossy()).unwrap_or_default();
+        eprintln!("{}: {}", name, err);
+        EIO
+    }
+
+    fn decrypt_into<W: Write>(&self, ino: u64, output: &mut W) -> Result<(), i32> {
+        let bytes = fs::read(self.backing_path(ino)?).map_err(io_errno)?;
+        let result = if bytes.starts_with(STREAM_MAGIC) {
+            decrypt_stream(&self.key, &mut bytes.as_slice(), output, &self.config)
+        } else {
+            Envelope::from_bytes(&bytes)
+                .and_then(|envelope| envelope.open(&self.key))
+                .and_then(|plaintext| Ok(output.write_all(&plaintext)?))
+        };
+        result.map_err(|err| self.crypto_errno(ino, err))
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, RabinError> {
+        if plaintext.len() < STREAM_THRESHOLD {
+            return Ok(Envelope::seal(&self.recipient, plaintext)?.to_der());
+        }
+        let mut output = Vec::with_capacity(plaintext.len() + plaintext.len() / 16 + 1024);
+        encrypt_stream(&self.recipient, &mut &plaintext[..], &mut output, &self.config)?;
+        Ok(output)
+    }
+
+    // Writes plaintext as the new contents of ino's backing file, through a temporary file
+    fn save(&mut self, ino: u64, plaintext: &[u8]) -> Result<(), i32> {
+        let path = self.backing_path(ino)?;
+        let ciphertext = self.encrypt(plaintext).map_err(|err| self.crypto_errno(ino, err))?;
+        let mut temporary = path.clone().into_os_string();
+        temporary.push(TEMP_SUFFIX);
+        fs::write(&temporary, ciphertext).map_err(io_errno)?;
+        if let Ok(metadata) = fs::metadata(&path) {
+            fs::set_permissions(&temporary, metadata.permissions()).map_err(io_errno)?;
+        }
+        fs::rename(&temporary, &path).map_err(io_errno)?;
+        let mtime = fs::metadata(&path).and_then(|metadata| metadata.modified()).map_err(io_errno)?;
+        self.sizes.insert(ino, (mtime, plaintext.len() as u64));
+        Ok(())
+    }
+
+    // Saves ino's open contents if they changed; files unlinked while open are not brought back
+    fn save_if_dirty(&mut self, ino: u64) -> Result<(), i32> {
+        if !self.names.contains_key(&ino) {
+            return Ok(());
+        }
+        let data = match self.open.get_mut(&ino) {
+            Some(file) if file.dirty => std::mem::take(&mut file.data),
+            _ => return Ok(()),
+        };
+        let result = self.save(ino, &data);
+        let file = self.open.get_mut(&ino).expect("the file is open");
+        file.data = data;
+        file.dirty = result.is_err();
+        result
+    }
+
+    fn load(&mut self, ino: u64) -> Result<&mut OpenFile, i32> {
+        if !self.open.contains_key(&ino) {
+            let mut data = Vec::new();
+            self.decrypt_into(ino, &mut data)?;
+            self.open.insert(ino, OpenFile { data, dirty: false, handles: 0 });
+        }
+        Ok(self.open.get_mut(&ino).expect("the file was just loaded"))
+    }
+
+    fn plaintext_size(&mut self, ino: u64, metadata: &Metadata) -> Result<u64, i32> {
+        if let Some(file) = self.open.get(&ino) {
+            return Ok(file.data.len() as u64);
+        }
+        let mtime = metadata.modified().map_err(io_errno)?;
+        if let Some(&(cached, size)) = self.sizes.get(&ino) {
+            if cached == mtime {
+                return Ok(size);
+            }
+        }
+        let mut counter = Counter(0);
+        self.decrypt_into(ino, &mut counter)?;
+        self.sizes.insert(ino, (mtime, counter.0));
+        Ok(counter.0)
+    }
+
+    fn attr(&mut self, ino: u64) -> Result<FileAttr, i32> {
+        let (kind, metadata, size) = if ino == FUSE_ROOT_ID {
+            let metadata = fs::metadata(&self.backing).map_err(io_errno)?;
+            (FileType::Directory, metadata, 0)
+        } else {
+            let metadata = fs::metadata(self.backing_path(ino)?).map_err(io_errno)?;
+            let size = self.plaintext_size(ino, &metadata)?;
+            (FileType::RegularFile, metadata, size)
+        };
+        let time = |seconds: i64, nanos: i64| {
+            SystemTime::UNIX_EPOCH + Duration::new(seconds.max(0) as u64, nanos.clamp(0, 999_999_999) as u32)
+        };
+        Ok(FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: time(metadata.atime(), metadata.atime_nsec()),
+            mtime: time(metadata.mtime(), metadata.mtime_nsec()),
+            ctime: time(metadata.ctime(), metadata.ctime_nsec()),
+            crtime: time(metadata.ctime(), metadata.ctime_nsec()),
+            kind,
+            perm: (metadata.mode() & 0o7777) as u16,
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        })
+    }
+
+    // Only plain files directly in the backing directory are part of the vault
+    fn lookup_name(&mut self, parent: u64, name: &OsStr) -> Result<u64, i32> {
+        if parent != FUSE_ROOT_ID || Self::is_temporary(name) {
+            return Err(ENOENT);
+        }
+        match fs::symlink_metadata(self.path(name)) {
+            Ok(metadata) if metadata.is_file() => Ok(self.inode(name)),
+            Ok(_) => Err(ENOENT),
+            Err(err) => {
+                self.forget(name);
+                Err(io_errno(err))
+            }
+        }
+    }
+
+    fn truncate(&mut self, ino: u64, size: u64) -> Result<(), i32> {
+        let size = usize::try_from(size).map_err(|_| EINVAL)?;
+        let file = self.load(ino)?;
+        file.data.resize(size, 0);
+        file.dirty = true;
+        // Without an open handle there is no later flush, so the change is saved now
+        if file.handles == 0 {
+            self.save_if_dirty(ino)?;
+            self.open.remove(&ino);
+        }
+        Ok(())
+    }
+
+    fn release_handle(&mut self, ino: u64) -> Result<(), i32> {
+        let result = self.save_if_dirty(ino);
+        if let Some(file) = self.open.get_mut(&ino) {
+            file.handles = file.handles.saturating_sub(1);
+            if file.handles == 0 {
+                self.open.remove(&ino);
+            }
+        }
+        result
+    }
+}
+
+impl Filesystem for Vault {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.lookup_name(parent, name).and_then(|ino| self.attr(ino)) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        _mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        _fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        let result = (|| {
+            let path = if ino == FUSE_ROOT_ID { self.backing.clone() } else { self.backing_path(ino)? };
+            if let Some(mode) = mode {
+                fs::set_permissions(path, Permissions::from_mode(mode & 0o7777)).map_err(io_errno)?;
+            }
+            if let Some(size) = size {
+                if ino == FUSE_ROOT_ID {
+                    return Err(EINVAL);
+                }
+                self.truncate(ino, size)?;
+            }
+            self.attr(ino)
+        })();
+        match result {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let result = self.lookup_name(parent, name).and_then(|_| fs::remove_file(self.path(name)).map_err(io_errno));
+        match result {
+            Ok(()) => {
+                self.forget(name);
+                reply.ok()
+            }
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        let result = (|| {
+            let ino = self.lookup_name(parent, name)?;
+            if newparent != FUSE_ROOT_ID || Self::is_temporary(newname) {
+                return Err(EINVAL);
+            }
+            let target = self.path(newname);
+            if flags & libc::RENAME_NOREPLACE != 0 && fs::symlink_metadata(&target).is_ok() {
+                return Err(EEXIST);
+            }
+            if flags & !libc::RENAME_NOREPLACE != 0 {
+                return Err(EINVAL);
+            }
+            fs::rename(self.path(name), target).map_err(io_errno)?;
+            // The inode keeps its number, so open handles follow the file to its new name
+            self.forget(newname);
+            self.inodes.remove(name);
+            self.names.insert(ino, newname.to_owned());
+            self.inodes.insert(newname.to_owned(), ino);
+            Ok(())
+        })();
+        match result {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        match self.load(ino) {
+            Ok(file) => {
+                if flags & libc::O_TRUNC != 0 && !file.data.is_empty() {
+                    file.data.clear();
+                    file.dirty = true;
+                }
+                file.handles += 1;
+                // One handle per inode is enough, since handles share the decrypted contents
+                reply.opened(ino, 0)
+            }
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        match self.open.get(&ino) {
+            Some(file) => {
+                let start = (offset.max(0) as usize).min(file.data.len());
+                let end = start.saturating_add(size as usize).min(file.data.len());
+                reply.data(&file.data[start..end])
+            }
+            None => reply.error(EIO),
+        }
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        let Some(file) = self.open.get_mut(&ino) else {
+            return reply.error(EIO);
+        };
+        let Some(end) = usize::try_from(offset).ok().and_then(|offset| offset.checked_add(data.len())) else {
+            return reply.error(EINVAL);
+        };
+        if file.data.len() < end {
+            file.data.resize(end, 0);
+        }
+        file.data[end - data.len()..end].copy_from_slice(data);
+        file.dirty = true;
+        reply.written(data.len() as u32)
+    }
+
+    fn flush(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
+        match self.save_if_dirty(ino) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn fsync(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, _datasync: bool, reply: ReplyEmpty) {
+        match self.save_if_dirty(ino) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        match self.release_handle(ino) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+
+    fn readdir(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory) {
+        if ino != FUSE_ROOT_ID {
+            return reply.error(ENOENT);
+        }
+        let entries = match fs::read_dir(&self.backing) {
+            Ok(entries) => entries,
+            Err(err) => return reply.error(io_errno(err)),
+        };
+        // Sorted, so that an offset means the same entry across calls
+        let mut names: Vec<OsString> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+            .map(|entry| entry.file_name())
+            .filter(|name| !Self::is_temporary(name))
+            .collect();
+        names.sort();
+        let mut listing = vec![
+            (FUSE_ROOT_ID, FileType::Directory, OsString::from(".")),
+            (FUSE_ROOT_ID, FileType::Directory, OsString::from("..")),
+        ];
+        for name in names {
+            listing.push((self.inode(&name), FileType::RegularFile, name));
+        }
+        for (index, (ino, kind, name)) in listing.into_iter().enumerate().skip(offset.max(0) as usize) {
+            if reply.add(ino, index as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok()
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        _flags: i32,
+        reply: ReplyCreate,
+    ) {
+        let result = (|| {
+            if parent != FUSE_ROOT_ID || Self::is_temporary(name) {
+                return Err(EINVAL);
+            }
+            let path = self.path(name);
+            if fs::symlink_metadata(&path).is_ok() {
+                return Err(EEXIST);
+            }
+            // An empty file is still an envelope, so that every backing file decrypts
+            let ino = self.inode(name);
+            let ciphertext = self.encrypt(&[]).map_err(|err| self.crypto_errno(ino, err))?;
+            fs::write(&path, ciphertext).map_err(io_errno)?;
+            fs::set_permissions(&path, Permissions::from_mode(mode & !umask & 0o7777)).map_err(io_errno)?;
+            self.open.insert(ino, OpenFile { data: Vec::new(), dirty: false, handles: 1 });
+            self.attr(ino)
+        })();
+        match result {
+            Ok(attr) => reply.created(&TTL, &attr, 0, attr.ino, 0),
+            Err(errno) => reply.error(errno),
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let mut args = args.into_iter();
+    let (backing, mountpoint) = match (args.next(), args.next()) {
+        (Some(backing), Some(mountpoint)) => (PathBuf::from(backing), PathBuf::from(mountpoint)),
+        _ => return Err(USAGE.into()),
+    };
+    let mut key_path = None;
+    let mut insecure = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--key" => key_path = Some(args.next().ok_or("--key needs a path")?),
+            "--insecure-demo" => insecure = Some(InsecureDemo),
+            _ => return Err(format!("unexpected argument '{}'\n\n{}", arg, USAGE).into()),
+        }
+    }
+    let key = PrivateKey::from_pkcs8_pem(&fs::read_to_string(key_path.ok_or(USAGE)?)?)?;
+    let key = match insecure {
+        Some(opt_in) => key.allow_insecure(opt_in),
+        None => key,
+    };
+    // Checks the key's size (and the opt-in) before mounting rather than on the first write
+    Envelope::seal(&key.public_key(), b"")?;
+    if !fs::metadata(&backing)?.is_dir() {
+        return Err(format!("{} is not a directory", backing.display()).into());
+    }
+
+    println!("key {}", key.public_key().fingerprint());
+    println!("mounting {} on {}", backing.display(), mountpoint.display());
+    let options = [
+        MountOption::FSName("rabin-vault".to_string()),
+        MountOption::Subtype("rabin".to_string()),
+        MountOption::DefaultPermissions,
+    ];
+    fuser::mount2(Vault::new(backing, key), &mountpoint, &options)?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
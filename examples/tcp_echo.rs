@@ -0,0 +1,256 @@
+// Key establishment with the Rabin KEM over a plain TCP socket, then an AEAD-protected echo
+// session, plus a file upload through the streaming API.
+//
+// Run with:
+//     cargo run --example tcp_echo -- server 127.0.0.1:9002 [--bits N] [--insecure-demo]
+//     cargo run --example tcp_echo -- client 127.0.0.1:9002 [--insecure-demo]
+//     cargo run --example tcp_echo -- client 127.0.0.1:9002 --file PATH [--insecure-demo]
+//
+// The server sends its public key and the client picks a mode with one byte. In echo mode the
+// client encapsulates a session key to the server's key and sends the encapsulated key; both
+// sides then seal every line with AES-256-GCM under that key, with the direction and a counter
+// in the nonce and the encapsulated key as associated data, and the server seals each line
+// back. In file mode the client writes the file with encrypt_stream straight onto the socket
+// and the server answers with the length and SHA-256 of what decrypt_stream recovered.
+//
+// Everything on the wire is a 4-byte big-endian length followed by that many bytes, except the
+// mode byte and the stream, which frames itself. The client prints the server's fingerprint but
+// has no way to check it; a real protocol would pin it.
+
+use naive_rabin_cryptosystem::aead::{aes256_gcm_decrypt, aes256_gcm_encrypt, KEY_LEN, NONCE_LEN};
+use naive_rabin_cryptosystem::hash::Sha256;
+use naive_rabin_cryptosystem::kem::{decapsulate, encapsulate, EncapsulatedKey};
+use naive_rabin_cryptosystem::keygen::KeygenConfig;
+use naive_rabin_cryptosystem::keys::{InsecureDemo, PrivateKey, PublicKey};
+use naive_rabin_cryptosystem::stream::{decrypt_stream, encrypt_stream, StreamConfig};
+use rand::rngs::OsRng;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::thread;
+
+type EchoResult<T = ()> = Result<T, Box<dyn Error + Send + Sync>>;
+
+const USAGE: &str = "usage: tcp_echo server ADDR [--bits N] [--insecure-demo]
+       tcp_echo client ADDR [--file PATH] [--insecure-demo]";
+
+// Bits per prime of the server key
+const DEFAULT_BITS: usize = 1024;
+// Larger frames are refused, so a peer cannot make the other side allocate without bound
+const MAX_FRAME_LEN: usize = 1 << 20;
+const MODE_ECHO: u8 = b'E';
+const MODE_FILE: u8 = b'F';
+// First nonce byte, so the two directions never share a nonce under the session key
+const FROM_CLIENT: u8 = 0;
+const FROM_SERVER: u8 = 1;
+
+fn write_frame(stream: &mut impl Write, data: &[u8]) -> EchoResult {
+    stream.write_all(&(data.len() as u32).to_be_bytes())?;
+    stream.write_all(data)?;
+    Ok(stream.flush()?)
+}
+
+// None when the peer closed the connection between frames
+fn read_frame(stream: &mut impl Read) -> EchoResult<Option<Vec<u8>>> {
+    let mut length = [0u8; 4];
+    match stream.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_LEN {
+        return Err(format!("frame of {} bytes is too large", length).into());
+    }
+    let mut data = vec![0u8; length];
+    stream.read_exact(&mut data)?;
+    Ok(Some(data))
+}
+
+fn expect_frame(stream: &mut impl Read, what: &str) -> EchoResult<Vec<u8>> {
+    read_frame(stream)?.ok_or_else(|| format!("the connection closed before the {}", what).into())
+}
+
+// One direction of the session: a counter in the nonce, bound to the encapsulated key
+struct Channel<'a> {
+    key: &'a [u8; KEY_LEN],
+    direction: u8,
+    counter: u64,
+    encapsulated: &'a [u8],
+}
+
+impl<'a> Channel<'a> {
+    fn new(key: &'a [u8; KEY_LEN], direction: u8, encapsulated: &'a [u8]) -> Self {
+        Channel {
+            key,
+            direction,
+            counter: 0,
+            encapsulated,
+        }
+    }
+
+    fn next_nonce(&mut self) -> [u8; NONCE_LEN] {
+        let mut nonce = [0u8; NONCE_LEN];
+        nonce[0] = self.direction;
+        nonce[4..].copy_from_slice(&self.counter.to_be_bytes());
+        self.counter += 1;
+        nonce
+    }
+
+    fn seal(&mut self, message: &[u8]) -> Vec<u8> {
+        let nonce = self.next_nonce();
+        aes256_gcm_encrypt(self.key, &nonce, message, self.encapsulated)
+    }
+
+    fn open(&mut self, sealed: &[u8]) -> EchoResult<Vec<u8>> {
+        let nonce = self.next_nonce();
+        Ok(aes256_gcm_decrypt(self.key, &nonce, sealed, self.encapsulated)?)
+    }
+}
+
+// Hashes and counts what decrypt_stream writes, instead of keeping it
+struct Digest {
+    hash: Sha256,
+    length: u64,
+}
+
+impl Write for Digest {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.hash.update(data);
+        self.length += data.len() as u64;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn serve_client(key: &PrivateKey, mut stream: TcpStream) -> EchoResult {
+    write_frame(&mut stream, key.public_key().to_pem().as_bytes())?;
+    let mut mode = [0u8; 1];
+    stream.read_exact(&mut mode)?;
+    match mode[0] {
+        MODE_ECHO => {
+            let encapsulated = expect_frame(&mut stream, "encapsulated key")?;
+            let session_key = decapsulate(key, &EncapsulatedKey::from_bytes(&encapsulated))?;
+            let mut incoming = Channel::new(&session_key, FROM_CLIENT, &encapsulated);
+            let mut outgoing = Channel::new(&session_key, FROM_SERVER, &encapsulated);
+            while let Some(sealed) = read_frame(&mut stream)? {
+                let line = incoming.open(&sealed)?;
+                println!("echoing {} bytes", line.len());
+                write_frame(&mut stream, &outgoing.seal(&line))?;
+            }
+        }
+        MODE_FILE => {
+            let mut digest = Digest {
+                hash: Sha256::new(),
+                length: 0,
+            };
+            decrypt_stream(key, &mut stream, &mut digest, &StreamConfig::default())?;
+            let hex: String = digest.hash.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+            let receipt = format!("received {} bytes, SHA-256 {}", digest.length, hex);
+            println!("{}", receipt);
+            write_frame(&mut stream, receipt.as_bytes())?;
+        }
+        other => return Err(format!("unknown mode byte {:#04x}", other).into()),
+    }
+    Ok(())
+}
+
+fn server(addr: &str, bits: usize, insecure: Option<InsecureDemo>) -> EchoResult {
+    let config = KeygenConfig::new(bits);
+    let config = match insecure {
+        Some(opt_in) => config.allow_insecure(opt_in),
+        None => config,
+    };
+    println!("generating a key...");
+    let key = Arc::new(PrivateKey::generate_with(&config)?.0);
+    let listener = TcpListener::bind(addr)?;
+    println!("listening on {} with key {}", listener.local_addr()?, key.public_key().fingerprint());
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let key = Arc::clone(&key);
+        thread::spawn(move || {
+            let peer = stream.peer_addr().map_or_else(|_| "?".to_string(), |peer| peer.to_string());
+            match serve_client(&key, stream) {
+                Ok(()) => println!("{} done", peer),
+                Err(err) => println!("{}: {}", peer, err),
+            }
+        });
+    }
+    Ok(())
+}
+
+fn client(addr: &str, file: Option<String>, insecure: Option<InsecureDemo>) -> EchoResult {
+    let mut stream = TcpStream::connect(addr)?;
+    let pem = String::from_utf8(expect_frame(&mut stream, "server key")?)?;
+    let server_key = PublicKey::from_pem(&pem)?;
+    let server_key = match insecure {
+        Some(opt_in) => server_key.allow_insecure(opt_in),
+        None => server_key,
+    };
+    println!("server key {}", server_key.fingerprint());
+
+    if let Some(path) = file {
+        stream.write_all(&[MODE_FILE])?;
+        let mut input = BufReader::new(File::open(path)?);
+        encrypt_stream(&server_key, &mut input, &mut stream, &StreamConfig::default())?;
+        stream.shutdown(Shutdown::Write)?;
+        println!("{}", String::from_utf8(expect_frame(&mut stream, "receipt")?)?);
+        return Ok(());
+    }
+
+    stream.write_all(&[MODE_ECHO])?;
+    let (encapsulated, session_key) = encapsulate(&mut OsRng, &server_key)?;
+    let encapsulated = encapsulated.to_bytes(server_key.n())?;
+    write_frame(&mut stream, &encapsulated)?;
+    let mut outgoing = Channel::new(&session_key, FROM_CLIENT, &encapsulated);
+    let mut incoming = Channel::new(&session_key, FROM_SERVER, &encapsulated);
+    println!("session established; type lines to have them echoed");
+    for line in io::stdin().lock().lines() {
+        write_frame(&mut stream, &outgoing.seal(line?.as_bytes()))?;
+        let echoed = incoming.open(&expect_frame(&mut stream, "echo")?)?;
+        println!("echo: {}", String::from_utf8_lossy(&echoed));
+    }
+    Ok(())
+}
+
+fn run(args: Vec<String>) -> EchoResult {
+    let mut args = args.into_iter();
+    let (mode, addr) = match (args.next(), args.next()) {
+        (Some(mode), Some(addr)) => (mode, addr),
+        _ => return Err(USAGE.into()),
+    };
+    let mut bits = DEFAULT_BITS;
+    let mut file = None;
+    let mut insecure = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bits" if mode == "server" => {
+                bits = args.next().and_then(|bits| bits.parse().ok()).ok_or("--bits needs a number")?
+            }
+            "--file" if mode == "client" => file = Some(args.next().ok_or("--file needs a path")?),
+            "--insecure-demo" => insecure = Some(InsecureDemo),
+            _ => return Err(format!("unexpected argument '{}'\n\n{}", arg, USAGE).into()),
+        }
+    }
+    match mode.as_str() {
+        "server" => server(&addr, bits, insecure),
+        "client" => client(&addr, file, insecure),
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// End-to-end encrypted chat over a WebSocket relay: two chat clients swap public keys through
+// the relay and then send each other envelopes (a Rabin-encapsulated key plus AEAD), so the
+// relay only ever sees public keys and ciphertext.
+//
+// Run with:
+//     cargo run --features ws-chat --example ws_chat -- relay 127.0.0.1:9001
+//     cargo run --features ws-chat --example ws_chat -- chat ws://127.0.0.1:9001
+//     cargo run --features ws-chat --example ws_chat -- chat ws://127.0.0.1:9001
+//
+// Each client generates a fresh key on start (--bits N per prime, default 1024; below that
+// --insecure-demo is needed) and prints its fingerprint, which the two people should compare
+// out of band: the relay could otherwise swap in keys of its own.
+//
+// Text frames carry PEM public keys and binary frames carry envelopes (DER). A client sends its
+// key when it connects and again the first time it sees a new peer key, so whoever joins second
+// still learns the first one's key. The relay forwards every frame to every other client.
+
+use futures_util::{SinkExt, StreamExt};
+use naive_rabin_cryptosystem::envelope::Envelope;
+use naive_rabin_cryptosystem::keygen::KeygenConfig;
+use naive_rabin_cryptosystem::keys::{InsecureDemo, PrivateKey, PublicKey};
+use std::error::Error;
+use std::process::ExitCode;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+
+type ChatResult = Result<(), Box<dyn Error + Send + Sync>>;
+
+const USAGE: &str = "usage: ws_chat relay ADDR
+       ws_chat chat URL [--bits N] [--insecure-demo]";
+
+// Bits per prime of the chat keys
+const DEFAULT_BITS: usize = 1024;
+// Frames the relay buffers for a slow client before it starts dropping them
+const RELAY_BACKLOG: usize = 64;
+
+async fn relay(addr: &str) -> ChatResult {
+    let listener = TcpListener::bind(addr).await?;
+    println!("relaying on ws://{}", listener.local_addr()?);
+    // Every frame goes to every client, tagged with its sender so that it is not echoed back
+    let (frames, _) = broadcast::channel::<(usize, Message)>(RELAY_BACKLOG);
+    for id in 0.. {
+        let (stream, peer) = listener.accept().await?;
+        let frames = frames.clone();
+        tokio::spawn(async move {
+            println!("client {} connected from {}", id, peer);
+            if let Err(err) = relay_client(id, stream, frames).await {
+                println!("client {}: {}", id, err);
+            }
+            println!("client {} left", id);
+        });
+    }
+    Ok(())
+}
+
+async fn relay_client(id: usize, stream: TcpStream, frames: broadcast::Sender<(usize, Message)>) -> ChatResult {
+    let (mut outgoing, mut incoming) = tokio_tungstenite::accept_async(stream).await?.split();
+    let mut others = frames.subscribe();
+    loop {
+        tokio::select! {
+            frame = incoming.next() => match frame {
+                Some(Ok(frame @ (Message::Text(_) | Message::Binary(_)))) => {
+                    // No receivers just means nobody else is connected yet
+                    let _ = frames.send((id, frame));
+                }
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(err.into()),
+            },
+            frame = others.recv() => match frame {
+                Ok((sender, frame)) if sender != id => outgoing.send(frame).await?,
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(missed)) => println!("client {} missed {} frames", id, missed),
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+        }
+    }
+}
+
+async fn chat(url: &str, bits: usize, insecure: Option<InsecureDemo>) -> ChatResult {
+    let config = KeygenConfig::new(bits);
+    let config = match insecure {
+        Some(opt_in) => config.allow_insecure(opt_in),
+        None => config,
+    };
+    println!("generating a key...");
+    let (key, _) = PrivateKey::generate_with(&config)?;
+    let own_key = key.public_key().to_pem();
+    println!("your fingerprint: {}", key.public_key().fingerprint());
+
+    let (socket, _) = tokio_tungstenite::connect_async(url).await?;
+    let (mut outgoing, mut incoming) = socket.split();
+    outgoing.send(Message::text(own_key.clone())).await?;
+    println!("waiting for the other side; type a line and press enter to send it");
+
+    let mut peer: Option<PublicKey> = None;
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    outgoing.send(Message::Close(None)).await?;
+                    return Ok(());
+                };
+                let Some(peer) = &peer else {
+                    println!("(nobody to send to yet)");
+                    continue;
+                };
+                let envelope = Envelope::seal(peer, line.as_bytes())?;
+                outgoing.send(Message::binary(envelope.to_der())).await?;
+            }
+            frame = incoming.next() => match frame {
+                Some(Ok(Message::Text(pem))) => {
+                    let key = PublicKey::from_pem(&pem)?;
+                    let key = match insecure {
+                        Some(opt_in) => key.allow_insecure(opt_in),
+                        None => key,
+                    };
+                    if peer.as_ref().is_some_and(|peer| peer.n() == key.n()) {
+                        continue;
+                    }
+                    println!("peer joined, fingerprint {}", key.fingerprint());
+                    peer = Some(key);
+                    outgoing.send(Message::text(own_key.clone())).await?;
+                }
+                Some(Ok(Message::Binary(der))) => match Envelope::from_bytes(&der).and_then(|envelope| envelope.open(&key)) {
+                    Ok(text) => println!("peer: {}", String::from_utf8_lossy(&text)),
+                    Err(err) => println!("(a message could not be decrypted: {})", err),
+                },
+                Some(Ok(Message::Close(_))) | None => {
+                    println!("the relay closed the connection");
+                    return Ok(());
+                }
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(err.into()),
+            },
+        }
+    }
+}
+
+async fn run(args: Vec<String>) -> ChatResult {
+    let mut args = args.into_iter();
+    match (args.next().as_deref(), args.next()) {
+        (Some("relay"), Some(addr)) => relay(&addr).await,
+        (Some("chat"), Some(url)) => {
+            let mut bits = DEFAULT_BITS;
+            let mut insecure = None;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--bits" => bits = args.next().and_then(|bits| bits.parse().ok()).ok_or("--bits needs a number")?,
+                    "--insecure-demo" => insecure = Some(InsecureDemo),
+                    _ => return Err(format!("unexpected argument '{}'\n\n{}", arg, USAGE).into()),
+                }
+            }
+            chat(&url, bits, insecure).await
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
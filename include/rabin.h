@@ -0,0 +1,96 @@
+#ifndef RABIN_H
+#define RABIN_H
+
+/* Generated by cbindgen from src/ffi.rs with the ffi feature; do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Result of every fallible call; rabin_last_error has the details
+typedef enum RabinStatus {
+  RABIN_STATUS_OK = 0,
+  // A required pointer was null, or a buffer was not valid UTF-8 PEM
+  RABIN_STATUS_INVALID_ARGUMENT = 1,
+  // The key could not be parsed or is inconsistent
+  RABIN_STATUS_INVALID_KEY = 2,
+  // A demo-sized key, used without rabin_allow_insecure_demo()
+  RABIN_STATUS_INSECURE_KEY = 3,
+  // The key's usage flags or expiry forbid the operation
+  RABIN_STATUS_USAGE_VIOLATION = 4,
+  // The message does not fit below the modulus
+  RABIN_STATUS_MESSAGE_TOO_LARGE = 5,
+  // The ciphertext has no square roots, or none of them carries a valid tag
+  RABIN_STATUS_DECRYPTION_FAILED = 6,
+  // Any other failure
+  RABIN_STATUS_ERROR = 7,
+  // A bug in the library; the call had no effect
+  RABIN_STATUS_PANIC = 8,
+} RabinStatus;
+
+// Bytes owned by the library, released with rabin_buffer_free
+typedef struct RabinBuffer {
+  uint8_t *data;
+  size_t len;
+} RabinBuffer;
+
+// Lets demo-sized keys be generated and used by every later call in this process
+void rabin_allow_insecure_demo(void);
+
+// Generates a key with a modulus of `2 * bits` bits and writes it to `private_key_out` as
+// PKCS#8 PEM text (not NUL-terminated)
+//
+// # Safety
+//
+// `private_key_out` must be null or point to writable memory for one RabinBuffer.
+enum RabinStatus rabin_keygen(size_t bits, struct RabinBuffer *private_key_out);
+
+// Writes the public half of a PKCS#8 PEM private key to `public_key_out` as PEM text
+//
+// # Safety
+//
+// `private_key` must point to `private_key_len` readable bytes, and `public_key_out` must be
+// null or point to writable memory for one RabinBuffer.
+enum RabinStatus rabin_public_key(const uint8_t *private_key,
+                                  size_t private_key_len,
+                                  struct RabinBuffer *public_key_out);
+
+// Encrypts a big-endian message under a PEM public key. The ciphertext written to
+// `ciphertext_out` is zero-padded on the left to the byte length of the modulus.
+//
+// # Safety
+//
+// Each pointer must point to as many readable bytes as its length says (or be null with
+// length 0 for the message), and `ciphertext_out` must be null or point to writable memory for
+// one RabinBuffer.
+enum RabinStatus rabin_encrypt(const uint8_t *public_key,
+                               size_t public_key_len,
+                               const uint8_t *message,
+                               size_t message_len,
+                               struct RabinBuffer *ciphertext_out);
+
+// Decrypts a big-endian ciphertext with a PKCS#8 PEM private key and writes the message to
+// `message_out`, big-endian and without leading zero bytes
+//
+// # Safety
+//
+// Each pointer must point to as many readable bytes as its length says, and `message_out`
+// must be null or point to writable memory for one RabinBuffer.
+enum RabinStatus rabin_decrypt(const uint8_t *private_key,
+                               size_t private_key_len,
+                               const uint8_t *ciphertext,
+                               size_t ciphertext_len,
+                               struct RabinBuffer *message_out);
+
+// Releases a buffer filled in by this library. Empty buffers are accepted, so every out
+// buffer can be freed whatever the call returned.
+//
+// # Safety
+//
+// `buffer` must come from this library and must not be used or freed again afterwards.
+void rabin_buffer_free(struct RabinBuffer buffer);
+
+// The message for the last failed call on this thread, or null if none has failed. The
+// string stays valid until the next failing call on the same thread.
+const char *rabin_last_error(void);
+
+#endif  /* RABIN_H */
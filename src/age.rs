@@ -0,0 +1,145 @@
+// Rabin recipients and identities for age (https://age-encryption.org), built with the age
+// feature and served to the age CLI by the age-plugin-rabin binary:
+//
+//     age-plugin-rabin --generate > key.txt
+//     age -r age1rabin1... -o secret.age secret.txt
+//     age -d -i key.txt secret.age
+//
+// A recipient is the public key DER in bech32 with the "age1rabin" prefix, and an identity is
+// the PKCS#8 DER in upper-case bech32 with the "AGE-PLUGIN-RABIN-" prefix, which is how age
+// finds the plugin for both.
+//
+// The file key is wrapped like age's own X25519 stanzas, with the Rabin KEM in place of the
+// key agreement:
+//
+//     -> rabin <tag>
+//     <encapsulated key> || ChaCha20-Poly1305(wrap key, file key)
+//
+// where the encapsulated key is as wide as the modulus, the wrap key is HKDF-SHA-256 of the
+// shared secret with the encapsulated key as salt, and the tag is the first four bytes of the
+// recipient's fingerprint, so identities can skip stanzas meant for other keys.
+
+use crate::encoding::modulus_len;
+use crate::error::RabinError;
+use crate::kem::{decapsulate, encapsulate, EncapsulatedKey};
+use crate::keys::{PrivateKey, PublicKey};
+use age_core::format::{Stanza, FILE_KEY_BYTES};
+use age_core::primitives::{aead_decrypt, aead_encrypt, hkdf};
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use bech32::{FromBase32, ToBase32, Variant};
+use rand::rngs::OsRng;
+
+pub const RECIPIENT_HRP: &str = "age1rabin";
+pub const IDENTITY_HRP: &str = "age-plugin-rabin-";
+pub const STANZA_TAG: &str = "rabin";
+
+const WRAP_LABEL: &[u8] = b"age-encryption.org/v1/rabin";
+// Fingerprint bytes in the stanza tag
+const TAG_LEN: usize = 4;
+// ChaCha20-Poly1305 tag
+const WRAP_TAG_LEN: usize = 16;
+
+pub type FileKey = [u8; FILE_KEY_BYTES];
+
+fn decode_bech32(text: &str, hrp: &str, what: &'static str) -> Result<Vec<u8>, RabinError> {
+    match bech32::decode(text) {
+        Ok((found, data, Variant::Bech32)) if found == hrp => {
+            Vec::<u8>::from_base32(&data).map_err(|_| RabinError::InvalidKey(what))
+        }
+        _ => Err(RabinError::InvalidKey(what)),
+    }
+}
+
+pub fn encode_recipient(key: &PublicKey) -> String {
+    bech32::encode(RECIPIENT_HRP, key.to_der().to_base32(), Variant::Bech32).expect("the prefix is valid")
+}
+
+pub fn parse_recipient(text: &str) -> Result<PublicKey, RabinError> {
+    PublicKey::from_der(&decode_bech32(text, RECIPIENT_HRP, "not an age1rabin recipient")?)
+}
+
+pub fn encode_identity(key: &PrivateKey) -> String {
+    bech32::encode(IDENTITY_HRP, key.to_pkcs8_der().to_base32(), Variant::Bech32)
+        .expect("the prefix is valid")
+        .to_uppercase()
+}
+
+pub fn parse_identity(text: &str) -> Result<PrivateKey, RabinError> {
+    // bech32 strings are all lower or all upper case; age writes identities in upper case
+    let text = text.to_lowercase();
+    PrivateKey::from_pkcs8_der(&decode_bech32(&text, IDENTITY_HRP, "not an AGE-PLUGIN-RABIN identity")?)
+}
+
+fn stanza_tag(key: &PublicKey) -> String {
+    STANDARD_NO_PAD.encode(&key.fingerprint().as_bytes()[..TAG_LEN])
+}
+
+fn wrap_key(shared: &[u8], encapsulated: &[u8]) -> [u8; 32] {
+    hkdf(encapsulated, WRAP_LABEL, shared)
+}
+
+pub fn wrap_file_key(recipient: &PublicKey, file_key: &FileKey) -> Result<Stanza, RabinError> {
+    let (encapsulated, shared) = encapsulate(&mut OsRng, recipient)?;
+    let mut body = encapsulated.to_bytes(recipient.n())?;
+    let wrapped = aead_encrypt(&wrap_key(&shared, &body), file_key);
+    body.extend_from_slice(&wrapped);
+    Ok(Stanza {
+        tag: STANZA_TAG.to_string(),
+        args: vec![stanza_tag(recipient)],
+        body,
+    })
+}
+
+// None when the stanza is not a Rabin stanza for this identity; an error when it claims to be
+// one but does not open
+pub fn unwrap_file_key(identity: &PrivateKey, stanza: &Stanza) -> Option<Result<FileKey, RabinError>> {
+    let public = identity.public_key();
+    if stanza.tag != STANZA_TAG || stanza.args != [stanza_tag(&public)] {
+        return None;
+    }
+    let width = modulus_len(identity.n());
+    if stanza.body.len() != width + FILE_KEY_BYTES + WRAP_TAG_LEN {
+        return Some(Err(RabinError::MalformedDer("rabin stanza body has the wrong length")));
+    }
+    let (encapsulated, wrapped) = stanza.body.split_at(width);
+    Some(
+        decapsulate(identity, &EncapsulatedKey::from_bytes(encapsulated))
+            .and_then(|shared| {
+                aead_decrypt(&wrap_key(&shared, encapsulated), FILE_KEY_BYTES, wrapped)
+                    .map_err(|_| RabinError::DecryptionFailed)
+            })
+            .map(|file_key| file_key.try_into().expect("aead_decrypt checks the length")),
+    )
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+
+    #[test]
+    fn test_stanzas_open_only_for_their_identity() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let identity = encode_identity(&key);
+        assert!(identity.starts_with("AGE-PLUGIN-RABIN-1"));
+        let recipient = encode_recipient(&key.public_key());
+        assert!(recipient.starts_with("age1rabin1"));
+        let key = parse_identity(&identity).unwrap().allow_insecure(InsecureDemo);
+        let public = parse_recipient(&recipient).unwrap().allow_insecure(InsecureDemo);
+        assert_eq!(public, key.public_key());
+        assert!(parse_recipient(&identity).is_err());
+
+        let file_key = [7u8; FILE_KEY_BYTES];
+        let mut stanza = wrap_file_key(&public, &file_key).unwrap();
+        assert_eq!(unwrap_file_key(&key, &stanza).unwrap().unwrap(), file_key);
+
+        let other = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        assert!(unwrap_file_key(&other, &stanza).is_none());
+
+        let last = stanza.body.len() - 1;
+        stanza.body[last] ^= 1;
+        assert_eq!(unwrap_file_key(&key, &stanza).unwrap(), Err(RabinError::DecryptionFailed));
+    }
+}
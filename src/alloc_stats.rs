@@ -0,0 +1,69 @@
+// Heap accounting for `rabin bench --memory`, compiled in with the mem-stats feature. The
+// binary's global allocator forwards to the system allocator and keeps process-wide counters:
+// live bytes, the high-water mark of live bytes, and the number of allocations. The counters
+// are relaxed atomics, so the overhead is a few uncontended atomic operations per allocation.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+fn grow(bytes: usize) {
+    let live = LIVE.fetch_add(bytes, Ordering::Relaxed) + bytes;
+    PEAK.fetch_max(live, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+            grow(new_size);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapStats {
+    // Highest number of live heap bytes since the last reset
+    pub peak: usize,
+    pub allocations: u64,
+}
+
+// Starts a new measurement: the peak drops to what is live right now
+pub fn reset() -> (usize, u64) {
+    let live = LIVE.load(Ordering::Relaxed);
+    PEAK.store(live, Ordering::Relaxed);
+    (live, ALLOCATIONS.load(Ordering::Relaxed))
+}
+
+// Heap use since the reset that returned `start`, net of what was already live then
+pub fn since((live, allocations): (usize, u64)) -> HeapStats {
+    HeapStats {
+        peak: PEAK.load(Ordering::Relaxed).saturating_sub(live),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+    }
+}
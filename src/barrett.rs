@@ -0,0 +1,170 @@
+// Barrett reduction for repeated work under one modulus. With b = 2^64 and n spanning k words,
+// mu = floor(b^(2k) / n) is computed once; after that, reducing any x < b^(2k) takes two
+// partial multiplications and at most three subtractions instead of a long division:
+//
+//   q = floor(floor(x / b^(k-1)) * mu / b^(k+1))    (never more than 3 below x / n)
+//   r = x - q * n, computed mod b^(k+1)
+//
+// Unlike Montgomery form this needs no conversion in and out, and works for even moduli, so
+// it suits one-off squarings such as Rabin encryption of many blocks under the same key.
+
+use crate::montgomery::{from_limbs, less_than, mul_add_row, square_limbs, sub_in_place, to_limbs};
+use num_bigint::{BigInt, BigUint};
+use num_traits::{One, Zero};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Barrett {
+    modulus: BigUint,
+    limbs: Vec<u64>,
+    // floor(b^(2k) / n), k + 2 words so that mu = b^(k+1) (for n = b^(k-1)) still fits
+    mu: Vec<u64>,
+}
+
+impl Barrett {
+    // None for a zero modulus
+    pub fn new(modulus: &BigUint) -> Option<Self> {
+        if modulus.is_zero() {
+            return None;
+        }
+        let limbs = modulus.to_u64_digits();
+        let k = limbs.len();
+        let mu = (BigUint::one() << (128 * k)) / modulus;
+        Some(Barrett {
+            modulus: modulus.clone(),
+            mu: to_limbs(&mu, k + 2),
+            limbs,
+        })
+    }
+
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    // Reduces x (2k words) into out (k words)
+    fn reduce_limbs(&self, x: &[u64], out: &mut [u64]) {
+        let k = self.limbs.len();
+        // q1 * mu; only the words from k + 1 up are kept as q, so partial products landing
+        // below word k - 1 are skipped. The carries they would have sent up make q at most one
+        // smaller, which costs one more subtraction at the end.
+        let q1 = &x[k - 1..2 * k];
+        let mut product = vec![0u64; q1.len() + self.mu.len()];
+        for (i, &word) in q1.iter().enumerate() {
+            let skip = (k - 1).saturating_sub(i);
+            product[i + self.mu.len()] = mul_add_row(&mut product[i + skip..i + self.mu.len()], &self.mu[skip..], word);
+        }
+        let q = &product[k + 1..];
+
+        // q * n mod b^(k+1), skipping every partial product that lands above that
+        let mut qn = vec![0u64; k + 1];
+        for (i, &word) in q.iter().enumerate().take(k + 1) {
+            let width = (k + 1 - i).min(k);
+            let carry = mul_add_row(&mut qn[i..i + width], &self.limbs[..width], word);
+            if i + width <= k {
+                qn[i + width] = qn[i + width].wrapping_add(carry);
+            }
+        }
+
+        // x - q * n is below 4n, so it fits in k + 1 words and the wrap-around cancels out
+        let mut r = x[..k + 1].to_vec();
+        sub_in_place(&mut r, &qn);
+        while r[k] != 0 || !less_than(&r[..k], &self.limbs) {
+            let borrow = sub_in_place(&mut r[..k], &self.limbs);
+            r[k] -= u64::from(borrow);
+        }
+        out.copy_from_slice(&r[..k]);
+    }
+
+    // x mod n; values at or above n^2 are too wide for one Barrett step and take a division
+    pub fn reduce(&self, x: &BigUint) -> BigUint {
+        let k = self.limbs.len();
+        if x.bits() > 128 * k as u64 {
+            return x % &self.modulus;
+        }
+        let mut out = vec![0u64; k];
+        self.reduce_limbs(&to_limbs(x, 2 * k), &mut out);
+        from_limbs(&out)
+    }
+
+    // x^2 mod n
+    pub fn square(&self, x: &BigUint) -> BigUint {
+        let k = self.limbs.len();
+        let reduced;
+        let x = if x < &self.modulus {
+            x
+        } else {
+            reduced = x % &self.modulus;
+            &reduced
+        };
+        let mut wide = vec![0u64; 2 * k];
+        square_limbs(&to_limbs(x, k), &mut wide);
+        let mut out = vec![0u64; k];
+        self.reduce_limbs(&wide, &mut out);
+        from_limbs(&out)
+    }
+
+    // Rabin encryption, message^2 mod n, with the same result as rabin::encrypt for the messages
+    // it accepts. There is no range check here; callers on the hot path check once up front.
+    pub fn encrypt(&self, message: &BigInt) -> BigInt {
+        BigInt::from(self.square(message.magnitude()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::RandBigInt;
+    use num_integer::Integer;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_matches_division() {
+        let mut rng = thread_rng();
+        for bits in [1u64, 2, 63, 64, 65, 127, 128, 129, 512, 2048] {
+            for _ in 0..20 {
+                let modulus = rng.gen_biguint(bits) | (BigUint::one() << (bits - 1));
+                let context = Barrett::new(&modulus).unwrap();
+                let x = rng.gen_biguint(2 * bits);
+                assert_eq!(context.reduce(&x), &x % &modulus, "{}-bit modulus", bits);
+                let y = rng.gen_biguint_below(&modulus);
+                assert_eq!(context.square(&y), &y * &y % &modulus, "{}-bit modulus", bits);
+            }
+        }
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        assert!(Barrett::new(&BigUint::zero()).is_none());
+        // Powers of the word size give the largest mu; all-ones words the largest quotients
+        for modulus in [
+            BigUint::one(),
+            BigUint::one() << 64,
+            BigUint::one() << 128,
+            (BigUint::one() << 192) - 1u8,
+            BigUint::from(u64::MAX),
+        ] {
+            let context = Barrett::new(&modulus).unwrap();
+            for x in [BigUint::zero(), &modulus - 1u8, modulus.clone(), &modulus * &modulus - 1u8] {
+                assert_eq!(context.reduce(&x), &x % &modulus, "{} mod {}", x, modulus);
+                assert_eq!(context.square(&x), &x * &x % &modulus, "{}^2 mod {}", x, modulus);
+            }
+            let huge = &modulus * &modulus * 7u8 + 3u8;
+            assert_eq!(context.reduce(&huge), &huge % &modulus);
+        }
+    }
+
+    #[test]
+    fn test_encrypt_matches_rabin() {
+        let mut rng = thread_rng();
+        let n = BigInt::from(rng.gen_biguint(1024) | BigUint::one());
+        let context = Barrett::new(n.magnitude()).unwrap();
+        for message in [BigInt::zero(), rng.gen_bigint_range(&BigInt::zero(), &n), &n - 1] {
+            assert_eq!(context.encrypt(&message), crate::rabin::encrypt(&message, &n).unwrap());
+        }
+        // rabin::encrypt refuses these; the context just reduces them
+        for message in [BigInt::from(-5), &n + 12] {
+            assert!(crate::rabin::encrypt(&message, &n).is_err());
+            assert_eq!(context.encrypt(&message), (&message * &message).mod_floor(&n));
+        }
+    }
+}
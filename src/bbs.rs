@@ -0,0 +1,179 @@
+// Blum Blum Shub: x_{i+1} = x_i^2 mod n for a Blum integer n, emitting the low bits of each
+// state. Predicting the output is as hard as factoring n, which makes it a nice companion to
+// Rabin, but it is far too slow for bulk randomness.
+//
+// The seed s is squared once before any output (x_0 = s^2 mod n), so x_0 is always a quadratic
+// residue and the sequence is purely periodic.
+
+use crate::error::RabinError;
+use crate::keygen::KeygenConfig;
+use crate::keys::PrivateKey;
+use crate::math::gcd;
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::{One, ToPrimitive};
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlumBlumShub {
+    n: BigInt,
+    state: BigInt,
+    // Low bits taken from each state; at most log2(log2(n)) is considered safe
+    bits_per_step: u32,
+    buffer: u64,
+    buffered: u32,
+}
+
+impl BlumBlumShub {
+    pub fn new(n: &BigInt, seed: &BigInt) -> Result<Self, RabinError> {
+        if *n <= BigInt::from(3) {
+            return Err(RabinError::ModulusTooSmall);
+        }
+        let seed = seed % n;
+        if seed <= BigInt::one() || n - &seed == BigInt::one() {
+            return Err(RabinError::InvalidSeed("seed must not be 0, 1 or -1 modulo n"));
+        }
+        if !gcd(&seed, n).is_one() {
+            return Err(RabinError::InvalidSeed("seed must be coprime to the modulus"));
+        }
+        Ok(BlumBlumShub {
+            n: n.clone(),
+            state: &seed * &seed % n,
+            bits_per_step: 1,
+            buffer: 0,
+            buffered: 0,
+        })
+    }
+
+    // Reuses the modulus of an existing Blum key; only the seed needs to stay secret
+    pub fn from_key(key: &PrivateKey, seed: &BigInt) -> Result<Self, RabinError> {
+        BlumBlumShub::new(key.n(), seed)
+    }
+
+    // A fresh Blum modulus generated as configured, and a random seed; the factors are
+    // discarded, which is all a pure generator needs
+    pub fn generate(config: &KeygenConfig) -> Result<Self, RabinError> {
+        let (key, _) = PrivateKey::generate_with(config)?;
+        let mut rng = OsRng;
+        loop {
+            let seed = rng.gen_bigint_range(&BigInt::from(2), &(key.n() - 1u8));
+            if gcd(&seed, key.n()).is_one() {
+                return BlumBlumShub::new(key.n(), &seed);
+            }
+        }
+    }
+
+    pub fn max_bits_per_step(&self) -> u32 {
+        (self.n.bits() as f64).log2().floor().max(1.0) as u32
+    }
+
+    pub fn with_bits_per_step(mut self, bits: u32) -> Result<Self, RabinError> {
+        if bits == 0 || bits > self.max_bits_per_step() {
+            return Err(RabinError::InvalidSeed("bits per step must be between 1 and log2(log2(n))"));
+        }
+        self.bits_per_step = bits;
+        Ok(self)
+    }
+
+    pub fn n(&self) -> &BigInt {
+        &self.n
+    }
+
+    // The most recent x_i
+    pub fn state(&self) -> &BigInt {
+        &self.state
+    }
+
+    // Squares the state once and returns the new x_i
+    pub fn step(&mut self) -> &BigInt {
+        self.state = &self.state * &self.state % &self.n;
+        &self.state
+    }
+
+    // Bits come out lowest first from each state
+    pub fn next_bit(&mut self) -> bool {
+        if self.buffered == 0 {
+            let mask = (1u64 << self.bits_per_step) - 1;
+            let low = (self.step() & BigInt::from(mask)).to_u64().expect("masked to at most 64 bits");
+            self.buffer = low;
+            self.buffered = self.bits_per_step;
+        }
+        let bit = self.buffer & 1 == 1;
+        self.buffer >>= 1;
+        self.buffered -= 1;
+        bit
+    }
+
+    // The next `count` bits (at most 64), first bit in the most significant position
+    pub fn next_bits(&mut self, count: u32) -> u64 {
+        assert!(count <= 64, "at most 64 bits at a time");
+        (0..count).fold(0, |acc, _| (acc << 1) | u64::from(self.next_bit()))
+    }
+}
+
+impl RngCore for BlumBlumShub {
+    fn next_u32(&mut self) -> u32 {
+        self.next_bits(32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.next_bits(64)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            *byte = self.next_bits(8) as u8;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+    use crate::keygen::check_rng;
+
+    #[test]
+    fn test_textbook_sequence() {
+        // n = 11 * 23, seed 3: x_0 = 9, then 81, 236, 36, 31, 202
+        let mut bbs = BlumBlumShub::new(&BigInt::from(253), &BigInt::from(3)).unwrap();
+        assert_eq!(*bbs.state(), BigInt::from(9));
+        let bits: Vec<bool> = (0..5).map(|_| bbs.next_bit()).collect();
+        assert_eq!(bits, vec![true, false, false, true, false]);
+        assert_eq!(*bbs.state(), BigInt::from(202));
+    }
+
+    #[test]
+    fn test_multiple_bits_per_step() {
+        let n = BigInt::from(253);
+        let mut single = BlumBlumShub::new(&n, &BigInt::from(3)).unwrap();
+        let mut double = single.clone().with_bits_per_step(2).unwrap();
+        // 81 = 0b1010001 and 236 = 0b11101100: low two bits, lowest first
+        assert_eq!(double.next_bits(4), 0b1000);
+        single.step();
+        assert_eq!(single.step(), &BigInt::from(236));
+        assert!(double.clone().with_bits_per_step(4).is_err(), "log2(log2(253)) is 2");
+    }
+
+    #[test]
+    fn test_rejects_bad_seeds() {
+        let n = BigInt::from(253);
+        for seed in [0, 1, 252, 11, 46] {
+            assert!(BlumBlumShub::new(&n, &BigInt::from(seed)).is_err(), "seed {} should be rejected", seed);
+        }
+    }
+
+    #[test]
+    fn test_generated_stream_passes_self_test() {
+        let mut bbs = BlumBlumShub::generate(&KeygenConfig::new(64).allow_insecure(InsecureDemo)).unwrap();
+        assert_eq!(check_rng(&mut bbs), Ok(()));
+        let ones: u32 = (0..64).map(|_| bbs.next_u32().count_ones()).sum();
+        assert!((800..1250).contains(&ones), "{} ones in 2048 bits looks biased", ones);
+    }
+}
@@ -0,0 +1,347 @@
+// `rabin bench`: times a fixed set of workloads and optionally compares them against a stored
+// baseline, failing when any of them got noticeably slower. Inputs come from a fixed seed, so
+// two runs on the same machine do the same work.
+//
+// Each workload is run in batches of a calibrated size and reports the fastest batch, in
+// nanoseconds per operation, which keeps scheduler noise out of the comparison. Baselines are a
+// flat JSON object mapping workload names to those numbers, e.g. {"modpow-2048": 3512000.0}.
+//
+// With --memory it instead runs key generation and decryption once each, for a two-prime and a
+// three-prime key of the chosen size, and reports peak resident memory from /proc (Linux only).
+// Builds with the mem-stats feature also report the heap high-water mark and allocation count
+// of each step, from a counting global allocator.
+//
+// With --timing (in builds with the timing feature) it runs the statistical timing-leak test
+// in timing.rs instead.
+
+use crate::cli::{Args, CliResult};
+use naive_rabin_cryptosystem::encoding::{num2str, str2num, Alphabet, DEFAULT_SYMBOLS};
+use naive_rabin_cryptosystem::keygen::KeygenConfig;
+use naive_rabin_cryptosystem::keys::{InsecureDemo, PrivateKey};
+use naive_rabin_cryptosystem::montgomery::modpow;
+use naive_rabin_cryptosystem::multiprime::MultiPrimeKey;
+use naive_rabin_cryptosystem::rabin::encrypt;
+use num_bigint::{BigInt, RandBigInt};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::error::Error;
+use std::fs;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const DEFAULT_THRESHOLD_PERCENT: f64 = 10.0;
+// Modulus size for --memory
+const DEFAULT_MEMORY_BITS: usize = 4096;
+// Each batch runs for at least this long, and the best of BATCHES batches is kept
+const BATCH_TIME: Duration = Duration::from_millis(100);
+const BATCHES: usize = 5;
+const SEED: [u8; 32] = *b"rabin bench fixed workload seed!";
+// Runs of each target for --timing
+const DEFAULT_TIMING_SAMPLES: usize = 10_000;
+
+type Workload = (&'static str, Box<dyn FnMut()>);
+
+fn workloads() -> Vec<Workload> {
+    let mut rng = ChaCha20Rng::from_seed(SEED);
+    let key = PrivateKey::from_seed(&SEED, 1024);
+    let n = key.n().clone();
+    let public = key.public_key();
+    let context = public.barrett().expect("seeded key has a positive modulus");
+
+    let base = rng.gen_bigint_range(&BigInt::from(2), &n);
+    let exponent = rng.gen_bigint_range(&BigInt::from(2), &n);
+    let message = rng.gen_bigint_range(&BigInt::from(2), &n);
+    let ciphertext = encrypt(&message, &n).unwrap();
+    let text: String = DEFAULT_SYMBOLS.chars().cycle().skip(1).take(4096).collect();
+    let number = str2num(&text, Alphabet::default_symbols()).unwrap();
+
+    let modulus = n.clone();
+    let barrett_message = message.clone();
+    vec![
+        ("modpow-2048", Box::new(move || {
+            black_box(modpow(&base, &exponent, &modulus));
+        })),
+        ("encrypt-2048", Box::new(move || {
+            black_box(encrypt(&message, &n).unwrap());
+        })),
+        ("encrypt-barrett-2048", Box::new(move || {
+            black_box(context.encrypt(&barrett_message));
+        })),
+        ("decrypt-2048", Box::new(move || {
+            black_box(key.decrypt(&ciphertext).unwrap());
+        })),
+        ("str2num-4k", Box::new(move || {
+            black_box(str2num(&text, Alphabet::default_symbols()).unwrap());
+        })),
+        ("num2str-4k", Box::new(move || {
+            black_box(num2str(&number, Alphabet::default_symbols()).unwrap());
+        })),
+    ]
+}
+
+// Nanoseconds per operation: the fastest of several batches, each long enough to time reliably
+fn measure(operation: &mut dyn FnMut()) -> f64 {
+    let mut iterations = 1u32;
+    loop {
+        let started = Instant::now();
+        for _ in 0..iterations {
+            operation();
+        }
+        if started.elapsed() >= BATCH_TIME / 10 || iterations >= 1 << 20 {
+            let per_batch = BATCH_TIME.as_secs_f64() / (started.elapsed().as_secs_f64() / iterations as f64);
+            iterations = (per_batch as u32).max(1);
+            break;
+        }
+        iterations *= 2;
+    }
+    (0..BATCHES)
+        .map(|_| {
+            let started = Instant::now();
+            for _ in 0..iterations {
+                operation();
+            }
+            started.elapsed().as_nanos() as f64 / iterations as f64
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn to_json(results: &[(String, f64)]) -> String {
+    let entries: Vec<String> = results.iter().map(|(name, ns)| format!("  \"{}\": {:.1}", name, ns)).collect();
+    format!("{{\n{}\n}}\n", entries.join(",\n"))
+}
+
+// Parses the flat {"name": number, ...} object written by to_json. Names may not contain
+// escapes; nothing else is needed for workload names.
+fn from_json(text: &str) -> Result<Vec<(String, f64)>, String> {
+    let body = text
+        .trim()
+        .strip_prefix('{')
+        .and_then(|rest| rest.strip_suffix('}'))
+        .ok_or("baseline is not a JSON object")?;
+    let mut results = Vec::new();
+    if body.trim().is_empty() {
+        return Ok(results);
+    }
+    for entry in body.split(',') {
+        let (name, value) = entry.split_once(':').ok_or("baseline entry without a value")?;
+        let name = name
+            .trim()
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+            .filter(|name| !name.contains(['"', '\\']))
+            .ok_or("baseline names must be plain strings")?;
+        let value: f64 = value.trim().parse().map_err(|_| format!("'{}' has no numeric timing", name))?;
+        results.push((name.to_string(), value));
+    }
+    Ok(results)
+}
+
+// Workloads slower than the baseline by more than threshold percent
+fn regressions(baseline: &[(String, f64)], current: &[(String, f64)], threshold: f64) -> Vec<String> {
+    current
+        .iter()
+        .filter(|(name, ns)| {
+            baseline
+                .iter()
+                .find(|(base_name, _)| base_name == name)
+                .is_some_and(|(_, base_ns)| *ns > base_ns * (1.0 + threshold / 100.0))
+        })
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+fn format_ns(ns: f64) -> String {
+    format!("{:.2?}", Duration::from_nanos(ns as u64))
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+// VmHWM, the most memory the process has had resident
+fn peak_rss() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kib * 1024)
+}
+
+// Writing 5 to clear_refs resets VmHWM to the current RSS (Linux 4.0 and later)
+fn reset_peak_rss() -> bool {
+    fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+// Runs one step and prints its time, heap figures (with mem-stats) and peak RSS
+fn measure_memory<T>(name: &str, operation: impl FnOnce() -> T) -> T {
+    let rss_reset = reset_peak_rss();
+    #[cfg(feature = "mem-stats")]
+    let start = crate::alloc_stats::reset();
+    let started = Instant::now();
+    let result = operation();
+    let elapsed = started.elapsed();
+
+    #[cfg(feature = "mem-stats")]
+    let heap = {
+        let stats = crate::alloc_stats::since(start);
+        (format_bytes(stats.peak as u64), stats.allocations.to_string())
+    };
+    #[cfg(not(feature = "mem-stats"))]
+    let heap = ("-".to_string(), "-".to_string());
+    let rss = match peak_rss() {
+        // Without a reset the figure covers the whole process so far
+        Some(bytes) if rss_reset => format_bytes(bytes),
+        Some(bytes) => format!("{} (process)", format_bytes(bytes)),
+        None => "n/a".to_string(),
+    };
+    println!("{:<18} {:>10.2?} {:>12} {:>12}  {}", name, elapsed, heap.0, heap.1, rss);
+    result
+}
+
+fn run_memory(bits: usize) -> CliResult {
+    if !cfg!(feature = "mem-stats") {
+        println!("heap figures need a build with --features mem-stats");
+    }
+    println!("{:<18} {:>10} {:>12} {:>12}  peak RSS", "step", "time", "heap peak", "allocations");
+    let mut rng = ChaCha20Rng::from_seed(SEED);
+
+    // These keys are only measured, never used for data, so demo sizes are allowed
+    let config = KeygenConfig::new(bits / 2)
+        .with_rng(ChaCha20Rng::from_seed(SEED))
+        .allow_insecure(InsecureDemo);
+    let (key, _) = measure_memory(&format!("keygen-{}", bits), || PrivateKey::generate_with(&config))?;
+    let ciphertext = encrypt(&rng.gen_bigint_range(&BigInt::from(2), key.n()), key.n())?;
+    measure_memory(&format!("decrypt-{}", bits), || key.decrypt(&ciphertext))?;
+
+    let config = KeygenConfig::new(bits / 3)
+        .with_prime_count(3)
+        .with_rng(ChaCha20Rng::from_seed(SEED))
+        .allow_insecure(InsecureDemo);
+    let name = format!("keygen-3x{}", bits / 3);
+    let (key, _) = measure_memory(&name, || MultiPrimeKey::generate_with(&config))?;
+    let ciphertext = encrypt(&rng.gen_bigint_range(&BigInt::from(2), key.n()), key.n())?;
+    measure_memory(&format!("decrypt-3x{}", bits / 3), || key.decrypt(&ciphertext))?;
+    Ok(())
+}
+
+#[cfg(feature = "timing")]
+fn run_timing(samples: usize) -> CliResult {
+    crate::timing::run_timing(samples)
+}
+
+#[cfg(not(feature = "timing"))]
+fn run_timing(_: usize) -> CliResult {
+    Err("this build has no timing harness (rebuild with --features timing)".into())
+}
+
+pub fn run_bench(mut args: Args) -> CliResult {
+    let memory = args.flag("memory");
+    let timing = args.flag("timing");
+    let samples = args.option("samples")?;
+    let bits = args.option("bits")?;
+    let save = args.option("save")?;
+    let check = args.option("check")?;
+    let threshold = match args.option("threshold")? {
+        Some(value) => match value.parse::<f64>() {
+            Ok(threshold) if threshold >= 0.0 => threshold,
+            _ => return Err(format!("invalid threshold '{}'", value).into()),
+        },
+        None => DEFAULT_THRESHOLD_PERCENT,
+    };
+    args.finish()?;
+
+    if timing {
+        if memory || bits.is_some() || save.is_some() || check.is_some() {
+            return Err("--timing takes no other options than --samples".into());
+        }
+        let samples = match samples {
+            Some(samples) => match samples.parse() {
+                Ok(samples) if samples >= 100 => samples,
+                _ => return Err(format!("invalid sample count '{}'", samples).into()),
+            },
+            None => DEFAULT_TIMING_SAMPLES,
+        };
+        return run_timing(samples);
+    }
+    if samples.is_some() {
+        return Err("--samples only applies to --timing".into());
+    }
+    if memory {
+        if save.is_some() || check.is_some() {
+            return Err("--memory takes no baseline options".into());
+        }
+        let bits = match bits {
+            Some(bits) => match bits.parse() {
+                Ok(bits) if bits >= 64 => bits,
+                _ => return Err(format!("invalid bit size '{}'", bits).into()),
+            },
+            None => DEFAULT_MEMORY_BITS,
+        };
+        return run_memory(bits);
+    }
+    if bits.is_some() {
+        return Err("--bits only applies to --memory".into());
+    }
+
+    let baseline = match &check {
+        Some(path) => Some(from_json(&fs::read_to_string(path)?)?),
+        None => None,
+    };
+
+    let mut results = Vec::new();
+    for (name, mut operation) in workloads() {
+        let ns = measure(&mut *operation);
+        let compared = baseline
+            .as_ref()
+            .and_then(|baseline| baseline.iter().find(|(base_name, _)| base_name == name))
+            .map(|(_, base_ns)| format!("  baseline {:>10}  {:+6.1}%", format_ns(*base_ns), (ns / base_ns - 1.0) * 100.0))
+            .unwrap_or_default();
+        println!("{:<22} {:>10}{}", name, format_ns(ns), compared);
+        results.push((name.to_string(), ns));
+    }
+
+    if let Some(path) = save {
+        fs::write(path, to_json(&results))?;
+    }
+    if let Some(baseline) = baseline {
+        let slower = regressions(&baseline, &results, threshold);
+        if !slower.is_empty() {
+            let message = format!("{} slower than the baseline by more than {}%", slower.join(", "), threshold);
+            return Err(Box::<dyn Error>::from(message));
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_baseline_json_round_trip() {
+        let results = vec![("modpow-2048".to_string(), 3512000.5), ("num2str-4k".to_string(), 12.0)];
+        assert_eq!(from_json(&to_json(&results)).unwrap(), results);
+        assert_eq!(from_json(" {} ").unwrap(), vec![]);
+        assert!(from_json("[1, 2]").is_err());
+        assert!(from_json("{\"a\": fast}").is_err());
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1_048_576), "3.0 MiB");
+    }
+
+    #[test]
+    fn test_regressions_respect_the_threshold() {
+        let baseline = vec![("a".to_string(), 100.0), ("b".to_string(), 100.0)];
+        let current = vec![("a".to_string(), 109.0), ("b".to_string(), 111.0), ("new".to_string(), 1e9)];
+        assert_eq!(regressions(&baseline, &current, 10.0), vec!["b".to_string()]);
+        assert!(regressions(&baseline, &current, 20.0).is_empty());
+    }
+}
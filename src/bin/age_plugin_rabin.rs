@@ -0,0 +1,260 @@
+// age-plugin-rabin: the age plugin for Rabin recipients (see src/age.rs), built with the age
+// feature. Put it on PATH and age runs it for age1rabin1... recipients and AGE-PLUGIN-RABIN-1...
+// identities.
+//
+//     age-plugin-rabin --generate [--bits N]   a new identity, with its recipient in a comment
+//     age-plugin-rabin --from-pem FILE         the identity for an existing PKCS#8 PEM key
+//
+// age gives plugins no way to pass flags, so demo-sized keys are allowed by setting
+// RABIN_INSECURE_DEMO=1 in the environment instead of --insecure-demo.
+
+use age_core::format::{Stanza, FILE_KEY_BYTES};
+use age_core::plugin::{Connection, IDENTITY_V1, RECIPIENT_V1};
+use naive_rabin_cryptosystem::age::{
+    encode_identity, encode_recipient, parse_identity, parse_recipient, unwrap_file_key, wrap_file_key, FileKey,
+};
+use naive_rabin_cryptosystem::keygen::KeygenConfig;
+use naive_rabin_cryptosystem::keys::{InsecureDemo, PrivateKey, PublicKey};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: age-plugin-rabin --generate [--bits N]
+       age-plugin-rabin --from-pem FILE
+
+Run by age for age1rabin1... recipients and AGE-PLUGIN-RABIN-1... identities; set
+RABIN_INSECURE_DEMO=1 to allow demo keys, with a modulus below 2048 bits.";
+
+// Bits per prime for --generate
+const DEFAULT_BITS: usize = 1024;
+
+fn insecure() -> Option<InsecureDemo> {
+    std::env::var("RABIN_INSECURE_DEMO").is_ok_and(|value| value == "1").then_some(InsecureDemo)
+}
+
+fn allow_private(key: PrivateKey) -> PrivateKey {
+    match insecure() {
+        Some(opt_in) => key.allow_insecure(opt_in),
+        None => key,
+    }
+}
+
+fn allow_public(key: PublicKey) -> PublicKey {
+    match insecure() {
+        Some(opt_in) => key.allow_insecure(opt_in),
+        None => key,
+    }
+}
+
+// The single argument of a phase-one command
+fn argument(stanza: &Stanza) -> Result<String, String> {
+    match stanza.args.as_slice() {
+        [argument] => Ok(argument.clone()),
+        _ => Err(format!("{} takes one argument", stanza.tag)),
+    }
+}
+
+// A failure reported back to age in phase two: the command's metadata and its message
+struct PluginError {
+    metadata: Vec<String>,
+    message: String,
+}
+
+impl PluginError {
+    fn new(metadata: &[&str], message: impl ToString) -> Self {
+        PluginError {
+            metadata: metadata.iter().map(|item| item.to_string()).collect(),
+            message: message.to_string(),
+        }
+    }
+}
+
+fn send_errors<R: io::Read, W: io::Write>(connection: &mut Connection<R, W>, errors: Vec<PluginError>) -> io::Result<()> {
+    connection.bidir_send(|mut phase| {
+        for error in errors {
+            let metadata: Vec<&str> = error.metadata.iter().map(String::as_str).collect();
+            let _ = phase.send("error", &metadata, error.message.as_bytes())?;
+        }
+        Ok(())
+    })
+}
+
+fn run_recipient_v1() -> io::Result<()> {
+    let mut connection = Connection::accept();
+    let (recipients, identities, file_keys, _) = connection.unidir_receive(
+        ("add-recipient", |stanza: Stanza| argument(&stanza)),
+        ("add-identity", |stanza: Stanza| argument(&stanza)),
+        (Some("wrap-file-key"), |stanza: Stanza| {
+            FileKey::try_from(stanza.body.as_slice()).map_err(|_| format!("a file key has {} bytes", FILE_KEY_BYTES))
+        }),
+        (Some("extension-labels"), |_: Stanza| Ok::<_, String>(())),
+    )?;
+    let internal = |errors: Vec<String>| errors.into_iter().map(|message| PluginError::new(&["internal"], message)).collect();
+    let (recipients, identities, file_keys) = match (recipients, identities, file_keys.unwrap_or(Ok(vec![]))) {
+        (Ok(recipients), Ok(identities), Ok(file_keys)) => (recipients, identities, file_keys),
+        (recipients, identities, file_keys) => {
+            let errors = [recipients.err(), identities.err(), file_keys.err()].into_iter().flatten().flatten().collect();
+            return send_errors(&mut connection, internal(errors));
+        }
+    };
+
+    // Identities given as recipients encrypt to their public half. Each key keeps the
+    // command it came from, for errors while wrapping.
+    let mut keys = Vec::new();
+    let mut errors = Vec::new();
+    for (index, recipient) in recipients.iter().enumerate() {
+        match parse_recipient(recipient) {
+            Ok(key) => keys.push((allow_public(key), "recipient", index)),
+            Err(err) => errors.push(PluginError::new(&["recipient", &index.to_string()], err)),
+        }
+    }
+    for (index, identity) in identities.iter().enumerate() {
+        match parse_identity(identity) {
+            Ok(key) => keys.push((allow_private(key).public_key(), "identity", index)),
+            Err(err) => errors.push(PluginError::new(&["identity", &index.to_string()], err)),
+        }
+    }
+    if !errors.is_empty() {
+        return send_errors(&mut connection, errors);
+    }
+
+    let mut stanzas = Vec::new();
+    for (file_index, file_key) in file_keys.iter().enumerate() {
+        for (key, kind, index) in &keys {
+            match wrap_file_key(key, file_key) {
+                Ok(stanza) => stanzas.push((file_index.to_string(), stanza)),
+                Err(err) => errors.push(PluginError::new(&[kind, &index.to_string()], err)),
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return send_errors(&mut connection, errors);
+    }
+    connection.bidir_send(|mut phase| {
+        for (file_index, stanza) in &stanzas {
+            let _ = phase.send_stanza("recipient-stanza", &[file_index], stanza)?;
+        }
+        Ok(())
+    })
+}
+
+fn run_identity_v1() -> io::Result<()> {
+    let mut connection = Connection::accept();
+    let (identities, stanzas, _, _) = connection.unidir_receive(
+        ("add-identity", |stanza: Stanza| argument(&stanza)),
+        ("recipient-stanza", |stanza: Stanza| match stanza.args.split_first() {
+            Some((file_index, args)) if !args.is_empty() => {
+                let file_index = file_index.parse::<usize>().map_err(|_| "invalid file index".to_string())?;
+                let stanza = Stanza {
+                    tag: args[0].clone(),
+                    args: args[1..].to_vec(),
+                    body: stanza.body,
+                };
+                Ok((file_index, stanza))
+            }
+            _ => Err("recipient-stanza needs a file index and a stanza type".to_string()),
+        }),
+        (None::<&str>, |_: Stanza| Ok::<_, String>(())),
+        (None::<&str>, |_: Stanza| Ok::<_, String>(())),
+    )?;
+    let (identities, stanzas) = match (identities, stanzas) {
+        (Ok(identities), Ok(stanzas)) => (identities, stanzas),
+        (identities, stanzas) => {
+            let errors = [identities.err(), stanzas.err()].into_iter().flatten().flatten();
+            return send_errors(&mut connection, errors.map(|message| PluginError::new(&["internal"], message)).collect());
+        }
+    };
+
+    let mut keys = Vec::new();
+    let mut errors = Vec::new();
+    for (index, identity) in identities.iter().enumerate() {
+        match parse_identity(identity) {
+            Ok(key) => keys.push(allow_private(key)),
+            Err(err) => errors.push(PluginError::new(&["identity", &index.to_string()], err)),
+        }
+    }
+    if !errors.is_empty() {
+        return send_errors(&mut connection, errors);
+    }
+
+    // The first stanza of each file that opens gives its file key; stanzas that are meant for
+    // one of the keys but do not open are reported
+    let mut file_keys: Vec<(usize, FileKey)> = Vec::new();
+    let mut stanza_index = Vec::new();
+    for (file_index, stanza) in &stanzas {
+        let index = stanza_index.iter().filter(|&&seen| seen == *file_index).count();
+        stanza_index.push(*file_index);
+        if file_keys.iter().any(|(done, _)| done == file_index) {
+            continue;
+        }
+        for key in &keys {
+            match unwrap_file_key(key, stanza) {
+                Some(Ok(file_key)) => {
+                    file_keys.push((*file_index, file_key));
+                    break;
+                }
+                Some(Err(err)) => {
+                    errors.push(PluginError::new(&["stanza", &file_index.to_string(), &index.to_string()], err))
+                }
+                None => {}
+            }
+        }
+    }
+    connection.bidir_send(|mut phase| {
+        for (file_index, file_key) in &file_keys {
+            let _ = phase.send("file-key", &[&file_index.to_string()], file_key)?;
+        }
+        for error in &errors {
+            let metadata: Vec<&str> = error.metadata.iter().map(String::as_str).collect();
+            let _ = phase.send("error", &metadata, error.message.as_bytes())?;
+        }
+        Ok(())
+    })
+}
+
+fn print_identity(key: &PrivateKey) {
+    println!("# recipient: {}", encode_recipient(&key.public_key()));
+    println!("{}", encode_identity(key));
+}
+
+fn run(args: Vec<String>) -> Result<(), Box<dyn Error>> {
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some(flag) if flag.starts_with("--age-plugin=") => match &flag["--age-plugin=".len()..] {
+            RECIPIENT_V1 => Ok(run_recipient_v1()?),
+            IDENTITY_V1 => Ok(run_identity_v1()?),
+            other => Err(format!("unknown state machine '{}'", other).into()),
+        },
+        Some("--generate") => {
+            let bits = match (args.next().as_deref(), args.next()) {
+                (None, _) => DEFAULT_BITS,
+                (Some("--bits"), Some(bits)) => bits.parse().map_err(|_| format!("invalid bit size '{}'", bits))?,
+                _ => return Err(USAGE.into()),
+            };
+            let config = KeygenConfig::new(bits);
+            let config = match insecure() {
+                Some(opt_in) => config.allow_insecure(opt_in),
+                None => config,
+            };
+            print_identity(&PrivateKey::generate_with(&config)?.0);
+            Ok(())
+        }
+        Some("--from-pem") => {
+            let path = args.next().ok_or(USAGE)?;
+            print_identity(&PrivateKey::from_pkcs8_pem(&fs::read_to_string(path)?)?);
+            Ok(())
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1).collect()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
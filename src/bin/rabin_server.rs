@@ -0,0 +1,53 @@
+// rabin-server: the gRPC service from src/grpc.rs, built with the grpc feature
+//
+//     cargo run --features grpc --bin rabin-server -- [--listen ADDR] [--insecure-demo]
+//
+// Clients in other languages generate their stubs from proto/rabin.proto.
+
+use naive_rabin_cryptosystem::grpc::{RabinServer, RabinService};
+use naive_rabin_cryptosystem::keys::InsecureDemo;
+use std::net::SocketAddr;
+use std::process::ExitCode;
+use tonic::transport::Server;
+
+const USAGE: &str = "usage: rabin-server [--listen ADDR] [--insecure-demo]
+
+  --listen ADDR     address to serve on (default 127.0.0.1:50051)
+  --insecure-demo   allow demo keys, with a modulus below 2048 bits";
+
+const DEFAULT_ADDR: &str = "127.0.0.1:50051";
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(SocketAddr, RabinService), String> {
+    let mut addr = DEFAULT_ADDR.to_string();
+    let mut service = RabinService::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--listen" => addr = args.next().ok_or("--listen needs an address")?,
+            "--insecure-demo" => service = service.allow_insecure(InsecureDemo),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+    let addr = addr.parse().map_err(|_| format!("'{}' is not a socket address", addr))?;
+    Ok((addr, service))
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let (addr, service) = match parse_args(std::env::args().skip(1)) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+    log::info!("serving rabin.v1.Rabin on {}", addr);
+    match Server::builder().add_service(RabinServer::new(service)).serve(addr).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
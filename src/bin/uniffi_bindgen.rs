@@ -0,0 +1,5 @@
+// uniffi-bindgen, pinned to the uniffi version the library is built with, for generating the
+// Swift and Kotlin bindings; see src/mobile.rs
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
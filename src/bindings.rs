@@ -0,0 +1,71 @@
+// Key loading shared by the foreign-language bindings: the browser (wasm), C (ffi) and
+// Swift/Kotlin (uniffi). All of them take keys as PEM text and refuse demo-sized keys until the
+// host program opts in, the counterpart of the CLI's --insecure-demo.
+//
+// Each binding owns one OptIn. A program may link more than one binding, e.g. an Android app
+// with a C component, and opting in through one API leaves the others strict.
+
+#[cfg(any(feature = "wasm", feature = "uniffi"))]
+use crate::encoding::Alphabet;
+use crate::error::RabinError;
+use crate::keys::{InsecureDemo, PrivateKey, PublicKey};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Off until allow() is called, and never switched back
+pub(crate) struct OptIn(AtomicBool);
+
+impl OptIn {
+    pub(crate) const fn new() -> Self {
+        OptIn(AtomicBool::new(false))
+    }
+
+    pub(crate) fn allow(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> Option<InsecureDemo> {
+        self.0.load(Ordering::Relaxed).then_some(InsecureDemo)
+    }
+
+    // Private keys are PKCS#8, as in the CLI
+    pub(crate) fn load_private(&self, pem: &str) -> Result<PrivateKey, RabinError> {
+        let key = PrivateKey::from_pkcs8_pem(pem)?;
+        Ok(match self.get() {
+            Some(opt_in) => key.allow_insecure(opt_in),
+            None => key,
+        })
+    }
+
+    pub(crate) fn load_public(&self, pem: &str) -> Result<PublicKey, RabinError> {
+        let key = PublicKey::from_pem(pem)?;
+        Ok(match self.get() {
+            Some(opt_in) => key.allow_insecure(opt_in),
+            None => key,
+        })
+    }
+}
+
+// The alphabet behind encode and decode: the given symbols in digit order, or the default set
+#[cfg(any(feature = "wasm", feature = "uniffi"))]
+pub(crate) fn alphabet(digits: Option<String>) -> Result<Alphabet, RabinError> {
+    match digits {
+        Some(digits) => Ok(Alphabet::new(&digits)?),
+        None => Ok(Alphabet::default_symbols().clone()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opt_in_is_per_instance() {
+        let (strict, relaxed) = (OptIn::new(), OptIn::new());
+        relaxed.allow();
+        let pem = PrivateKey::generate(256).to_pkcs8_pem();
+        assert_eq!(strict.get(), None);
+        assert_eq!(strict.load_private(&pem).unwrap().check_size(), Err(RabinError::InsecureKey(512)));
+        assert!(relaxed.load_private(&pem).unwrap().check_size().is_ok());
+    }
+}
@@ -0,0 +1,180 @@
+// Blind Rabin signatures. The requester hides the salted message hash h behind a random square,
+// b = h * r^2 mod n, and the signer returns a square root of b without learning anything about
+// h. Dividing by r gives a square root of h, which is an ordinary Signature.
+//
+// WARNING: sign_blinded is a square-root oracle. A requester who submits x^2 for a random x of
+// their own gets back a root other than ±x half the time, and gcd(x - root, n) is then a factor
+// of n. Only the interactive protocol is modelled here; never expose sign_blinded to parties
+// who are not trusted with the key. (See test_sign_blinded_is_a_square_root_oracle.)
+//
+// Not every h is a square, and the requester cannot tell without the factors. blind() filters
+// out hashes with Jacobi symbol -1; of the rest, half are rejected by the signer with
+// NotAResidue, and the requester simply blinds again with a new salt.
+
+use crate::ct::ct_eq_int;
+use crate::encoding::modulus_len;
+use crate::error::RabinError;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::math::{gcd, jacobi, mod_inverse};
+use crate::metadata::KeyUsage;
+use crate::rabin::verify_root;
+use crate::secret::Secret;
+use crate::signature::{message_hash, Signature, SALT_LEN};
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::One;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlindedMessage(BigInt);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlindedSignature(BigInt);
+
+// Kept by the requester between blind and unblind
+#[derive(Debug, Clone)]
+pub struct BlindingFactor {
+    salt: [u8; SALT_LEN],
+    hash: BigInt,
+    r_inverse: BigInt,
+}
+
+impl BlindedMessage {
+    pub fn new(value: BigInt) -> Self {
+        BlindedMessage(value)
+    }
+
+    pub fn value(&self) -> &BigInt {
+        &self.0
+    }
+}
+
+impl BlindedSignature {
+    pub fn new(value: BigInt) -> Self {
+        BlindedSignature(value)
+    }
+
+    pub fn value(&self) -> &BigInt {
+        &self.0
+    }
+}
+
+// Requester, step 1: salt and hash the message, then blind it for the signer
+pub fn blind(key: &PublicKey, message: &[u8]) -> Result<(BlindedMessage, BlindingFactor), RabinError> {
+    key.check_usage(KeyUsage::Sign)?;
+    let n = key.n();
+    let mut rng = OsRng;
+
+    let (salt, hash) = loop {
+        let mut salt = [0u8; SALT_LEN];
+        rng.fill_bytes(&mut salt);
+        let hash = message_hash(n, &salt, message)?;
+        if jacobi(&hash, n) == 1 {
+            break (salt, hash);
+        }
+    };
+    let r = loop {
+        let r = rng.gen_bigint_range(&BigInt::from(2), n);
+        if gcd(&r, n).is_one() {
+            break r;
+        }
+    };
+
+    let blinded = &hash * &r * &r % n;
+    let r_inverse = mod_inverse(&r, n)?;
+    Ok((BlindedMessage(blinded), BlindingFactor { salt, hash, r_inverse }))
+}
+
+// Signer: returns a square root of the blinded value, or NotAResidue if it has none
+pub fn sign_blinded(key: &PrivateKey, blinded: &BlindedMessage) -> Result<BlindedSignature, RabinError> {
+    key.check_usage(KeyUsage::Sign)?;
+    let value = &blinded.0;
+    if *value <= BigInt::one() || value >= key.n() {
+        return Err(RabinError::MessageOutOfRange);
+    }
+    let roots = Secret::new(key.square_roots(value)?);
+    verify_root(&roots[0], value, key.n())?;
+    Ok(BlindedSignature(roots[0].clone()))
+}
+
+// Requester, step 2: strip the blinding factor. The result is an ordinary Signature over the
+// message passed to blind, checked here so a misbehaving signer is caught immediately.
+pub fn unblind(
+    key: &PublicKey,
+    blinded: &BlindedSignature,
+    factor: BlindingFactor,
+) -> Result<Signature, RabinError> {
+    let n = key.n();
+    let root = &blinded.0 * &factor.r_inverse % n;
+    if !ct_eq_int(&(&root * &root % n), &factor.hash, modulus_len(n)) {
+        return Err(RabinError::InvalidSignature);
+    }
+    Ok(Signature::from_parts(factor.salt, root))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+    use rand::thread_rng;
+
+    // Runs the protocol to completion, blinding again whenever the signer finds no root
+    fn blind_sign(key: &PrivateKey, message: &[u8]) -> Signature {
+        let public = key.public_key();
+        loop {
+            let (blinded, factor) = blind(&public, message).unwrap();
+            match sign_blinded(key, &blinded) {
+                Ok(signature) => return unblind(&public, &signature, factor).unwrap(),
+                Err(RabinError::NotAResidue) => continue,
+                Err(err) => panic!("unexpected signer error: {}", err),
+            }
+        }
+    }
+
+    #[test]
+    fn test_blind_signature_round_trip() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let public = key.public_key();
+        let signature = blind_sign(&key, b"vote for candidate 3");
+
+        assert_eq!(signature.verify(&public, b"vote for candidate 3"), Ok(()));
+        assert_eq!(signature.verify(&public, b"vote for candidate 4"), Err(RabinError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_blinding_hides_the_message() {
+        let public = PrivateKey::generate(256).allow_insecure(InsecureDemo).public_key();
+        let (first, _) = blind(&public, b"same message").unwrap();
+        let (second, _) = blind(&public, b"same message").unwrap();
+        assert_ne!(first, second, "Blinding the same message twice should look unrelated");
+    }
+
+    #[test]
+    fn test_unblind_rejects_a_bad_signer_response() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let public = key.public_key();
+        let (_, factor) = blind(&public, b"message").unwrap();
+        let bogus = BlindedSignature::new(BigInt::from(12345));
+        assert_eq!(unblind(&public, &bogus, factor).unwrap_err(), RabinError::InvalidSignature);
+    }
+
+    #[test]
+    fn test_sign_blinded_is_a_square_root_oracle() {
+        // Demonstrates the warning at the top of this module: submitting squares of our own
+        // random values recovers a factor of n within a few requests
+        let key = PrivateKey::generate(128).allow_insecure(InsecureDemo);
+        let n = key.n();
+        let mut rng = thread_rng();
+        for _ in 0..64 {
+            let x = rng.gen_bigint_range(&BigInt::from(2), n);
+            let root = sign_blinded(&key, &BlindedMessage::new(&x * &x % n)).unwrap();
+            let factor = gcd(&(&x - root.value()), n);
+            if !factor.is_one() && factor != *n {
+                assert!(factor == *key.p() || factor == *key.q());
+                return;
+            }
+        }
+        panic!("64 requests should have revealed a factor");
+    }
+}
@@ -0,0 +1,221 @@
+// Block mode: textbook Rabin applied directly to a long message, cut into chunks that fit below
+// n. Each chunk becomes one block, one byte shorter than n:
+//
+//   0x01 || chunk length (2 bytes) || chunk || zero fill || SHA-256(index || last || chunk)[..4]
+//
+// The check bytes pick the right one of the four square roots on decryption, and binding the
+// block index and a last-block flag into them makes reordered, duplicated or truncated block
+// lists fail to decrypt. Blocks are independent, so both directions are spread over rayon's
+// thread pool; the output keeps the input order.
+//
+// There is no randomization: the same chunk at the same position always encrypts the same way.
+// This exists for teaching and for measuring bulk Rabin throughput; real data belongs in an
+// Envelope.
+
+use crate::ct::{ct_eq, ct_is_zero};
+use crate::encoding::{from_be_bytes, modulus_len, to_fixed_be_bytes, Alphabet, FixedWidth};
+use crate::error::RabinError;
+use crate::hash::sha256;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::metadata::KeyUsage;
+use crate::secret::Secret;
+use num_bigint::{BigInt, Sign};
+use rayon::prelude::*;
+
+const MARKER: u8 = 0x01;
+const LENGTH_LEN: usize = 2;
+const CHECK_LEN: usize = 4;
+
+fn block_len(n: &BigInt) -> usize {
+    modulus_len(n).saturating_sub(1)
+}
+
+// Message bytes carried by each block under the modulus n
+pub fn block_capacity(n: &BigInt) -> Result<usize, RabinError> {
+    block_len(n)
+        .checked_sub(1 + LENGTH_LEN + CHECK_LEN)
+        .filter(|capacity| *capacity > 0)
+        .map(|capacity| capacity.min(u16::MAX as usize))
+        .ok_or(RabinError::ModulusTooSmall)
+}
+
+fn block_check(index: usize, last: bool, chunk: &[u8]) -> [u8; CHECK_LEN] {
+    let digest = sha256(&[&(index as u64).to_be_bytes()[..], &[u8::from(last)], chunk].concat());
+    digest[..CHECK_LEN].try_into().unwrap()
+}
+
+fn encode_block(n: &BigInt, index: usize, last: bool, chunk: &[u8]) -> BigInt {
+    let len = block_len(n);
+    let mut block = Vec::with_capacity(len);
+    block.push(MARKER);
+    block.extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+    block.extend_from_slice(chunk);
+    block.resize(len - CHECK_LEN, 0);
+    block.extend_from_slice(&block_check(index, last, chunk));
+    from_be_bytes(&block)
+}
+
+fn decode_block(n: &BigInt, index: usize, last: bool, candidate: &BigInt) -> Option<Vec<u8>> {
+    let block = to_fixed_be_bytes(candidate, block_len(n))?;
+    let body = &block[1 + LENGTH_LEN..block.len() - CHECK_LEN];
+    let declared_len = u16::from_be_bytes([block[1], block[2]]) as usize;
+    let chunk_len = declared_len.min(body.len());
+    let chunk = &body[..chunk_len];
+    // Marker, filler and check are all verified before any of them decides
+    let valid = (block[0] == MARKER)
+        & (declared_len == chunk_len)
+        & ct_is_zero(&body[chunk_len..])
+        & ct_eq(&block_check(index, last, chunk), &block[block.len() - CHECK_LEN..]);
+    valid.then(|| chunk.to_vec())
+}
+
+// One ciphertext per chunk; an empty message still produces one (empty) block
+pub fn encrypt_blocks(key: &PublicKey, message: &[u8]) -> Result<Vec<BigInt>, RabinError> {
+    key.check_usage(KeyUsage::Encrypt)?;
+    let n = key.n();
+    let capacity = block_capacity(n)?;
+    let chunks: Vec<&[u8]> = if message.is_empty() {
+        vec![&[]]
+    } else {
+        message.chunks(capacity).collect()
+    };
+    let last = chunks.len() - 1;
+    let context = key.barrett()?;
+    Ok(chunks
+        .par_iter()
+        .enumerate()
+        .map(|(index, chunk)| context.encrypt(&encode_block(n, index, index == last, chunk)))
+        .collect())
+}
+
+pub fn decrypt_blocks(key: &PrivateKey, ciphertexts: &[BigInt]) -> Result<Vec<u8>, RabinError> {
+    key.check_usage(KeyUsage::Encrypt)?;
+    if ciphertexts.is_empty() {
+        return Err(RabinError::DecryptionFailed);
+    }
+    let n = key.n();
+    let last = ciphertexts.len() - 1;
+    let chunks = ciphertexts
+        .par_iter()
+        .enumerate()
+        .map(|(index, ciphertext)| {
+            if ciphertext.sign() == Sign::Minus || ciphertext >= n {
+                return Err(RabinError::MessageOutOfRange);
+            }
+            Secret::new(key.square_roots(ciphertext)?)
+                .iter()
+                .find_map(|candidate| decode_block(n, index, index == last, candidate))
+                .ok_or(RabinError::DecryptionFailed)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(chunks.concat())
+}
+
+// Textbook Rabin over symbol blocks from FixedWidth, each guaranteed to stay below n. There is no
+// redundancy to pick a square root with, so decryption yields four candidates per block and the
+// caller chooses, with FixedWidth::decode rejecting candidates that no text encodes to.
+pub fn encrypt_symbol_blocks(key: &PublicKey, text: &str, alphabet: &Alphabet) -> Result<Vec<BigInt>, RabinError> {
+    key.check_usage(KeyUsage::Encrypt)?;
+    let blocks = FixedWidth::new(alphabet, key.n())?.encode(text)?;
+    let context = key.barrett()?;
+    Ok(blocks.par_iter().map(|block| context.encrypt(block)).collect())
+}
+
+// Ciphertexts laid end to end, each left-padded to the byte length of n
+pub fn ciphertexts_to_bytes(n: &BigInt, ciphertexts: &[BigInt]) -> Result<Vec<u8>, RabinError> {
+    let width = modulus_len(n);
+    let mut out = Vec::with_capacity(width * ciphertexts.len());
+    for ciphertext in ciphertexts {
+        if ciphertext >= n {
+            return Err(RabinError::MessageOutOfRange);
+        }
+        out.extend(to_fixed_be_bytes(ciphertext, width).ok_or(RabinError::MessageOutOfRange)?);
+    }
+    Ok(out)
+}
+
+pub fn ciphertexts_from_bytes(n: &BigInt, bytes: &[u8]) -> Result<Vec<BigInt>, RabinError> {
+    let width = modulus_len(n);
+    if width == 0 || !bytes.len().is_multiple_of(width) {
+        return Err(RabinError::DecryptionFailed);
+    }
+    Ok(bytes.chunks(width).map(from_be_bytes).collect())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+
+    #[test]
+    fn test_round_trip_across_many_blocks() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let capacity = block_capacity(key.n()).unwrap();
+        let message: Vec<u8> = (0..capacity * 9 + 5).map(|i| (i * 7) as u8).collect();
+
+        let ciphertexts = encrypt_blocks(&key.public_key(), &message).unwrap();
+        assert_eq!(ciphertexts.len(), 10);
+        assert_eq!(decrypt_blocks(&key, &ciphertexts).unwrap(), message);
+
+        let empty = encrypt_blocks(&key.public_key(), b"").unwrap();
+        assert_eq!(empty.len(), 1);
+        assert!(decrypt_blocks(&key, &empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reordered_or_truncated_blocks_fail() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let message = vec![0x5a; block_capacity(key.n()).unwrap() * 3];
+        let ciphertexts = encrypt_blocks(&key.public_key(), &message).unwrap();
+
+        let mut swapped = ciphertexts.clone();
+        swapped.swap(0, 1);
+        assert_eq!(decrypt_blocks(&key, &swapped), Err(RabinError::DecryptionFailed));
+        assert_eq!(decrypt_blocks(&key, &ciphertexts[..2]), Err(RabinError::DecryptionFailed));
+        assert_eq!(decrypt_blocks(&key, &[]), Err(RabinError::DecryptionFailed));
+    }
+
+    #[test]
+    fn test_ciphertext_bytes_round_trip() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let message = vec![0xa5; block_capacity(key.n()).unwrap() * 2 + 1];
+        let ciphertexts = encrypt_blocks(&key.public_key(), &message).unwrap();
+
+        let bytes = ciphertexts_to_bytes(key.n(), &ciphertexts).unwrap();
+        assert_eq!(bytes.len(), 3 * modulus_len(key.n()), "Every ciphertext should take the full modulus width");
+        let decoded = ciphertexts_from_bytes(key.n(), &bytes).unwrap();
+        assert_eq!(decoded, ciphertexts);
+        assert_eq!(decrypt_blocks(&key, &decoded).unwrap(), message);
+
+        assert_eq!(ciphertexts_from_bytes(key.n(), &bytes[1..]), Err(RabinError::DecryptionFailed));
+        assert_eq!(
+            ciphertexts_to_bytes(key.n(), &[key.n().clone()]),
+            Err(RabinError::MessageOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_symbol_blocks_decrypt_to_their_text() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let alphabet = Alphabet::default_symbols();
+        let layout = FixedWidth::new(alphabet, key.n()).unwrap();
+        let text: String = "Non scholae, sed vitae discimus. ".repeat(4);
+        let ciphertexts = encrypt_symbol_blocks(&key.public_key(), &text, alphabet).unwrap();
+        assert_eq!(ciphertexts.len(), text.len() / layout.width() + 1);
+
+        let blocks = layout.encode(&text).unwrap();
+        for (block, ciphertext) in blocks.iter().zip(&ciphertexts) {
+            assert!(key.decrypt(ciphertext).unwrap().contains(block), "The block should be one of the roots");
+        }
+        assert_eq!(layout.decode(&blocks).unwrap(), text);
+        assert!(encrypt_symbol_blocks(&key.public_key(), "not $ymbols", alphabet).is_err());
+    }
+
+    #[test]
+    fn test_small_modulus_is_rejected() {
+        let key = PrivateKey::from_primes(BigInt::from(43), BigInt::from(47)).unwrap().allow_insecure(InsecureDemo);
+        assert_eq!(block_capacity(key.n()), Err(RabinError::ModulusTooSmall));
+        assert!(encrypt_blocks(&key.public_key(), b"x").is_err());
+    }
+}
@@ -0,0 +1,1001 @@
+use log::info;
+use naive_rabin_cryptosystem::compat::{check_vectors, REFERENCE_VECTORS};
+use naive_rabin_cryptosystem::encoding::{Alphabet, Codec, Escaped, Hex, Utf8, DEFAULT_ESCAPE};
+use naive_rabin_cryptosystem::envelope::Envelope;
+use naive_rabin_cryptosystem::error::RabinError;
+use naive_rabin_cryptosystem::fiat_shamir::{Prover, Verifier};
+use naive_rabin_cryptosystem::keygen::{EntropySource, KeygenConfig, PrimeCongruence, PrimeKind};
+use naive_rabin_cryptosystem::keys::{InsecureDemo, PrivateKey, PublicKey, MIN_SECURE_BITS};
+use naive_rabin_cryptosystem::keystore::Keystore;
+use naive_rabin_cryptosystem::metadata::{now, KeyMetadata, KeyUsage};
+use naive_rabin_cryptosystem::mnemonic::{generate_mnemonic, DEFAULT_ENTROPY_LEN};
+use naive_rabin_cryptosystem::rabin::{
+    decrypt, decrypt_str, decrypt_str_tagged, default_workers, encrypt, encrypt_str, encrypt_str_tagged,
+    generate_keypair,
+};
+use naive_rabin_cryptosystem::redact::{secret, set_log_secrets};
+use naive_rabin_cryptosystem::shamir::Share;
+use naive_rabin_cryptosystem::signature::Signature;
+use naive_rabin_cryptosystem::stream::{decrypt_stream_body, encrypt_stream, StreamConfig, STREAM_MAGIC};
+use num_bigint::BigInt;
+use std::error::Error;
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+pub type CliResult = Result<(), Box<dyn Error>>;
+
+// Per prime, for a 2048-bit modulus
+pub const DEFAULT_BITS: usize = 1024;
+// Appended after the END line of PEM output made with a demo key; PEM readers ignore it
+const DEMO_NOTICE: &str = "insecure demo key: for testing only, never for real data";
+// Pixels per QR module in exported PNGs
+#[cfg(feature = "qr")]
+const QR_PNG_SCALE: usize = 8;
+#[cfg(not(feature = "qr"))]
+const NO_QR_SUPPORT: &str = "this build has no QR code support (rebuild with --features qr)";
+#[cfg(not(any(unix, feature = "windows-daemon")))]
+const NO_DAEMON_SUPPORT: &str = "this build has no daemon support (on Windows, rebuild with --features windows-daemon)";
+#[cfg(not(feature = "http"))]
+const NO_HTTP_SUPPORT: &str = "this build has no HTTP server (rebuild with --features http)";
+
+const USAGE: &str = "usage: rabin <command> [options]
+
+commands:
+  demo                                  encrypt and decrypt a small number with a fresh key
+  keys create <name> [--bits N] [--default] [--mnemonic] [--qr]
+              [--expires DURATION] [--usage encrypt|sign|encrypt,sign]
+              [--primes random|safe|strong] [--entropy os|thread|getrandom]
+              [--any-congruence] [--workers N]
+                                        safe and strong primes resist special-purpose
+                                        factoring but take much longer to generate;
+                                        --any-congruence drops the p ≡ 3 (mod 4) rule;
+                                        --workers sets the parallel prime searches
+  keys recover <name> [--bits N] [--default]
+                                        rebuild a key from a recovery phrase read on stdin
+  keys import <name> (--p P --q Q | --n N --p P) [--default]
+                                        store a key given by its decimal components
+  keys import [<name>] --qr IMAGE       store a public key scanned from a PNG QR code,
+                                        named after its short fingerprint by default
+  keys export <name> [--qr] [--out FILE]
+                                        print the public key as PEM or as a QR code
+                                        (terminal text, or PNG with --out)
+  keys list
+  keys delete <name>
+  keys default [<name>]                 show or set the default key
+  keygen, import                        shorthands for keys create and keys import,
+                                        e.g. rabin keygen alice --qr, rabin import --qr key.png
+  pubkey [--in FILE] [--out FILE]       write the public half of a PEM private key
+  encrypt [--to KEY] [--armor] [--stream [--workers N]] [--in FILE] [--out FILE]
+                                        --stream encrypts in chunks on all cores with
+                                        constant memory, for large files
+  decrypt [--key KEY] [--workers N] [--in FILE] [--out FILE]
+                                        envelopes and streams are told apart automatically
+  encrypt --codec CODEC [--alphabet-file FILE] [--untagged] [--to KEY] [--in FILE] [--out FILE]
+  decrypt --codec CODEC [--alphabet-file FILE] [--untagged] [--key KEY] [--in FILE] [--out FILE]
+                                        a short text as one number: the ciphertext is
+                                        written in decimal, with an integrity tag that
+                                        decryption checks before printing the text;
+                                        --untagged is textbook Rabin, and decryption
+                                        then prints every candidate the codec can read,
+                                        one per line (never decrypt untagged numbers
+                                        from others: their roots can reveal the key);
+                                        CODEC is alphabet, escaped (the alphabet, with
+                                        other characters written as |xx bytes), utf8
+                                        or hex; --alphabet-file reads the alphabet's
+                                        symbols from a text file instead of using the
+                                        built-in one, and implies --codec alphabet
+  sign [--key KEY] [--armor] [--in FILE] [--out FILE]
+                                        write a detached signature of the input
+  verify [--key KEY] --sig FILE [--in FILE]
+                                        check a detached signature against the input
+  rekey --old KEY --new KEY <files...>  re-encrypt envelopes in place for a new key
+  shares split [--key KEY] --threshold K --shares N --out-dir DIR
+  shares combine <share files...> [--out FILE]
+  fiat-shamir [--key KEY] [--identity NAME] [--rounds N]
+                                        run Fiat-Shamir identification locally, with the
+                                        key acting as the trusted center
+  bench [--save FILE] [--check FILE] [--threshold PERCENT]
+                                        time the core operations; --check fails when any
+                                        is slower than the saved baseline by more than
+                                        PERCENT (default 10)
+  bench --memory [--bits N]             peak memory of key generation and decryption for
+                                        an N-bit modulus (default 4096); heap figures need
+                                        a build with --features mem-stats
+  bench --timing [--samples N]          test whether decryption time depends on the
+                                        ciphertext (dudect-style, N runs per target,
+                                        default 10000); needs --features timing
+  serve --http PORT [--bind ADDR] [--key KEY]
+                                        JSON endpoints on ADDR (default 127.0.0.1):
+                                        GET /public-key, POST /encrypt and POST /decrypt
+                                        with the key; needs --features http
+  daemon [--socket PATH]                serve JSON-RPC 2.0, one request per line, on a
+                                        Unix socket (default daemon.sock in the keystore)
+                                        or on Windows, with the windows-daemon feature, a
+                                        named pipe (default \\\\.\\pipe\\rabin-daemon);
+                                        loaded keys stay in memory until it exits. Methods:
+                                        keygen, keys.list, keys.load, keys.unload,
+                                        public_key, encrypt, decrypt, sign, verify, encode,
+                                        decode, selftest
+  selftest [--reference FILE]           check key validation, decryption and the alphabet
+                                        codec against vectors from an independent
+                                        implementation (compat/reference.py); FILE
+                                        replaces the built-in vectors. Also health-checks
+                                        the operating system's random number generator
+
+KEY is a keystore name or a path to a PEM key file; the default key is used when omitted.
+
+global options:
+  --keystore <dir>                      keystore root (default: $RABIN_HOME or ~/.rabin)
+  --insecure-demo                       allow demo keys, with a modulus below 2048 bits;
+                                        their output is marked as insecure
+  --log-secrets                         write private values (roots, candidates, decoded
+                                        numbers) to the log instead of <redacted>;
+                                        for debugging this program only";
+
+// Minimal argument handling: options are pulled out by name, the rest stays positional
+#[derive(Clone)]
+pub struct Args {
+    items: Vec<String>,
+    // The global --keystore option, taken out up front so every command can resolve key names
+    keystore_dir: Option<String>,
+    // The global --insecure-demo flag
+    insecure: Option<InsecureDemo>,
+    // The global --log-secrets flag
+    log_secrets: bool,
+}
+
+impl Args {
+    pub fn new(items: Vec<String>) -> Result<Self, Box<dyn Error>> {
+        let mut args = Args {
+            items,
+            keystore_dir: None,
+            insecure: None,
+            log_secrets: false,
+        };
+        args.keystore_dir = args.option("keystore")?;
+        args.insecure = args.flag("insecure-demo").then_some(InsecureDemo);
+        args.log_secrets = args.flag("log-secrets");
+        Ok(args)
+    }
+
+    pub fn option(&mut self, name: &str) -> Result<Option<String>, Box<dyn Error>> {
+        let long = format!("--{}", name);
+        let prefix = format!("--{}=", name);
+        if let Some(pos) = self.items.iter().position(|item| item.starts_with(&prefix)) {
+            let item = self.items.remove(pos);
+            return Ok(Some(item[prefix.len()..].to_string()));
+        }
+        match self.items.iter().position(|item| *item == long) {
+            Some(pos) if pos + 1 < self.items.len() => {
+                self.items.remove(pos);
+                Ok(Some(self.items.remove(pos)))
+            }
+            Some(_) => Err(format!("option {} needs a value", long).into()),
+            None => Ok(None),
+        }
+    }
+
+    #[cfg(any(unix, feature = "windows-daemon"))]
+    pub fn insecure(&self) -> Option<InsecureDemo> {
+        self.insecure
+    }
+
+    pub fn flag(&mut self, name: &str) -> bool {
+        let long = format!("--{}", name);
+        match self.items.iter().position(|item| *item == long) {
+            Some(pos) => {
+                self.items.remove(pos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn positional(&mut self) -> Option<String> {
+        if self.items.is_empty() {
+            None
+        } else {
+            Some(self.items.remove(0))
+        }
+    }
+
+    pub fn required(&mut self, what: &str) -> Result<String, Box<dyn Error>> {
+        self.positional().ok_or_else(|| format!("missing {}", what).into())
+    }
+
+    fn with_subcommand(mut self, name: &str) -> Self {
+        self.items.insert(0, name.to_string());
+        self
+    }
+
+    pub fn rest(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.items)
+    }
+
+    pub fn finish(self) -> CliResult {
+        match self.items.first() {
+            Some(extra) => Err(format!("unexpected argument '{}'", extra).into()),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn open_keystore(args: &Args) -> Result<Keystore, Box<dyn Error>> {
+    Ok(match &args.keystore_dir {
+        Some(dir) => Keystore::open(dir)?,
+        None => Keystore::open_default()?,
+    })
+}
+
+// Demo keys are refused unless --insecure-demo was given, and then warned about
+fn check_demo(insecure: Option<InsecureDemo>, demo: bool) -> CliResult {
+    if !demo {
+        return Ok(());
+    }
+    if insecure.is_none() {
+        return Err(format!(
+            "demo key: the modulus is below {} bits; pass --insecure-demo to use it anyway",
+            MIN_SECURE_BITS
+        )
+        .into());
+    }
+    eprintln!("warning: {}", DEMO_NOTICE);
+    Ok(())
+}
+
+// Marks PEM output made with a demo key
+fn annotate_demo(pem: String, demo: bool) -> String {
+    if demo {
+        format!("{}{}\n", pem, DEMO_NOTICE)
+    } else {
+        pem
+    }
+}
+
+// A key argument is a PEM file if such a path exists, otherwise a keystore name
+pub fn load_public_key(args: &Args, spec: Option<String>) -> Result<PublicKey, Box<dyn Error>> {
+    let key = if let Some(path) = spec.as_deref().filter(|spec| Path::new(spec).is_file()) {
+        let text = fs::read_to_string(path)?;
+        match PublicKey::from_pem(&text) {
+            Ok(key) => key,
+            Err(_) => PrivateKey::from_pkcs8_pem(&text)?.public_key(),
+        }
+    } else {
+        let store = open_keystore(args)?;
+        let name = store.resolve_name(spec.as_deref())?;
+        store.load_public(&name)?
+    };
+    check_demo(args.insecure, key.is_demo())?;
+    Ok(match args.insecure {
+        Some(opt_in) => key.allow_insecure(opt_in),
+        None => key,
+    })
+}
+
+pub fn load_private_key(args: &Args, spec: Option<String>) -> Result<PrivateKey, Box<dyn Error>> {
+    let key = if let Some(path) = spec.as_deref().filter(|spec| Path::new(spec).is_file()) {
+        PrivateKey::from_pkcs8_pem(&fs::read_to_string(path)?)?
+    } else {
+        let store = open_keystore(args)?;
+        let name = store.resolve_name(spec.as_deref())?;
+        store.load_private(&name)?
+    };
+    check_demo(args.insecure, key.is_demo())?;
+    Ok(match args.insecure {
+        Some(opt_in) => key.allow_insecure(opt_in),
+        None => key,
+    })
+}
+
+fn read_input(path: Option<&str>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut data = Vec::new();
+    match path {
+        Some(path) => data = fs::read(path)?,
+        None => {
+            std::io::stdin().read_to_end(&mut data)?;
+        }
+    }
+    Ok(data)
+}
+
+fn write_output(path: Option<&str>, data: &[u8]) -> CliResult {
+    match path {
+        Some(path) => fs::write(path, data)?,
+        None => std::io::stdout().write_all(data)?,
+    }
+    Ok(())
+}
+
+fn temporary_sibling(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let file_name = path.file_name().ok_or("not a file path")?.to_string_lossy();
+    Ok(path.with_file_name(format!(".{}.rabin-tmp", file_name)))
+}
+
+// Replace a file by writing a sibling temporary file and renaming it over the original,
+// so readers never observe a half-written file
+pub fn write_atomically(path: &Path, data: &[u8]) -> CliResult {
+    let tmp = temporary_sibling(path)?;
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+fn open_input(path: Option<&str>) -> Result<Box<dyn Read + Send>, Box<dyn Error>> {
+    Ok(match path {
+        Some(path) => Box::new(BufReader::new(fs::File::open(path)?)),
+        None => Box::new(std::io::stdin()),
+    })
+}
+
+// Streams output into a file through a temporary sibling that only replaces the target once
+// everything was written, so a failed decryption leaves no partial plaintext behind
+fn write_streamed(
+    path: Option<&str>,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), RabinError>,
+) -> CliResult {
+    let Some(path) = path else {
+        return Ok(write(&mut std::io::stdout().lock())?);
+    };
+    let path = Path::new(path);
+    let tmp = temporary_sibling(path)?;
+    let mut file = BufWriter::new(fs::File::create(&tmp)?);
+    let result = write(&mut file).map_err(Box::<dyn Error>::from).and_then(|_| {
+        file.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+        Ok(fs::rename(&tmp, path)?)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+fn parse_bits(args: &mut Args) -> Result<usize, Box<dyn Error>> {
+    match args.option("bits")? {
+        Some(bits) => Ok(bits.parse().map_err(|_| format!("invalid bit size '{}'", bits))?),
+        None => Ok(DEFAULT_BITS),
+    }
+}
+
+fn parse_workers(args: &mut Args) -> Result<usize, Box<dyn Error>> {
+    match args.option("workers")? {
+        Some(workers) => match workers.parse() {
+            Ok(workers) if workers > 0 => Ok(workers),
+            _ => Err(format!("invalid worker count '{}'", workers).into()),
+        },
+        None => Ok(default_workers()),
+    }
+}
+
+#[cfg(feature = "qr")]
+fn write_qr(key: &PublicKey, path: Option<&str>) -> CliResult {
+    let code = key.to_qr()?;
+    match path {
+        Some(path) => write_output(Some(path), &code.to_png(QR_PNG_SCALE)),
+        None => {
+            print!("{}", code.to_text());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "qr")]
+fn read_qr(path: &str) -> Result<PublicKey, Box<dyn Error>> {
+    Ok(PublicKey::from_qr_png(&fs::read(path)?)?)
+}
+
+#[cfg(not(feature = "qr"))]
+fn write_qr(_: &PublicKey, _: Option<&str>) -> CliResult {
+    Err(NO_QR_SUPPORT.into())
+}
+
+#[cfg(not(feature = "qr"))]
+fn read_qr(_: &str) -> Result<PublicKey, Box<dyn Error>> {
+    Err(NO_QR_SUPPORT.into())
+}
+
+fn parse_prime_kind(args: &mut Args) -> Result<PrimeKind, Box<dyn Error>> {
+    match args.option("primes")?.as_deref() {
+        None | Some("random") => Ok(PrimeKind::Random),
+        Some("safe") => Ok(PrimeKind::Safe),
+        Some("strong") => Ok(PrimeKind::Strong),
+        Some(other) => Err(format!("unknown prime kind '{}'", other).into()),
+    }
+}
+
+fn parse_entropy(args: &mut Args) -> Result<EntropySource, Box<dyn Error>> {
+    match args.option("entropy")?.as_deref() {
+        Some("thread") => Ok(EntropySource::Thread),
+        None | Some("os") => Ok(EntropySource::Os),
+        Some("getrandom") => Ok(EntropySource::Getrandom),
+        Some(other) => Err(format!("unknown entropy source '{}'", other).into()),
+    }
+}
+
+fn parse_metadata(args: &mut Args, bits: usize) -> Result<KeyMetadata, Box<dyn Error>> {
+    let mut metadata = KeyMetadata::new(Some(bits));
+    if let Some(lifetime) = args.option("expires")? {
+        let lifetime = humantime::parse_duration(&lifetime)
+            .map_err(|err| format!("invalid --expires '{}': {}", lifetime, err))?;
+        metadata = metadata.expires_after(lifetime);
+    }
+    if let Some(usage) = args.option("usage")? {
+        let usages = usage
+            .split(',')
+            .map(|item| match item.trim() {
+                "encrypt" => Ok(KeyUsage::Encrypt),
+                "sign" => Ok(KeyUsage::Sign),
+                other => Err(format!("unknown key usage '{}'", other)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        metadata = metadata.restrict_to(&usages);
+    }
+    Ok(metadata)
+}
+
+fn parse_codec(args: &mut Args) -> Result<Option<Box<dyn Codec>>, Box<dyn Error>> {
+    let codec = args.option("codec")?;
+    let alphabet = match args.option("alphabet-file")? {
+        Some(path) => Some(Alphabet::from_file(&path).map_err(|err| format!("alphabet file {}: {}", path, err))?),
+        None => None,
+    };
+    match (codec.as_deref(), alphabet) {
+        (None, None) => Ok(None),
+        (None | Some("alphabet"), alphabet) => {
+            Ok(Some(Box::new(alphabet.unwrap_or_else(|| Alphabet::default_symbols().clone()))))
+        }
+        (Some("escaped"), None) => Ok(Some(Box::new(Escaped::default_symbols().clone()))),
+        (Some("escaped"), Some(alphabet)) => Ok(Some(Box::new(Escaped::new(alphabet, DEFAULT_ESCAPE)?))),
+        (Some("utf8" | "hex"), Some(_)) => Err("--alphabet-file only applies to the alphabet and escaped codecs".into()),
+        (Some("utf8"), None) => Ok(Some(Box::new(Utf8))),
+        (Some("hex"), None) => Ok(Some(Box::new(Hex(None)))),
+        (Some(other), _) => Err(format!("unknown codec '{}'", other).into()),
+    }
+}
+
+// Text read for a codec, without the line break an editor or echo leaves at the end
+fn read_text(path: Option<&str>) -> Result<String, Box<dyn Error>> {
+    let text = String::from_utf8(read_input(path)?)?;
+    Ok(text.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn format_timestamp(seconds: u64) -> String {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(seconds)).to_string()
+}
+
+fn parse_decimal(text: &str) -> Result<BigInt, Box<dyn Error>> {
+    Ok(BigInt::parse_bytes(text.as_bytes(), 10).ok_or_else(|| format!("invalid number '{}'", text))?)
+}
+
+pub fn run(items: Vec<String>) -> CliResult {
+    let mut args = Args::new(items)?;
+    set_log_secrets(args.log_secrets);
+    match args.positional().as_deref() {
+        None | Some("demo") => run_demo(args),
+        Some("keys") => run_keys(args),
+        // Shorthands for `keys create` and `keys import`
+        Some("keygen") => run_keys(args.with_subcommand("create")),
+        Some("import") => run_keys(args.with_subcommand("import")),
+        Some("pubkey") => run_pubkey(args),
+        Some("encrypt") => run_encrypt(args),
+        Some("decrypt") => run_decrypt(args),
+        Some("sign") => run_sign(args),
+        Some("verify") => run_verify(args),
+        Some("rekey") => run_rekey(args),
+        Some("shares") => run_shares(args),
+        Some("fiat-shamir") => run_fiat_shamir(args),
+        Some("bench") => crate::bench::run_bench(args),
+        Some("serve") => run_serve(args),
+        Some("selftest") => run_selftest(args),
+        Some("daemon") => run_daemon(args),
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(other) => Err(format!("unknown command '{}'\n\n{}", other, USAGE).into()),
+    }
+}
+
+#[cfg(any(unix, feature = "windows-daemon"))]
+fn run_daemon(args: Args) -> CliResult {
+    crate::daemon::run_daemon(args)
+}
+
+#[cfg(not(any(unix, feature = "windows-daemon")))]
+fn run_daemon(_: Args) -> CliResult {
+    Err(NO_DAEMON_SUPPORT.into())
+}
+
+#[cfg(feature = "http")]
+fn run_serve(args: Args) -> CliResult {
+    crate::serve::run_serve(args)
+}
+
+#[cfg(not(feature = "http"))]
+fn run_serve(_: Args) -> CliResult {
+    Err(NO_HTTP_SUPPORT.into())
+}
+
+fn run_selftest(mut args: Args) -> CliResult {
+    let reference = args.option("reference")?;
+    args.finish()?;
+    let vectors = match &reference {
+        Some(path) => fs::read_to_string(path).map_err(|err| format!("cannot read {}: {}", path, err))?,
+        None => REFERENCE_VECTORS.to_string(),
+    };
+    let report = check_vectors(&vectors)?;
+    println!("reference vectors from {}", report.generator);
+    for failure in &report.failures {
+        println!("FAILED {}", failure);
+    }
+    if !report.is_ok() {
+        return Err(format!("{} of {} reference checks failed", report.failures.len(), report.total()).into());
+    }
+    println!("all {} reference checks passed", report.total());
+    EntropySource::Os.self_test()?;
+    println!("the operating system's random number generator passed its health check");
+    Ok(())
+}
+
+// Follows a fingerprint printed for a demo key
+fn demo_suffix(demo: bool) -> &'static str {
+    if demo {
+        " (insecure demo key)"
+    } else {
+        ""
+    }
+}
+
+fn run_demo(args: Args) -> CliResult {
+    args.finish()?;
+    info!("Hello, Naive Rabin Cryptosystem Implementation...");
+
+    let (n, p, q) = generate_keypair(DEFAULT_BITS);
+
+    let message = BigInt::from(42u8);
+    let ciphertext = encrypt(&message, &n)?;
+    let plaintext_candidates = decrypt(&ciphertext, &p, &q)?;
+
+    info!("Public key (n): {}", n);
+    info!("Public key fingerprint: {}", PublicKey::new(n.clone()).fingerprint());
+    info!("Message: {}", message);
+    info!("Ciphertext: {}", ciphertext);
+    info!("Plaintext candidates: {}", secret(&format_args!("{:?}", plaintext_candidates)));
+    Ok(())
+}
+
+fn run_keys(mut args: Args) -> CliResult {
+    let store = open_keystore(&args)?;
+    let insecure = args.insecure;
+    match args.required("keys subcommand")?.as_str() {
+        "create" => {
+            let bits = parse_bits(&mut args)?;
+            let make_default = args.flag("default");
+            let with_mnemonic = args.flag("mnemonic");
+            let show_qr = args.flag("qr");
+            let metadata = parse_metadata(&mut args, bits)?;
+            let primes = parse_prime_kind(&mut args)?;
+            let entropy = parse_entropy(&mut args)?;
+            let workers = parse_workers(&mut args)?;
+            let congruence = if args.flag("any-congruence") {
+                PrimeCongruence::Any
+            } else {
+                PrimeCongruence::ThreeModFour
+            };
+            let name = args.required("key name")?;
+            args.finish()?;
+            if with_mnemonic && (primes != PrimeKind::Random || congruence != PrimeCongruence::ThreeModFour) {
+                return Err("--primes and --any-congruence cannot be combined with --mnemonic".into());
+            }
+            check_demo(insecure, KeygenConfig::new(bits).is_demo())?;
+
+            let key = if with_mnemonic {
+                // The phrase is the key's only entropy, drawn from the OS outside any KeygenConfig
+                EntropySource::Os.self_test()?;
+                let phrase = generate_mnemonic(DEFAULT_ENTROPY_LEN);
+                let key = PrivateKey::from_mnemonic(&phrase, bits)?.with_metadata(metadata);
+                store.import_private(&name, &key)?;
+                eprintln!("recovery phrase ({} bits, keep it secret):\n{}", bits, phrase);
+                key
+            } else {
+                let mut config = KeygenConfig::new(bits)
+                    .with_primes(primes)
+                    .with_congruence(congruence)
+                    .with_entropy(entropy)
+                    .with_workers(workers);
+                config.insecure = insecure;
+                let (key, report) = store.create_with_config(&name, &config, metadata)?;
+                eprintln!("generated {}", report);
+                key
+            };
+            if make_default || store.default_name()?.is_none() {
+                store.set_default(&name)?;
+            }
+            println!("{} {}{}", name, key.public_key().fingerprint(), demo_suffix(key.is_demo()));
+            if show_qr {
+                write_qr(&key.public_key(), None)?;
+            }
+        }
+        "recover" => {
+            let bits = parse_bits(&mut args)?;
+            let make_default = args.flag("default");
+            let name = args.required("key name")?;
+            args.finish()?;
+            check_demo(insecure, KeygenConfig::new(bits).is_demo())?;
+
+            // Read from stdin rather than argv so the phrase stays out of shell history
+            let phrase = String::from_utf8(read_input(None)?)?;
+            let key = PrivateKey::from_mnemonic(&phrase, bits)?;
+            store.import_private(&name, &key)?;
+            if make_default || store.default_name()?.is_none() {
+                store.set_default(&name)?;
+            }
+            println!("{} {}{}", name, key.public_key().fingerprint(), demo_suffix(key.is_demo()));
+        }
+        "import" => {
+            let n = args.option("n")?;
+            let p = args.option("p")?;
+            let q = args.option("q")?;
+            let qr = args.option("qr")?;
+            let make_default = args.flag("default");
+            let name = args.positional();
+            args.finish()?;
+
+            if let Some(image) = qr {
+                if n.is_some() || p.is_some() || q.is_some() {
+                    return Err("--qr cannot be combined with key components".into());
+                }
+                let public = read_qr(&image)?;
+                check_demo(insecure, public.is_demo())?;
+                // A scanned key is named after its fingerprint unless a name is given
+                let name = name.unwrap_or_else(|| public.fingerprint().short());
+                store.import_public(&name, &public)?;
+                println!("{} {}{}", name, public.fingerprint(), demo_suffix(public.is_demo()));
+                return Ok(());
+            }
+
+            let name = name.ok_or("missing key name")?;
+            let key = match (n, p, q) {
+                (None, Some(p), Some(q)) => PrivateKey::from_primes(parse_decimal(&p)?, parse_decimal(&q)?)?,
+                (Some(n), Some(p), None) => PrivateKey::from_modulus_and_prime(parse_decimal(&n)?, parse_decimal(&p)?)?,
+                _ => return Err("keys import needs either --p and --q, or --n and --p".into()),
+            };
+            let violations = key.validate();
+            if !violations.is_empty() {
+                let list: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                return Err(format!("refusing to import an invalid key: {}", list.join(", ")).into());
+            }
+            check_demo(insecure, key.is_demo())?;
+            store.import_private(&name, &key)?;
+            if make_default || store.default_name()?.is_none() {
+                store.set_default(&name)?;
+            }
+            println!("{} {}{}", name, key.public_key().fingerprint(), demo_suffix(key.is_demo()));
+        }
+        "export" => {
+            let as_qr = args.flag("qr");
+            let output = args.option("out")?;
+            let name = args.required("key name")?;
+            args.finish()?;
+
+            let public = store.load_public(&name)?;
+            if as_qr {
+                write_qr(&public, output.as_deref())?;
+            } else {
+                write_output(output.as_deref(), annotate_demo(public.to_pem(), public.is_demo()).as_bytes())?;
+            }
+        }
+        "list" => {
+            args.finish()?;
+            for entry in store.list()? {
+                let expiry = match entry.metadata.as_ref().and_then(|metadata| metadata.expires) {
+                    Some(expires) if expires <= now() => " expired".to_string(),
+                    Some(expires) => format!(" expires {}", format_timestamp(expires)),
+                    None => String::new(),
+                };
+                println!(
+                    "{}{:<16} {} {}{}{}",
+                    if entry.is_default { "* " } else { "  " },
+                    entry.name,
+                    entry.fingerprint.short(),
+                    if entry.has_private { "private" } else { "public" },
+                    if entry.demo { " demo" } else { "" },
+                    expiry
+                );
+            }
+        }
+        "delete" => {
+            let name = args.required("key name")?;
+            args.finish()?;
+            store.delete(&name)?;
+        }
+        "default" => match args.positional() {
+            Some(name) => {
+                args.finish()?;
+                store.set_default(&name)?;
+            }
+            None => match store.default_name()? {
+                Some(name) => println!("{}", name),
+                None => return Err("no default key set".into()),
+            },
+        },
+        other => return Err(format!("unknown keys subcommand '{}'", other).into()),
+    }
+    Ok(())
+}
+
+fn run_pubkey(mut args: Args) -> CliResult {
+    let input = args.option("in")?;
+    let output = args.option("out")?;
+    args.finish()?;
+
+    let text = String::from_utf8(read_input(input.as_deref())?)?;
+    let public = PrivateKey::from_pkcs8_pem(&text)?.public_key();
+    write_output(output.as_deref(), annotate_demo(public.to_pem(), public.is_demo()).as_bytes())
+}
+
+fn run_encrypt(mut args: Args) -> CliResult {
+    let untagged = args.flag("untagged");
+    let armor = args.flag("armor");
+    let stream = args.flag("stream");
+    let workers = parse_workers(&mut args)?;
+    let codec = parse_codec(&mut args)?;
+    let input = args.option("in")?;
+    let output = args.option("out")?;
+    let to = args.option("to")?;
+    let recipient = load_public_key(&args, to)?;
+    args.finish()?;
+
+    if let Some(codec) = codec {
+        if armor || stream {
+            return Err("--codec cannot be combined with --armor or --stream".into());
+        }
+        // The codec functions work on bare integers, so the key's usage and expiry are checked here
+        recipient.check_usage(KeyUsage::Encrypt)?;
+        let text = read_text(input.as_deref())?;
+        let ciphertext = if untagged {
+            encrypt_str(&text, recipient.n(), codec.as_ref())?
+        } else {
+            encrypt_str_tagged(&text, recipient.n(), codec.as_ref())?
+        };
+        return write_output(output.as_deref(), format!("{}\n", ciphertext).as_bytes());
+    }
+    if untagged {
+        return Err("--untagged only applies to --codec".into());
+    }
+
+    if stream {
+        if armor {
+            return Err("--armor cannot be combined with --stream".into());
+        }
+        let config = StreamConfig::default().with_workers(workers);
+        let mut reader = open_input(input.as_deref())?;
+        return write_streamed(output.as_deref(), |out| encrypt_stream(&recipient, &mut reader, out, &config));
+    }
+
+    let envelope = Envelope::seal(&recipient, &read_input(input.as_deref())?)?;
+    let encoded = if armor {
+        annotate_demo(envelope.to_pem(), recipient.is_demo()).into_bytes()
+    } else {
+        envelope.to_der()
+    };
+    write_output(output.as_deref(), &encoded)
+}
+
+fn run_decrypt(mut args: Args) -> CliResult {
+    let untagged = args.flag("untagged");
+    let workers = parse_workers(&mut args)?;
+    let codec = parse_codec(&mut args)?;
+    let input = args.option("in")?;
+    let output = args.option("out")?;
+    let key_spec = args.option("key")?;
+    let key = load_private_key(&args, key_spec)?;
+    args.finish()?;
+
+    if let Some(codec) = codec {
+        key.check_usage(KeyUsage::Encrypt)?;
+        // The number may have been wrapped or copied with stray spaces
+        let digits: String = read_text(input.as_deref())?.split_whitespace().collect();
+        let ciphertext = parse_decimal(&digits)?;
+        if !untagged {
+            let text = decrypt_str_tagged(&ciphertext, key.p(), key.q(), codec.as_ref())?;
+            return write_output(output.as_deref(), format!("{}\n", text).as_bytes());
+        }
+        let candidates = decrypt_str(&ciphertext, key.p(), key.q(), codec.as_ref())?;
+        let lines: String = candidates.iter().map(|candidate| format!("{}\n", candidate)).collect();
+        return write_output(output.as_deref(), lines.as_bytes());
+    }
+    if untagged {
+        return Err("--untagged only applies to --codec".into());
+    }
+
+    // Streams are recognised by their magic bytes; anything else is read whole as an envelope
+    let mut reader = open_input(input.as_deref())?;
+    let mut prefix = Vec::with_capacity(STREAM_MAGIC.len());
+    (&mut reader).take(STREAM_MAGIC.len() as u64).read_to_end(&mut prefix)?;
+    if prefix == STREAM_MAGIC {
+        let config = StreamConfig::default().with_workers(workers);
+        return write_streamed(output.as_deref(), |out| decrypt_stream_body(&key, &mut reader, out, &config));
+    }
+
+    reader.read_to_end(&mut prefix)?;
+    let envelope = Envelope::from_bytes(&prefix)?;
+    write_output(output.as_deref(), &envelope.open(&key)?)
+}
+
+fn run_sign(mut args: Args) -> CliResult {
+    let armor = args.flag("armor");
+    let input = args.option("in")?;
+    let output = args.option("out")?;
+    let key_spec = args.option("key")?;
+    let key = load_private_key(&args, key_spec)?;
+    args.finish()?;
+
+    let signature = Signature::sign(&key, &read_input(input.as_deref())?)?;
+    let encoded = if armor {
+        annotate_demo(signature.to_pem(), key.is_demo()).into_bytes()
+    } else {
+        signature.to_der()
+    };
+    write_output(output.as_deref(), &encoded)
+}
+
+fn run_verify(mut args: Args) -> CliResult {
+    let input = args.option("in")?;
+    let signature_path = args.option("sig")?.ok_or("missing --sig file")?;
+    let key_spec = args.option("key")?;
+    let key = load_public_key(&args, key_spec)?;
+    args.finish()?;
+
+    let signature = Signature::from_bytes(&fs::read(&signature_path)?)?;
+    signature.verify(&key, &read_input(input.as_deref())?)?;
+    println!("good signature from {}", key.fingerprint());
+    Ok(())
+}
+
+fn run_rekey(mut args: Args) -> CliResult {
+    let old_spec = args.option("old")?.ok_or("missing --old key")?;
+    let new_spec = args.option("new")?.ok_or("missing --new key")?;
+    let old_key = load_private_key(&args, Some(old_spec))?;
+    let new_key = load_public_key(&args, Some(new_spec))?;
+    let files = args.rest();
+    if files.is_empty() {
+        return Err("no files given".into());
+    }
+
+    let mut failures = 0;
+    for file in &files {
+        let result = (|| -> CliResult {
+            let bytes = fs::read(file)?;
+            let rotated = Envelope::from_bytes(&bytes)?.rekey(&old_key, &new_key)?;
+            // Keep the file in the format it was found in
+            let encoded = if bytes.starts_with(b"-----BEGIN") {
+                annotate_demo(rotated.to_pem(), new_key.is_demo()).into_bytes()
+            } else {
+                rotated.to_der()
+            };
+            write_atomically(Path::new(file), &encoded)
+        })();
+
+        match result {
+            Ok(()) => println!("rotated {}", file),
+            Err(err) => {
+                failures += 1;
+                println!("skipped {}: {}", file, err);
+            }
+        }
+    }
+
+    println!("{} of {} files rotated to {}", files.len() - failures, files.len(), new_key.fingerprint().short());
+    if failures > 0 {
+        return Err(format!("{} files could not be rotated", failures).into());
+    }
+    Ok(())
+}
+
+fn parse_count(args: &mut Args, name: &str) -> Result<u8, Box<dyn Error>> {
+    let value = args.option(name)?.ok_or_else(|| format!("missing --{}", name))?;
+    Ok(value.parse().map_err(|_| format!("--{} must be between 1 and 255", name))?)
+}
+
+fn run_shares(mut args: Args) -> CliResult {
+    match args.required("shares subcommand")?.as_str() {
+        "split" => {
+            let key_spec = args.option("key")?;
+            let threshold = parse_count(&mut args, "threshold")?;
+            let share_count = parse_count(&mut args, "shares")?;
+            let out_dir = args.option("out-dir")?.ok_or("missing --out-dir")?;
+            let key = load_private_key(&args, key_spec)?;
+            args.finish()?;
+
+            fs::create_dir_all(&out_dir)?;
+            for share in key.split_shares(threshold, share_count)? {
+                let path = Path::new(&out_dir).join(format!("share-{}.pem", share.index));
+                fs::write(&path, annotate_demo(share.to_pem(), key.is_demo()))?;
+                println!("{}", path.display());
+            }
+            println!("any {} of {} shares reconstruct the key", threshold, share_count);
+        }
+        "combine" => {
+            let output = args.option("out")?;
+            let files = args.rest();
+            let shares = files
+                .iter()
+                .map(|file| Ok(Share::from_pem(&fs::read_to_string(file)?)?))
+                .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+            let key = PrivateKey::from_shares(&shares)?;
+            write_output(output.as_deref(), annotate_demo(key.to_pkcs8_pem(), key.is_demo()).as_bytes())?;
+        }
+        other => return Err(format!("unknown shares subcommand '{}'", other).into()),
+    }
+    Ok(())
+}
+
+fn run_fiat_shamir(mut args: Args) -> CliResult {
+    let identity = args.option("identity")?.unwrap_or_else(|| "alice".to_string());
+    let rounds: u32 = match args.option("rounds")? {
+        Some(value) => value.parse().map_err(|_| format!("invalid round count '{}'", value))?,
+        None => 20,
+    };
+    let key_spec = args.option("key")?;
+    let key = load_private_key(&args, key_spec)?;
+    args.finish()?;
+
+    let public = key.public_key();
+    let (mut prover, index) = Prover::issue(&key, identity.as_bytes())?;
+    let mut verifier = Verifier::for_identity(&public, identity.as_bytes(), index)?;
+    println!("issued a secret for '{}' (identity index {})", identity, index);
+
+    for round in 1..=rounds {
+        let commitment = prover.commit();
+        let challenge = verifier.challenge(&commitment)?;
+        let response = prover.respond(challenge)?;
+        verifier.verify(&response)?;
+        println!(
+            "round {:>3}: x = {:x}..., e = {}, y = {:x}..., accepted",
+            round,
+            commitment.value() >> (commitment.value().bits().saturating_sub(32)),
+            u8::from(challenge),
+            response.value() >> (response.value().bits().saturating_sub(32)),
+        );
+    }
+    println!(
+        "'{}' identified after {} rounds; an impostor gets this far with probability {:.1e}",
+        identity,
+        verifier.accepted_rounds(),
+        verifier.cheating_probability()
+    );
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with(dir: &Path, command: &str) -> CliResult {
+        let mut items = vec!["--keystore".to_string(), dir.display().to_string(), "--insecure-demo".to_string()];
+        items.extend(command.split_whitespace().map(str::to_string));
+        run(items)
+    }
+
+    #[test]
+    fn test_codec_refuses_a_sign_only_key() {
+        let dir = std::env::temp_dir().join(format!("rabin-cli-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("input.txt");
+        fs::write(&input, "12345").unwrap();
+        run_with(&dir, "keys create signer --bits 256 --usage sign").unwrap();
+
+        let refused = RabinError::UsageViolation(KeyUsage::Encrypt).to_string();
+        for command in ["encrypt --to signer", "decrypt --key signer"] {
+            let command = format!("{} --codec utf8 --in {}", command, input.display());
+            let err = run_with(&dir, &command).unwrap_err();
+            assert_eq!(err.to_string(), refused, "{}", command);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,326 @@
+// Cross-checks against an independent implementation. compat/reference.py computes key
+// validation, square roots modulo n and the alphabet codec with sympy and plain Python, and
+// writes the results to compat/vectors.json; this module runs the same inputs through the crate
+// and reports every case where the two disagree. The vectors are built in, for `rabin selftest`
+// and the tests below, and other files in the same format can be checked with
+// `rabin selftest --reference FILE`.
+//
+// The file is one JSON object with three lists of cases. Numbers are decimal strings, and a
+// case either has the expected result or an "error" naming the expected failure:
+//
+//     keygen    {"p", "q", "bits"?} -> {"n", "violations": [...]} or {"error"}
+//     crt       {"p", "q", "c"} -> {"roots": [...], sorted and distinct} or {"error"}
+//     alphabet  {"text", "symbols"?, "bijective"} -> {"number", "encoded"} or {"error", "index"}
+
+use crate::encoding::{Alphabet, EncodingError};
+use crate::error::RabinError;
+use crate::keys::PrivateKey;
+use crate::rabin::{compute_candidates_with, decrypt, DecryptionParams};
+use crate::validate::KeyViolation;
+use num_bigint::BigInt;
+use serde_json::{Map, Value};
+use std::fmt;
+
+pub const REFERENCE_VECTORS: &str = include_str!("../compat/vectors.json");
+
+type Case = Map<String, Value>;
+
+// One case where the crate and the reference disagree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub section: &'static str,
+    // Position in the section's list, from 0
+    pub case: usize,
+    pub note: String,
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} case {}", self.section, self.case)?;
+        if !self.note.is_empty() {
+            write!(f, " ({})", self.note)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    // What produced the vectors, as the file says
+    pub generator: String,
+    pub passed: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    pub fn total(&self) -> usize {
+        self.passed + self.failures.len()
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+// The names the vector files use for errors; anything else is spelled as the Rust value, which
+// no vector file expects, so it always shows up as a mismatch
+fn error_name(err: &RabinError) -> String {
+    match err {
+        RabinError::InvalidKey(_) => "invalid_key".to_string(),
+        RabinError::NotInvertible => "not_invertible".to_string(),
+        RabinError::MessageOutOfRange => "message_out_of_range".to_string(),
+        RabinError::NotQuadraticResidue | RabinError::NotAResidue => "not_quadratic_residue".to_string(),
+        RabinError::Encoding(EncodingError::InvalidSymbol { .. }) => "invalid_symbol".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn violation_name(violation: &KeyViolation) -> &'static str {
+    match violation {
+        KeyViolation::PNotPrime => "p_not_prime",
+        KeyViolation::QNotPrime => "q_not_prime",
+        KeyViolation::PNotThreeModFour => "p_not_three_mod_four",
+        KeyViolation::QNotThreeModFour => "q_not_three_mod_four",
+        KeyViolation::EqualPrimes => "equal_primes",
+        KeyViolation::ModulusMismatch => "modulus_mismatch",
+        KeyViolation::BitLengthMismatch { .. } => "bit_length_mismatch",
+    }
+}
+
+fn malformed(section: &str, case: usize, what: &str) -> RabinError {
+    RabinError::MalformedVectors(format!("{} case {}: {}", section, case, what))
+}
+
+// Reads the fields of one case, with errors that point at it
+struct Fields<'a> {
+    section: &'static str,
+    index: usize,
+    case: &'a Case,
+}
+
+impl<'a> Fields<'a> {
+    fn get(&self, name: &str) -> Option<&'a Value> {
+        self.case.get(name)
+    }
+
+    fn string(&self, name: &str) -> Result<Option<&'a str>, RabinError> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(Value::String(text)) => Ok(Some(text)),
+            Some(_) => Err(malformed(self.section, self.index, &format!("\"{}\" must be a string", name))),
+        }
+    }
+
+    fn required(&self, name: &str) -> Result<&'a str, RabinError> {
+        self.string(name)?.ok_or_else(|| malformed(self.section, self.index, &format!("missing \"{}\"", name)))
+    }
+
+    fn number(&self, name: &str) -> Result<BigInt, RabinError> {
+        self.parse(self.required(name)?, name)
+    }
+
+    fn parse(&self, text: &str, name: &str) -> Result<BigInt, RabinError> {
+        text.parse()
+            .map_err(|_| malformed(self.section, self.index, &format!("\"{}\" must be a decimal number", name)))
+    }
+
+    fn strings(&self, name: &str) -> Result<Vec<&'a str>, RabinError> {
+        let list = match self.get(name) {
+            Some(Value::Array(list)) => list,
+            _ => return Err(malformed(self.section, self.index, &format!("\"{}\" must be a list", name))),
+        };
+        list.iter()
+            .map(|item| {
+                item.as_str()
+                    .ok_or_else(|| malformed(self.section, self.index, &format!("\"{}\" must hold strings", name)))
+            })
+            .collect()
+    }
+
+    fn unsigned(&self, name: &str) -> Result<Option<usize>, RabinError> {
+        match self.get(name) {
+            None => Ok(None),
+            Some(value) => value
+                .as_u64()
+                .map(|value| Some(value as usize))
+                .ok_or_else(|| malformed(self.section, self.index, &format!("\"{}\" must be a whole number", name))),
+        }
+    }
+
+    // The result against the expected error, when the case names one: true when the case
+    // expected this failure, false when it expects a result, which the caller then compares
+    fn expect_error<T>(&self, result: &Result<T, RabinError>) -> Result<bool, CaseError> {
+        match (self.string("error")?, result) {
+            (Some(expected), Err(err)) if error_name(err) == expected => Ok(true),
+            (Some(expected), Err(err)) => mismatch(format!("expected {}, got {}", expected, error_name(err))),
+            (Some(expected), Ok(_)) => mismatch(format!("expected {}, but it succeeded", expected)),
+            (None, Err(err)) => mismatch(format!("expected a result, got {}", error_name(err))),
+            (None, Ok(_)) => Ok(false),
+        }
+    }
+}
+
+// Why a case did not pass: the crate disagrees with the reference, or the case cannot be read
+enum CaseError {
+    Mismatch(String),
+    Malformed(RabinError),
+}
+
+impl From<RabinError> for CaseError {
+    fn from(err: RabinError) -> Self {
+        CaseError::Malformed(err)
+    }
+}
+
+fn mismatch<T>(message: String) -> Result<T, CaseError> {
+    Err(CaseError::Mismatch(message))
+}
+
+fn compare<T: PartialEq + fmt::Debug>(what: &str, expected: T, found: T) -> Result<(), CaseError> {
+    if expected == found {
+        Ok(())
+    } else {
+        mismatch(format!("{}: expected {:?}, got {:?}", what, expected, found))
+    }
+}
+
+fn check_keygen(fields: &Fields) -> Result<(), CaseError> {
+    let result = PrivateKey::from_primes(fields.number("p")?, fields.number("q")?);
+    if fields.expect_error(&result)? {
+        return Ok(());
+    }
+    let key = result.expect("expect_error handles failures");
+    compare("n", &fields.number("n")?, key.n())?;
+    let violations = match fields.unsigned("bits")? {
+        Some(bits) => key.validate_bit_size(bits),
+        None => key.validate(),
+    };
+    let found: Vec<&str> = violations.iter().map(violation_name).collect();
+    compare("violations", fields.strings("violations")?, found)
+}
+
+// Distinct roots in ascending order, as the reference lists them. Roots of a ciphertext that
+// shares a factor with n coincide in pairs.
+fn sorted_roots(mut roots: Vec<BigInt>) -> Vec<BigInt> {
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+fn check_crt(fields: &Fields) -> Result<(), CaseError> {
+    let (p, q, c) = (fields.number("p")?, fields.number("q")?, fields.number("c")?);
+    // Both the one-off recombination and the path keys take with their cached parameters
+    let direct = decrypt(&c, &p, &q).map(sorted_roots);
+    let cached = DecryptionParams::new(&p, &q)
+        .and_then(|params| compute_candidates_with(&c, &p, &q, &(&p * &q), &params))
+        .map(sorted_roots);
+    let expects_error = fields.expect_error(&direct)?;
+    let cached_expects_error = fields.expect_error(&cached).map_err(|err| match err {
+        CaseError::Mismatch(message) => CaseError::Mismatch(format!("with cached parameters, {}", message)),
+        malformed => malformed,
+    })?;
+    if expects_error || cached_expects_error {
+        return Ok(());
+    }
+    let expected = fields
+        .strings("roots")?
+        .into_iter()
+        .map(|root| fields.parse(root, "roots"))
+        .collect::<Result<Vec<_>, _>>()?;
+    compare("roots", &expected, direct.as_ref().expect("expect_error handles failures"))?;
+    compare("roots with cached parameters", &expected, cached.as_ref().expect("expect_error handles failures"))
+}
+
+fn check_alphabet(fields: &Fields) -> Result<(), CaseError> {
+    let alphabet = match fields.string("symbols")? {
+        Some(symbols) => Alphabet::new(symbols).map_err(RabinError::from)?,
+        None => Alphabet::default_symbols().clone(),
+    };
+    let bijective = fields.get("bijective").and_then(Value::as_bool).unwrap_or(false);
+    let text = fields.required("text")?;
+    let decoded = if bijective { alphabet.digits_to_num_bijective(text) } else { alphabet.digits_to_num(text) };
+    let decoded = decoded.map_err(RabinError::from);
+    if fields.expect_error(&decoded)? {
+        if let (Some(expected), Err(RabinError::Encoding(EncodingError::InvalidSymbol { index, .. }))) =
+            (fields.unsigned("index")?, &decoded)
+        {
+            compare("index", expected, *index)?;
+        }
+        return Ok(());
+    }
+    let number = decoded.expect("expect_error handles failures");
+    compare("number", &fields.number("number")?, &number)?;
+    let encoded = if bijective { alphabet.num_to_digits_bijective(&number) } else { alphabet.num_to_digits(&number) };
+    match encoded {
+        Ok(encoded) => compare("encoded", fields.required("encoded")?, &encoded),
+        Err(err) => mismatch(format!("could not encode {}: {}", number, err)),
+    }
+}
+
+type Checker = fn(&Fields) -> Result<(), CaseError>;
+
+const SECTIONS: [(&str, Checker); 3] = [("keygen", check_keygen), ("crt", check_crt), ("alphabet", check_alphabet)];
+
+// Runs every case of a vector file; Err only when the file itself cannot be read
+pub fn check_vectors(json: &str) -> Result<Report, RabinError> {
+    let vectors: Map<String, Value> =
+        serde_json::from_str(json).map_err(|err| RabinError::MalformedVectors(err.to_string()))?;
+    let mut report = Report {
+        generator: vectors.get("generator").and_then(Value::as_str).unwrap_or("unknown").to_string(),
+        ..Report::default()
+    };
+    for (section, check) in SECTIONS {
+        let cases = match vectors.get(section) {
+            None => continue,
+            Some(Value::Array(cases)) => cases,
+            Some(_) => return Err(RabinError::MalformedVectors(format!("\"{}\" must be a list", section))),
+        };
+        for (index, case) in cases.iter().enumerate() {
+            let case = case.as_object().ok_or_else(|| malformed(section, index, "not an object"))?;
+            let fields = Fields { section, index, case };
+            match check(&fields) {
+                Ok(()) => report.passed += 1,
+                Err(CaseError::Mismatch(message)) => report.failures.push(Failure {
+                    section,
+                    case: index,
+                    note: fields.string("note")?.unwrap_or_default().to_string(),
+                    message,
+                }),
+                Err(CaseError::Malformed(err)) => return Err(err),
+            }
+        }
+    }
+    Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_vectors() {
+        let report = check_vectors(REFERENCE_VECTORS).unwrap();
+        let failures: Vec<String> = report.failures.iter().map(Failure::to_string).collect();
+        assert!(report.is_ok(), "Disagreements with the reference:\n{}", failures.join("\n"));
+        assert!(report.passed > 50, "Every section should have been checked, got {}", report.passed);
+    }
+
+    #[test]
+    fn test_disagreements_and_bad_files_are_reported() {
+        let json = r#"{"crt": [
+            {"p": "7", "q": "11", "c": "23", "roots": ["10", "32", "45", "67"]},
+            {"p": "7", "q": "11", "c": "23", "roots": ["10", "32", "45", "66"], "note": "tampered"},
+            {"p": "7", "q": "11", "c": "3", "roots": ["1"]}
+        ]}"#;
+        let report = check_vectors(json).unwrap();
+        assert_eq!(report.passed, 1);
+        assert_eq!(report.failures.len(), 2);
+        assert_eq!(report.failures[0].note, "tampered");
+        assert_eq!(report.failures[1].message, "expected a result, got not_quadratic_residue");
+
+        assert!(check_vectors("[]").is_err());
+        assert!(check_vectors(r#"{"keygen": [{"p": "seven", "q": "11"}]}"#).is_err());
+    }
+}
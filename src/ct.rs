@@ -0,0 +1,64 @@
+// Equality checks for tags, redundancy and other values derived from secrets. == on slices
+// returns at the first differing byte, so its running time tells an attacker how long a prefix
+// of a forged tag was right. These functions look at every byte and fold the differences
+// together, and black_box keeps the compiler from turning the fold back into an early exit.
+// Lengths are not hidden: they are public in every use here.
+
+use crate::encoding::to_fixed_be_bytes;
+use crate::fingerprint::Fingerprint;
+use num_bigint::BigInt;
+use std::hint::black_box;
+
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let difference = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | black_box(x ^ y));
+    black_box(difference) == 0
+}
+
+// Every byte is zero, e.g. the filler after a length-prefixed payload
+pub fn ct_is_zero(bytes: &[u8]) -> bool {
+    black_box(bytes.iter().fold(0u8, |acc, byte| acc | black_box(*byte))) == 0
+}
+
+// Compares two numbers below a modulus as `width`-byte strings, so their lengths do not show;
+// false if either does not fit
+pub fn ct_eq_int(a: &BigInt, b: &BigInt, width: usize) -> bool {
+    match (to_fixed_be_bytes(a, width), to_fixed_be_bytes(b, width)) {
+        (Some(a), Some(b)) => ct_eq(&a, &b),
+        _ => false,
+    }
+}
+
+impl Fingerprint {
+    pub fn ct_eq(&self, other: &Fingerprint) -> bool {
+        ct_eq(self.as_bytes(), other.as_bytes())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_time_comparisons() {
+        assert!(ct_eq(b"tag bytes", b"tag bytes"));
+        assert!(!ct_eq(b"tag bytes", b"tag bytez"));
+        assert!(!ct_eq(b"tag", b"tag bytes"), "Different lengths never match");
+        assert!(ct_eq(b"", b""));
+
+        assert!(ct_is_zero(&[0; 16]) && ct_is_zero(&[]));
+        assert!(!ct_is_zero(&[0, 0, 1, 0]));
+
+        assert!(ct_eq_int(&BigInt::from(0x1234), &BigInt::from(0x1234), 4));
+        assert!(!ct_eq_int(&BigInt::from(0x1234), &BigInt::from(0x1235), 4));
+        assert!(!ct_eq_int(&BigInt::from(0x123456), &BigInt::from(0x123456), 2), "Too wide to compare");
+        assert!(!ct_eq_int(&BigInt::from(-1), &BigInt::from(-1), 4));
+
+        let one = Fingerprint::from_bytes([1; 32]);
+        assert!(one.ct_eq(&one));
+        assert!(!one.ct_eq(&Fingerprint::from_bytes([2; 32])));
+    }
+}
@@ -0,0 +1,548 @@
+// `rabin daemon`: the library as JSON-RPC 2.0 over a Unix domain socket (a named pipe on
+// Windows, with the windows-daemon feature), for editors, notebooks and grading scripts that
+// would rather keep one process running than start the CLI for every call. The daemon keeps
+// every private key it has loaded, with its decryption parameters precomputed, until it exits
+// or is told to unload it.
+//
+// Requests and responses are one JSON value per line; a line may hold a batch (an array of
+// requests), and requests without an id are notifications, answered with nothing. Params are
+// by name. KEY is a keystore name or PEM path, as on the command line, or a key the daemon
+// generated; without one the default key is used. Bytes travel as base64.
+//
+//     keygen      {"bits"?}                          -> {"key", "public_key", "fingerprint"}
+//     keys.list   {}                                 -> [{"name", "fingerprint", "private", "default", "demo"}]
+//     keys.load   {"key"?}                           -> {"fingerprint", "demo"}
+//     keys.unload {"key"?}                           -> {"unloaded": bool}
+//     public_key  {"key"?}                           -> {"public_key", "fingerprint"}
+//     encrypt     {"to"?, "plaintext" | "plaintext_base64"}  -> {"ciphertext"}
+//     decrypt     {"key"?, "ciphertext"}             -> {"plaintext_base64", "plaintext"?}
+//     sign        {"key"?, "message" | "message_base64"}     -> {"signature"}
+//     verify      {"key"?, "message" | "message_base64", "signature"}  -> {"valid": bool}
+//     encode      {"text", "alphabet"?}              -> {"number"} (decimal)
+//     decode      {"number", "alphabet"?}            -> {"text"}
+//     selftest    {}                                 -> {"generator", "passed", "failures"}
+//
+// Generated keys live only in the daemon, under the name it returns (session-1, ...).
+// Ciphertexts are envelopes and signatures DER, as `rabin encrypt` and `rabin sign` write them.
+// Library errors come back with code -32000 and the RabinError variant as data.kind.
+//
+// Anyone who can connect can decrypt and sign with the loaded keys, so the socket is created
+// readable and writable by its owner only.
+
+use crate::cli::{load_private_key, load_public_key, open_keystore, Args, CliResult, DEFAULT_BITS};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::{info, warn};
+use naive_rabin_cryptosystem::compat::{check_vectors, REFERENCE_VECTORS};
+use naive_rabin_cryptosystem::encoding::Alphabet;
+use naive_rabin_cryptosystem::envelope::Envelope;
+use naive_rabin_cryptosystem::error::RabinError;
+use naive_rabin_cryptosystem::keygen::KeygenConfig;
+use naive_rabin_cryptosystem::keys::{PrivateKey, PublicKey};
+use naive_rabin_cryptosystem::signature::Signature;
+use num_bigint::BigInt;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// The start of the range JSON-RPC leaves to applications
+const LIBRARY_ERROR: i64 = -32000;
+
+#[cfg(windows)]
+const DEFAULT_PIPE: &str = r"\\.\pipe\rabin-daemon";
+
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut error = json!({ "code": self.code, "message": self.message });
+        if let Some(data) = &self.data {
+            error["data"] = data.clone();
+        }
+        error
+    }
+}
+
+// The variant name, e.g. "DecryptionFailed" for RabinError::DecryptionFailed
+fn error_kind(err: &RabinError) -> String {
+    let debug = format!("{:?}", err);
+    debug.split(['(', ' ', '{']).next().unwrap_or_default().to_string()
+}
+
+impl From<RabinError> for RpcError {
+    fn from(err: RabinError) -> Self {
+        RpcError {
+            code: LIBRARY_ERROR,
+            message: err.to_string(),
+            data: Some(json!({ "kind": error_kind(&err) })),
+        }
+    }
+}
+
+// Errors from the CLI's key loading, which are library errors or plain messages
+impl From<Box<dyn Error>> for RpcError {
+    fn from(err: Box<dyn Error>) -> Self {
+        match err.downcast::<RabinError>() {
+            Ok(err) => RpcError::from(*err),
+            Err(err) => RpcError::new(LIBRARY_ERROR, err),
+        }
+    }
+}
+
+type Params = Map<String, Value>;
+
+fn string_param<'a>(params: &'a Params, name: &str) -> Result<Option<&'a str>, RpcError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(text)) => Ok(Some(text)),
+        Some(_) => Err(RpcError::new(INVALID_PARAMS, format!("\"{}\" must be a string", name))),
+    }
+}
+
+fn required_param<'a>(params: &'a Params, name: &str) -> Result<&'a str, RpcError> {
+    string_param(params, name)?.ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing \"{}\"", name)))
+}
+
+fn base64_param(params: &Params, name: &str) -> Result<Option<Vec<u8>>, RpcError> {
+    string_param(params, name)?
+        .map(|text| {
+            let invalid = || RpcError::new(INVALID_PARAMS, format!("\"{}\" is not valid base64", name));
+            STANDARD.decode(text).map_err(|_| invalid())
+        })
+        .transpose()
+}
+
+// Bytes given either as text under `name` or as base64 under `name`_base64
+fn bytes_param(params: &Params, name: &str) -> Result<Vec<u8>, RpcError> {
+    let encoded = format!("{}_base64", name);
+    match (string_param(params, name)?, base64_param(params, &encoded)?) {
+        (Some(text), None) => Ok(text.as_bytes().to_vec()),
+        (None, Some(bytes)) => Ok(bytes),
+        _ => Err(RpcError::new(INVALID_PARAMS, format!("give exactly one of \"{}\" and \"{}\"", name, encoded))),
+    }
+}
+
+fn alphabet_param(params: &Params) -> Result<Alphabet, RpcError> {
+    match string_param(params, "alphabet")? {
+        Some(symbols) => Alphabet::new(symbols).map_err(|err| RpcError::from(RabinError::from(err))),
+        None => Ok(Alphabet::default_symbols().clone()),
+    }
+}
+
+pub struct Daemon {
+    // The global options (--keystore, --insecure-demo) that key loading goes by
+    args: Args,
+    // Loaded and generated private keys by the KEY they were asked for ("" for the default)
+    keys: Mutex<HashMap<String, Arc<PrivateKey>>>,
+    sessions: AtomicUsize,
+}
+
+impl Daemon {
+    fn new(args: Args) -> Self {
+        Daemon {
+            args,
+            keys: Mutex::new(HashMap::new()),
+            sessions: AtomicUsize::new(0),
+        }
+    }
+
+    fn cached(&self, spec: &str) -> Option<Arc<PrivateKey>> {
+        self.keys.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).get(spec).cloned()
+    }
+
+    fn remember(&self, spec: String, key: PrivateKey) -> Arc<PrivateKey> {
+        let key = Arc::new(key.precompute());
+        self.keys.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(spec, Arc::clone(&key));
+        key
+    }
+
+    fn private_key(&self, params: &Params) -> Result<Arc<PrivateKey>, RpcError> {
+        let spec = string_param(params, "key")?;
+        if let Some(key) = self.cached(spec.unwrap_or_default()) {
+            return Ok(key);
+        }
+        let key = load_private_key(&self.args, spec.map(str::to_string))?;
+        Ok(self.remember(spec.unwrap_or_default().to_string(), key))
+    }
+
+    // A loaded private key's public half, or the key read from the keystore or a file
+    fn public_key(&self, params: &Params, name: &str) -> Result<PublicKey, RpcError> {
+        let spec = string_param(params, name)?;
+        match self.cached(spec.unwrap_or_default()) {
+            Some(key) => Ok(key.public_key()),
+            None => Ok(load_public_key(&self.args, spec.map(str::to_string))?),
+        }
+    }
+
+    fn keygen(&self, params: &Params) -> Result<Value, RpcError> {
+        let bits = match params.get("bits") {
+            None => DEFAULT_BITS,
+            Some(bits) => bits
+                .as_u64()
+                .map(|bits| bits as usize)
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "\"bits\" must be a whole number"))?,
+        };
+        let config = KeygenConfig::new(bits);
+        let (key, _) = match self.args.insecure() {
+            Some(opt_in) => PrivateKey::generate_with(&config.allow_insecure(opt_in))?,
+            None => PrivateKey::generate_with(&config)?,
+        };
+        let key = match self.args.insecure() {
+            Some(opt_in) => key.allow_insecure(opt_in),
+            None => key,
+        };
+        let name = format!("session-{}", self.sessions.fetch_add(1, Ordering::Relaxed) + 1);
+        let key = self.remember(name.clone(), key);
+        Ok(json!({
+            "key": name,
+            "public_key": key.public_key().to_pem(),
+            "fingerprint": key.public_key().fingerprint().to_string(),
+        }))
+    }
+
+    fn list_keys(&self) -> Result<Value, RpcError> {
+        let entries = open_keystore(&self.args)?.list()?;
+        Ok(entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "name": entry.name,
+                    "fingerprint": entry.fingerprint.to_string(),
+                    "private": entry.has_private,
+                    "default": entry.is_default,
+                    "demo": entry.demo,
+                })
+            })
+            .collect())
+    }
+
+    fn call(&self, method: &str, params: &Params) -> Result<Value, RpcError> {
+        match method {
+            "keygen" => self.keygen(params),
+            "keys.list" => self.list_keys(),
+            "keys.load" => {
+                let key = self.private_key(params)?;
+                Ok(json!({ "fingerprint": key.public_key().fingerprint().to_string(), "demo": key.is_demo() }))
+            }
+            "keys.unload" => {
+                let spec = string_param(params, "key")?.unwrap_or_default();
+                let removed = self.keys.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(spec);
+                Ok(json!({ "unloaded": removed.is_some() }))
+            }
+            "public_key" => {
+                let key = self.public_key(params, "key")?;
+                Ok(json!({ "public_key": key.to_pem(), "fingerprint": key.fingerprint().to_string() }))
+            }
+            "encrypt" => {
+                let key = self.public_key(params, "to")?;
+                let envelope = Envelope::seal(&key, &bytes_param(params, "plaintext")?)?;
+                Ok(json!({ "ciphertext": STANDARD.encode(envelope.to_der()) }))
+            }
+            "decrypt" => {
+                let key = self.private_key(params)?;
+                let ciphertext = base64_param(params, "ciphertext")?
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing \"ciphertext\""))?;
+                let plaintext = Envelope::from_bytes(&ciphertext)?.open(&key)?;
+                let mut result = json!({ "plaintext_base64": STANDARD.encode(&plaintext) });
+                if let Ok(text) = String::from_utf8(plaintext) {
+                    result["plaintext"] = Value::String(text);
+                }
+                Ok(result)
+            }
+            "sign" => {
+                let key = self.private_key(params)?;
+                let signature = Signature::sign(&key, &bytes_param(params, "message")?)?;
+                Ok(json!({ "signature": STANDARD.encode(signature.to_der()) }))
+            }
+            "verify" => {
+                let key = self.public_key(params, "key")?;
+                let signature = base64_param(params, "signature")?
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing \"signature\""))?;
+                match Signature::from_bytes(&signature)?.verify(&key, &bytes_param(params, "message")?) {
+                    Ok(()) => Ok(json!({ "valid": true })),
+                    Err(RabinError::InvalidSignature) => Ok(json!({ "valid": false })),
+                    Err(err) => Err(err.into()),
+                }
+            }
+            "encode" => {
+                let number = alphabet_param(params)?
+                    .digits_to_num(required_param(params, "text")?)
+                    .map_err(RabinError::from)?;
+                Ok(json!({ "number": number.to_string() }))
+            }
+            "decode" => {
+                let number: BigInt = required_param(params, "number")?
+                    .parse()
+                    .map_err(|_| RpcError::new(INVALID_PARAMS, "\"number\" must be a decimal number"))?;
+                let text = alphabet_param(params)?.num_to_digits(&number).map_err(RabinError::from)?;
+                Ok(json!({ "text": text }))
+            }
+            "selftest" => {
+                let report = check_vectors(REFERENCE_VECTORS)?;
+                let failures: Vec<String> = report.failures.iter().map(ToString::to_string).collect();
+                Ok(json!({ "generator": report.generator, "passed": report.passed, "failures": failures }))
+            }
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("no method '{}'", method))),
+        }
+    }
+
+    // The response to one request, or None for a notification
+    fn handle_request(&self, request: &Value) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let result = match request {
+            Value::Object(object) if object.get("jsonrpc") == Some(&json!("2.0")) => {
+                match (object.get("method"), object.get("params")) {
+                    (Some(Value::String(method)), None) => Ok((method, Params::new())),
+                    (Some(Value::String(method)), Some(Value::Object(params))) => Ok((method, params.clone())),
+                    (Some(Value::String(_)), Some(_)) => Err(RpcError::new(INVALID_PARAMS, "params must be an object")),
+                    _ => Err(RpcError::new(INVALID_REQUEST, "the request has no method")),
+                }
+            }
+            _ => Err(RpcError::new(INVALID_REQUEST, "not a JSON-RPC 2.0 request")),
+        };
+        let result = result.and_then(|(method, params)| {
+            let result = self.call(method, &params);
+            match &result {
+                Ok(_) => info!("{} -> ok", method),
+                Err(err) => info!("{} -> {}", method, err.message),
+            }
+            result
+        });
+        // Notifications get no answer, even when they fail
+        let id = match (id, &result) {
+            (Some(id), _) => id,
+            (None, Ok(_)) => return None,
+            (None, Err(err)) if err.code != INVALID_REQUEST => return None,
+            (None, Err(_)) => Value::Null,
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => json!({ "jsonrpc": "2.0", "id": id, "error": err.to_json() }),
+        })
+    }
+
+    // The response line for one request line, or None when nothing needs answering
+    fn handle_line(&self, line: &str) -> Option<String> {
+        let response = match serde_json::from_str::<Value>(line) {
+            Err(err) => {
+                let error = RpcError::new(PARSE_ERROR, format!("invalid JSON: {}", err));
+                json!({ "jsonrpc": "2.0", "id": null, "error": error.to_json() })
+            }
+            Ok(Value::Array(batch)) if batch.is_empty() => {
+                let error = RpcError::new(INVALID_REQUEST, "empty batch");
+                json!({ "jsonrpc": "2.0", "id": null, "error": error.to_json() })
+            }
+            Ok(Value::Array(batch)) => {
+                let responses: Vec<Value> = batch.iter().filter_map(|request| self.handle_request(request)).collect();
+                if responses.is_empty() {
+                    return None;
+                }
+                Value::Array(responses)
+            }
+            Ok(request) => self.handle_request(&request)?,
+        };
+        Some(response.to_string())
+    }
+
+    // Answers a client until it hangs up
+    fn serve<S: Read + Write>(&self, stream: S) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_line(&line) {
+                let stream = reader.get_mut();
+                stream.write_all(response.as_bytes())?;
+                stream.write_all(b"\n")?;
+                stream.flush()?;
+            }
+        }
+    }
+}
+
+pub fn run_daemon(mut args: Args) -> CliResult {
+    let socket = args.option("socket")?;
+    let daemon = Arc::new(Daemon::new(args.clone()));
+    args.finish()?;
+    listen(daemon, socket)
+}
+
+// The socket is bound inside a directory only the owner can enter, made 0600 and only then
+// moved into place, so no other user can connect in the moment between bind and chmod
+#[cfg(unix)]
+fn bind_private(path: &std::path::Path) -> std::io::Result<std::os::unix::net::UnixListener> {
+    use std::fs;
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+
+    let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("daemon.sock");
+    let staging = path.with_file_name(format!(".{}.{}", name, std::process::id()));
+    fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join(name);
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&staged);
+    fs::remove_dir(&staging)?;
+    bound
+}
+
+#[cfg(unix)]
+fn listen(daemon: Arc<Daemon>, socket: Option<String>) -> CliResult {
+    use std::fs;
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+
+    let path = match socket {
+        Some(path) => PathBuf::from(path),
+        None => open_keystore(&daemon.args)?.root().join("daemon.sock"),
+    };
+    // A socket file nobody answers on is left over from a daemon that did not exit cleanly
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(format!("a daemon is already listening on {}", path.display()).into());
+        }
+        fs::remove_file(&path)?;
+    }
+    let listener = bind_private(&path).map_err(|err| format!("cannot listen on {}: {}", path.display(), err))?;
+    info!("Listening for JSON-RPC on {}", path.display());
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let daemon = Arc::clone(&daemon);
+        std::thread::spawn(move || {
+            if let Err(err) = daemon.serve(stream) {
+                warn!("client connection failed: {}", err);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn listen(daemon: Arc<Daemon>, socket: Option<String>) -> CliResult {
+    use named_pipe::PipeOptions;
+
+    let name = socket.unwrap_or_else(|| DEFAULT_PIPE.to_string());
+    let mut options = PipeOptions::new(&name);
+    info!("Listening for JSON-RPC on {}", name);
+    loop {
+        // Each client gets its own instance of the pipe; the first one claims the name
+        let server = options.single()?.wait()?;
+        options.first(false);
+        let daemon = Arc::clone(&daemon);
+        std::thread::spawn(move || {
+            if let Err(err) = daemon.serve(server) {
+                warn!("client connection failed: {}", err);
+            }
+        });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(daemon: &Daemon, method: &str, params: Value) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Value = serde_json::from_str(&daemon.handle_line(&request.to_string()).unwrap()).unwrap();
+        assert_eq!(response["id"], 1);
+        response
+    }
+
+    #[test]
+    fn test_requests() {
+        let daemon = Daemon::new(Args::new(vec!["--insecure-demo".to_string()]).unwrap());
+        let key = call(&daemon, "keygen", json!({ "bits": 256 }))["result"].clone();
+        assert_eq!(key["key"], "session-1");
+        let session = json!({ "key": "session-1" });
+        assert_eq!(call(&daemon, "public_key", session.clone())["result"]["public_key"], key["public_key"]);
+
+        let sealed = call(&daemon, "encrypt", json!({ "to": "session-1", "plaintext": "hello, notebook" }));
+        let ciphertext = sealed["result"]["ciphertext"].clone();
+        let opened = call(&daemon, "decrypt", json!({ "key": "session-1", "ciphertext": ciphertext }));
+        assert_eq!(opened["result"]["plaintext"], "hello, notebook");
+
+        let signed = call(&daemon, "sign", json!({ "key": "session-1", "message": "graded" }));
+        let signature = signed["result"]["signature"].clone();
+        let verify = |message| {
+            let params = json!({ "key": "session-1", "message": message, "signature": signature });
+            call(&daemon, "verify", params)
+        };
+        assert_eq!(verify("graded")["result"]["valid"], true);
+        assert_eq!(verify("regraded")["result"]["valid"], false);
+
+        let encoded = call(&daemon, "encode", json!({ "text": "10", "alphabet": "0123456789" }));
+        assert_eq!(encoded["result"]["number"], "10");
+        assert_eq!(call(&daemon, "decode", json!({ "number": "255", "alphabet": "01" }))["result"]["text"], "11111111");
+
+        let failed = call(&daemon, "decrypt", json!({ "key": "session-1", "ciphertext": "AAAA" }));
+        assert_eq!(failed["error"]["code"], LIBRARY_ERROR);
+        assert_eq!(failed["error"]["data"]["kind"], "MalformedDer");
+        assert_eq!(call(&daemon, "encrypt", json!({ "to": "session-1" }))["error"]["code"], INVALID_PARAMS);
+        assert_eq!(call(&daemon, "factor", json!({}))["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(call(&daemon, "keys.unload", session)["result"]["unloaded"], true);
+    }
+
+    #[test]
+    fn test_batches_and_notifications() {
+        let daemon = Daemon::new(Args::new(vec![]).unwrap());
+        let batch = json!([
+            { "jsonrpc": "2.0", "id": "a", "method": "encode", "params": { "text": "z" } },
+            { "jsonrpc": "2.0", "method": "encode", "params": { "text": "y" } },
+            { "jsonrpc": "2.0", "id": "b", "method": "encode", "params": ["z"] },
+        ]);
+        let responses: Value = serde_json::from_str(&daemon.handle_line(&batch.to_string()).unwrap()).unwrap();
+        assert_eq!(responses.as_array().unwrap().len(), 2, "The notification should get no response");
+        assert_eq!(responses[0]["id"], "a");
+        assert_eq!(responses[1]["error"]["code"], INVALID_PARAMS);
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "selftest" });
+        assert_eq!(daemon.handle_line(&notification.to_string()), None);
+        let response: Value = serde_json::from_str(&daemon.handle_line("{not json").unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+        assert_eq!(response["id"], Value::Null);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_socket_is_private_from_the_start() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("rabin-daemon-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("daemon.sock");
+        let _listener = bind_private(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        let entries: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(entries, vec!["daemon.sock"], "The staging directory is removed");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,291 @@
+use crate::error::RabinError;
+use num_bigint::BigInt;
+
+// Only the handful of universal tags needed for the key containers are supported
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_SEQUENCE: u8 = 0x30;
+
+pub fn write_tlv(out: &mut Vec<u8>, tag: u8, content: &[u8]) {
+    out.push(tag);
+    let len = content.len();
+    if len < 0x80 {
+        // Short form: the length fits into the low seven bits
+        out.push(len as u8);
+    } else {
+        // Long form: 0x80 | number of length bytes, followed by the big-endian length
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+}
+
+pub fn encode_integer(value: &BigInt) -> Vec<u8> {
+    let mut out = Vec::new();
+    // to_signed_bytes_be already yields the minimal two's complement form DER requires
+    write_tlv(&mut out, TAG_INTEGER, &value.to_signed_bytes_be());
+    out
+}
+
+pub fn encode_octet_string(content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_tlv(&mut out, TAG_OCTET_STRING, content);
+    out
+}
+
+pub fn encode_null() -> Vec<u8> {
+    vec![TAG_NULL, 0x00]
+}
+
+pub fn encode_oid(arcs: &[u128]) -> Vec<u8> {
+    assert!(arcs.len() >= 2, "an OID needs at least two arcs");
+
+    // The first two arcs are packed into a single subidentifier
+    let mut subidentifiers = vec![arcs[0] * 40 + arcs[1]];
+    subidentifiers.extend_from_slice(&arcs[2..]);
+
+    let mut content = Vec::new();
+    for mut arc in subidentifiers {
+        // Base-128, most significant group first, continuation bit on all but the last byte
+        let mut groups = vec![(arc & 0x7f) as u8];
+        arc >>= 7;
+        while arc > 0 {
+            groups.push(0x80 | (arc & 0x7f) as u8);
+            arc >>= 7;
+        }
+        content.extend(groups.iter().rev());
+    }
+
+    let mut out = Vec::new();
+    write_tlv(&mut out, TAG_OID, &content);
+    out
+}
+
+pub fn encode_sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    let content: Vec<u8> = items.concat();
+    let mut out = Vec::new();
+    write_tlv(&mut out, TAG_SEQUENCE, &content);
+    out
+}
+
+pub struct DerReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> DerReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        DerReader { data, pos: 0 }
+    }
+
+    fn next_byte(&mut self) -> Result<u8, RabinError> {
+        let byte = *self
+            .data
+            .get(self.pos)
+            .ok_or(RabinError::MalformedDer("unexpected end of input"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn read_length(&mut self) -> Result<usize, RabinError> {
+        let first = self.next_byte()?;
+        if first < 0x80 {
+            return Ok(first as usize);
+        }
+
+        let count = (first & 0x7f) as usize;
+        if count == 0 {
+            return Err(RabinError::MalformedDer("indefinite length is not allowed in DER"));
+        }
+        if count > std::mem::size_of::<usize>() {
+            return Err(RabinError::MalformedDer("length does not fit into usize"));
+        }
+
+        let mut len = 0usize;
+        for _ in 0..count {
+            len = (len << 8) | self.next_byte()? as usize;
+        }
+        if len < 0x80 {
+            return Err(RabinError::MalformedDer("non-minimal length encoding"));
+        }
+        Ok(len)
+    }
+
+    pub fn read_tlv(&mut self, expected_tag: u8) -> Result<&'a [u8], RabinError> {
+        let tag = self.next_byte()?;
+        if tag != expected_tag {
+            return Err(RabinError::MalformedDer("unexpected tag"));
+        }
+        let len = self.read_length()?;
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(RabinError::MalformedDer("length exceeds input"))?;
+        let content = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(content)
+    }
+
+    pub fn read_integer(&mut self) -> Result<BigInt, RabinError> {
+        let content = self.read_tlv(TAG_INTEGER)?;
+        if content.is_empty() {
+            return Err(RabinError::MalformedDer("empty INTEGER"));
+        }
+        // A leading 0x00 is only allowed before a set high bit, and a leading 0xff only
+        // before a clear one; anything else could have been encoded in fewer bytes
+        if let [first, second, ..] = content {
+            if (*first == 0x00 && second & 0x80 == 0) || (*first == 0xff && second & 0x80 != 0) {
+                return Err(RabinError::MalformedDer("non-minimal INTEGER encoding"));
+            }
+        }
+        Ok(BigInt::from_signed_bytes_be(content))
+    }
+
+    pub fn read_octet_string(&mut self) -> Result<&'a [u8], RabinError> {
+        self.read_tlv(TAG_OCTET_STRING)
+    }
+
+    pub fn read_null(&mut self) -> Result<(), RabinError> {
+        if !self.read_tlv(TAG_NULL)?.is_empty() {
+            return Err(RabinError::MalformedDer("NULL with content"));
+        }
+        Ok(())
+    }
+
+    pub fn read_oid(&mut self) -> Result<Vec<u128>, RabinError> {
+        let content = self.read_tlv(TAG_OID)?;
+        if content.is_empty() || content[content.len() - 1] & 0x80 != 0 {
+            return Err(RabinError::MalformedDer("truncated OID"));
+        }
+
+        let mut subidentifiers = Vec::new();
+        let mut current: u128 = 0;
+        for &byte in content {
+            // A subidentifier starting with 0x80 has a leading zero group, which DER forbids
+            if current == 0 && byte == 0x80 {
+                return Err(RabinError::MalformedDer("non-minimal OID encoding"));
+            }
+            if current.leading_zeros() < 7 {
+                return Err(RabinError::MalformedDer("OID arc too large"));
+            }
+            current = (current << 7) | (byte & 0x7f) as u128;
+            if byte & 0x80 == 0 {
+                subidentifiers.push(current);
+                current = 0;
+            }
+        }
+
+        // Undo the packing of the first two arcs
+        let first = subidentifiers[0];
+        let (a, b) = if first < 80 { (first / 40, first % 40) } else { (2, first - 80) };
+        let mut arcs = vec![a, b];
+        arcs.extend_from_slice(&subidentifiers[1..]);
+        Ok(arcs)
+    }
+
+    pub fn read_sequence(&mut self) -> Result<DerReader<'a>, RabinError> {
+        Ok(DerReader::new(self.read_tlv(TAG_SEQUENCE)?))
+    }
+
+    // True once every element has been read; used for trailing OPTIONAL fields
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    // Fails if any bytes are left over, so trailing garbage is never silently accepted
+    pub fn finish(&self) -> Result<(), RabinError> {
+        if self.pos != self.data.len() {
+            return Err(RabinError::MalformedDer("trailing data"));
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_round_trip() {
+        for value in [0i64, 1, 127, 128, 255, 256, -1, -128, -129, 1 << 40] {
+            let value = BigInt::from(value);
+            let encoded = encode_integer(&value);
+            let mut reader = DerReader::new(&encoded);
+            assert_eq!(reader.read_integer().unwrap(), value, "INTEGER should round-trip");
+            reader.finish().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_integer_known_encoding() {
+        // 128 needs a leading zero byte so it is not read back as negative
+        assert_eq!(encode_integer(&BigInt::from(128)), vec![0x02, 0x02, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn test_rejects_non_minimal_integers() {
+        for encoded in [[0x02, 0x02, 0x00, 0x7f], [0x02, 0x02, 0xff, 0x80]] {
+            assert_eq!(
+                DerReader::new(&encoded).read_integer(),
+                Err(RabinError::MalformedDer("non-minimal INTEGER encoding"))
+            );
+        }
+        assert_eq!(DerReader::new(&[0x02, 0x02, 0xff, 0x7f]).read_integer(), Ok(BigInt::from(-129)));
+    }
+
+    #[test]
+    fn test_long_form_length() {
+        let content = vec![0xabu8; 300];
+        let encoded = encode_octet_string(&content);
+        assert_eq!(&encoded[..4], &[0x04, 0x82, 0x01, 0x2c]);
+
+        let mut reader = DerReader::new(&encoded);
+        assert_eq!(reader.read_octet_string().unwrap(), content.as_slice());
+    }
+
+    #[test]
+    fn test_oid_round_trip() {
+        // rsaEncryption, a well-known OID with a multi-byte arc
+        let arcs = [1u128, 2, 840, 113549, 1, 1, 1];
+        let encoded = encode_oid(&arcs);
+        assert_eq!(
+            encoded,
+            vec![0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01]
+        );
+        assert_eq!(DerReader::new(&encoded).read_oid().unwrap(), arcs.to_vec());
+    }
+
+    #[test]
+    fn test_oid_rejects_non_minimal_subidentifiers() {
+        // 840 as 0x80 0x86 0x48, and the packed first arcs (0x2a) as 0x80 0x2a
+        for encoded in [
+            vec![0x06, 0x04, 0x2a, 0x80, 0x86, 0x48],
+            vec![0x06, 0x03, 0x80, 0x2a, 0x01],
+        ] {
+            assert_eq!(
+                DerReader::new(&encoded).read_oid(),
+                Err(RabinError::MalformedDer("non-minimal OID encoding"))
+            );
+        }
+        assert_eq!(DerReader::new(&[0x06, 0x03, 0x2a, 0x00, 0x01]).read_oid().unwrap(), vec![1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn test_rejects_truncated_and_trailing_input() {
+        let encoded = encode_integer(&BigInt::from(1_000_000));
+        let mut truncated = DerReader::new(&encoded[..encoded.len() - 1]);
+        assert!(truncated.read_integer().is_err(), "Truncated input should be rejected");
+
+        let mut padded = encoded.clone();
+        padded.push(0x00);
+        let mut reader = DerReader::new(&padded);
+        reader.read_integer().unwrap();
+        assert!(reader.finish().is_err(), "Trailing bytes should be rejected");
+    }
+}
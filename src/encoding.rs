@@ -1,59 +1,1169 @@
-use num_bigint::BigInt;
+use num_bigint::{BigInt, BigUint, Sign};
+use num_integer::Integer;
 use std::collections::HashMap;
-use num_traits::cast::ToPrimitive;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::error::RabinError;
+use crate::hash::crc32;
+use crate::redact::secret;
 
 pub const DEFAULT_SYMBOLS: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz(.,;:!?)[<+-*/=>]@| ";
+// RFC 4648 alphabets. They work as positional alphabets for str2num/num2str like any other, but
+// only num2rfc4648/rfc4648_to_num produce text that base64 and base32 tools read back.
+pub const BASE64_SYMBOLS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+pub const BASE64URL_SYMBOLS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+pub const BASE32_SYMBOLS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
 
 
-use log::{info, warn, error};
+use log::trace;
 use num_traits::Zero;
 
-pub fn str2num(s: &str, digitstring: &str) -> Option<BigInt> {
-    let base = BigInt::from(digitstring.len());
-    let mut num = BigInt::zero();
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodingError {
+    // A symbol outside the alphabet; the index counts symbols (grapheme clusters), not bytes
+    InvalidSymbol { symbol: String, index: usize },
+    // A number that no text encodes to (negative, missing the marker, or not UTF-8)
+    NotText(&'static str),
+    // Alphabet construction: fewer than two or more than u32::MAX symbols, a repeated symbol,
+    // a code point that cannot stand for a digit (controls and Unicode noncharacters), or two
+    // symbols that fuse into one grapheme cluster when written next to each other
+    AlphabetTooShort(usize),
+    AlphabetTooLong(usize),
+    DuplicateSymbol(String),
+    UnassignableSymbol(char),
+    InseparableSymbols(String, String),
+    // Case folding was asked for, but two symbols differ only in case
+    CaseAmbiguous(String, String),
+    // An escape that is not a symbol of the alphabet, or an alphabet without the hex digits
+    UnusableEscape(String),
+    // Checksummed text whose checksum is missing or does not match the rest
+    BadChecksum,
+    // Strict decoding: the text decodes, but is not the one encoding would write
+    NonCanonical(&'static str),
+    // Fixed-width blocks: the modulus is not above the alphabet size, so no block fits
+    ModulusTooSmall,
+    // A zero or padding symbol that is not a single symbol of the alphabet (zero) or clashes
+    // with one (padding)
+    UnknownSymbol(String),
+    // num_to_digits_width: the number needs more symbols than the width allows
+    TooWide(usize),
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::InvalidSymbol { symbol, index } => {
+                write!(f, "symbol {:?} at position {} is not in the alphabet", symbol, index)
+            }
+            EncodingError::NotText(what) => write!(f, "number does not encode text: {}", what),
+            EncodingError::AlphabetTooShort(len) => {
+                write!(f, "an alphabet needs at least two symbols, got {}", len)
+            }
+            EncodingError::AlphabetTooLong(len) => {
+                write!(f, "an alphabet holds at most {} symbols, got {}", u32::MAX, len)
+            }
+            EncodingError::DuplicateSymbol(symbol) => write!(f, "symbol {:?} appears twice in the alphabet", symbol),
+            EncodingError::UnassignableSymbol(symbol) => {
+                write!(f, "{:?} (U+{:04X}) cannot be used as a symbol", symbol, *symbol as u32)
+            }
+            EncodingError::InseparableSymbols(first, second) => {
+                write!(f, "symbols {:?} and {:?} merge into one when written side by side", first, second)
+            }
+            EncodingError::CaseAmbiguous(first, second) => {
+                write!(f, "symbols {:?} and {:?} differ only in case", first, second)
+            }
+            EncodingError::UnusableEscape(escape) => {
+                write!(f, "{:?} cannot escape: it and the digits 0-9a-f must all be symbols", escape)
+            }
+            EncodingError::NonCanonical(what) => write!(f, "non-canonical encoding: {}", what),
+            EncodingError::BadChecksum => write!(f, "checksum mismatch: the text is mistyped or incomplete"),
+            EncodingError::ModulusTooSmall => write!(f, "the modulus cannot hold a single symbol"),
+            EncodingError::UnknownSymbol(symbol) => write!(f, "{:?} is not a single symbol of the alphabet", symbol),
+            EncodingError::TooWide(width) => write!(f, "the number does not fit in {} symbols", width),
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+// The symbols of a text, one extended grapheme cluster each: "🇩🇪" and "e\u{301}" are single
+// symbols even though they take two code points. Every codec here reads text this way.
+pub fn split_symbols(text: &str) -> impl Iterator<Item = &str> {
+    text.graphemes(true)
+}
+
+// A validated digit string with its lookup tables: symbols by value, and values by symbol
+// (single ASCII characters through a flat table, anything else through a map). Every function
+// in this module takes one of these instead of a raw string, so validation and table building
+// happen once. Symbols are grapheme clusters, so emoji with skin tones or flags count as one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alphabet {
+    symbols: Vec<String>,
+    ascii: [Option<u32>; 128],
+    others: HashMap<String, u32>,
+    folds_case: bool,
+    skips_whitespace: bool,
+    strict: bool,
+    padding: Option<(String, Padding)>,
+}
+
+// Where num_to_digits_width puts the padding symbol that fills a number out to its width. Decoding
+// drops padding from that end only (from the right for Omitted, as base64url readers do), so
+// a padding symbol anywhere else is an invalid symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Padding {
+    Leading,
+    Trailing,
+    Omitted,
+}
+
+impl Alphabet {
+    pub fn new(digitstring: &str) -> Result<Self, EncodingError> {
+        let symbols: Vec<String> = split_symbols(digitstring).map(str::to_string).collect();
+        if symbols.len() < 2 {
+            return Err(EncodingError::AlphabetTooShort(symbols.len()));
+        }
+        if u32::try_from(symbols.len()).is_err() {
+            return Err(EncodingError::AlphabetTooLong(symbols.len()));
+        }
+        let mut ascii = [None; 128];
+        let mut others = HashMap::new();
+        for (value, symbol) in symbols.iter().enumerate() {
+            if let Some(code) = symbol.chars().find(|&code| !is_assignable(code)) {
+                return Err(EncodingError::UnassignableSymbol(code));
+            }
+            let previous = match ascii_index(symbol) {
+                Some(index) => ascii[index].replace(value as u32),
+                None => others.insert(symbol.clone(), value as u32),
+            };
+            if previous.is_some() {
+                return Err(EncodingError::DuplicateSymbol(symbol.clone()));
+            }
+        }
+        check_separable(&symbols)?;
+        Ok(Alphabet { symbols, ascii, others, folds_case: false, skips_whitespace: false, strict: false, padding: None })
+    }
+
+    // Decoding that also reads every symbol in the other case, for hand-typed text: "deadbeef"
+    // under an upper-case hex alphabet. Encoding still writes the symbols as given. Only for
+    // alphabets where no two symbols differ just in case, so DEFAULT_SYMBOLS cannot fold.
+    pub fn fold_case(mut self) -> Result<Self, EncodingError> {
+        let mut folded: HashMap<String, usize> = HashMap::new();
+        for (value, symbol) in self.symbols.iter().enumerate() {
+            if let Some(other) = folded.insert(symbol.to_lowercase(), value) {
+                return Err(EncodingError::CaseAmbiguous(self.symbols[other].clone(), symbol.clone()));
+            }
+        }
+        for (value, symbol) in self.symbols.iter().enumerate() {
+            for variant in [symbol.to_lowercase(), symbol.to_uppercase()] {
+                match ascii_index(&variant) {
+                    Some(index) => self.ascii[index] = Some(value as u32),
+                    None => {
+                        self.others.insert(variant, value as u32);
+                    }
+                }
+            }
+        }
+        self.folds_case = true;
+        Ok(self)
+    }
+
+    pub fn folds_case(&self) -> bool {
+        self.folds_case
+    }
+
+    // Decoding that passes over spaces, tabs and line breaks, for numbers copied out of PDFs or
+    // wrapped at 72 columns. Whitespace the alphabet uses as a symbol (the space in
+    // DEFAULT_SYMBOLS) still counts as a digit. Error positions point into the text as given.
+    pub fn skip_whitespace(mut self) -> Self {
+        self.skips_whitespace = true;
+        self
+    }
+
+    pub fn skips_whitespace(&self) -> bool {
+        self.skips_whitespace
+    }
+
+    // Decoding that accepts only the text encoding writes, so every number has exactly one
+    // spelling, as signatures and fingerprints over encoded values need: no leading zero
+    // symbols (and "" is not 0; that is the zero symbol alone), symbols only in the case the
+    // alphabet gives them, no whitespace. Overrides fold_case and skip_whitespace.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    // The alphabet with `zero` moved to value 0 and the symbols before it shifted up by one.
+    // Leading zero symbols vanish when decoding, so alphabets whose first symbol carries
+    // meaning (the '0' of DEFAULT_SYMBOLS) can hand that role to one that does not, like the
+    // space. Every value changes, so both sides must agree on the zero symbol.
+    pub fn with_zero(self, zero: &str) -> Result<Self, EncodingError> {
+        let value = match self.symbols.iter().position(|symbol| symbol == zero) {
+            Some(value) => value,
+            None => return Err(EncodingError::UnknownSymbol(zero.to_string())),
+        };
+        let mut symbols = self.symbols.clone();
+        let symbol = symbols.remove(value);
+        symbols.insert(0, symbol);
+        let mut alphabet = Alphabet::new(&symbols.concat())?;
+        if self.folds_case {
+            alphabet = alphabet.fold_case()?;
+        }
+        alphabet.skips_whitespace = self.skips_whitespace;
+        alphabet.strict = self.strict;
+        alphabet.padding = self.padding;
+        Ok(alphabet)
+    }
+
+    // A padding symbol outside the digits, so fixed-width text can be filled out without
+    // zero symbols and a padded "007" stays distinct from the digits "007". It is validated
+    // like a symbol appended to the alphabet: one grapheme cluster, assignable, not a digit
+    // already, and not fusing with any digit. Strict decoding drops it too: the width is layout,
+    // the digits are the spelling.
+    pub fn with_padding(mut self, symbol: &str, placement: Padding) -> Result<Self, EncodingError> {
+        let unknown = || EncodingError::UnknownSymbol(symbol.to_string());
+        let mut symbols = self.symbols.clone();
+        symbols.push(symbol.to_string());
+        let extended = Alphabet::new(&symbols.concat()).map_err(|_| unknown())?;
+        if extended.len() != symbols.len() {
+            return Err(unknown());
+        }
+        self.padding = Some((symbol.to_string(), placement));
+        Ok(self)
+    }
+
+    pub fn padding(&self) -> Option<(&str, Padding)> {
+        self.padding.as_ref().map(|(symbol, placement)| (symbol.as_str(), *placement))
+    }
+
+    // The symbols of `s` with their positions, minus any whitespace the alphabet skips
+    fn digits<'s>(&'s self, s: &'s str) -> impl Iterator<Item = (usize, &'s str)> + 's {
+        let skips_whitespace = self.skips_whitespace && !self.strict;
+        split_symbols(s).enumerate().filter(move |(_, symbol)| {
+            !(skips_whitespace && self.value(symbol).is_none() && symbol.chars().all(char::is_whitespace))
+        })
+    }
+
+    // The value of a symbol at `index`; in strict mode only its canonical spelling counts
+    fn lookup(&self, symbol: &str, index: usize) -> Result<usize, EncodingError> {
+        let value = self.value(symbol).ok_or_else(|| invalid_symbol(symbol, index))?;
+        if self.strict && self.symbols[value] != symbol {
+            return Err(EncodingError::NonCanonical("symbol in a different case"));
+        }
+        Ok(value)
+    }
+
+    // A symbol table kept in a UTF-8 text file, validated like any other. Line breaks and a
+    // leading byte order mark are dropped, so long alphabets can be spread over several lines;
+    // every other character, spaces included, is a symbol.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, RabinError> {
+        let text = fs::read_to_string(path)?;
+        let symbols: String = text.trim_start_matches('\u{feff}').split(['\r', '\n']).collect();
+        Ok(Alphabet::new(&symbols)?)
+    }
+
+    // The built-in alphabets are checked by the tests, so building them cannot fail
+    fn built_in(cell: &'static OnceLock<Alphabet>, digitstring: &str) -> &'static Alphabet {
+        cell.get_or_init(|| Alphabet::new(digitstring).expect("built-in alphabets are valid"))
+    }
 
-    for (i, c) in s.chars().enumerate() {
-        if let Some(pos) = digitstring.find(c) {
-            let pos_value = BigInt::from(pos);
-            num = num * &base + pos_value;
+    // DEFAULT_SYMBOLS, built on first use
+    pub fn default_symbols() -> &'static Alphabet {
+        static DEFAULT: OnceLock<Alphabet> = OnceLock::new();
+        Alphabet::built_in(&DEFAULT, DEFAULT_SYMBOLS)
+    }
+
+    pub fn symbols(&self) -> &[String] {
+        &self.symbols
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    // Always false for a validated alphabet; kept alongside len
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn symbol(&self, value: usize) -> Option<&str> {
+        self.symbols.get(value).map(String::as_str)
+    }
+
+    pub fn value(&self, symbol: &str) -> Option<usize> {
+        match ascii_index(symbol) {
+            Some(index) => self.ascii[index].map(|value| value as usize),
+            None => self.others.get(symbol).map(|value| *value as usize),
+        }
+    }
+
+    pub fn digits_to_num(&self, s: &str) -> Result<BigInt, EncodingError> {
+        let mut values = Vec::with_capacity(s.len());
+        for (index, symbol) in self.unpadded(self.digits(s).collect()) {
+            values.push(self.lookup(symbol, index)? as u32);
+        }
+        if self.strict {
+            match values.as_slice() {
+                [] => return Err(EncodingError::NonCanonical("empty text")),
+                [0, _, ..] => return Err(EncodingError::NonCanonical("leading zero symbol")),
+                _ => {}
+            }
+        }
+        Ok(combine_digits(&values, self.len() as u32))
+    }
+
+    // Negative numbers have no spelling in an alphabet, since the minus sign is not a digit
+    pub fn num_to_digits(&self, n: &BigInt) -> Result<String, EncodingError> {
+        if n.sign() == Sign::Minus {
+            return Err(EncodingError::NotText("negative"));
+        }
+        if n.is_zero() {
+            return Ok(self.symbols[0].clone());
+        }
+
+        let base = BigInt::from(self.len());
+        let mut digits = Vec::new();
+        let mut current = n.clone();
+        while !current.is_zero() {
+            let (quotient, remainder) = current.div_rem(&base);
+            digits.push(self.digit_symbol(&remainder));
+            current = quotient;
+        }
+        Ok(digits.iter().rev().copied().collect())
+    }
+
+    // The number in exactly `width` symbols: filled out with the padding symbol where one is
+    // set (and not omitted), with leading zero symbols otherwise
+    pub fn num_to_digits_width(&self, n: &BigInt, width: usize) -> Result<String, EncodingError> {
+        let digits = self.num_to_digits(n)?;
+        let len = split_symbols(&digits).count();
+        if len > width {
+            return Err(EncodingError::TooWide(width));
+        }
+        let fill = width - len;
+        Ok(match &self.padding {
+            Some((symbol, Padding::Leading)) => symbol.repeat(fill) + &digits,
+            Some((symbol, Padding::Trailing)) => digits + &symbol.repeat(fill),
+            Some((_, Padding::Omitted)) => digits,
+            None => self.symbols[0].repeat(fill) + &digits,
+        })
+    }
+
+    // The digits without the padding at the end num_to_digits_width puts it
+    fn unpadded<'s>(&self, mut digits: Vec<(usize, &'s str)>) -> Vec<(usize, &'s str)> {
+        let Some((padding, placement)) = &self.padding else {
+            return digits;
+        };
+        let is_padding = |(_, symbol): &(usize, &str)| symbol == padding;
+        if *placement == Padding::Leading {
+            let start = digits.iter().position(|digit| !is_padding(digit)).unwrap_or(digits.len());
+            digits.drain(..start);
         } else {
-            error!("Invalid character '{}' at position {}", c, i);
+            let end = digits.iter().rposition(|digit| !is_padding(digit)).map_or(0, |last| last + 1);
+            digits.truncate(end);
+        }
+        digits
+    }
+
+    // The symbol for a remainder below the base. Alphabets hold at most u32::MAX symbols, so it
+    // is a single 32-bit digit (none for zero).
+    fn digit_symbol(&self, remainder: &BigInt) -> &str {
+        let value = remainder.magnitude().iter_u32_digits().next().unwrap_or(0);
+        &self.symbols[value as usize]
+    }
+}
+
+// Bijective numeration: symbol values count from 1 instead of 0, so there is no zero digit to
+// drop and every string, including the empty one, maps to its own number. "0abc" and "abc"
+// no longer collide. Values differ from str2num's, and both sides must use the same variant.
+impl Alphabet {
+    pub fn digits_to_num_bijective(&self, s: &str) -> Result<BigInt, EncodingError> {
+        let mut values = Vec::with_capacity(s.len());
+        for (index, symbol) in self.digits(s) {
+            values.push(self.lookup(symbol, index)? as u32 + 1);
+        }
+        Ok(combine_digits(&values, self.len() as u32))
+    }
+
+    pub fn num_to_digits_bijective(&self, n: &BigInt) -> Result<String, EncodingError> {
+        if n.sign() == Sign::Minus {
+            return Err(EncodingError::NotText("negative"));
+        }
+        let base = BigInt::from(self.len());
+        let mut current = n.clone();
+        let mut result = Vec::new();
+        while !current.is_zero() {
+            current -= 1;
+            let (quotient, remainder) = current.div_rem(&base);
+            result.push(self.digit_symbol(&remainder));
+            current = quotient;
+        }
+        Ok(result.iter().rev().copied().collect())
+    }
+}
+
+// Runs this short go through Horner's rule directly
+const HORNER_DIGITS: usize = 64;
+
+// Digit values, most significant first, as a number in `base`. Horner's rule over the whole
+// text multiplies an ever longer number by one small digit per symbol, which is quadratic in
+// the length. Instead the digits are split so the low part holds HORNER_DIGITS * 2^k of them,
+// both parts are converted the same way, and they are joined with one multiplication by
+// base^(HORNER_DIGITS * 2^k). The operands stay balanced, num-bigint's Karatsuba and Toom-3
+// multiplication do the work, and the powers are squared up once per call.
+fn combine_digits(values: &[u32], base: u32) -> BigInt {
+    if values.len() <= HORNER_DIGITS {
+        return BigInt::from(horner(values, base));
+    }
+    let mut powers = vec![BigUint::from(base).pow(HORNER_DIGITS as u32)];
+    while HORNER_DIGITS << powers.len() < values.len() {
+        let last = powers.last().unwrap();
+        powers.push(last * last);
+    }
+    BigInt::from(combine_split(values, base, &powers))
+}
+
+fn combine_split(values: &[u32], base: u32, powers: &[BigUint]) -> BigUint {
+    if values.len() <= HORNER_DIGITS {
+        return horner(values, base);
+    }
+    // The largest HORNER_DIGITS * 2^k below the length
+    let level = (values.len() - 1) / HORNER_DIGITS;
+    let level = level.ilog2() as usize;
+    let (high, low) = values.split_at(values.len() - (HORNER_DIGITS << level));
+    combine_split(high, base, powers) * &powers[level] + combine_split(low, base, powers)
+}
+
+// Plain Horner's rule, gathering digits in a machine word while they fit
+fn horner(values: &[u32], base: u32) -> BigUint {
+    let mut num = BigUint::zero();
+    let mut word = 0u64;
+    let mut scale = 1u64;
+    for &value in values {
+        if scale.checked_mul(u64::from(base)).is_none() {
+            num = num * scale + word;
+            word = 0;
+            scale = 1;
+        }
+        word = word * u64::from(base) + u64::from(value);
+        scale *= u64::from(base);
+    }
+    num * scale + word
+}
+
+fn invalid_symbol(symbol: &str, index: usize) -> EncodingError {
+    EncodingError::InvalidSymbol { symbol: symbol.to_string(), index }
+}
+
+// Slot in the flat table for a symbol that is a single ASCII character
+fn ascii_index(symbol: &str) -> Option<usize> {
+    match symbol.as_bytes() {
+        &[byte] => Some(byte as usize),
+        _ => None,
+    }
+}
+
+// Controls and the Unicode noncharacters (U+FDD0..U+FDEF and the last two code points of every
+// plane) have no printable form, so they cannot be written down as digits
+fn is_assignable(symbol: char) -> bool {
+    let code = symbol as u32;
+    !symbol.is_control() && !(0xfdd0..=0xfdef).contains(&code) && code & 0xfffe != 0xfffe
+}
+
+// Each symbol is one grapheme cluster on its own, but some fuse with a neighbour: a lone
+// regional indicator pairs into a flag, Hangul jamo join into syllables, a symbol ending in
+// ZWJ glues onto a following emoji. Encoded text would then split into different symbols than
+// were written. Whether a boundary holds depends only on the two clusters around it, so every
+// ordered pair is tried. Single ASCII characters never fuse with each other, and behave alike
+// towards the rest, so one of them stands in for all.
+fn check_separable(symbols: &[String]) -> Result<(), EncodingError> {
+    let ascii = symbols.iter().find(|symbol| ascii_index(symbol).is_some());
+    let candidates: Vec<&String> =
+        symbols.iter().filter(|symbol| ascii_index(symbol).is_none()).chain(ascii).collect();
+    let mut pair = String::new();
+    for first in &candidates {
+        for second in &candidates {
+            pair.clear();
+            pair.push_str(first);
+            pair.push_str(second);
+            if split_symbols(&pair).nth(1) != Some(second.as_str()) {
+                return Err(EncodingError::InseparableSymbols(first.to_string(), second.to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn str2num_bijective(s: &str, alphabet: &Alphabet) -> Result<BigInt, EncodingError> {
+    alphabet.digits_to_num_bijective(s)
+}
+
+pub fn num2str_bijective(n: &BigInt, alphabet: &Alphabet) -> Result<String, EncodingError> {
+    alphabet.num_to_digits_bijective(n)
+}
+
+pub fn str2num(s: &str, alphabet: &Alphabet) -> Result<BigInt, EncodingError> {
+    alphabet.digits_to_num(s)
+}
+
+
+pub fn num2str(n: &BigInt, alphabet: &Alphabet) -> Result<String, EncodingError> {
+    trace!("Decoding number: {}", secret(n));
+    trace!("Using an alphabet of {} symbols", alphabet.len());
+    alphabet.num_to_digits(n)
+}
+
+// Incremental encoding for texts too long to hold as one number: the symbols are cut into
+// groups of `group_len` and each group becomes its own number, bijectively so that a short last
+// group and leading zero symbols survive. Only one group is ever held in memory. The encoder
+// takes one symbol per item, as split_symbols yields them; decoding turns each number back into
+// its group and yields the symbols one by one.
+pub struct GroupEncoder<'a, I> {
+    symbols: I,
+    alphabet: &'a Alphabet,
+    group_len: usize,
+    // Symbols consumed so far, for error positions relative to the whole stream
+    offset: usize,
+    failed: bool,
+}
+
+impl<I, S> Iterator for GroupEncoder<'_, I>
+where
+    I: Iterator<Item = S>,
+    S: AsRef<str>,
+{
+    type Item = Result<BigInt, EncodingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
             return None;
         }
+        let base = BigInt::from(self.alphabet.len());
+        let mut num = BigInt::zero();
+        let mut taken = 0;
+        for symbol in self.symbols.by_ref().take(self.group_len) {
+            let pos = match self.alphabet.lookup(symbol.as_ref(), self.offset + taken) {
+                Ok(pos) => pos,
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            };
+            num = num * &base + (pos + 1);
+            taken += 1;
+        }
+        self.offset += taken;
+        (taken > 0).then_some(Ok(num))
+    }
+}
+
+pub fn encode_groups<I, S>(symbols: I, alphabet: &Alphabet, group_len: usize) -> GroupEncoder<'_, I::IntoIter>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    assert!(group_len > 0, "groups need at least one symbol");
+    GroupEncoder { symbols: symbols.into_iter(), alphabet, group_len, offset: 0, failed: false }
+}
+
+pub struct GroupDecoder<'a, I> {
+    numbers: I,
+    alphabet: &'a Alphabet,
+    pending: std::vec::IntoIter<String>,
+    failed: bool,
+}
+
+impl<I: Iterator<Item = BigInt>> Iterator for GroupDecoder<'_, I> {
+    type Item = Result<String, EncodingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(symbol) = self.pending.next() {
+                return Some(Ok(symbol));
+            }
+            if self.failed {
+                return None;
+            }
+            match self.alphabet.num_to_digits_bijective(&self.numbers.next()?) {
+                Ok(group) => self.pending = split_symbols(&group).map(str::to_string).collect::<Vec<_>>().into_iter(),
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+pub fn decode_groups<I>(numbers: I, alphabet: &Alphabet) -> GroupDecoder<'_, I::IntoIter>
+where
+    I: IntoIterator<Item = BigInt>,
+{
+    GroupDecoder { numbers: numbers.into_iter(), alphabet, pending: Vec::new().into_iter(), failed: false }
+}
+
+// Blocks aligned to a modulus n: every full block holds exactly w = floor(log_base(n)) symbols
+// as a positional number, so its value stays below base^w <= n and never wraps around mod n.
+// The text ends with one short block of 0..w-1 symbols in bijective numeration, which also
+// stays below base^w; a text whose length is a multiple of w ends with an empty block. Full
+// blocks keep leading zero symbols because their width is fixed, the last block keeps its own.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedWidth<'a> {
+    alphabet: &'a Alphabet,
+    width: usize,
+}
+
+impl<'a> FixedWidth<'a> {
+    pub fn new(alphabet: &'a Alphabet, n: &BigInt) -> Result<Self, EncodingError> {
+        let base = BigInt::from(alphabet.len());
+        let mut width = 0;
+        let mut limit = base.clone();
+        while &limit <= n {
+            width += 1;
+            limit *= &base;
+        }
+        if width == 0 {
+            return Err(EncodingError::ModulusTooSmall);
+        }
+        Ok(FixedWidth { alphabet, width })
+    }
+
+    // Symbols per full block
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn encode(&self, text: &str) -> Result<Vec<BigInt>, EncodingError> {
+        let symbols: Vec<&str> = split_symbols(text).collect();
+        let mut blocks = Vec::with_capacity(symbols.len() / self.width + 1);
+        let mut chunks = symbols.chunks_exact(self.width);
+        for (block, chunk) in chunks.by_ref().enumerate() {
+            let value = self.alphabet.digits_to_num(&chunk.concat()).map_err(|err| self.offset(err, block))?;
+            blocks.push(value);
+        }
+        let tail = chunks.remainder().concat();
+        let value = self.alphabet.digits_to_num_bijective(&tail).map_err(|err| self.offset(err, blocks.len()))?;
+        blocks.push(value);
+        Ok(blocks)
+    }
+
+    pub fn decode(&self, blocks: &[BigInt]) -> Result<String, EncodingError> {
+        let (last, full) = blocks.split_last().ok_or(EncodingError::NotText("no blocks"))?;
+        let base = BigInt::from(self.alphabet.len());
+        let mut text = String::with_capacity(blocks.len() * self.width);
+        let mut digits = Vec::with_capacity(self.width);
+        for block in full {
+            if block.sign() == Sign::Minus {
+                return Err(EncodingError::NotText("negative"));
+            }
+            digits.clear();
+            let mut current = block.clone();
+            for _ in 0..self.width {
+                let (quotient, remainder) = current.div_rem(&base);
+                digits.push(self.alphabet.digit_symbol(&remainder));
+                current = quotient;
+            }
+            if !current.is_zero() {
+                return Err(EncodingError::NotText("block wider than the modulus allows"));
+            }
+            text.extend(digits.iter().rev().copied());
+        }
+        let tail = self.alphabet.num_to_digits_bijective(last)?;
+        if split_symbols(&tail).count() >= self.width {
+            return Err(EncodingError::NotText("last block too long"));
+        }
+        text.push_str(&tail);
+        Ok(text)
+    }
+
+    // Error positions count from the start of the text, not of the block
+    fn offset(&self, err: EncodingError, block: usize) -> EncodingError {
+        match err {
+            EncodingError::InvalidSymbol { symbol, index } => {
+                EncodingError::InvalidSymbol { symbol, index: block * self.width + index }
+            }
+            other => other,
+        }
+    }
+}
+
+// Any UTF-8 text, through its bytes: the number is 0x01 || UTF-8 bytes, read big-endian. The
+// marker byte keeps leading NUL characters (and the empty string) from vanishing as leading
+// zeros. Text takes one byte per ASCII character, so the modulus bounds the length in bytes.
+const TEXT_MARKER: u8 = 0x01;
+
+pub fn text2num(text: &str) -> BigInt {
+    from_be_bytes(&[&[TEXT_MARKER], text.as_bytes()].concat())
+}
+
+pub fn num2text(n: &BigInt) -> Result<String, EncodingError> {
+    let (sign, bytes) = n.to_bytes_be();
+    if sign == Sign::Minus {
+        return Err(EncodingError::NotText("negative"));
+    }
+    match bytes.split_first() {
+        Some((&TEXT_MARKER, text)) => {
+            String::from_utf8(text.to_vec()).map_err(|_| EncodingError::NotText("invalid UTF-8"))
+        }
+        _ => Err(EncodingError::NotText("missing marker byte")),
+    }
+}
+
+// RFC 4648 text for a number: its minimal big-endian bytes, encoded the way base64 and base32
+// tools do, in groups of 3 bytes to 4 symbols (5 bytes to 8 symbols for base32). Base64 and
+// base32 pad the last group with '='; base64url leaves the padding off, as URLs and JWTs do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rfc4648 {
+    Base64,
+    Base64Url,
+    Base32,
+}
+
+impl Rfc4648 {
+    pub fn alphabet(self) -> &'static Alphabet {
+        static ALPHABETS: [OnceLock<Alphabet>; 3] = [OnceLock::new(), OnceLock::new(), OnceLock::new()];
+        match self {
+            Rfc4648::Base64 => Alphabet::built_in(&ALPHABETS[0], BASE64_SYMBOLS),
+            Rfc4648::Base64Url => Alphabet::built_in(&ALPHABETS[1], BASE64URL_SYMBOLS),
+            Rfc4648::Base32 => Alphabet::built_in(&ALPHABETS[2], BASE32_SYMBOLS),
+        }
+    }
+
+    pub fn padded(self) -> bool {
+        self != Rfc4648::Base64Url
+    }
+
+    fn bits_per_symbol(self) -> u32 {
+        match self {
+            Rfc4648::Base32 => 5,
+            _ => 6,
+        }
+    }
+
+    // Symbols per padded group: 4 for base64, 8 for base32
+    fn group_len(self) -> usize {
+        match self {
+            Rfc4648::Base32 => 8,
+            _ => 4,
+        }
+    }
+}
+
+pub fn num2rfc4648(n: &BigInt, variant: Rfc4648) -> Result<String, EncodingError> {
+    let (sign, bytes) = n.to_bytes_be();
+    if sign == Sign::Minus {
+        return Err(EncodingError::NotText("negative"));
+    }
+    let symbols = variant.alphabet().symbols();
+    let width = variant.bits_per_symbol();
+    let mut out = String::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= width {
+            bits -= width;
+            out.push_str(&symbols[(buffer >> bits) as usize & ((1 << width) - 1)]);
+        }
+    }
+    if bits > 0 {
+        out.push_str(&symbols[(buffer << (width - bits)) as usize & ((1 << width) - 1)]);
+    }
+    if variant.padded() {
+        while !out.len().is_multiple_of(variant.group_len()) {
+            out.push('=');
+        }
+    }
+    Ok(out)
+}
+
+// Accepts the text with or without padding
+pub fn rfc4648_to_num(s: &str, variant: Rfc4648) -> Result<BigInt, EncodingError> {
+    let alphabet = variant.alphabet();
+    let width = variant.bits_per_symbol();
+    let body = s.trim_end_matches('=');
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0u32);
+    for (index, symbol) in split_symbols(body).enumerate() {
+        let value = alphabet.value(symbol).ok_or_else(|| invalid_symbol(symbol, index))?;
+        buffer = (buffer << width) | value as u32;
+        bits += width;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    // Leftover bits must be the zero fill of the last symbol, and a whole symbol cannot be left
+    if bits >= width || buffer & ((1 << bits) - 1) != 0 {
+        return Err(EncodingError::NotText("truncated RFC 4648 group"));
+    }
+    Ok(from_be_bytes(&bytes))
+}
+
+// Compact display of key and ciphertext values: lowercase hex of the minimal big-endian bytes,
+// or unpadded RFC 4648 base32, optionally cut into groups of `size` symbols from the left, as
+// in "3f2a:91c0:7d". Parsing drops the grouping's separator wherever it appears and takes hex in
+// either case; error positions still point into the text as given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grouping {
+    pub size: usize,
+    pub separator: char,
+}
+
+impl Grouping {
+    pub fn new(size: usize, separator: char) -> Self {
+        Grouping { size, separator }
+    }
+
+    fn apply(self, text: &str) -> String {
+        if self.size == 0 {
+            return text.to_string();
+        }
+        let symbols: Vec<char> = text.chars().collect();
+        let mut out = String::with_capacity(text.len() + text.len() / self.size);
+        for (index, group) in symbols.chunks(self.size).enumerate() {
+            if index > 0 {
+                out.push(self.separator);
+            }
+            out.extend(group);
+        }
+        out
     }
-    Some(num)
 }
 
+// The symbols without separators, and each one's position in the original text
+fn ungroup(s: &str, grouping: Option<Grouping>) -> (Vec<&str>, Vec<usize>) {
+    let mut separator = [0; 4];
+    let separator = grouping.map(|grouping| &*grouping.separator.encode_utf8(&mut separator));
+    split_symbols(s)
+        .enumerate()
+        .filter(|(_, symbol)| Some(*symbol) != separator)
+        .map(|(index, symbol)| (symbol, index))
+        .unzip()
+}
+
+fn regroup_error(err: EncodingError, positions: &[usize]) -> EncodingError {
+    match err {
+        EncodingError::InvalidSymbol { symbol, index } => {
+            EncodingError::InvalidSymbol { symbol, index: positions[index] }
+        }
+        other => other,
+    }
+}
+
+pub fn num2hex(n: &BigInt, grouping: Option<Grouping>) -> Result<String, EncodingError> {
+    let (sign, bytes) = n.to_bytes_be();
+    if sign == Sign::Minus {
+        return Err(EncodingError::NotText("negative"));
+    }
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    Ok(match grouping {
+        Some(grouping) => grouping.apply(&hex),
+        None => hex,
+    })
+}
+
+pub fn hex2num(s: &str, grouping: Option<Grouping>) -> Result<BigInt, EncodingError> {
+    let (digits, positions) = ungroup(s, grouping);
+    let mut num = BigInt::zero();
+    for (index, symbol) in digits.into_iter().enumerate() {
+        let value = ascii_index(symbol)
+            .and_then(|byte| char::from(byte as u8).to_digit(16))
+            .ok_or_else(|| invalid_symbol(symbol, positions[index]))?;
+        num = (num << 4) + value;
+    }
+    Ok(num)
+}
+
+pub fn num2base32(n: &BigInt, grouping: Option<Grouping>) -> Result<String, EncodingError> {
+    let padded = num2rfc4648(n, Rfc4648::Base32)?;
+    let text = padded.trim_end_matches('=');
+    Ok(match grouping {
+        Some(grouping) => grouping.apply(text),
+        None => text.to_string(),
+    })
+}
+
+// Takes the padded form as well
+pub fn base32_to_num(s: &str, grouping: Option<Grouping>) -> Result<BigInt, EncodingError> {
+    let (symbols, positions) = ungroup(s, grouping);
+    rfc4648_to_num(&symbols.concat(), Rfc4648::Base32).map_err(|err| regroup_error(err, &positions))
+}
+
+// Text to number and back, for APIs that should not care which encoding is in use. Alphabets
+// map symbols to digits (str2num/num2str), Utf8 goes through the text's bytes
+// (text2num/num2text) and Hex reads the text as a hexadecimal number (hex2num/num2hex).
+// Alphabet's inherent encode/decode run the other way round and win in method calls, so call
+// these through the trait (`Codec::encode(alphabet, text)`) or a generic parameter.
+pub trait Codec {
+    fn encode(&self, text: &str) -> Result<BigInt, EncodingError>;
+    fn decode(&self, n: &BigInt) -> Result<String, EncodingError>;
+}
+
+impl<C: Codec + ?Sized> Codec for &C {
+    fn encode(&self, text: &str) -> Result<BigInt, EncodingError> {
+        (**self).encode(text)
+    }
+
+    fn decode(&self, n: &BigInt) -> Result<String, EncodingError> {
+        (**self).decode(n)
+    }
+}
+
+impl Codec for Alphabet {
+    fn encode(&self, text: &str) -> Result<BigInt, EncodingError> {
+        str2num(text, self)
+    }
+
+    fn decode(&self, n: &BigInt) -> Result<String, EncodingError> {
+        num2str(n, self)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Utf8;
+
+impl Codec for Utf8 {
+    fn encode(&self, text: &str) -> Result<BigInt, EncodingError> {
+        Ok(text2num(text))
+    }
+
+    fn decode(&self, n: &BigInt) -> Result<String, EncodingError> {
+        num2text(n)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Hex(pub Option<Grouping>);
+
+impl Codec for Hex {
+    fn encode(&self, text: &str) -> Result<BigInt, EncodingError> {
+        hex2num(text, self.0)
+    }
+
+    fn decode(&self, n: &BigInt) -> Result<String, EncodingError> {
+        num2hex(n, self.0)
+    }
+}
+
+// Texts that are mostly in an alphabet: every symbol outside it, and the escape symbol itself,
+// is written as one escape per UTF-8 byte, the escape symbol followed by two lower-case hex
+// digits. Under DEFAULT_SYMBOLS with '|', "año $5" becomes "a|c3|b1o |245". The escaped text
+// goes through bijective numeration, so leading zero symbols survive as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Escaped {
+    alphabet: Alphabet,
+    escape: String,
+}
+
+pub const DEFAULT_ESCAPE: &str = "|";
+const HEX_DIGITS: &str = "0123456789abcdef";
+
+impl Escaped {
+    // The escape and the hex digits must all be symbols of the alphabet
+    pub fn new(alphabet: Alphabet, escape: &str) -> Result<Self, EncodingError> {
+        let usable = alphabet.value(escape).is_some()
+            && split_symbols(HEX_DIGITS).all(|digit| alphabet.value(digit).is_some());
+        if !usable {
+            return Err(EncodingError::UnusableEscape(escape.to_string()));
+        }
+        Ok(Escaped { alphabet, escape: escape.to_string() })
+    }
+
+    // DEFAULT_SYMBOLS with DEFAULT_ESCAPE, built on first use
+    pub fn default_symbols() -> &'static Escaped {
+        static DEFAULT: OnceLock<Escaped> = OnceLock::new();
+        DEFAULT.get_or_init(|| {
+            Escaped::new(Alphabet::default_symbols().clone(), DEFAULT_ESCAPE).expect("the default escape is usable")
+        })
+    }
+
+    pub fn alphabet(&self) -> &Alphabet {
+        &self.alphabet
+    }
 
-pub fn num2str(n: &BigInt, digitstring: &str) -> String {
-    let base = BigInt::from(digitstring.len());
-    let mut result = String::new();
-    let mut current = n.clone();
+    pub fn escape(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for symbol in split_symbols(text) {
+            if symbol != self.escape && self.alphabet.value(symbol).is_some() {
+                out.push_str(symbol);
+                continue;
+            }
+            for byte in symbol.bytes() {
+                out.push_str(&self.escape);
+                out.push_str(&format!("{:02x}", byte));
+            }
+        }
+        out
+    }
+
+    // Runs of escaped bytes must form whole UTF-8 characters
+    pub fn unescape(&self, s: &str) -> Result<String, EncodingError> {
+        let mut out = String::with_capacity(s.len());
+        let mut bytes = Vec::new();
+        let mut symbols = split_symbols(s);
+        while let Some(symbol) = symbols.next() {
+            if symbol != self.escape {
+                flush_escaped(&mut bytes, &mut out)?;
+                out.push_str(symbol);
+                continue;
+            }
+            let mut byte = 0;
+            for _ in 0..2 {
+                let digit = symbols
+                    .next()
+                    .and_then(ascii_index)
+                    .and_then(|digit| char::from(digit as u8).to_digit(16))
+                    .ok_or(EncodingError::NotText("escape without two hex digits"))?;
+                byte = byte << 4 | digit as u8;
+            }
+            bytes.push(byte);
+        }
+        flush_escaped(&mut bytes, &mut out)?;
+        Ok(out)
+    }
+}
+
+fn flush_escaped(bytes: &mut Vec<u8>, out: &mut String) -> Result<(), EncodingError> {
+    if !bytes.is_empty() {
+        out.push_str(std::str::from_utf8(bytes).map_err(|_| EncodingError::NotText("escapes are not UTF-8"))?);
+        bytes.clear();
+    }
+    Ok(())
+}
+
+impl Codec for Escaped {
+    fn encode(&self, text: &str) -> Result<BigInt, EncodingError> {
+        self.alphabet.digits_to_num_bijective(&self.escape(text))
+    }
+
+    fn decode(&self, n: &BigInt) -> Result<String, EncodingError> {
+        self.unescape(&self.alphabet.num_to_digits_bijective(n)?)
+    }
+}
+
+// A guard against transcription mistakes for any codec: the text form ends in the CRC-32 of the
+// number's big-endian bytes, as eight hex digits. decode appends it and encode checks and
+// strips it, so a mistyped or dropped symbol fails with BadChecksum instead of standing for
+// some other number, which would decrypt to garbage. The digits are read in either case.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Checksummed<C>(pub C);
+
+const CHECKSUM_LEN: usize = 8;
+
+fn checksum(n: &BigInt) -> Result<u32, EncodingError> {
+    let bytes = num2bytes(n, ByteOrder::BigEndian, None).ok_or(EncodingError::NotText("negative"))?;
+    Ok(crc32(&bytes))
+}
+
+impl<C: Codec> Codec for Checksummed<C> {
+    fn encode(&self, text: &str) -> Result<BigInt, EncodingError> {
+        let split = text.len().checked_sub(CHECKSUM_LEN).ok_or(EncodingError::BadChecksum)?;
+        let (body, digits) = text.split_at_checked(split).ok_or(EncodingError::BadChecksum)?;
+        if !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return Err(EncodingError::BadChecksum);
+        }
+        let expected = u32::from_str_radix(digits, 16).map_err(|_| EncodingError::BadChecksum)?;
+        let n = self.0.encode(body)?;
+        if checksum(&n)? != expected {
+            return Err(EncodingError::BadChecksum);
+        }
+        Ok(n)
+    }
+
+    fn decode(&self, n: &BigInt) -> Result<String, EncodingError> {
+        let sum = checksum(n)?;
+        Ok(format!("{}{:08x}", self.0.decode(n)?, sum))
+    }
+}
 
-    info!("Decoding number: {}", n);
-    info!("Using digitstring: '{}'", digitstring);
+// Strict decoding for any codec: text is only accepted if the codec would write it back
+// unchanged, which rules out hex in upper case or with extra leading zeros, RFC 4648 padding
+// where the codec leaves none, and whatever else a codec reads leniently.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Strict<C>(pub C);
 
-    if n.is_zero() {
-        return digitstring.chars().next().unwrap().to_string();
+impl<C: Codec> Codec for Strict<C> {
+    fn encode(&self, text: &str) -> Result<BigInt, EncodingError> {
+        let n = self.0.encode(text)?;
+        if self.0.decode(&n)? != text {
+            return Err(EncodingError::NonCanonical("not the codec's own spelling of the value"));
+        }
+        Ok(n)
     }
 
-    // Handle negative numbers
-    let is_negative = current < BigInt::zero();
-    if is_negative {
-        current = -current;
+    fn decode(&self, n: &BigInt) -> Result<String, EncodingError> {
+        self.0.decode(n)
     }
+}
+
+// The byte-oriented formats (envelopes, block mode, key files) convert between numbers and byte
+// strings directly, without any textual encoding in between. Everything that must agree on
+// the bytes of a number goes through bytes2num/num2bytes; the formats in this crate are all
+// big-endian and fixed to the modulus width where the length is not stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    #[default]
+    BigEndian,
+    LittleEndian,
+}
 
-    while current > BigInt::zero() {
-        let remainder = (&current % &base).to_usize().unwrap();
-        result.push(digitstring.chars().nth(remainder).unwrap());
-        current /= &base;
+// Bytes needed to hold any value below n
+pub fn modulus_len(n: &BigInt) -> usize {
+    n.bits().div_ceil(8) as usize
+}
+
+// Bytes read as an unsigned number; the empty string is 0
+pub fn bytes2num(bytes: &[u8], order: ByteOrder) -> BigInt {
+    match order {
+        ByteOrder::BigEndian => BigInt::from_bytes_be(Sign::Plus, bytes),
+        ByteOrder::LittleEndian => BigInt::from_bytes_le(Sign::Plus, bytes),
     }
+}
 
-    if is_negative {
-        result.push('-');
+// The bytes of a non-negative number: as few as possible (one for zero) without a width, or
+// exactly `width` padded with zeros at the high end (the left for big-endian, the right for
+// little-endian). The bytes are written straight from the number's digits into the output.
+// None for negative values and values wider than `width`.
+pub fn num2bytes(value: &BigInt, order: ByteOrder, width: Option<usize>) -> Option<Vec<u8>> {
+    let width = width.unwrap_or_else(|| modulus_len(value).max(1));
+    if value.sign() == Sign::Minus || value.bits().div_ceil(8) > width as u64 {
+        return None;
     }
+    let mut out = vec![0u8; width];
+    let digits = value.magnitude().iter_u64_digits();
+    match order {
+        ByteOrder::BigEndian => {
+            for (chunk, digit) in out.rchunks_mut(8).zip(digits) {
+                let bytes = digit.to_be_bytes();
+                chunk.copy_from_slice(&bytes[8 - chunk.len()..]);
+            }
+        }
+        ByteOrder::LittleEndian => {
+            for (chunk, digit) in out.chunks_mut(8).zip(digits) {
+                let bytes = digit.to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+    }
+    Some(out)
+}
+
+pub fn from_be_bytes(bytes: &[u8]) -> BigInt {
+    bytes2num(bytes, ByteOrder::BigEndian)
+}
 
-    result.chars().rev().collect()
+// Exactly `width` big-endian bytes, left-padded with zeros
+pub fn to_fixed_be_bytes(value: &BigInt, width: usize) -> Option<Vec<u8>> {
+    num2bytes(value, ByteOrder::BigEndian, Some(width))
 }
 
 
@@ -64,7 +1174,7 @@ mod tests {
 
     #[test]
     fn num2str_simple() {
-        let result = num2str(&BigInt::from_str("5028722558842848375853089736952727210229032068167510534250475").unwrap(), DEFAULT_SYMBOLS);
+        let result = num2str(&BigInt::from_str("5028722558842848375853089736952727210229032068167510534250475").unwrap(), Alphabet::default_symbols()).unwrap();
         let expected_result = "Non scholae, sed vitae discimus.";
         assert_eq!(result, expected_result);
     }
@@ -74,9 +1184,9 @@ mod tests {
         use num_bigint::BigInt;
         use num_traits::Zero;
 
-        let digitstring = DEFAULT_SYMBOLS; // Assuming this constant is defined
+        let alphabet = Alphabet::default_symbols();
         let text = "012";
-        let base = digitstring.len(); // Base of the custom symbol set
+        let base = alphabet.len(); // Base of the custom symbol set
 
         // "012" -> positions [0, 1, 2], encoded in positional numeral system
         // Expected: 0 * base^2 + 1 * base^1 + 2 * base^0
@@ -85,44 +1195,547 @@ mod tests {
             + BigInt::from(1) * BigInt::from(base).pow(1) // 1 * base^1
             + BigInt::from(2) * BigInt::from(base).pow(0); // 2 * base^0
 
-        let result = str2num(text, digitstring);
+        let result = str2num(text, alphabet);
 
-        assert_eq!(result, Some(expected_num));
+        assert_eq!(result, Ok(expected_num));
     }
 
 
     #[test]
     fn test_num2str_basic() {
         let expected_text = "abc";
-        let number = str2num(expected_text, DEFAULT_SYMBOLS).unwrap();
-        let result = num2str(&number, DEFAULT_SYMBOLS);
+        let number = str2num(expected_text, Alphabet::default_symbols()).unwrap();
+        let result = num2str(&number, Alphabet::default_symbols()).unwrap();
         assert_eq!(result, expected_text);
     }
 
+    #[test]
+    fn test_long_texts_match_horners_rule() {
+        let mut rng = rand::thread_rng();
+        for alphabet in [Alphabet::default_symbols(), &Alphabet::new("01").unwrap(), &Alphabet::new("🦀👍🏽🇩🇪").unwrap()] {
+            for len in [0, 1, 63, 64, 65, 128, 129, 1000, 4097] {
+                let text: String = (0..len)
+                    .map(|_| alphabet.symbol(rand::Rng::gen_range(&mut rng, 0..alphabet.len())).unwrap())
+                    .collect();
+                let base = BigInt::from(alphabet.len());
+                let mut expected = BigInt::zero();
+                let mut bijective = BigInt::zero();
+                for symbol in split_symbols(&text) {
+                    expected = expected * &base + alphabet.value(symbol).unwrap();
+                    bijective = bijective * &base + alphabet.value(symbol).unwrap() + 1;
+                }
+                assert_eq!(str2num(&text, alphabet).unwrap(), expected, "{} symbols", len);
+                assert_eq!(str2num_bijective(&text, alphabet).unwrap(), bijective, "{} symbols", len);
+            }
+        }
+    }
+
     #[test]
     fn test_str2num_and_num2str_round_trip() {
         let text = "HELLO";
-        let encoded = str2num(text, DEFAULT_SYMBOLS).unwrap();
-        let decoded = num2str(&encoded, DEFAULT_SYMBOLS);
+        let encoded = str2num(text, Alphabet::default_symbols()).unwrap();
+        let decoded = num2str(&encoded, Alphabet::default_symbols()).unwrap();
 
         assert_eq!(decoded, text, "Round-trip encoding and decoding should match the original text");
     }
 
     #[test]
     fn test_maximum_value() {
-        let digitstring = DEFAULT_SYMBOLS;
-        let text = "   "; // assuming 'Z' is the highest valid character in `digitstring`
+        let alphabet = Alphabet::default_symbols();
+        let text = "   "; // ' ' is the highest valid character in the default alphabet
 
-        let encoded = str2num(text, digitstring).unwrap();
-        let decoded = num2str(&encoded, digitstring);
+        let encoded = str2num(text, alphabet).unwrap();
+        let decoded = num2str(&encoded, alphabet).unwrap();
 
         assert_eq!(decoded, text, "The decoded value of the maximum character sequence should match the original");
     }
 
-    // #[test]
-    // fn test_invalid_character() {
-    //     let text = "HELLO$"; // '$' is not in `DEFAULT_SYMBOLS`, so should handle this gracefully
-    //     let result = str2num(text, DEFAULT_SYMBOLS);
-    //     assert!(result.is_err(), "Encoding text with invalid characters should return an error, not panic");
-    // }
+    #[test]
+    fn test_alphabet_tables() {
+        assert!(std::ptr::eq(Alphabet::default_symbols(), Alphabet::default_symbols()), "Built once");
+        let alphabet = Alphabet::default_symbols();
+        assert_eq!(alphabet.len(), DEFAULT_SYMBOLS.chars().count());
+        assert_eq!(alphabet.value("a"), DEFAULT_SYMBOLS.find('a'));
+        assert_eq!(alphabet.symbol(alphabet.value(" ").unwrap()), Some(" "));
+        assert_eq!(alphabet.value("$"), None);
+
+        // Non-ASCII symbols count as one digit each
+        let greek = Alphabet::new("αβγδ").unwrap();
+        let number = str2num("βγα", &greek).unwrap();
+        assert_eq!(number, BigInt::from(16 + 2 * 4));
+        assert_eq!(num2str(&number, &greek).unwrap(), "βγα");
+    }
+
+    #[test]
+    fn test_alphabet_validation() {
+        for symbols in [DEFAULT_SYMBOLS, BASE64_SYMBOLS, BASE64URL_SYMBOLS, BASE32_SYMBOLS] {
+            assert!(Alphabet::new(symbols).is_ok(), "Built-in alphabet {:?} is valid", symbols);
+        }
+        assert_eq!(Alphabet::new(""), Err(EncodingError::AlphabetTooShort(0)));
+        assert_eq!(Alphabet::new("α"), Err(EncodingError::AlphabetTooShort(1)));
+        assert_eq!(Alphabet::new("abca"), Err(EncodingError::DuplicateSymbol("a".into())));
+        assert_eq!(Alphabet::new("aβγβ"), Err(EncodingError::DuplicateSymbol("β".into())));
+        assert_eq!(Alphabet::new("ab\n"), Err(EncodingError::UnassignableSymbol('\n')));
+        assert_eq!(Alphabet::new("ab\u{fdd0}"), Err(EncodingError::UnassignableSymbol('\u{fdd0}')));
+        assert_eq!(Alphabet::new("ab\u{1fffe}"), Err(EncodingError::UnassignableSymbol('\u{1fffe}')));
+        assert_eq!(
+            EncodingError::UnassignableSymbol('\u{ffff}').to_string(),
+            "'\\u{ffff}' (U+FFFF) cannot be used as a symbol"
+        );
+        assert!(Alphabet::new("01").is_ok(), "Two symbols are enough");
+    }
+
+    #[test]
+    fn test_alphabet_from_file() {
+        let dir = std::env::temp_dir().join(format!("rabin-alphabet-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("symbols.txt");
+
+        fs::write(&path, "\u{feff}ABCDEFGHIJ\r\nKLMNOPQRST\nUVWXYZ .,\n").unwrap();
+        let alphabet = Alphabet::from_file(&path).unwrap();
+        assert_eq!(alphabet.len(), 29, "Line breaks and the BOM are not symbols, the space is");
+        assert_eq!(alphabet.value(" "), Some(26));
+
+        fs::write(&path, "ABC\nCDE\n").unwrap();
+        assert_eq!(
+            Alphabet::from_file(&path),
+            Err(RabinError::Encoding(EncodingError::DuplicateSymbol("C".into())))
+        );
+        assert!(matches!(Alphabet::from_file(dir.join("missing.txt")), Err(RabinError::Io(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_case_folding() {
+        let hex = Alphabet::new("0123456789ABCDEF").unwrap().fold_case().unwrap();
+        assert!(hex.folds_case());
+        assert_eq!(str2num("deadBEEF", &hex).unwrap(), BigInt::from(0xdead_beefu32));
+        assert_eq!(num2str(&BigInt::from(0xbeefu32), &hex).unwrap(), "BEEF", "Encoding keeps the given case");
+        assert_eq!(hex.len(), 16);
+
+        let greek = Alphabet::new("αβγδ").unwrap().fold_case().unwrap();
+        assert_eq!(str2num("ΒΓα", &greek), str2num("βγα", &greek));
+        assert!(str2num("ab", &Alphabet::new("AB").unwrap()).is_err(), "Folding is opt-in");
+
+        assert_eq!(
+            Alphabet::default_symbols().clone().fold_case(),
+            Err(EncodingError::CaseAmbiguous("A".into(), "a".into()))
+        );
+    }
+
+    #[test]
+    fn test_whitespace_skipping() {
+        let base32 = Alphabet::new(BASE32_SYMBOLS).unwrap();
+        let lenient = base32.clone().skip_whitespace();
+        let wrapped = "JVQW4\r\nII\tAB  CD\n";
+        assert_eq!(str2num(wrapped, &lenient), str2num("JVQW4IIABCD", &base32));
+        assert_eq!(str2num_bijective(wrapped, &lenient), str2num_bijective("JVQW4IIABCD", &base32));
+        assert_eq!(
+            str2num("AB\n1", &lenient),
+            Err(EncodingError::InvalidSymbol { symbol: "1".into(), index: 3 }),
+            "Positions still count the skipped symbols"
+        );
+        assert!(str2num("AB CD", &base32).is_err(), "Skipping is opt-in");
+
+        // The space is a digit of the default alphabet, so only the line break goes
+        let default = Alphabet::default_symbols().clone().skip_whitespace();
+        assert_eq!(num2str(&str2num("a b\nc", &default).unwrap(), &default).unwrap(), "a bc");
+    }
+
+    #[test]
+    fn test_strict_decoding_has_one_spelling_per_value() {
+        let strict = Alphabet::default_symbols().clone().strict();
+        assert!(strict.is_strict());
+        assert_eq!(str2num("abc", &strict), str2num("abc", Alphabet::default_symbols()));
+        assert_eq!(str2num("0", &strict), Ok(BigInt::zero()));
+        assert_eq!(str2num("0abc", &strict), Err(EncodingError::NonCanonical("leading zero symbol")));
+        assert_eq!(str2num("00", &strict), Err(EncodingError::NonCanonical("leading zero symbol")));
+        assert_eq!(str2num("", &strict), Err(EncodingError::NonCanonical("empty text")));
+        assert!(str2num("a$", &strict).is_err());
+        assert_eq!(str2num_bijective("0abc", &strict), str2num_bijective("0abc", Alphabet::default_symbols()));
+
+        let hex = Alphabet::new("0123456789abcdef").unwrap().fold_case().unwrap().skip_whitespace();
+        assert!(str2num("AB CD", &hex).is_ok());
+        let hex = hex.strict();
+        assert_eq!(str2num("aB", &hex), Err(EncodingError::NonCanonical("symbol in a different case")));
+        assert!(str2num("ab cd", &hex).is_err(), "Whitespace is not skipped");
+        let mut encoder = encode_groups(split_symbols("abC"), &hex, 2);
+        assert!(encoder.next().unwrap().is_ok());
+        assert!(encoder.next().unwrap().is_err());
+
+        // Every value decodes from what encode writes, and from nothing else
+        for value in 0..600u32 {
+            let value = BigInt::from(value);
+            assert_eq!(str2num(&num2str(&value, &hex).unwrap(), &hex), Ok(value));
+        }
+
+        let codec = Strict(Hex(None));
+        assert_eq!(codec.encode("0a0b"), Ok(BigInt::from(0x0a0b)));
+        for lenient in ["a0b", "000a0b", "0A0B"] {
+            assert!(Hex(None).encode(lenient).is_ok());
+            assert!(codec.encode(lenient).is_err(), "{:?}", lenient);
+        }
+        let checksummed = Checksummed(Hex(None)).decode(&BigInt::from(0xc0ffee)).unwrap();
+        assert!(Strict(Checksummed(Hex(None))).encode(&checksummed).is_ok());
+        assert!(Strict(Checksummed(Hex(None))).encode(&checksummed.to_uppercase()).is_err());
+    }
+
+    #[test]
+    fn test_grapheme_alphabets() {
+        // Skin tone, flag, ZWJ family and a combining accent: five code point sequences, five digits
+        let emoji = Alphabet::new("🦀👍🏽🇩🇪👨‍👩‍👧e\u{301}").unwrap();
+        assert_eq!(emoji.len(), 5);
+        assert_eq!(emoji.symbol(2), Some("🇩🇪"));
+        assert_eq!(emoji.value("e\u{301}"), Some(4));
+        assert_eq!(emoji.value("e"), None, "A prefix of a symbol is not a symbol");
+
+        let text = "👨‍👩‍👧🇩🇪🦀e\u{301}👍🏽🇩🇪🇩🇪";
+        let number = str2num(text, &emoji).unwrap();
+        assert_eq!(number, str2num("3204122", &Alphabet::new("01234").unwrap()).unwrap());
+        assert_eq!(num2str(&number, &emoji).unwrap(), text);
+        assert_eq!(num2str_bijective(&str2num_bijective("🦀🦀👍🏽", &emoji).unwrap(), &emoji).unwrap(), "🦀🦀👍🏽");
+
+        let blocks = FixedWidth::new(&emoji, &BigInt::from(5u32.pow(3))).unwrap();
+        assert_eq!(blocks.decode(&blocks.encode(text).unwrap()).unwrap(), text);
+        let groups: Vec<BigInt> = encode_groups(split_symbols(text), &emoji, 3).map(Result::unwrap).collect();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(decode_groups(groups, &emoji).map(Result::unwrap).collect::<String>(), text);
+
+        assert_eq!(
+            str2num("🦀👍x", &emoji),
+            Err(EncodingError::InvalidSymbol { symbol: "👍".into(), index: 1 }),
+            "A bare thumb is not the toned one, and positions count clusters"
+        );
+
+        // Symbols that would fuse with a neighbour cannot be told apart in encoded text
+        assert_eq!(
+            Alphabet::new("\u{301}a"),
+            Err(EncodingError::InseparableSymbols("\u{301}".into(), "\u{301}".into()))
+        );
+        assert_eq!(
+            Alphabet::new("🇦🇧🇨"),
+            Err(EncodingError::InseparableSymbols("🇨".into(), "🇦🇧".into())),
+            "A lone regional indicator pairs with the next one"
+        );
+        assert!(matches!(Alphabet::new("🦀👍\u{200d}"), Err(EncodingError::InseparableSymbols(..))));
+    }
+
+    #[test]
+    fn test_bijective_codec_keeps_leading_zero_symbols() {
+        assert_eq!(num2str(&str2num("0abc", Alphabet::default_symbols()).unwrap(), Alphabet::default_symbols()).unwrap(), "abc");
+        for text in ["0abc", "00", "0", "", "abc", "   ", "Non scholae, sed vitae discimus."] {
+            let number = str2num_bijective(text, Alphabet::default_symbols()).unwrap();
+            assert_eq!(num2str_bijective(&number, Alphabet::default_symbols()).unwrap(), text, "{:?}", text);
+        }
+        // Every number is some string: 0 is "", then "0", "1", ..., " ", "00", ...
+        let base = DEFAULT_SYMBOLS.chars().count();
+        assert_eq!(num2str_bijective(&BigInt::from(1), Alphabet::default_symbols()).unwrap(), "0");
+        assert_eq!(num2str_bijective(&BigInt::from(base), Alphabet::default_symbols()).unwrap(), " ");
+        assert_eq!(num2str_bijective(&BigInt::from(base + 1), Alphabet::default_symbols()).unwrap(), "00");
+        for value in 0..500u32 {
+            let binary = Alphabet::new("ab").unwrap();
+            let text = num2str_bijective(&BigInt::from(value), &binary).unwrap();
+            assert_eq!(str2num_bijective(&text, &binary).unwrap(), BigInt::from(value));
+        }
+        assert!(num2str_bijective(&BigInt::from(-3), Alphabet::default_symbols()).is_err());
+    }
+
+    #[test]
+    fn test_group_codec_streams_long_text() {
+        let alphabet = Alphabet::default_symbols();
+        let text: String = DEFAULT_SYMBOLS.chars().cycle().skip(3).take(10_001).collect();
+        let groups: Vec<BigInt> = encode_groups(split_symbols(&text), alphabet, 64).collect::<Result<_, _>>().unwrap();
+        assert_eq!(groups.len(), 157, "156 full groups and a one-character tail");
+        assert_eq!(groups[1], str2num_bijective(&text[64..128], alphabet).unwrap());
+        let decoded: String = decode_groups(groups, alphabet).collect::<Result<_, _>>().unwrap();
+        assert_eq!(decoded, text);
+
+        // Leading zero symbols inside a group are kept; empty input gives no groups
+        let zeros: Vec<_> = encode_groups(split_symbols("000a"), alphabet, 3).map(Result::unwrap).collect();
+        assert_eq!(decode_groups(zeros, alphabet).map(Result::unwrap).collect::<String>(), "000a");
+        assert_eq!(encode_groups(split_symbols(""), alphabet, 8).count(), 0);
+    }
+
+    #[test]
+    fn test_group_codec_errors() {
+        let alphabet = Alphabet::default_symbols();
+        let mut encoder = encode_groups(split_symbols("abcdef$gh"), alphabet, 4);
+        assert!(encoder.next().unwrap().is_ok());
+        assert_eq!(encoder.next(), Some(Err(EncodingError::InvalidSymbol { symbol: "$".into(), index: 6 })));
+        assert_eq!(encoder.next(), None, "Encoding stops at the first error");
+
+        let mut decoder = decode_groups([BigInt::from(1), BigInt::from(-1), BigInt::from(2)], alphabet);
+        assert_eq!(decoder.next(), Some(Ok("0".to_string())));
+        assert!(decoder.next().unwrap().is_err());
+        assert_eq!(decoder.next(), None, "Decoding stops at the first error");
+    }
+
+    #[test]
+    fn test_fixed_width_blocks_stay_below_modulus() {
+        let alphabet = Alphabet::default_symbols();
+        let base = BigInt::from(alphabet.len());
+        let n = base.pow(5) + 1;
+        let blocks = FixedWidth::new(alphabet, &n).unwrap();
+        assert_eq!(blocks.width(), 5);
+        assert_eq!(FixedWidth::new(alphabet, &(&n - 2)).unwrap().width(), 4, "base^5 - 1 only holds 4 symbols");
+
+        let long: String = DEFAULT_SYMBOLS.chars().rev().cycle().take(203).collect();
+        for text in ["", "0", "00000", "00000   ", "     ", long.as_str()] {
+            let values = blocks.encode(text).unwrap();
+            assert_eq!(values.len(), text.chars().count() / 5 + 1, "{:?}", text);
+            assert!(values.iter().all(|value| value < &n), "{:?} overflows the modulus", text);
+            assert_eq!(blocks.decode(&values).unwrap(), text);
+        }
+
+        assert_eq!(
+            blocks.encode("abcdefg$").unwrap_err(),
+            EncodingError::InvalidSymbol { symbol: "$".into(), index: 7 }
+        );
+        assert_eq!(FixedWidth::new(alphabet, &base).unwrap().width(), 1);
+        assert_eq!(FixedWidth::new(alphabet, &(&base - 1)).unwrap_err(), EncodingError::ModulusTooSmall);
+        assert!(blocks.decode(&[]).is_err());
+        assert!(blocks.decode(&[base.pow(5), BigInt::zero()]).is_err(), "Full block too wide");
+        assert!(blocks.decode(&[base.pow(5) - 1]).is_err(), "Last block must be shorter than a full one");
+    }
+
+    #[test]
+    fn test_hex_and_base32_codecs() {
+        let value = BigInt::from(0x01_0203_0405u64);
+        assert_eq!(num2hex(&value, None).unwrap(), "0102030405");
+        assert_eq!(num2hex(&BigInt::zero(), None).unwrap(), "00");
+        let grouped = num2hex(&value, Some(Grouping::new(4, ':'))).unwrap();
+        assert_eq!(grouped, "0102:0304:05");
+        assert_eq!(hex2num(&grouped, Some(Grouping::new(4, ':'))).unwrap(), value);
+        assert_eq!(hex2num("DEADbeef", None).unwrap(), BigInt::from(0xdead_beefu32));
+        assert_eq!(
+            hex2num("01:0g", Some(Grouping::new(2, ':'))),
+            Err(EncodingError::InvalidSymbol { symbol: "g".into(), index: 4 }),
+            "Positions count the separators"
+        );
+        assert!(hex2num("01:02", None).is_err(), "Separators are only skipped when configured");
+        assert!(num2hex(&BigInt::from(-1), None).is_err());
+
+        let man = BigInt::from(0x4d616eu32 * 256 + 0x21);
+        assert_eq!(num2base32(&man, None).unwrap(), "JVQW4II");
+        assert_eq!(num2base32(&man, Some(Grouping::new(4, '-'))).unwrap(), "JVQW-4II");
+        assert_eq!(base32_to_num("JVQW-4II", Some(Grouping::new(4, '-'))).unwrap(), man);
+        assert_eq!(base32_to_num("JVQW4II=", None).unwrap(), man);
+        assert_eq!(
+            base32_to_num("JV-Q1", Some(Grouping::new(2, '-'))),
+            Err(EncodingError::InvalidSymbol { symbol: "1".into(), index: 4 })
+        );
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let value = BigInt::from(num_bigint::RandBigInt::gen_biguint(&mut rng, 700));
+            let grouping = Some(Grouping::new(8, ' '));
+            assert_eq!(hex2num(&num2hex(&value, grouping).unwrap(), grouping).unwrap(), value);
+            assert_eq!(base32_to_num(&num2base32(&value, grouping).unwrap(), grouping).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_codecs_are_interchangeable() {
+        let codecs: [(&dyn Codec, &str); 3] =
+            [(Alphabet::default_symbols(), "Hello"), (&Utf8, "Grüße 🦀"), (&Hex(Some(Grouping::new(2, ' '))), "01 ab ff")];
+        for (codec, text) in codecs {
+            assert_eq!(codec.decode(&codec.encode(text).unwrap()).unwrap(), text);
+        }
+        let alphabet = Alphabet::default_symbols();
+        assert_eq!(Codec::encode(alphabet, "abc"), str2num("abc", alphabet));
+        assert_eq!(Codec::encode(&Hex(None), "ff").unwrap(), BigInt::from(255));
+        assert!(Utf8.decode(&BigInt::from(-1)).is_err());
+    }
+
+    #[test]
+    fn test_escaped_codec_round_trips_stray_symbols() {
+        let escaped = Escaped::default_symbols();
+        assert_eq!(escaped.escape("año $5"), "a|c3|b1o |245");
+        assert_eq!(escaped.escape("a|b"), "a|7cb", "The escape symbol escapes itself");
+        assert_eq!(escaped.escape("👍🏽"), "|f0|9f|91|8d|f0|9f|8f|bd");
+        for text in ["", "plain text", "año $5", "||", "0|7c", "Grüße, 世界! 🦀", "trailing ñ"] {
+            assert_eq!(escaped.unescape(&escaped.escape(text)).unwrap(), text);
+            assert_eq!(escaped.decode(&escaped.encode(text).unwrap()).unwrap(), text, "{:?}", text);
+        }
+        assert_eq!(escaped.unescape("a|C3|B1"), Ok("añ".to_string()), "Hex digits in either case");
+        assert!(escaped.unescape("a|c3").is_err(), "Half a character");
+        assert!(escaped.unescape("a|4").is_err());
+        assert!(escaped.unescape("a|zz").is_err());
+
+        let base32 = Alphabet::new(BASE32_SYMBOLS).unwrap();
+        assert_eq!(Escaped::new(base32, "A"), Err(EncodingError::UnusableEscape("A".into())));
+        assert!(Escaped::new(Alphabet::default_symbols().clone(), "~").is_err());
+    }
+
+    #[test]
+    fn test_checksummed_codec_catches_typos() {
+        let codec = Checksummed(Alphabet::default_symbols());
+        let n = str2num("Attack at dawn", Alphabet::default_symbols()).unwrap();
+        let text = codec.decode(&n).unwrap();
+        let sum = crate::hash::crc32(&num2bytes(&n, ByteOrder::BigEndian, None).unwrap());
+        assert_eq!(text, format!("Attack at dawn{:08x}", sum));
+        assert_eq!(codec.encode(&text), Ok(n.clone()));
+        assert_eq!(codec.encode(&format!("Attack at dawn{:08X}", sum)), Ok(n), "Either case");
+
+        for typo in ["Attack at dawm", "Atack at dawn", "Attakc at dawn"] {
+            let mistyped = format!("{}{}", typo, &text[text.len() - 8..]);
+            assert_eq!(codec.encode(&mistyped), Err(EncodingError::BadChecksum), "{:?}", typo);
+        }
+        assert_eq!(codec.encode(&text[..text.len() - 1]), Err(EncodingError::BadChecksum));
+        assert_eq!(codec.encode("1234567"), Err(EncodingError::BadChecksum));
+        assert_eq!(codec.encode("abc+2345678"), Err(EncodingError::BadChecksum));
+
+        let hex = Checksummed(Hex(None));
+        assert_eq!(hex.encode(&hex.decode(&BigInt::from(0xc0ffee)).unwrap()), Ok(BigInt::from(0xc0ffee)));
+    }
+
+    #[test]
+    fn test_rfc4648_matches_common_tooling() {
+        use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+        use base64::Engine;
+
+        let value = BigInt::from(0x4d616eu32 * 256 + 0x21);
+        assert_eq!(num2rfc4648(&value, Rfc4648::Base64).unwrap(), "TWFuIQ==");
+        assert_eq!(num2rfc4648(&value, Rfc4648::Base32).unwrap(), "JVQW4II=");
+        for bytes in [vec![0xffu8], vec![1, 2], vec![1, 2, 3], vec![0xfb; 17], (1..=200u8).collect()] {
+            let value = from_be_bytes(&bytes);
+            let base64 = num2rfc4648(&value, Rfc4648::Base64).unwrap();
+            assert_eq!(base64, STANDARD.encode(&bytes));
+            let url = num2rfc4648(&value, Rfc4648::Base64Url).unwrap();
+            assert_eq!(url, URL_SAFE_NO_PAD.encode(&bytes));
+            for (text, variant) in [(base64, Rfc4648::Base64), (url, Rfc4648::Base64Url)] {
+                assert_eq!(rfc4648_to_num(&text, variant).unwrap(), value);
+            }
+            let base32 = num2rfc4648(&value, Rfc4648::Base32).unwrap();
+            assert!(base32.len().is_multiple_of(8));
+            assert_eq!(rfc4648_to_num(&base32, Rfc4648::Base32).unwrap(), value);
+        }
+
+        assert_eq!(
+            rfc4648_to_num("TW*u", Rfc4648::Base64),
+            Err(EncodingError::InvalidSymbol { symbol: "*".into(), index: 2 })
+        );
+        assert_eq!(rfc4648_to_num("TWE", Rfc4648::Base64).unwrap(), BigInt::from(0x4d61), "Padding is optional");
+        assert!(rfc4648_to_num("TWFuI", Rfc4648::Base64).is_err(), "One symbol cannot end a group");
+        assert!(rfc4648_to_num("TWFuIR==", Rfc4648::Base64).is_err(), "Non-zero fill bits");
+        assert!(num2rfc4648(&BigInt::from(-1), Rfc4648::Base64).is_err());
+    }
+
+    #[test]
+    fn test_text_codec_round_trip() {
+        for text in ["", "\0\0leading NULs", "Grüße, 世界! 🦀", "$ is fine here"] {
+            assert_eq!(num2text(&text2num(text)).unwrap(), text);
+        }
+        assert_eq!(text2num("A"), BigInt::from(0x0141));
+
+        // Survives encryption when the modulus is wide enough
+        let key = crate::keys::PrivateKey::generate(256).allow_insecure(crate::keys::InsecureDemo);
+        let message = text2num("Größe 🦀");
+        let ciphertext = crate::rabin::encrypt(&message, key.n()).unwrap();
+        let decoded: Vec<String> = key
+            .decrypt(&ciphertext)
+            .unwrap()
+            .iter()
+            .filter_map(|candidate| num2text(candidate).ok())
+            .collect();
+        assert!(decoded.contains(&"Größe 🦀".to_string()));
+
+        assert_eq!(num2text(&BigInt::from(-1)), Err(EncodingError::NotText("negative")));
+        assert_eq!(num2text(&BigInt::from(0x0241)), Err(EncodingError::NotText("missing marker byte")));
+        assert_eq!(num2text(&BigInt::from(0x01ff)), Err(EncodingError::NotText("invalid UTF-8")));
+    }
+
+    #[test]
+    fn test_fixed_width_bytes() {
+        let value = BigInt::from(0x0102_0304_0506_0708_090au128);
+        let bytes = to_fixed_be_bytes(&value, 12).unwrap();
+        assert_eq!(bytes, [0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10], "Left-padded to the requested width");
+        assert_eq!(from_be_bytes(&bytes), value);
+
+        assert_eq!(to_fixed_be_bytes(&value, 9), None, "Too wide for 9 bytes");
+        assert_eq!(to_fixed_be_bytes(&BigInt::from(-1), 4), None);
+        assert_eq!(to_fixed_be_bytes(&BigInt::zero(), 0), Some(vec![]));
+        assert_eq!(modulus_len(&BigInt::from(256)), 2);
+        assert_eq!(modulus_len(&BigInt::from(255)), 1);
+    }
+
+    #[test]
+    fn test_byte_order_and_width() {
+        let value = BigInt::from(0x0102_0304_0506_0708_090au128);
+        let big = num2bytes(&value, ByteOrder::BigEndian, None).unwrap();
+        assert_eq!(big, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        let little = num2bytes(&value, ByteOrder::LittleEndian, Some(12)).unwrap();
+        assert_eq!(little, [10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0], "Padded at the high end");
+        assert_eq!(bytes2num(&little, ByteOrder::LittleEndian), value);
+        assert_eq!(bytes2num(&big, ByteOrder::BigEndian), value);
+        assert_eq!(num2bytes(&BigInt::zero(), ByteOrder::LittleEndian, None), Some(vec![0]));
+        assert_eq!(bytes2num(&[], ByteOrder::BigEndian), BigInt::zero());
+        assert_eq!(num2bytes(&value, ByteOrder::LittleEndian, Some(9)), None);
+        assert_eq!(num2bytes(&BigInt::from(-1), ByteOrder::BigEndian, None), None);
+
+        // Both orders agree with num-bigint on values spanning several digits
+        let mut rng = rand::thread_rng();
+        for bits in [1, 63, 64, 65, 500, 1024] {
+            let value = BigInt::from(num_bigint::RandBigInt::gen_biguint(&mut rng, bits));
+            let width = modulus_len(&value) + 3;
+            let little = num2bytes(&value, ByteOrder::LittleEndian, Some(width)).unwrap();
+            assert_eq!(little[..modulus_len(&value)], value.to_bytes_le().1[..modulus_len(&value)]);
+            assert_eq!(bytes2num(&little, ByteOrder::LittleEndian), value);
+            let big = num2bytes(&value, ByteOrder::BigEndian, Some(width)).unwrap();
+            assert_eq!(big.iter().rev().copied().collect::<Vec<_>>(), little);
+        }
+    }
+
+    #[test]
+    fn test_num2str_rejects_negative_numbers() {
+        let alphabet = Alphabet::default_symbols();
+        assert_eq!(num2str(&BigInt::from(-5), alphabet), Err(EncodingError::NotText("negative")));
+        assert_eq!(num2str(&BigInt::zero(), alphabet), Ok("0".to_string()));
+        let binary = Alphabet::new("01").unwrap();
+        assert_eq!(num2str(&BigInt::from(u64::MAX), &binary).unwrap(), "1".repeat(64));
+        assert!(Codec::decode(alphabet, &BigInt::from(-1)).is_err());
+    }
+
+    #[test]
+    fn test_zero_symbol_and_padding() {
+        let spaced = Alphabet::default_symbols().clone().with_zero(" ").unwrap();
+        assert_eq!(spaced.symbol(0), Some(" "));
+        assert_eq!(spaced.value("0"), Some(1));
+        let text = "007 Bond";
+        assert_eq!(num2str(&str2num(text, &spaced).unwrap(), &spaced).unwrap(), text, "Leading '0's survive");
+        assert_eq!(num2str(&str2num(text, Alphabet::default_symbols()).unwrap(), Alphabet::default_symbols()).unwrap(), "7 Bond");
+        assert_eq!(Alphabet::new("01").unwrap().with_zero("2"), Err(EncodingError::UnknownSymbol("2".to_string())));
+
+        let digits = Alphabet::new("0123456789").unwrap();
+        assert_eq!(digits.num_to_digits_width(&BigInt::from(7), 3).unwrap(), "007");
+        assert_eq!(digits.num_to_digits_width(&BigInt::from(1234), 3), Err(EncodingError::TooWide(3)));
+        let leading = digits.clone().with_padding("_", Padding::Leading).unwrap();
+        assert_eq!(leading.num_to_digits_width(&BigInt::from(7), 3).unwrap(), "__7");
+        assert_eq!(leading.digits_to_num("__7").unwrap(), BigInt::from(7));
+        assert_eq!(
+            leading.digits_to_num("7__"),
+            Err(EncodingError::InvalidSymbol { symbol: "_".to_string(), index: 1 })
+        );
+        let trailing = digits.clone().with_padding("=", Padding::Trailing).unwrap();
+        assert_eq!(trailing.num_to_digits_width(&BigInt::from(70), 4).unwrap(), "70==");
+        assert_eq!(trailing.digits_to_num("70==").unwrap(), BigInt::from(70));
+        let omitted = digits.clone().with_padding("=", Padding::Omitted).unwrap();
+        assert_eq!(omitted.num_to_digits_width(&BigInt::from(70), 4).unwrap(), "70");
+        assert_eq!(omitted.digits_to_num("70=").unwrap(), BigInt::from(70), "Padding is still accepted");
+        assert_eq!(digits.clone().with_padding("5", Padding::Leading), Err(EncodingError::UnknownSymbol("5".to_string())));
+        assert_eq!(digits.clone().with_padding("==", Padding::Leading), Err(EncodingError::UnknownSymbol("==".to_string())));
+        let zeroed = trailing.with_zero("9").unwrap();
+        assert_eq!(zeroed.padding(), Some(("=", Padding::Trailing)), "Settings carry over");
+    }
+
+    #[test]
+    fn test_invalid_character() {
+        let text = "HELLO$"; // '$' is not in `DEFAULT_SYMBOLS`, so should handle this gracefully
+        let result = str2num(text, Alphabet::default_symbols());
+        assert!(result.is_err(), "Encoding text with invalid characters should return an error, not panic");
+
+        let error = str2num("añb", Alphabet::default_symbols()).unwrap_err();
+        assert_eq!(error, EncodingError::InvalidSymbol { symbol: "ñ".into(), index: 1 }, "Positions count symbols");
+        assert_eq!(error.to_string(), "symbol \"ñ\" at position 1 is not in the alphabet");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,240 @@
+use crate::aead::{aes256_gcm_decrypt, aes256_gcm_encrypt, KEY_LEN, NONCE_LEN};
+use crate::ct::ct_eq;
+use crate::der::{encode_integer, encode_octet_string, encode_sequence, DerReader};
+use crate::encoding::{from_be_bytes, modulus_len, to_fixed_be_bytes};
+use crate::error::RabinError;
+use crate::fingerprint::Fingerprint;
+use crate::hash::sha256;
+use crate::kem::{decapsulate, encapsulate, EncapsulatedKey};
+use crate::keys::{PrivateKey, PublicKey};
+use crate::pem;
+use num_bigint::BigInt;
+use num_traits::Zero;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+pub const ENVELOPE_PEM_LABEL: &str = "RABIN ENVELOPE";
+
+// Redundancy appended to the session key so the right square root can be recognised
+const CHECK_LEN: usize = 16;
+// Random padding keeps the encoded block close to n, so squaring always wraps around the
+// modulus and the ciphertext cannot be undone with an integer square root
+const MIN_PADDING_LEN: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    recipient: Fingerprint,
+    encrypted_key: BigInt,
+    nonce: [u8; NONCE_LEN],
+    ciphertext: Vec<u8>,
+}
+
+fn key_check(padding: &[u8], session_key: &[u8]) -> [u8; CHECK_LEN] {
+    let digest = sha256(&[padding, session_key].concat());
+    digest[..CHECK_LEN].try_into().unwrap()
+}
+
+// Block layout (one byte shorter than n, so the value is always below n):
+//   random padding || session key (32 bytes) || SHA-256(padding || key)[..16]
+pub(crate) fn encode_session_key<R: RngCore + ?Sized>(
+    rng: &mut R,
+    n: &BigInt,
+    session_key: &[u8; KEY_LEN],
+) -> Result<BigInt, RabinError> {
+    let block_len = modulus_len(n) - 1;
+    let padding_len = block_len
+        .checked_sub(KEY_LEN + CHECK_LEN)
+        .filter(|len| *len >= MIN_PADDING_LEN)
+        .ok_or(RabinError::ModulusTooSmall)?;
+
+    let mut padding = vec![0u8; padding_len];
+    rng.fill_bytes(&mut padding);
+    // A non-zero leading byte keeps the block at full width
+    padding[0] |= 0x80;
+
+    let check = key_check(&padding, session_key);
+    let block = [padding.as_slice(), session_key, &check].concat();
+    Ok(from_be_bytes(&block))
+}
+
+pub(crate) fn decode_session_key(n: &BigInt, candidate: &BigInt) -> Option<[u8; KEY_LEN]> {
+    let block_len = modulus_len(n) - 1;
+    let block = to_fixed_be_bytes(candidate, block_len)?;
+
+    let padding_len = block_len.checked_sub(KEY_LEN + CHECK_LEN)?;
+    let (padding, rest) = block.split_at(padding_len);
+    let (session_key, check) = rest.split_at(KEY_LEN);
+    // Both checks are always made, so a wrong root fails in the same time either way
+    if !(ct_eq(&key_check(padding, session_key), check) & (padding[0] & 0x80 != 0)) {
+        return None;
+    }
+    Some(session_key.try_into().unwrap())
+}
+
+impl Envelope {
+    pub fn seal(recipient: &PublicKey, plaintext: &[u8]) -> Result<Self, RabinError> {
+        let mut rng = OsRng;
+        let (encrypted_key, session_key) = encapsulate(&mut rng, recipient)?;
+        let encrypted_key = encrypted_key.value().clone();
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+        let fingerprint = recipient.fingerprint();
+
+        let aad = header_der(&fingerprint, &encrypted_key, &nonce);
+        let ciphertext = aes256_gcm_encrypt(&session_key, &nonce, plaintext, &aad);
+        Ok(Envelope {
+            recipient: fingerprint,
+            encrypted_key,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    pub fn open(&self, key: &PrivateKey) -> Result<Vec<u8>, RabinError> {
+        let public = key.public_key();
+        if !public.fingerprint().ct_eq(&self.recipient) {
+            return Err(RabinError::WrongRecipient);
+        }
+        // Exactly one of the four square roots carries valid redundancy
+        let session_key = decapsulate(key, &EncapsulatedKey::new(self.encrypted_key.clone()))?;
+
+        let aad = header_der(&self.recipient, &self.encrypted_key, &self.nonce);
+        aes256_gcm_decrypt(&session_key, &self.nonce, &self.ciphertext, &aad)
+    }
+
+    // Opens the envelope with the old key and seals the payload again for a new recipient
+    pub fn rekey(&self, old_key: &PrivateKey, new_recipient: &PublicKey) -> Result<Self, RabinError> {
+        Envelope::seal(new_recipient, &self.open(old_key)?)
+    }
+
+    pub fn recipient(&self) -> &Fingerprint {
+        &self.recipient
+    }
+
+    // RabinEnvelope ::= SEQUENCE {
+    //     version      INTEGER (0),
+    //     recipient    OCTET STRING (SHA-256 fingerprint of the public key),
+    //     encryptedKey INTEGER,
+    //     nonce        OCTET STRING,
+    //     ciphertext   OCTET STRING (AES-256-GCM, tag appended)
+    // }
+    // The first four fields, DER-encoded as a SEQUENCE, are the associated data.
+    pub fn to_der(&self) -> Vec<u8> {
+        encode_sequence(&[
+            encode_integer(&BigInt::zero()),
+            encode_octet_string(self.recipient.as_bytes()),
+            encode_integer(&self.encrypted_key),
+            encode_octet_string(&self.nonce),
+            encode_octet_string(&self.ciphertext),
+        ])
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, RabinError> {
+        let mut outer = DerReader::new(der);
+        let mut seq = outer.read_sequence()?;
+        outer.finish()?;
+
+        if !seq.read_integer()?.is_zero() {
+            return Err(RabinError::UnsupportedVersion);
+        }
+        let recipient: [u8; 32] = seq
+            .read_octet_string()?
+            .try_into()
+            .map_err(|_| RabinError::MalformedDer("recipient fingerprint has the wrong length"))?;
+        let encrypted_key = seq.read_integer()?;
+        let nonce: [u8; NONCE_LEN] = seq
+            .read_octet_string()?
+            .try_into()
+            .map_err(|_| RabinError::MalformedDer("nonce has the wrong length"))?;
+        let ciphertext = seq.read_octet_string()?.to_vec();
+        seq.finish()?;
+
+        Ok(Envelope {
+            recipient: Fingerprint::from_bytes(recipient),
+            encrypted_key,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    pub fn to_pem(&self) -> String {
+        pem::encode(ENVELOPE_PEM_LABEL, &self.to_der())
+    }
+
+    pub fn from_pem(pem_text: &str) -> Result<Self, RabinError> {
+        Envelope::from_der(&pem::decode(ENVELOPE_PEM_LABEL, pem_text)?)
+    }
+
+    // Accepts both the binary DER form and the PEM armor
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RabinError> {
+        if bytes.starts_with(b"-----BEGIN") {
+            let text = std::str::from_utf8(bytes).map_err(|_| RabinError::MalformedPem("not UTF-8"))?;
+            Envelope::from_pem(text)
+        } else {
+            Envelope::from_der(bytes)
+        }
+    }
+}
+
+fn header_der(recipient: &Fingerprint, encrypted_key: &BigInt, nonce: &[u8]) -> Vec<u8> {
+    encode_sequence(&[
+        encode_integer(&BigInt::zero()),
+        encode_octet_string(recipient.as_bytes()),
+        encode_integer(encrypted_key),
+        encode_octet_string(nonce),
+    ])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let message = b"Non scholae, sed vitae discimus.";
+
+        let envelope = Envelope::seal(&key.public_key(), message).expect("Failed to seal");
+        let decoded = Envelope::from_bytes(&envelope.to_der()).unwrap();
+        assert_eq!(decoded, envelope);
+        assert_eq!(decoded.open(&key).unwrap(), message);
+
+        let armored = Envelope::from_bytes(envelope.to_pem().as_bytes()).unwrap();
+        assert_eq!(armored.open(&key).unwrap(), message);
+    }
+
+    #[test]
+    fn test_envelope_rejects_wrong_key_and_tampering() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let other = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let mut envelope = Envelope::seal(&key.public_key(), b"secret").unwrap();
+
+        assert_eq!(envelope.open(&other), Err(RabinError::WrongRecipient));
+
+        envelope.ciphertext[0] ^= 1;
+        assert_eq!(envelope.open(&key), Err(RabinError::DecryptionFailed));
+    }
+
+    #[test]
+    fn test_envelope_rekey() {
+        let old_key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let new_key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let envelope = Envelope::seal(&old_key.public_key(), b"rotate me").unwrap();
+
+        let rotated = envelope.rekey(&old_key, &new_key.public_key()).unwrap();
+        assert_eq!(rotated.recipient(), &new_key.public_key().fingerprint());
+        assert_eq!(rotated.open(&new_key).unwrap(), b"rotate me");
+        assert_eq!(rotated.open(&old_key), Err(RabinError::WrongRecipient));
+    }
+
+    #[test]
+    fn test_envelope_requires_large_enough_modulus() {
+        let key = PrivateKey::generate(128).allow_insecure(InsecureDemo);
+        assert_eq!(
+            Envelope::seal(&key.public_key(), b"x"),
+            Err(RabinError::ModulusTooSmall)
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use crate::encoding::EncodingError;
+use crate::keys::MIN_SECURE_BITS;
+use crate::metadata::KeyUsage;
+use std::fmt;
+
+// With the uniffi feature this is the error Swift and Kotlin callers see: each variant becomes
+// a case of a RabinException (Kotlin) or RabinError (Swift) carrying the Display message
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "uniffi", derive(uniffi::Error), uniffi(flat_error))]
+pub enum RabinError {
+    // The DER input could not be parsed; the message names the offending element
+    MalformedDer(&'static str),
+    // The PEM armor is missing, has the wrong label, or contains invalid base64
+    MalformedPem(&'static str),
+    // A container carried an algorithm identifier other than naive-rabin
+    UnsupportedAlgorithm,
+    // A structure declared a version this crate does not understand
+    UnsupportedVersion,
+    // The key components are inconsistent (e.g. n != p * q)
+    InvalidKey(&'static str),
+    // Authenticated decryption failed: wrong passphrase or tampered data
+    DecryptionFailed,
+    // Keystore lookups and updates
+    KeyNotFound(String),
+    KeyExists(String),
+    InvalidKeyName(String),
+    // The message (or ciphertext) is outside the range the operation accepts
+    MessageOutOfRange,
+    // A plaintext of `bits` bits, too large for a single encryption under a `modulus_bits`-bit n
+    MessageTooLarge { bits: u64, modulus_bits: u64 },
+    // The modulus is too short to hold the encoded block
+    ModulusTooSmall,
+    // An envelope was addressed to a different public key
+    WrongRecipient,
+    // Secret shares are missing, inconsistent, or below the threshold
+    InvalidShares(&'static str),
+    // A recovery phrase has an unknown word or a bad checksum
+    InvalidMnemonic(String),
+    // The key's metadata forbids the operation
+    KeyExpired,
+    // The key's usage flags do not include this usage
+    UsageViolation(KeyUsage),
+    // A demo-sized modulus of this many bits, used without the InsecureDemo opt-in
+    InsecureKey(u64),
+    // A keyring line (1-based) could not be parsed
+    MalformedKeyring(usize, &'static str),
+    // An image could not be read as PNG
+    MalformedPng(&'static str),
+    // No readable QR code, or data too long to fit in one
+    InvalidQrCode(&'static str),
+    // A signature does not match the message and key
+    InvalidSignature,
+    // A modular inverse was requested for a value sharing a factor with the modulus
+    NotInvertible,
+    // A square root was requested for a value that is not a square modulo the prime
+    NotQuadraticResidue,
+    // A ciphertext is not a square modulo n, so it has no square roots to decrypt to
+    NotAResidue,
+    // A generator seed is degenerate or shares a factor with the modulus
+    InvalidSeed(&'static str),
+    // The random number generator failed its self-test
+    RngFailure(&'static str),
+    // An interactive protocol step was run out of order or given a degenerate value
+    ProtocolViolation(&'static str),
+    // The prover in an identification protocol could not answer a challenge
+    IdentificationFailed,
+    // A long-running operation was stopped on request
+    Cancelled,
+    // A private-key result failed its re-check, so it was withheld rather than released
+    FaultDetected,
+    // Text could not be converted to a number
+    Encoding(EncodingError),
+    // A file of reference vectors could not be read; the message says where
+    MalformedVectors(String),
+    // Filesystem errors, flattened to a message so the enum stays comparable
+    Io(String),
+}
+
+impl fmt::Display for RabinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RabinError::MalformedDer(what) => write!(f, "malformed DER: {}", what),
+            RabinError::MalformedPem(what) => write!(f, "malformed PEM: {}", what),
+            RabinError::UnsupportedAlgorithm => write!(f, "unsupported algorithm identifier"),
+            RabinError::UnsupportedVersion => write!(f, "unsupported structure version"),
+            RabinError::InvalidKey(what) => write!(f, "invalid key: {}", what),
+            RabinError::DecryptionFailed => write!(f, "decryption failed (wrong passphrase or corrupted data)"),
+            RabinError::KeyNotFound(name) => write!(f, "no key named '{}'", name),
+            RabinError::KeyExists(name) => write!(f, "a key named '{}' already exists", name),
+            RabinError::InvalidKeyName(name) => write!(f, "invalid key name '{}'", name),
+            RabinError::MessageOutOfRange => write!(f, "value is outside the accepted range"),
+            RabinError::MessageTooLarge { bits, modulus_bits } => write!(
+                f,
+                "a {}-bit message does not fit below the {}-bit modulus; use block mode to split it",
+                bits, modulus_bits
+            ),
+            RabinError::ModulusTooSmall => write!(f, "modulus is too small for this operation"),
+            RabinError::WrongRecipient => write!(f, "envelope is addressed to a different key"),
+            RabinError::InvalidShares(what) => write!(f, "invalid shares: {}", what),
+            RabinError::InvalidMnemonic(what) => write!(f, "invalid recovery phrase: {}", what),
+            RabinError::KeyExpired => write!(f, "key has expired"),
+            RabinError::UsageViolation(KeyUsage::Encrypt) => write!(f, "key is not allowed to encrypt"),
+            RabinError::UsageViolation(KeyUsage::Sign) => write!(f, "key is not allowed to sign"),
+            RabinError::InsecureKey(bits) => write!(
+                f,
+                "{}-bit modulus is a demo key, below the {}-bit minimum; it must be allowed explicitly",
+                bits, MIN_SECURE_BITS
+            ),
+            RabinError::MalformedKeyring(line, what) => write!(f, "malformed keyring line {}: {}", line, what),
+            RabinError::MalformedPng(what) => write!(f, "malformed PNG: {}", what),
+            RabinError::InvalidQrCode(what) => write!(f, "invalid QR code: {}", what),
+            RabinError::InvalidSignature => write!(f, "signature verification failed"),
+            RabinError::NotInvertible => write!(f, "value has no inverse modulo the given modulus"),
+            RabinError::NotQuadraticResidue => write!(f, "value is not a square modulo the prime"),
+            RabinError::NotAResidue => {
+                write!(f, "ciphertext is not a square modulo n (corrupted, or encrypted to another key)")
+            }
+            RabinError::InvalidSeed(what) => write!(f, "invalid seed: {}", what),
+            RabinError::RngFailure(what) => write!(f, "random number generator failed self-test: {}", what),
+            RabinError::ProtocolViolation(what) => write!(f, "protocol violation: {}", what),
+            RabinError::IdentificationFailed => write!(f, "identification failed: response does not match"),
+            RabinError::Cancelled => write!(f, "cancelled"),
+            RabinError::FaultDetected => write!(f, "computation fault detected; the result was withheld"),
+            RabinError::Encoding(err) => write!(f, "{}", err),
+            RabinError::MalformedVectors(what) => write!(f, "malformed reference vectors: {}", what),
+            RabinError::Io(message) => write!(f, "I/O error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RabinError {}
+
+impl From<std::io::Error> for RabinError {
+    fn from(err: std::io::Error) -> Self {
+        RabinError::Io(err.to_string())
+    }
+}
+
+impl From<EncodingError> for RabinError {
+    fn from(err: EncodingError) -> Self {
+        RabinError::Encoding(err)
+    }
+}
@@ -0,0 +1,334 @@
+// C ABI for lab assignments in C and C++, built with the ffi feature. The matching header is
+// include/rabin.h, generated by cbindgen; link against the cdylib or staticlib.
+//
+// Everything goes through byte buffers. Keys are PEM text (PKCS#8 for private keys, as in the
+// CLI), and messages and ciphertexts are big-endian numbers. Functions return a RabinStatus and
+// write their result to an out-parameter, which the caller releases with rabin_buffer_free.
+// rabin_last_error describes the most recent failure on the calling thread.
+//
+// Encryption is tagged and decryption strict, as in the CLI. Demo-sized keys are refused until
+// rabin_allow_insecure_demo() has been called. A panic inside the library is caught at the
+// boundary and reported as RABIN_PANIC rather than unwinding into C.
+
+use crate::bindings::OptIn;
+use crate::encoding::{bytes2num, modulus_len, num2bytes, to_fixed_be_bytes, ByteOrder};
+use crate::error::RabinError;
+use crate::keygen::KeygenConfig;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::metadata::KeyUsage;
+use crate::rabin::{decrypt_tagged, encrypt_tagged};
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// Result of every fallible call; rabin_last_error has the details
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RabinStatus {
+    Ok = 0,
+    /// A required pointer was null, or a buffer was not valid UTF-8 PEM
+    InvalidArgument = 1,
+    /// The key could not be parsed or is inconsistent
+    InvalidKey = 2,
+    /// A demo-sized key, used without rabin_allow_insecure_demo()
+    InsecureKey = 3,
+    /// The key's usage flags or expiry forbid the operation
+    UsageViolation = 4,
+    /// The message does not fit below the modulus
+    MessageTooLarge = 5,
+    /// The ciphertext has no square roots, or none of them carries a valid tag
+    DecryptionFailed = 6,
+    /// Any other failure
+    Error = 7,
+    /// A bug in the library; the call had no effect
+    Panic = 8,
+}
+
+/// Bytes owned by the library, released with rabin_buffer_free
+#[repr(C)]
+#[derive(Debug)]
+pub struct RabinBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl RabinBuffer {
+    const EMPTY: RabinBuffer = RabinBuffer {
+        data: ptr::null_mut(),
+        len: 0,
+    };
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let bytes = Box::into_raw(bytes.into_boxed_slice());
+        RabinBuffer {
+            data: bytes as *mut u8,
+            len: bytes.len(),
+        }
+    }
+}
+
+static INSECURE_DEMO: OptIn = OptIn::new();
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message).unwrap_or_else(|_| c"error message contained a NUL byte".to_owned());
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+enum Failure {
+    Status(RabinStatus, String),
+    Rabin(RabinError),
+}
+
+impl From<RabinError> for Failure {
+    fn from(err: RabinError) -> Self {
+        Failure::Rabin(err)
+    }
+}
+
+fn status_of(err: &RabinError) -> RabinStatus {
+    match err {
+        RabinError::MalformedDer(_)
+        | RabinError::MalformedPem(_)
+        | RabinError::UnsupportedAlgorithm
+        | RabinError::UnsupportedVersion
+        | RabinError::InvalidKey(_) => RabinStatus::InvalidKey,
+        RabinError::InsecureKey(_) => RabinStatus::InsecureKey,
+        RabinError::UsageViolation(_) | RabinError::KeyExpired => RabinStatus::UsageViolation,
+        RabinError::MessageTooLarge { .. } | RabinError::MessageOutOfRange => RabinStatus::MessageTooLarge,
+        RabinError::DecryptionFailed | RabinError::NotAResidue => RabinStatus::DecryptionFailed,
+        _ => RabinStatus::Error,
+    }
+}
+
+// Runs `body` and stores its result in `out`, catching panics and recording any error
+fn run(out: *mut RabinBuffer, body: impl FnOnce() -> Result<Vec<u8>, Failure>) -> RabinStatus {
+    if out.is_null() {
+        set_last_error("output buffer pointer is null".into());
+        return RabinStatus::InvalidArgument;
+    }
+    // Left empty on failure, so freeing it is always safe
+    unsafe { out.write(RabinBuffer::EMPTY) };
+    let (status, message) = match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(bytes)) => {
+            unsafe { out.write(RabinBuffer::from_vec(bytes)) };
+            return RabinStatus::Ok;
+        }
+        Ok(Err(Failure::Status(status, message))) => (status, message),
+        Ok(Err(Failure::Rabin(err))) => (status_of(&err), err.to_string()),
+        Err(_) => (RabinStatus::Panic, "internal error (panic) in the Rabin library".into()),
+    };
+    set_last_error(message);
+    status
+}
+
+// The bytes behind a pointer and length from C; a null pointer is only accepted for length 0
+unsafe fn input<'a>(data: *const u8, len: usize, what: &str) -> Result<&'a [u8], Failure> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(Failure::Status(RabinStatus::InvalidArgument, format!("{} pointer is null", what))),
+        (false, _) => Ok(std::slice::from_raw_parts(data, len)),
+    }
+}
+
+fn pem_text<'a>(bytes: &'a [u8], what: &str) -> Result<&'a str, Failure> {
+    std::str::from_utf8(bytes)
+        .map_err(|_| Failure::Status(RabinStatus::InvalidArgument, format!("{} is not UTF-8 PEM text", what)))
+}
+
+fn load_private(pem: &[u8]) -> Result<PrivateKey, Failure> {
+    Ok(INSECURE_DEMO.load_private(pem_text(pem, "private key")?)?)
+}
+
+fn load_public(pem: &[u8]) -> Result<PublicKey, Failure> {
+    Ok(INSECURE_DEMO.load_public(pem_text(pem, "public key")?)?)
+}
+
+/// Lets demo-sized keys be generated and used by every later call in this process
+#[no_mangle]
+pub extern "C" fn rabin_allow_insecure_demo() {
+    INSECURE_DEMO.allow();
+}
+
+/// Generates a key with a modulus of `2 * bits` bits and writes it to `private_key_out` as
+/// PKCS#8 PEM text (not NUL-terminated)
+///
+/// # Safety
+///
+/// `private_key_out` must be null or point to writable memory for one RabinBuffer.
+#[no_mangle]
+pub unsafe extern "C" fn rabin_keygen(bits: usize, private_key_out: *mut RabinBuffer) -> RabinStatus {
+    run(private_key_out, || {
+        let config = KeygenConfig::new(bits);
+        let config = match INSECURE_DEMO.get() {
+            Some(opt_in) => config.allow_insecure(opt_in),
+            None => config,
+        };
+        let (key, _) = PrivateKey::generate_with(&config)?;
+        Ok(key.to_pkcs8_pem().into_bytes())
+    })
+}
+
+/// Writes the public half of a PKCS#8 PEM private key to `public_key_out` as PEM text
+///
+/// # Safety
+///
+/// `private_key` must point to `private_key_len` readable bytes, and `public_key_out` must be
+/// null or point to writable memory for one RabinBuffer.
+#[no_mangle]
+pub unsafe extern "C" fn rabin_public_key(
+    private_key: *const u8,
+    private_key_len: usize,
+    public_key_out: *mut RabinBuffer,
+) -> RabinStatus {
+    run(public_key_out, || {
+        let key = load_private(input(private_key, private_key_len, "private key")?)?;
+        Ok(key.public_key().to_pem().into_bytes())
+    })
+}
+
+/// Encrypts a big-endian message under a PEM public key. The ciphertext written to
+/// `ciphertext_out` is zero-padded on the left to the byte length of the modulus.
+///
+/// # Safety
+///
+/// Each pointer must point to as many readable bytes as its length says (or be null with
+/// length 0 for the message), and `ciphertext_out` must be null or point to writable memory for
+/// one RabinBuffer.
+#[no_mangle]
+pub unsafe extern "C" fn rabin_encrypt(
+    public_key: *const u8,
+    public_key_len: usize,
+    message: *const u8,
+    message_len: usize,
+    ciphertext_out: *mut RabinBuffer,
+) -> RabinStatus {
+    run(ciphertext_out, || {
+        let key = load_public(input(public_key, public_key_len, "public key")?)?;
+        key.check_usage(KeyUsage::Encrypt)?;
+        let message = bytes2num(input(message, message_len, "message")?, ByteOrder::BigEndian);
+        let ciphertext = encrypt_tagged(&message, key.n())?;
+        Ok(to_fixed_be_bytes(&ciphertext, modulus_len(key.n())).expect("the ciphertext is below n"))
+    })
+}
+
+/// Decrypts a big-endian ciphertext with a PKCS#8 PEM private key and writes the message to
+/// `message_out`, big-endian and without leading zero bytes
+///
+/// # Safety
+///
+/// Each pointer must point to as many readable bytes as its length says, and `message_out`
+/// must be null or point to writable memory for one RabinBuffer.
+#[no_mangle]
+pub unsafe extern "C" fn rabin_decrypt(
+    private_key: *const u8,
+    private_key_len: usize,
+    ciphertext: *const u8,
+    ciphertext_len: usize,
+    message_out: *mut RabinBuffer,
+) -> RabinStatus {
+    run(message_out, || {
+        let key = load_private(input(private_key, private_key_len, "private key")?)?;
+        key.check_usage(KeyUsage::Encrypt)?;
+        let ciphertext = bytes2num(input(ciphertext, ciphertext_len, "ciphertext")?, ByteOrder::BigEndian);
+        let message = decrypt_tagged(&ciphertext, key.p(), key.q())?;
+        Ok(num2bytes(&message, ByteOrder::BigEndian, None).expect("the message is not negative"))
+    })
+}
+
+/// Releases a buffer filled in by this library. Empty buffers are accepted, so every out
+/// buffer can be freed whatever the call returned.
+///
+/// # Safety
+///
+/// `buffer` must come from this library and must not be used or freed again afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn rabin_buffer_free(buffer: RabinBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+    }
+}
+
+/// The message for the last failed call on this thread, or null if none has failed. The
+/// string stays valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn rabin_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn take(buffer: RabinBuffer) -> Vec<u8> {
+        let bytes = unsafe { std::slice::from_raw_parts(buffer.data, buffer.len) }.to_vec();
+        unsafe { rabin_buffer_free(buffer) };
+        bytes
+    }
+
+    #[test]
+    fn test_c_round_trip_and_errors() {
+        let mut private_key = RabinBuffer::EMPTY;
+        let mut public_key = RabinBuffer::EMPTY;
+        let mut ciphertext = RabinBuffer::EMPTY;
+        let mut decrypted = RabinBuffer::EMPTY;
+        unsafe {
+            assert_eq!(rabin_keygen(256, &mut private_key), RabinStatus::InsecureKey);
+            let error = CStr::from_ptr(rabin_last_error()).to_str().unwrap();
+            assert!(error.contains("demo key"), "{}", error);
+
+            rabin_allow_insecure_demo();
+            assert_eq!(rabin_keygen(256, &mut private_key), RabinStatus::Ok);
+            let private_key = take(private_key);
+            let status = rabin_public_key(private_key.as_ptr(), private_key.len(), &mut public_key);
+            assert_eq!(status, RabinStatus::Ok);
+            let public_key = take(public_key);
+
+            let message = b"lab 3";
+            let status =
+                rabin_encrypt(public_key.as_ptr(), public_key.len(), message.as_ptr(), message.len(), &mut ciphertext);
+            assert_eq!(status, RabinStatus::Ok);
+            let mut ciphertext = take(ciphertext);
+            let status = rabin_decrypt(
+                private_key.as_ptr(),
+                private_key.len(),
+                ciphertext.as_ptr(),
+                ciphertext.len(),
+                &mut decrypted,
+            );
+            assert_eq!(status, RabinStatus::Ok);
+            assert_eq!(take(decrypted), message);
+
+            ciphertext[1] ^= 1;
+            let mut failed = RabinBuffer::EMPTY;
+            let status =
+                rabin_decrypt(private_key.as_ptr(), private_key.len(), ciphertext.as_ptr(), ciphertext.len(), &mut failed);
+            assert_eq!(status, RabinStatus::DecryptionFailed);
+            assert!(failed.data.is_null(), "Failed calls leave the buffer empty");
+            rabin_buffer_free(failed);
+
+            let mut unused = RabinBuffer::EMPTY;
+            assert_eq!(rabin_public_key(ptr::null(), 10, &mut unused), RabinStatus::InvalidArgument);
+            assert_eq!(rabin_keygen(256, ptr::null_mut()), RabinStatus::InvalidArgument);
+        }
+    }
+
+    // The build generates the header into OUT_DIR; this keeps the committed copy from going stale.
+    // Run with RABIN_UPDATE_HEADER=1 to overwrite include/rabin.h after changing the ABI.
+    #[test]
+    fn test_committed_header_is_current() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/rabin.h"));
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/include/rabin.h");
+        if std::env::var_os("RABIN_UPDATE_HEADER").is_some() {
+            std::fs::write(path, generated).unwrap();
+        }
+        let committed = std::fs::read_to_string(path).unwrap();
+        assert!(committed == generated, "include/rabin.h is stale; rerun with RABIN_UPDATE_HEADER=1");
+    }
+}
@@ -0,0 +1,286 @@
+// Feige-Fiat-Shamir signatures: the identification protocol from fiat_shamir, widened and made
+// non-interactive. The signer holds k secrets s_j and publishes v_j = s_j^-2 mod n. A signature
+// runs t rounds at once:
+//
+//   x_i = r_i^2 for random r_i
+//   e = H(message, x_1 .. x_t), read as t rows of k challenge bits
+//   y_i = r_i * product of the s_j whose bit e_ij is set
+//
+// and the verifier recomputes x_i = y_i^2 * product of the matching v_j before checking the
+// hash. The hash stands in for the verifier's coin flips, so a forger has to hit all k * t bits
+// at once: k * t is the security level in bits. More secrets make keys bigger but signatures
+// shorter and cheaper (fewer rounds, fewer squarings), so params trade one against the other.
+//
+// Any modulus whose factors the signer does not know works; nobody needs to extract roots.
+
+use crate::ct::ct_eq;
+use crate::error::RabinError;
+use crate::fiat_shamir::random_unit;
+use crate::hash::{sha256, Sha256};
+use crate::keys::PublicKey;
+use crate::math::mod_inverse;
+use num_bigint::BigInt;
+use num_traits::Zero;
+
+const DOMAIN: &[u8] = b"naive-rabin feige-fiat-shamir v1";
+const DEFAULT_SECURITY_BITS: usize = 128;
+const DEFAULT_SECRETS: usize = 16;
+const MAX_SECRETS: usize = 64;
+const MAX_ROUNDS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FfsParams {
+    // k, the number of secrets in a key
+    pub secrets: usize,
+    // t, the number of parallel rounds in a signature
+    pub rounds: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FfsPrivateKey {
+    n: BigInt,
+    params: FfsParams,
+    secrets: Vec<BigInt>,
+    public: Vec<BigInt>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FfsPublicKey {
+    n: BigInt,
+    params: FfsParams,
+    values: Vec<BigInt>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FfsSignature {
+    // k * t challenge bits, row by row, least significant bit of each byte first
+    challenge: Vec<u8>,
+    responses: Vec<BigInt>,
+}
+
+impl FfsParams {
+    pub fn new(secrets: usize, rounds: usize) -> Result<Self, RabinError> {
+        if !(1..=MAX_SECRETS).contains(&secrets) {
+            return Err(RabinError::InvalidKey("the number of secrets must be between 1 and 64"));
+        }
+        if !(1..=MAX_ROUNDS).contains(&rounds) {
+            return Err(RabinError::InvalidKey("the number of rounds must be between 1 and 256"));
+        }
+        Ok(FfsParams { secrets, rounds })
+    }
+
+    // The default number of secrets and just enough rounds for k * t >= bits
+    pub fn for_security(bits: usize) -> Result<Self, RabinError> {
+        let secrets = DEFAULT_SECRETS.min(bits.max(1));
+        FfsParams::new(secrets, bits.div_ceil(secrets).max(1))
+    }
+
+    // log2 of the number of hash queries a forger expects to need
+    pub fn security_bits(&self) -> usize {
+        self.secrets * self.rounds
+    }
+
+    fn challenge_len(&self) -> usize {
+        self.security_bits().div_ceil(8)
+    }
+}
+
+impl Default for FfsParams {
+    fn default() -> Self {
+        FfsParams {
+            secrets: DEFAULT_SECRETS,
+            rounds: DEFAULT_SECURITY_BITS / DEFAULT_SECRETS,
+        }
+    }
+}
+
+fn challenge_bit(challenge: &[u8], round: usize, secret: usize, params: &FfsParams) -> bool {
+    let index = round * params.secrets + secret;
+    (challenge[index / 8] >> (index % 8)) & 1 == 1
+}
+
+// SHA-256 in counter mode over the key, message digest and commitments, cut to k * t bits
+fn derive_challenge(n: &BigInt, params: &FfsParams, message: &[u8], commitments: &[BigInt]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN);
+    for item in [&n.to_signed_bytes_be()[..], &sha256(message)] {
+        hasher.update(&(item.len() as u32).to_be_bytes());
+        hasher.update(item);
+    }
+    hasher.update(&(params.secrets as u32).to_be_bytes());
+    hasher.update(&(params.rounds as u32).to_be_bytes());
+    for x in commitments {
+        let bytes = x.to_signed_bytes_be();
+        hasher.update(&(bytes.len() as u32).to_be_bytes());
+        hasher.update(&bytes);
+    }
+    let seed = hasher.finalize();
+
+    let len = params.challenge_len();
+    let mut out = Vec::with_capacity(len + 32);
+    let mut counter = 0u32;
+    while out.len() < len {
+        out.extend_from_slice(&sha256(&[&seed[..], &counter.to_be_bytes()].concat()));
+        counter += 1;
+    }
+    out.truncate(len);
+    // Unused bits of the last byte stay zero so every signature has one encoding
+    let used = params.security_bits() % 8;
+    if used != 0 {
+        out[len - 1] &= (1u8 << used) - 1;
+    }
+    out
+}
+
+impl FfsPrivateKey {
+    // Draws k random secrets modulo the given modulus
+    pub fn generate(modulus: &PublicKey, params: FfsParams) -> Self {
+        let n = modulus.n().clone();
+        let mut secrets = Vec::with_capacity(params.secrets);
+        let mut public = Vec::with_capacity(params.secrets);
+        while secrets.len() < params.secrets {
+            let secret = random_unit(&n);
+            // Only fails for secrets sharing a factor with n, which would factor it
+            if let Ok(inverse) = mod_inverse(&(&secret * &secret % &n), &n) {
+                secrets.push(secret);
+                public.push(inverse);
+            }
+        }
+        FfsPrivateKey { n, params, secrets, public }
+    }
+
+    pub fn params(&self) -> FfsParams {
+        self.params
+    }
+
+    pub fn public_key(&self) -> FfsPublicKey {
+        FfsPublicKey {
+            n: self.n.clone(),
+            params: self.params,
+            values: self.public.clone(),
+        }
+    }
+
+    pub fn sign(&self, message: &[u8]) -> FfsSignature {
+        let blinds: Vec<BigInt> = (0..self.params.rounds).map(|_| random_unit(&self.n)).collect();
+        let commitments: Vec<BigInt> = blinds.iter().map(|r| r * r % &self.n).collect();
+        let challenge = derive_challenge(&self.n, &self.params, message, &commitments);
+
+        let responses = blinds
+            .into_iter()
+            .enumerate()
+            .map(|(round, r)| {
+                self.secrets
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| challenge_bit(&challenge, round, *j, &self.params))
+                    .fold(r, |y, (_, s)| y * s % &self.n)
+            })
+            .collect();
+        FfsSignature { challenge, responses }
+    }
+}
+
+impl FfsPublicKey {
+    pub fn n(&self) -> &BigInt {
+        &self.n
+    }
+
+    pub fn params(&self) -> FfsParams {
+        self.params
+    }
+
+    pub fn values(&self) -> &[BigInt] {
+        &self.values
+    }
+
+    pub fn verify(&self, message: &[u8], signature: &FfsSignature) -> Result<(), RabinError> {
+        let params = &self.params;
+        if signature.responses.len() != params.rounds || signature.challenge.len() != params.challenge_len() {
+            return Err(RabinError::InvalidSignature);
+        }
+        let mut commitments = Vec::with_capacity(params.rounds);
+        for (round, y) in signature.responses.iter().enumerate() {
+            // y = 0 would make every commitment 0 regardless of the secrets
+            if *y <= BigInt::zero() || *y >= self.n {
+                return Err(RabinError::InvalidSignature);
+            }
+            let x = self
+                .values
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| challenge_bit(&signature.challenge, round, *j, params))
+                .fold(y * y % &self.n, |x, (_, v)| x * v % &self.n);
+            commitments.push(x);
+        }
+        if !ct_eq(&derive_challenge(&self.n, params, message, &commitments), &signature.challenge) {
+            return Err(RabinError::InvalidSignature);
+        }
+        Ok(())
+    }
+}
+
+impl FfsSignature {
+    pub fn challenge(&self) -> &[u8] {
+        &self.challenge
+    }
+
+    pub fn responses(&self) -> &[BigInt] {
+        &self.responses
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::PrivateKey;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let modulus = PrivateKey::generate(256).public_key();
+        let key = FfsPrivateKey::generate(&modulus, FfsParams::default());
+        let public = key.public_key();
+
+        let signature = key.sign(b"attack at dawn");
+        assert_eq!(signature.responses().len(), 8);
+        public.verify(b"attack at dawn", &signature).unwrap();
+        assert_eq!(public.verify(b"attack at dusk", &signature), Err(RabinError::InvalidSignature));
+
+        let other = FfsPrivateKey::generate(&modulus, FfsParams::default()).public_key();
+        assert!(other.verify(b"attack at dawn", &signature).is_err(), "Signature should not verify under another key");
+    }
+
+    #[test]
+    fn test_tampered_signatures_fail() {
+        let modulus = PrivateKey::generate(256).public_key();
+        let params = FfsParams::new(5, 3).unwrap();
+        let key = FfsPrivateKey::generate(&modulus, params);
+        let public = key.public_key();
+        let signature = key.sign(b"message");
+
+        let mut flipped = signature.clone();
+        flipped.challenge[0] ^= 1;
+        assert!(public.verify(b"message", &flipped).is_err());
+
+        let mut bumped = signature.clone();
+        bumped.responses[1] += 1;
+        assert!(public.verify(b"message", &bumped).is_err());
+
+        let mut zeroed = signature;
+        zeroed.responses[2] = BigInt::zero();
+        assert!(public.verify(b"message", &zeroed).is_err());
+    }
+
+    #[test]
+    fn test_parameter_selection() {
+        let params = FfsParams::for_security(128).unwrap();
+        assert!(params.security_bits() >= 128);
+        assert_eq!(params, FfsParams::default());
+        assert_eq!(FfsParams::for_security(80).unwrap(), FfsParams { secrets: 16, rounds: 5 });
+        assert_eq!(FfsParams::for_security(4).unwrap(), FfsParams { secrets: 4, rounds: 1 });
+        assert!(FfsParams::new(0, 4).is_err());
+        assert!(FfsParams::new(65, 4).is_err());
+        assert!(FfsParams::new(8, 0).is_err());
+    }
+}
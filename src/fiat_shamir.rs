@@ -0,0 +1,251 @@
+// Fiat-Shamir identification. The prover knows a square root s of a public value v = s^2 mod n
+// and convinces a verifier of it without revealing s, over any number of rounds of:
+//
+//   commit:    the prover picks a random r and sends x = r^2
+//   challenge: the verifier answers with a random bit e
+//   respond:   the prover sends y = r * s^e
+//   check:     the verifier accepts the round if y^2 = x * v^e
+//
+// A prover without s can prepare x for one value of e but not for both, so every round halves
+// a cheater's odds: k accepted rounds leave 2^-k. Answering both challenges for the same r
+// gives away s = y1 / y0, which is why a commitment is consumed by its response.
+//
+// Finding s for a given v is exactly Rabin decryption, so only the holder of the factors can
+// do it. That holder acts as a trusted center: it hashes an identity to a square and hands the
+// root to the prover (Prover::issue), and verifiers recompute v from the identity alone.
+
+use crate::error::RabinError;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::math::legendre;
+use crate::rabin::verify_root;
+use crate::secret::Secret;
+use crate::signature::full_domain_hash;
+use num_bigint::{BigInt, RandBigInt};
+use num_traits::{One, Zero};
+use rand::rngs::OsRng;
+use rand::Rng;
+
+const DOMAIN: &[u8] = b"naive-rabin fiat-shamir identity v1";
+// Each index is a square modulo n with probability about 1/4
+const MAX_IDENTITY_INDEX: u32 = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commitment(BigInt);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response(BigInt);
+
+#[derive(Debug, Clone)]
+pub struct Prover {
+    n: BigInt,
+    secret: BigInt,
+    public: BigInt,
+    // r from the last commitment, until the response uses it up
+    pending: Option<BigInt>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Verifier {
+    n: BigInt,
+    public: BigInt,
+    pending: Option<(BigInt, bool)>,
+    accepted: u32,
+}
+
+impl Commitment {
+    pub fn value(&self) -> &BigInt {
+        &self.0
+    }
+}
+
+impl Response {
+    pub fn value(&self) -> &BigInt {
+        &self.0
+    }
+}
+
+// The public value for an identity: the index-th hash of it, which the center chose to be a square
+pub fn identity_value(key: &PublicKey, identity: &[u8], index: u32) -> Result<BigInt, RabinError> {
+    full_domain_hash(key.n(), DOMAIN, &index.to_be_bytes(), identity)
+}
+
+pub(crate) fn random_unit(n: &BigInt) -> BigInt {
+    OsRng.gen_bigint_range(&BigInt::one(), n)
+}
+
+impl Prover {
+    // A prover with a freshly drawn secret; the public value is published by the prover itself
+    pub fn generate(key: &PublicKey) -> Self {
+        let n = key.n().clone();
+        let secret = random_unit(&n);
+        let public = &secret * &secret % &n;
+        Prover {
+            n,
+            secret,
+            public,
+            pending: None,
+        }
+    }
+
+    // Trusted-center issuance: finds the first index whose identity hash is a square and returns
+    // a prover holding its root, together with the index verifiers need
+    pub fn issue(key: &PrivateKey, identity: &[u8]) -> Result<(Self, u32), RabinError> {
+        let public_key = key.public_key();
+        for index in 0..MAX_IDENTITY_INDEX {
+            let public = identity_value(&public_key, identity, index)?;
+            if legendre(&public, key.p()) != 1 || legendre(&public, key.q()) != 1 {
+                continue;
+            }
+            let secret = Secret::new(key.square_roots(&public)?)[0].clone();
+            verify_root(&secret, &public, key.n())?;
+            let prover = Prover {
+                n: key.n().clone(),
+                secret,
+                public,
+                pending: None,
+            };
+            return Ok((prover, index));
+        }
+        Err(RabinError::InvalidKey("no identity hash was a square; p and q are probably not prime"))
+    }
+
+    pub fn public_value(&self) -> &BigInt {
+        &self.public
+    }
+
+    pub fn commit(&mut self) -> Commitment {
+        let r = random_unit(&self.n);
+        let x = &r * &r % &self.n;
+        self.pending = Some(r);
+        Commitment(x)
+    }
+
+    pub fn respond(&mut self, challenge: bool) -> Result<Response, RabinError> {
+        let r = self
+            .pending
+            .take()
+            .ok_or(RabinError::ProtocolViolation("respond called without a fresh commitment"))?;
+        Ok(Response(if challenge { r * &self.secret % &self.n } else { r }))
+    }
+}
+
+impl Verifier {
+    pub fn new(key: &PublicKey, public: BigInt) -> Self {
+        Verifier {
+            n: key.n().clone(),
+            public,
+            pending: None,
+            accepted: 0,
+        }
+    }
+
+    pub fn for_identity(key: &PublicKey, identity: &[u8], index: u32) -> Result<Self, RabinError> {
+        Ok(Verifier::new(key, identity_value(key, identity, index)?))
+    }
+
+    pub fn challenge(&mut self, commitment: &Commitment) -> Result<bool, RabinError> {
+        // x = 0 could be answered with y = 0 whatever the challenge
+        if commitment.0 <= BigInt::zero() || commitment.0 >= self.n {
+            return Err(RabinError::ProtocolViolation("commitment is out of range"));
+        }
+        let challenge = OsRng.gen_bool(0.5);
+        self.pending = Some((commitment.0.clone(), challenge));
+        Ok(challenge)
+    }
+
+    pub fn verify(&mut self, response: &Response) -> Result<(), RabinError> {
+        let (x, challenge) = self
+            .pending
+            .take()
+            .ok_or(RabinError::ProtocolViolation("verify called without an open challenge"))?;
+        let y = &response.0;
+        if *y <= BigInt::zero() || *y >= self.n {
+            return Err(RabinError::IdentificationFailed);
+        }
+        let expected = if challenge { x * &self.public % &self.n } else { x };
+        if y * y % &self.n != expected {
+            return Err(RabinError::IdentificationFailed);
+        }
+        self.accepted += 1;
+        Ok(())
+    }
+
+    pub fn accepted_rounds(&self) -> u32 {
+        self.accepted
+    }
+
+    // Chance that a prover without the secret got through every accepted round
+    pub fn cheating_probability(&self) -> f64 {
+        0.5f64.powi(self.accepted as i32)
+    }
+}
+
+// Runs the whole interaction locally, stopping at the first failed round
+pub fn identify(prover: &mut Prover, verifier: &mut Verifier, rounds: u32) -> Result<(), RabinError> {
+    for _ in 0..rounds {
+        let commitment = prover.commit();
+        let challenge = verifier.challenge(&commitment)?;
+        verifier.verify(&prover.respond(challenge)?)?;
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::mod_inverse;
+    use num_integer::Integer;
+
+    #[test]
+    fn test_honest_prover_is_accepted() {
+        let key = PrivateKey::generate(256);
+        let public = key.public_key();
+        let (mut prover, index) = Prover::issue(&key, b"alice").unwrap();
+        let mut verifier = Verifier::for_identity(&public, b"alice", index).unwrap();
+
+        identify(&mut prover, &mut verifier, 20).unwrap();
+        assert_eq!(verifier.accepted_rounds(), 20);
+        assert!(verifier.cheating_probability() < 1e-6);
+
+        let mut prover = Prover::generate(&public);
+        let mut verifier = Verifier::new(&public, prover.public_value().clone());
+        identify(&mut prover, &mut verifier, 20).unwrap();
+    }
+
+    #[test]
+    fn test_impostor_is_caught() {
+        let key = PrivateKey::generate(256);
+        let public = key.public_key();
+        let (_, index) = Prover::issue(&key, b"alice").unwrap();
+        let mut verifier = Verifier::for_identity(&public, b"alice", index).unwrap();
+        let v = identity_value(&public, b"alice", index).unwrap();
+
+        // Without s the best strategy is to guess e = 1 and send x = y^2 / v for a random y
+        let n = public.n();
+        let caught = (0..40).any(|_| {
+            let y = random_unit(n);
+            let x = &y * &y * mod_inverse(&v, n).unwrap() % n;
+            let challenge = verifier.challenge(&Commitment(x.mod_floor(n))).unwrap();
+            let response = if challenge { y } else { random_unit(n) };
+            verifier.verify(&Response(response)).is_err()
+        });
+        assert!(caught, "A guessing prover should fail a round within 40 tries");
+    }
+
+    #[test]
+    fn test_commitments_are_single_use() {
+        let public = PrivateKey::generate(128).public_key();
+        let mut prover = Prover::generate(&public);
+        prover.commit();
+        prover.respond(true).unwrap();
+        assert!(
+            matches!(prover.respond(false), Err(RabinError::ProtocolViolation(_))),
+            "Answering both challenges for one commitment would leak the secret"
+        );
+
+        let mut verifier = Verifier::new(&public, prover.public_value().clone());
+        assert!(verifier.challenge(&Commitment(BigInt::zero())).is_err());
+        assert!(verifier.verify(&Response(BigInt::one())).is_err());
+    }
+}
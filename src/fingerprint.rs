@@ -0,0 +1,95 @@
+use crate::der::{encode_integer, encode_sequence};
+use crate::hash::sha256;
+use crate::keys::PublicKey;
+use std::fmt;
+
+// Number of leading digest bytes shown in the short rendering (64 bits)
+const SHORT_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint([u8; 32]);
+
+impl Fingerprint {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Fingerprint(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix("SHA256:").unwrap_or(hex);
+        // from_str_radix alone would also take a sign, reading "+f" as a byte
+        if hex.len() != 64 || !hex.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return None;
+        }
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(Fingerprint(bytes))
+    }
+
+    // Short form for humans comparing keys out loud, e.g. "3f2a-91c0-7be4-d815"
+    pub fn short(&self) -> String {
+        let hex: String = self.0[..SHORT_LEN].iter().map(|b| format!("{:02x}", b)).collect();
+        hex.as_bytes()
+            .chunks(4)
+            .map(|group| std::str::from_utf8(group).unwrap())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SHA256:{}", self.to_hex())
+    }
+}
+
+impl PublicKey {
+    // Hashes the DER of the bare key, SEQUENCE { n }, so metadata does not affect it
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint(sha256(&encode_sequence(&[encode_integer(self.n())])))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigInt;
+
+    #[test]
+    fn test_fingerprint_is_sha256_of_der() {
+        let key = PublicKey::new(BigInt::from(3233));
+        assert_eq!(
+            key.fingerprint().to_hex(),
+            "872ab25b3034692217256f666203d52879f5673a106722af64ab5bcb70ab2120"
+        );
+        assert_eq!(key.fingerprint().short(), "872a-b25b-3034-6922");
+    }
+
+    #[test]
+    fn test_fingerprint_display_round_trip() {
+        let fingerprint = PublicKey::new(BigInt::from(77)).fingerprint();
+        let rendered = fingerprint.to_string();
+        assert!(rendered.starts_with("SHA256:"));
+        assert_eq!(Fingerprint::from_hex(&rendered), Some(fingerprint));
+        assert_eq!(Fingerprint::from_hex("not a fingerprint"), None);
+        let signed = format!("+f{}", &rendered["SHA256:".len() + 2..]);
+        assert_eq!(Fingerprint::from_hex(&signed), None, "A sign is not a hex digit");
+    }
+
+    #[test]
+    fn test_distinct_keys_have_distinct_fingerprints() {
+        let a = PublicKey::new(BigInt::from(77));
+        let b = PublicKey::new(BigInt::from(91));
+        assert_ne!(a.fingerprint(), b.fingerprint());
+    }
+}
@@ -0,0 +1,183 @@
+// GMP fast path, enabled with the fast-math feature. The heavy big-number operations, modular
+// exponentiation and the squaring in encryption, are handed to the system's libgmp through its
+// C interface; every other operation stays on num-bigint, and builds without the feature never
+// touch GMP. Linking needs libgmp and its development symlink (libgmp-dev or gmp-devel).
+//
+// Only the handful of mpz functions used here are bound, by their exported __gmpz_* names.
+// Values cross the boundary as little-endian byte strings, so nothing depends on GMP's limb
+// size.
+//
+// Every mpz_t lives in a Gmpz, which is the only place mpz_init and mpz_clear are called: new()
+// initializes, Drop clears, and Gmpz is neither Clone nor Copy, so each value is cleared exactly
+// once. None of the bound functions call back into Rust or unwind; GMP aborts the process if
+// an allocation fails.
+
+use num_bigint::{BigInt, BigUint, Sign};
+use num_integer::Integer;
+use num_traits::Zero;
+use std::mem::MaybeUninit;
+use std::os::raw::{c_int, c_void};
+
+// mpz_t: allocated limbs, used limbs (negative for negative values), limb pointer; the layout
+// of __mpz_struct in gmp.h. It holds no pointer to itself, so it may be moved once initialized.
+#[repr(C)]
+struct Mpz {
+    alloc: c_int,
+    size: c_int,
+    limbs: *mut c_void,
+}
+
+#[link(name = "gmp")]
+extern "C" {
+    fn __gmpz_init(x: *mut Mpz);
+    fn __gmpz_clear(x: *mut Mpz);
+    fn __gmpz_import(
+        rop: *mut Mpz,
+        count: usize,
+        order: c_int,
+        size: usize,
+        endian: c_int,
+        nails: usize,
+        op: *const c_void,
+    );
+    fn __gmpz_export(
+        rop: *mut c_void,
+        countp: *mut usize,
+        order: c_int,
+        size: usize,
+        endian: c_int,
+        nails: usize,
+        op: *const Mpz,
+    ) -> *mut c_void;
+    fn __gmpz_sizeinbase(op: *const Mpz, base: c_int) -> usize;
+    fn __gmpz_powm(rop: *mut Mpz, base: *const Mpz, exp: *const Mpz, modulus: *const Mpz);
+    fn __gmpz_mul(rop: *mut Mpz, a: *const Mpz, b: *const Mpz);
+    fn __gmpz_mod(rop: *mut Mpz, n: *const Mpz, d: *const Mpz);
+}
+
+// Owns one initialized mpz_t and clears it on drop
+struct Gmpz(Mpz);
+
+impl Gmpz {
+    fn new() -> Self {
+        let mut value = MaybeUninit::uninit();
+        // SAFETY: mpz_init writes all three fields of the struct it is given, so assume_init sees
+        // an initialized value; the Gmpz takes ownership and clears it on drop
+        unsafe {
+            __gmpz_init(value.as_mut_ptr());
+            Gmpz(value.assume_init())
+        }
+    }
+
+    fn from_biguint(value: &BigUint) -> Self {
+        let mut integer = Gmpz::new();
+        let bytes = value.to_bytes_le();
+        // SAFETY: integer is initialized. mpz_import reads count * size = bytes.len() bytes from
+        // op (one-byte words, least significant first), all inside the vector; for zero it reads
+        // nothing, so the dangling pointer of an empty vector is never dereferenced.
+        unsafe {
+            __gmpz_import(&mut integer.0, bytes.len(), -1, 1, 0, 0, bytes.as_ptr().cast());
+        }
+        integer
+    }
+
+    // Only ever called on non-negative values
+    fn to_biguint(&self) -> BigUint {
+        // SAFETY: the value is initialized. In base 2 the size is exact (1 for zero), so the
+        // buffer holds every byte of the value.
+        let bits = unsafe { __gmpz_sizeinbase(&self.0, 2) };
+        let mut bytes = vec![0u8; bits.div_ceil(8)];
+        let mut count = 0;
+        // SAFETY: the value is initialized, the buffer has room for all of its bytes, and GMP
+        // writes how many it stored (0 for zero) through count
+        unsafe {
+            __gmpz_export(bytes.as_mut_ptr().cast(), &mut count, -1, 1, 0, 0, &self.0);
+        }
+        bytes.truncate(count);
+        BigUint::from_bytes_le(&bytes)
+    }
+}
+
+impl Drop for Gmpz {
+    fn drop(&mut self) {
+        // SAFETY: the value was initialized in Gmpz::new and, with no Clone or Copy, is cleared
+        // exactly once, here
+        unsafe { __gmpz_clear(&mut self.0) }
+    }
+}
+
+// Same contract as montgomery::modpow: positive modulus, non-negative exponent, and a result
+// in [0, modulus)
+pub fn modpow(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> BigInt {
+    assert!(exponent.sign() != Sign::Minus, "negative exponent");
+    assert!(modulus.sign() == Sign::Plus, "modulus must be positive");
+    let base = Gmpz::from_biguint(base.mod_floor(modulus).magnitude());
+    let exponent = Gmpz::from_biguint(exponent.magnitude());
+    let modulus = Gmpz::from_biguint(modulus.magnitude());
+    let mut result = Gmpz::new();
+    // SAFETY: all four values are initialized and distinct from the output. The exponent is
+    // non-negative and the modulus non-zero, as asserted above; GMP would divide by zero
+    // otherwise.
+    unsafe { __gmpz_powm(&mut result.0, &base.0, &exponent.0, &modulus.0) };
+    BigInt::from(result.to_biguint())
+}
+
+// value^2 mod modulus, for a positive modulus
+pub fn square_mod(value: &BigInt, modulus: &BigInt) -> BigInt {
+    assert!(!modulus.is_zero(), "zero modulus");
+    let value = Gmpz::from_biguint(value.magnitude());
+    let divisor = Gmpz::from_biguint(modulus.magnitude());
+    let mut square = Gmpz::new();
+    let mut result = Gmpz::new();
+    // SAFETY: all values are initialized, and each output is distinct from its inputs. GMP
+    // allows the same operand to be passed twice to mpz_mul. The divisor is non-zero, as
+    // asserted above.
+    unsafe {
+        __gmpz_mul(&mut square.0, &value.0, &value.0);
+        __gmpz_mod(&mut result.0, &square.0, &divisor.0);
+    }
+    BigInt::from(result.to_biguint())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::montgomery::Montgomery;
+    use num_bigint::RandBigInt;
+    use num_traits::One;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_conversion_round_trip() {
+        let mut rng = thread_rng();
+        for bits in [0u64, 1, 31, 32, 63, 64, 65, 1000, 4096] {
+            let value = rng.gen_biguint(bits);
+            assert_eq!(Gmpz::from_biguint(&value).to_biguint(), value, "{} bits", bits);
+        }
+    }
+
+    #[test]
+    fn test_parity_with_num_bigint_and_montgomery() {
+        let mut rng = thread_rng();
+        for bits in [8u64, 64, 65, 512, 2048] {
+            for odd in [true, false] {
+                let mut modulus = rng.gen_biguint(bits) | (BigUint::one() << (bits - 1));
+                modulus.set_bit(0, odd);
+                let base = rng.gen_bigint(bits + 8);
+                let exponent = BigInt::from(rng.gen_biguint(bits));
+                let signed_modulus = BigInt::from(modulus.clone());
+
+                let expected = base.modpow(&exponent, &signed_modulus).mod_floor(&signed_modulus);
+                assert_eq!(modpow(&base, &exponent, &signed_modulus), expected, "{}-bit modulus", bits);
+                if let Some(context) = Montgomery::new(&modulus) {
+                    let reduced = base.mod_floor(&signed_modulus).to_biguint().unwrap();
+                    assert_eq!(BigInt::from(context.pow(&reduced, exponent.magnitude())), expected);
+                }
+
+                let value = rng.gen_bigint_range(&BigInt::zero(), &signed_modulus);
+                assert_eq!(square_mod(&value, &signed_modulus), &value * &value % &signed_modulus);
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+// Goldwasser-Micali: probabilistic encryption one bit at a time, using the same keys as Rabin.
+// A bit b becomes c = y^b * x^2 mod n for a fresh random x, where y is a pseudosquare: Jacobi
+// symbol +1 but a square modulo neither prime. Ciphertexts for 0 are squares and ciphertexts for
+// 1 are not, yet both have Jacobi symbol +1, so without the factors telling them apart is the
+// quadratic residuosity problem. With p in hand it is one Legendre symbol.
+//
+// For Blum integers (p ≡ q ≡ 3 mod 4) -1 is a pseudosquare, so y = n - 1 and the ordinary
+// public key is all an encrypting party needs. Other keys have to publish y alongside n.
+//
+// Each bit costs a full-size ciphertext, and multiplying two ciphertexts XORs their bits.
+
+use crate::error::RabinError;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::math::{jacobi, legendre};
+use crate::metadata::KeyUsage;
+use crate::residue::random_pseudosquare;
+use num_bigint::{BigInt, RandBigInt};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use rand::rngs::OsRng;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GmPublicKey {
+    n: BigInt,
+    pseudosquare: BigInt,
+}
+
+impl GmPublicKey {
+    // Only the Jacobi symbol of y can be checked without the factors
+    pub fn new(n: BigInt, pseudosquare: BigInt) -> Result<Self, RabinError> {
+        if n <= BigInt::from(2) || n.is_even() {
+            return Err(RabinError::InvalidKey("the modulus must be odd"));
+        }
+        if jacobi(&pseudosquare.mod_floor(&n), &n) != 1 {
+            return Err(RabinError::InvalidKey("the pseudosquare must have Jacobi symbol +1"));
+        }
+        Ok(GmPublicKey { n, pseudosquare })
+    }
+
+    // Assumes n is a Blum integer, as keys from the default generator are
+    pub fn from_blum(key: &PublicKey) -> Result<Self, RabinError> {
+        key.check_usage(KeyUsage::Encrypt)?;
+        GmPublicKey::new(key.n().clone(), key.n() - 1)
+    }
+
+    pub fn n(&self) -> &BigInt {
+        &self.n
+    }
+
+    pub fn pseudosquare(&self) -> &BigInt {
+        &self.pseudosquare
+    }
+
+    pub fn encrypt_bit(&self, bit: bool) -> BigInt {
+        let n = &self.n;
+        let mut rng = OsRng;
+        // x must be a unit, or c would share a factor with n
+        let x = loop {
+            let x = rng.gen_bigint_range(&BigInt::one(), n);
+            if x.gcd(n).is_one() {
+                break x;
+            }
+        };
+        let square = &x * &x % n;
+        if bit {
+            square * &self.pseudosquare % n
+        } else {
+            square
+        }
+    }
+
+    // One ciphertext per bit, most significant bit of each byte first
+    pub fn encrypt(&self, data: &[u8]) -> Vec<BigInt> {
+        data.iter()
+            .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+            .map(|bit| self.encrypt_bit(bit))
+            .collect()
+    }
+
+    // The encryption of a XOR b, from encryptions of a and b
+    pub fn xor(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        a * b % &self.n
+    }
+}
+
+// The key's GM public key: n - 1 for Blum keys, otherwise a random pseudosquare
+pub fn public_key(key: &PrivateKey) -> GmPublicKey {
+    let four = BigInt::from(4);
+    let three = BigInt::from(3);
+    let n = key.n().clone();
+    if key.p().mod_floor(&four) == three && key.q().mod_floor(&four) == three {
+        let pseudosquare = &n - 1;
+        return GmPublicKey { n, pseudosquare };
+    }
+    GmPublicKey {
+        pseudosquare: random_pseudosquare(&mut OsRng, key),
+        n,
+    }
+}
+
+pub fn decrypt_bit(key: &PrivateKey, ciphertext: &BigInt) -> Result<bool, RabinError> {
+    key.check_usage(KeyUsage::Encrypt)?;
+    let n = key.n();
+    if *ciphertext <= BigInt::zero() || ciphertext >= n {
+        return Err(RabinError::MessageOutOfRange);
+    }
+    // Valid ciphertexts are squares or non-squares modulo both primes at once
+    match (legendre(ciphertext, key.p()), legendre(ciphertext, key.q())) {
+        (1, 1) => Ok(false),
+        (-1, -1) => Ok(true),
+        _ => Err(RabinError::MessageOutOfRange),
+    }
+}
+
+pub fn decrypt(key: &PrivateKey, ciphertexts: &[BigInt]) -> Result<Vec<u8>, RabinError> {
+    if !ciphertexts.len().is_multiple_of(8) {
+        return Err(RabinError::MessageOutOfRange);
+    }
+    ciphertexts
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .try_fold(0u8, |byte, c| Ok((byte << 1) | u8::from(decrypt_bit(key, c)?)))
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+
+    #[test]
+    fn test_round_trip_with_blum_key() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let public = GmPublicKey::from_blum(&key.public_key()).unwrap();
+        assert_eq!(public, public_key(&key));
+
+        let ciphertexts = public.encrypt(b"GM");
+        assert_eq!(ciphertexts.len(), 16);
+        assert_eq!(decrypt(&key, &ciphertexts).unwrap(), b"GM");
+        assert_ne!(public.encrypt_bit(true), public.encrypt_bit(true), "Encryption should be randomized");
+    }
+
+    #[test]
+    fn test_round_trip_with_one_mod_four_primes() {
+        let key = PrivateKey::from_primes(BigInt::from(13), BigInt::from(17)).unwrap().allow_insecure(InsecureDemo);
+        let public = public_key(&key);
+        assert_ne!(*public.pseudosquare(), key.n() - 1, "-1 is a square modulo 13 and 17");
+        for bit in [false, true, true, false] {
+            assert_eq!(decrypt_bit(&key, &public.encrypt_bit(bit)).unwrap(), bit);
+        }
+    }
+
+    #[test]
+    fn test_xor_homomorphism() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let public = public_key(&key);
+        for (a, b) in [(false, false), (false, true), (true, false), (true, true)] {
+            let combined = public.xor(&public.encrypt_bit(a), &public.encrypt_bit(b));
+            assert_eq!(decrypt_bit(&key, &combined).unwrap(), a ^ b);
+        }
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        let key = PrivateKey::from_primes(BigInt::from(43), BigInt::from(47)).unwrap().allow_insecure(InsecureDemo);
+        // 3 is a square modulo 47 but not modulo 43
+        assert_eq!(decrypt_bit(&key, &BigInt::from(3)), Err(RabinError::MessageOutOfRange));
+        assert!(decrypt(&key, &[BigInt::one()]).is_err(), "Bits must come in whole bytes");
+        assert!(GmPublicKey::new(key.n().clone(), BigInt::from(3)).is_err());
+    }
+}
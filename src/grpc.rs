@@ -0,0 +1,201 @@
+// The gRPC service behind the rabin-server binary, built with the grpc feature. The API is
+// described in proto/rabin.proto, which cross-language clients compile too; build.rs generates
+// the messages and the server and client stubs from it.
+//
+// Keys travel as PEM text, messages and ciphertexts as big-endian numbers and signatures as
+// DER, as in the C and browser bindings. The service holds no keys between calls. Demo-sized
+// keys are refused unless the service was built with allow_insecure, which is the server's
+// --insecure-demo.
+
+use crate::encoding::{bytes2num, modulus_len, num2bytes, to_fixed_be_bytes, ByteOrder};
+use crate::error::RabinError;
+use crate::keygen::KeygenConfig;
+use crate::keys::{InsecureDemo, PrivateKey, PublicKey};
+use crate::metadata::KeyUsage;
+use crate::rabin::{decrypt_tagged, encrypt_tagged};
+use crate::signature::Signature;
+use tonic::{Code, Request, Response, Status};
+
+tonic::include_proto!("rabin.v1");
+
+pub use rabin_client::RabinClient;
+pub use rabin_server::{Rabin, RabinServer};
+
+fn to_status(err: RabinError) -> Status {
+    let code = match err {
+        RabinError::InsecureKey(_) | RabinError::UsageViolation(_) | RabinError::KeyExpired => {
+            Code::FailedPrecondition
+        }
+        RabinError::Cancelled => Code::Cancelled,
+        RabinError::RngFailure(_) | RabinError::FaultDetected | RabinError::Io(_) => Code::Internal,
+        _ => Code::InvalidArgument,
+    };
+    Status::new(code, err.to_string())
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RabinService {
+    insecure: Option<InsecureDemo>,
+}
+
+impl RabinService {
+    pub fn new() -> Self {
+        RabinService::default()
+    }
+
+    pub fn allow_insecure(mut self, opt_in: InsecureDemo) -> Self {
+        self.insecure = Some(opt_in);
+        self
+    }
+
+    fn load_private(&self, pem: &str) -> Result<PrivateKey, RabinError> {
+        let key = PrivateKey::from_pkcs8_pem(pem)?;
+        Ok(match self.insecure {
+            Some(opt_in) => key.allow_insecure(opt_in),
+            None => key,
+        })
+    }
+
+    fn load_public(&self, pem: &str) -> Result<PublicKey, RabinError> {
+        let key = PublicKey::from_pem(pem)?;
+        Ok(match self.insecure {
+            Some(opt_in) => key.allow_insecure(opt_in),
+            None => key,
+        })
+    }
+
+    fn key_gen(&self, request: KeyGenRequest) -> Result<KeyGenResponse, RabinError> {
+        let config = KeygenConfig::new(request.bits as usize);
+        let config = match self.insecure {
+            Some(opt_in) => config.allow_insecure(opt_in),
+            None => config,
+        };
+        let (key, _) = PrivateKey::generate_with(&config)?;
+        Ok(KeyGenResponse {
+            private_key_pem: key.to_pkcs8_pem(),
+            public_key_pem: key.public_key().to_pem(),
+        })
+    }
+
+    fn encrypt(&self, request: EncryptRequest) -> Result<EncryptResponse, RabinError> {
+        let key = self.load_public(&request.public_key_pem)?;
+        key.check_usage(KeyUsage::Encrypt)?;
+        let ciphertext = encrypt_tagged(&bytes2num(&request.message, ByteOrder::BigEndian), key.n())?;
+        Ok(EncryptResponse {
+            ciphertext: to_fixed_be_bytes(&ciphertext, modulus_len(key.n())).expect("the ciphertext is below n"),
+        })
+    }
+
+    fn decrypt(&self, request: DecryptRequest) -> Result<DecryptResponse, RabinError> {
+        let key = self.load_private(&request.private_key_pem)?;
+        key.check_usage(KeyUsage::Encrypt)?;
+        let message = decrypt_tagged(&bytes2num(&request.ciphertext, ByteOrder::BigEndian), key.p(), key.q())?;
+        Ok(DecryptResponse {
+            message: num2bytes(&message, ByteOrder::BigEndian, None).expect("the message is not negative"),
+        })
+    }
+
+    fn sign(&self, request: SignRequest) -> Result<SignResponse, RabinError> {
+        let key = self.load_private(&request.private_key_pem)?;
+        Ok(SignResponse {
+            signature_der: Signature::sign(&key, &request.message)?.to_der(),
+        })
+    }
+
+    fn verify(&self, request: VerifyRequest) -> Result<VerifyResponse, RabinError> {
+        let key = self.load_public(&request.public_key_pem)?;
+        let signature = Signature::from_der(&request.signature_der)?;
+        match signature.verify(&key, &request.message) {
+            Ok(()) => Ok(VerifyResponse { valid: true }),
+            Err(RabinError::InvalidSignature) => Ok(VerifyResponse { valid: false }),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Runs a call on the blocking pool: key generation and decryption are long computations
+    async fn call<T, U>(&self, request: Request<T>, body: fn(&Self, T) -> Result<U, RabinError>) -> Result<Response<U>, Status>
+    where
+        T: Send + 'static,
+        U: Send + 'static,
+    {
+        let service = *self;
+        let request = request.into_inner();
+        tokio::task::spawn_blocking(move || body(&service, request))
+            .await
+            .map_err(|_| Status::internal("the call panicked"))?
+            .map(Response::new)
+            .map_err(to_status)
+    }
+}
+
+#[tonic::async_trait]
+impl Rabin for RabinService {
+    async fn key_gen(&self, request: Request<KeyGenRequest>) -> Result<Response<KeyGenResponse>, Status> {
+        self.call(request, RabinService::key_gen).await
+    }
+
+    async fn encrypt(&self, request: Request<EncryptRequest>) -> Result<Response<EncryptResponse>, Status> {
+        self.call(request, RabinService::encrypt).await
+    }
+
+    async fn decrypt(&self, request: Request<DecryptRequest>) -> Result<Response<DecryptResponse>, Status> {
+        self.call(request, RabinService::decrypt).await
+    }
+
+    async fn sign(&self, request: Request<SignRequest>) -> Result<Response<SignResponse>, Status> {
+        self.call(request, RabinService::sign).await
+    }
+
+    async fn verify(&self, request: Request<VerifyRequest>) -> Result<Response<VerifyResponse>, Status> {
+        self.call(request, RabinService::verify).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rpcs_round_trip() {
+        let service = RabinService::new();
+        let err = Rabin::key_gen(&service, Request::new(KeyGenRequest { bits: 256 })).await.unwrap_err();
+        assert_eq!(err.code(), Code::FailedPrecondition);
+
+        let service = service.allow_insecure(InsecureDemo);
+        let keys = Rabin::key_gen(&service, Request::new(KeyGenRequest { bits: 256 })).await.unwrap().into_inner();
+
+        let request = EncryptRequest {
+            public_key_pem: keys.public_key_pem.clone(),
+            message: b"over the wire".to_vec(),
+        };
+        let ciphertext = Rabin::encrypt(&service, Request::new(request)).await.unwrap().into_inner().ciphertext;
+        let request = DecryptRequest {
+            private_key_pem: keys.private_key_pem.clone(),
+            ciphertext,
+        };
+        let message = Rabin::decrypt(&service, Request::new(request)).await.unwrap().into_inner().message;
+        assert_eq!(message, b"over the wire");
+
+        let request = SignRequest {
+            private_key_pem: keys.private_key_pem,
+            message: b"signed".to_vec(),
+        };
+        let signature_der = Rabin::sign(&service, Request::new(request)).await.unwrap().into_inner().signature_der;
+        for (message, valid) in [(&b"signed"[..], true), (&b"forged"[..], false)] {
+            let request = VerifyRequest {
+                public_key_pem: keys.public_key_pem.clone(),
+                message: message.to_vec(),
+                signature_der: signature_der.clone(),
+            };
+            assert_eq!(Rabin::verify(&service, Request::new(request)).await.unwrap().into_inner().valid, valid);
+        }
+
+        let request = DecryptRequest {
+            private_key_pem: "not a key".into(),
+            ciphertext: vec![1],
+        };
+        let err = Rabin::decrypt(&service, Request::new(request)).await.unwrap_err();
+        assert_eq!(err.code(), Code::InvalidArgument);
+    }
+}
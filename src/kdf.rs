@@ -0,0 +1,96 @@
+// Argon2id (RFC 9106, version 0x13), used to turn passphrases into sealing keys, through the
+// RustCrypto argon2 crate. Only the parameters and their container encoding live here.
+
+use crate::error::RabinError;
+use argon2::{Algorithm, Argon2, AssociatedData, ParamsBuilder, Version};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Argon2Params {
+    // Memory cost in KiB (one block = 1 KiB)
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub lanes: u32,
+}
+
+impl Default for Argon2Params {
+    fn default() -> Self {
+        // OWASP's recommended minimum for Argon2id
+        Argon2Params {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            lanes: 1,
+        }
+    }
+}
+
+// Fails for parameters RFC 9106 does not allow, such as fewer than 8 KiB per lane or a salt
+// shorter than 8 bytes
+pub fn argon2id(
+    password: &[u8],
+    salt: &[u8],
+    secret: &[u8],
+    associated_data: &[u8],
+    params: &Argon2Params,
+    out_len: usize,
+) -> Result<Vec<u8>, RabinError> {
+    let invalid = |_| RabinError::MalformedDer("unreasonable Argon2 parameters");
+    let params = ParamsBuilder::new()
+        .m_cost(params.memory_kib)
+        .t_cost(params.iterations)
+        .p_cost(params.lanes)
+        .data(AssociatedData::new(associated_data).map_err(invalid)?)
+        .output_len(out_len)
+        .build()
+        .map_err(invalid)?;
+    let argon2 = Argon2::new_with_secret(secret, Algorithm::Argon2id, Version::V0x13, params).map_err(invalid)?;
+    let mut out = vec![0u8; out_len];
+    argon2.hash_password_into(password, salt, &mut out).map_err(invalid)?;
+    Ok(out)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::to_hex;
+
+    #[test]
+    fn test_argon2id_rfc9106_vector() {
+        let params = Argon2Params {
+            memory_kib: 32,
+            iterations: 3,
+            lanes: 4,
+        };
+        let tag = argon2id(&[0x01; 32], &[0x02; 16], &[0x03; 8], &[0x04; 12], &params, 32).unwrap();
+        assert_eq!(
+            to_hex(&tag),
+            "0d640df58d78766c08c037a34a8b53c9d01ef0452d75b65eb52520e96b01e659"
+        );
+    }
+
+    #[test]
+    fn test_argon2id_single_lane() {
+        // Cross-checked against the `cryptography` Python package
+        let params = Argon2Params {
+            memory_kib: 64,
+            iterations: 2,
+            lanes: 1,
+        };
+        let tag = argon2id(b"password", b"somesaltsomesalt", b"", b"", &params, 32).unwrap();
+        assert_eq!(
+            to_hex(&tag),
+            "1a98fba8e6394425c9bdc333a9c750aaf811df070f5fb4a5788e1cf4db5793a1"
+        );
+    }
+
+    #[test]
+    fn test_argon2id_rejects_invalid_parameters() {
+        let params = Argon2Params {
+            memory_kib: 7,
+            iterations: 1,
+            lanes: 1,
+        };
+        assert!(argon2id(b"password", b"somesaltsomesalt", b"", b"", &params, 32).is_err());
+        assert!(argon2id(b"password", b"short", b"", b"", &Argon2Params::default(), 32).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+// The Rabin KEM that envelopes and streams are built on. Encapsulation draws a fresh 32-byte
+// key, wraps it in a block with random padding and redundancy (envelope::encode_session_key),
+// and squares the block modulo n. Decapsulation takes the four square roots and keeps the one
+// whose redundancy checks out; a ciphertext with no such root fails with DecryptionFailed.
+//
+// With the kem-traits feature, PublicKey and PrivateKey also implement the kem crate's
+// Encapsulate and Decapsulate, so the Rabin KEM can stand in for other KEMs in generic
+// hybrid-encryption code.
+
+use crate::aead::KEY_LEN;
+use crate::encoding::{from_be_bytes, modulus_len, to_fixed_be_bytes};
+use crate::envelope::{decode_session_key, encode_session_key};
+use crate::error::RabinError;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::metadata::KeyUsage;
+use crate::rabin::encrypt;
+use crate::secret::Secret;
+use num_bigint::BigInt;
+use rand::{CryptoRng, RngCore};
+
+pub type SharedSecret = [u8; KEY_LEN];
+
+// The encapsulated key: the squared block, a number below n
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncapsulatedKey(BigInt);
+
+impl EncapsulatedKey {
+    pub fn new(value: BigInt) -> Self {
+        EncapsulatedKey(value)
+    }
+
+    pub fn value(&self) -> &BigInt {
+        &self.0
+    }
+
+    // Big-endian, as wide as the modulus, so the length does not depend on the value
+    pub fn to_bytes(&self, n: &BigInt) -> Result<Vec<u8>, RabinError> {
+        to_fixed_be_bytes(&self.0, modulus_len(n)).ok_or(RabinError::MessageOutOfRange)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        EncapsulatedKey(from_be_bytes(bytes))
+    }
+}
+
+pub fn encapsulate<R: RngCore + CryptoRng + ?Sized>(
+    rng: &mut R,
+    recipient: &PublicKey,
+) -> Result<(EncapsulatedKey, SharedSecret), RabinError> {
+    recipient.check_usage(KeyUsage::Encrypt)?;
+    let mut shared = [0u8; KEY_LEN];
+    rng.fill_bytes(&mut shared);
+    let block = encode_session_key(rng, recipient.n(), &shared)?;
+    Ok((EncapsulatedKey(encrypt(&block, recipient.n())?), shared))
+}
+
+pub fn decapsulate(key: &PrivateKey, encapsulated: &EncapsulatedKey) -> Result<SharedSecret, RabinError> {
+    key.check_usage(KeyUsage::Encrypt)?;
+    Secret::new(key.square_roots(&encapsulated.0)?)
+        .iter()
+        .find_map(|candidate| decode_session_key(key.n(), candidate))
+        .ok_or(RabinError::DecryptionFailed)
+}
+
+#[cfg(feature = "kem-traits")]
+mod traits {
+    use super::*;
+    use ::kem::{Decapsulate, Encapsulate};
+    use rand_core::CryptoRngCore;
+
+    impl Encapsulate<EncapsulatedKey, SharedSecret> for PublicKey {
+        type Error = RabinError;
+
+        fn encapsulate(&self, rng: &mut impl CryptoRngCore) -> Result<(EncapsulatedKey, SharedSecret), RabinError> {
+            encapsulate(rng, self)
+        }
+    }
+
+    impl Decapsulate<EncapsulatedKey, SharedSecret> for PrivateKey {
+        type Error = RabinError;
+
+        fn decapsulate(&self, encapsulated_key: &EncapsulatedKey) -> Result<SharedSecret, RabinError> {
+            decapsulate(self, encapsulated_key)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_encapsulated_keys_open_only_with_the_right_key() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let (encapsulated, shared) = encapsulate(&mut OsRng, &key.public_key()).unwrap();
+        assert_eq!(decapsulate(&key, &encapsulated), Ok(shared));
+
+        let bytes = encapsulated.to_bytes(key.n()).unwrap();
+        assert_eq!(bytes.len(), modulus_len(key.n()));
+        assert_eq!(EncapsulatedKey::from_bytes(&bytes), encapsulated);
+
+        // A square whose roots carry no redundancy, e.g. one made up by an attacker
+        let forged = EncapsulatedKey::new(BigInt::from(0x5eed_u32).pow(2) % key.n());
+        assert_eq!(decapsulate(&key, &forged), Err(RabinError::DecryptionFailed));
+    }
+
+    #[cfg(feature = "kem-traits")]
+    #[test]
+    fn test_keys_work_through_the_kem_traits() {
+        use ::kem::{Decapsulate, Encapsulate};
+
+        // Written the way generic hybrid-encryption code would use it
+        fn agree<E, D, EK, SS>(sender: &E, receiver: &D) -> (SS, SS)
+        where
+            E: Encapsulate<EK, SS>,
+            D: Decapsulate<EK, SS>,
+        {
+            let Ok((encapsulated, sent)) = sender.encapsulate(&mut OsRng) else {
+                panic!("encapsulation should succeed");
+            };
+            let Ok(received) = receiver.decapsulate(&encapsulated) else {
+                panic!("decapsulation should succeed");
+            };
+            (sent, received)
+        }
+
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let (sent, received) = agree(&key.public_key(), &key);
+        assert_eq!(sent, received);
+    }
+}
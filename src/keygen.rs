@@ -0,0 +1,762 @@
+// Configurable key generation. Plain random primes are the default; safe and strong primes add
+// structure that defeats special-purpose factoring (Pollard p - 1, Williams p + 1) at a real
+// cost in generation time, which is measured and handed back in a KeygenReport. The source of
+// randomness is also configurable, and is self-tested before any key material is drawn from it.
+// generate_keypair_async and generate_keypair_with_progress run the same generation off the
+// caller's thread, for async code and for callers that want progress reports or cancellation.
+
+use crate::error::RabinError;
+use crate::keys::{InsecureDemo, PrivateKey, MIN_SECURE_BITS};
+use crate::metadata::KeyMetadata;
+use crate::primality::{is_probable_prime, PrimalityConfig};
+use crate::rabin::{default_workers, far_apart, race_for_primes, search_prime_until, SearchControl};
+use log::{info, warn};
+use num_bigint::{BigInt, BigUint};
+use num_integer::Integer;
+use num_prime::{PrimalityTestConfig, RandPrime};
+use num_traits::One;
+use rand::rngs::OsRng;
+use rand::{thread_rng, Rng, RngCore};
+use std::fmt;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrimeKind {
+    // Uniformly random primes ≡ 3 (mod 4): fastest
+    #[default]
+    Random,
+    // p = 2p' + 1 with p' prime. Typically tens to hundreds of times slower than random primes.
+    Safe,
+    // Gordon's strong primes: p - 1, p + 1 and r - 1 (for the large factor r of p - 1) all
+    // have large prime factors. A few times slower than random primes.
+    Strong,
+}
+
+pub type SharedRng = Arc<Mutex<dyn RngCore + Send>>;
+
+#[derive(Clone, Default)]
+pub enum EntropySource {
+    // rand's thread-local CSPRNG, seeded and periodically reseeded from the OS
+    Thread,
+    // rand's OsRng: every byte comes straight from the operating system. The default, as for
+    // every other random draw in the crate that ends up in key material or a ciphertext.
+    #[default]
+    Os,
+    // The getrandom syscall wrapper, without going through rand
+    Getrandom,
+    // A caller-supplied generator, e.g. a hardware RNG or a seeded one for tests. Both primes
+    // are drawn from it in turn rather than in parallel.
+    Custom(SharedRng),
+}
+
+// Decryption takes square roots modulo each prime. For p ≡ 3 (mod 4) that is a single
+// exponentiation; other primes need Tonelli-Shanks, which is slower but works for any odd prime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PrimeCongruence {
+    #[default]
+    ThreeModFour,
+    Any,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeygenConfig {
+    pub bits: usize,
+    pub primes: PrimeKind,
+    pub congruence: PrimeCongruence,
+    // 2 for ordinary keys; more is only supported by MultiPrimeKey
+    pub prime_count: usize,
+    pub entropy: EntropySource,
+    // Encryption exponent 2^squarings; only PowerKey supports more than one squaring
+    pub squarings: u32,
+    // Parallel prime searches; ignored for custom generators, which are drawn from in order
+    pub workers: usize,
+    // Needed to generate a modulus shorter than MIN_SECURE_BITS; carried over to the key
+    pub insecure: Option<InsecureDemo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeygenReport {
+    pub primes: PrimeKind,
+    // Full-size candidates drawn across both primes before one had the required structure
+    pub candidates: u64,
+    pub elapsed: Duration,
+    // The modulus is shorter than MIN_SECURE_BITS
+    pub demo: bool,
+}
+
+impl fmt::Display for PrimeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PrimeKind::Random => "random",
+            PrimeKind::Safe => "safe",
+            PrimeKind::Strong => "strong",
+        })
+    }
+}
+
+impl fmt::Debug for EntropySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EntropySource::Thread => "Thread",
+            EntropySource::Os => "Os",
+            EntropySource::Getrandom => "Getrandom",
+            EntropySource::Custom(_) => "Custom",
+        })
+    }
+}
+
+impl fmt::Display for KeygenReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} primes: {} candidates tested in {:.2?}",
+            self.primes, self.candidates, self.elapsed
+        )?;
+        if self.demo {
+            write!(f, " (insecure demo key)")?;
+        }
+        Ok(())
+    }
+}
+
+impl KeygenConfig {
+    pub fn new(bits: usize) -> Self {
+        KeygenConfig {
+            bits,
+            primes: PrimeKind::Random,
+            congruence: PrimeCongruence::ThreeModFour,
+            prime_count: 2,
+            entropy: EntropySource::Os,
+            squarings: 1,
+            workers: default_workers(),
+            insecure: None,
+        }
+    }
+
+    pub fn with_primes(mut self, primes: PrimeKind) -> Self {
+        self.primes = primes;
+        self
+    }
+
+    // Safe primes are always ≡ 3 (mod 4), so this only affects random and strong primes
+    pub fn with_congruence(mut self, congruence: PrimeCongruence) -> Self {
+        self.congruence = congruence;
+        self
+    }
+
+    pub fn with_prime_count(mut self, prime_count: usize) -> Self {
+        self.prime_count = prime_count;
+        self
+    }
+
+    pub fn with_entropy(mut self, entropy: EntropySource) -> Self {
+        self.entropy = entropy;
+        self
+    }
+
+    pub fn with_squarings(mut self, squarings: u32) -> Self {
+        self.squarings = squarings;
+        self
+    }
+
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    pub fn allow_insecure(mut self, opt_in: InsecureDemo) -> Self {
+        self.insecure = Some(opt_in);
+        self
+    }
+
+    // The modulus is the product of prime_count primes of the configured size
+    pub fn is_demo(&self) -> bool {
+        ((self.bits * self.prime_count) as u64) < MIN_SECURE_BITS
+    }
+
+    pub fn with_rng<R: RngCore + Send + 'static>(self, rng: R) -> Self {
+        self.with_entropy(EntropySource::Custom(Arc::new(Mutex::new(rng))))
+    }
+}
+
+// RngCore over the getrandom crate
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GetrandomRng;
+
+impl RngCore for GetrandomRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        getrandom::getrandom(dest).expect("getrandom failed");
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        getrandom::getrandom(dest).map_err(rand::Error::from)
+    }
+}
+
+const SELF_TEST_WORDS: usize = 16;
+// 20,000 bits, the sample size of the FIPS 140-2 monobit test
+const SELF_TEST_BYTES: usize = 2500;
+// Six standard deviations of the number of ones in the sample. A fair source falls outside
+// about once in 10^9 runs; the FIPS 140-2 bounds (±275) would fail it about once in 10^4.
+const MONOBIT_TOLERANCE: u32 = 424;
+
+// Any 8-byte block occurring twice: for a working generator that is a 2^-50 event in a
+// sample this size, for one stuck in a short cycle (a VM snapshot replaying the same state,
+// a seed that was never set) it is certain
+fn has_repeated_block(sample: &[u8]) -> bool {
+    let mut blocks: Vec<&[u8]> = sample.chunks_exact(8).collect();
+    blocks.sort_unstable();
+    blocks.windows(2).any(|pair| pair[0] == pair[1])
+}
+
+fn passes_monobit(sample: &[u8]) -> bool {
+    let ones: u32 = sample.iter().map(|byte| byte.count_ones()).sum();
+    ones.abs_diff(4 * sample.len() as u32) <= MONOBIT_TOLERANCE
+}
+
+// Catches generators that are obviously broken (stuck, repeating, returning constant bytes,
+// or heavily biased) before key material is drawn from them. Passing says nothing about
+// actual quality.
+pub fn check_rng<R: RngCore + ?Sized>(rng: &mut R) -> Result<(), RabinError> {
+    let mut words = [0u64; SELF_TEST_WORDS];
+    for word in words.iter_mut() {
+        *word = rng.next_u64();
+    }
+    for (i, word) in words.iter().enumerate() {
+        if words[i + 1..].contains(word) {
+            return Err(RabinError::RngFailure("repeated output"));
+        }
+    }
+
+    let mut sample = vec![0u8; SELF_TEST_BYTES];
+    rng.try_fill_bytes(&mut sample)
+        .map_err(|_| RabinError::RngFailure("source reported an error"))?;
+    if sample.iter().all(|&byte| byte == sample[0]) {
+        return Err(RabinError::RngFailure("constant output"));
+    }
+    if has_repeated_block(&sample) {
+        return Err(RabinError::RngFailure("repeated output"));
+    }
+    if !passes_monobit(&sample) {
+        return Err(RabinError::RngFailure("biased output"));
+    }
+    Ok(())
+}
+
+impl EntropySource {
+    pub fn self_test(&self) -> Result<(), RabinError> {
+        match self {
+            EntropySource::Thread => check_rng(&mut thread_rng()),
+            EntropySource::Os => check_rng(&mut OsRng),
+            EntropySource::Getrandom => check_rng(&mut GetrandomRng),
+            EntropySource::Custom(rng) => check_rng(&mut *lock(rng)),
+        }
+    }
+}
+
+fn lock(rng: &SharedRng) -> MutexGuard<'_, dyn RngCore + Send + 'static> {
+    rng.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn probably_prime(candidate: &BigUint) -> bool {
+    is_probable_prime(candidate, &PrimalityConfig::strict())
+}
+
+fn is_three_mod_four(value: &BigUint) -> bool {
+    value % 4u8 == BigUint::from(3u8)
+}
+
+impl PrimeCongruence {
+    pub fn accepts(self, prime: &BigUint) -> bool {
+        match self {
+            PrimeCongruence::ThreeModFour => is_three_mod_four(prime),
+            PrimeCongruence::Any => true,
+        }
+    }
+}
+
+// The prime generators below count their candidates in `control` and return None once it says
+// stop, which they check between candidates
+
+fn gen_random_prime<R: Rng>(
+    rng: &mut R,
+    bits: usize,
+    congruence: PrimeCongruence,
+    control: &SearchControl,
+) -> Option<BigUint> {
+    match congruence {
+        // Sieved search that only ever produces candidates ≡ 3 (mod 4)
+        PrimeCongruence::ThreeModFour => search_prime_until(rng, bits, control),
+        PrimeCongruence::Any => {
+            let prime = gen_plain_prime(rng, bits);
+            control.record_tested(1);
+            Some(prime)
+        }
+    }
+}
+
+// Every safe prime above 7 is ≡ 3 (mod 4), since p' is odd
+fn gen_safe_prime<R: Rng>(rng: &mut R, bits: usize, control: &SearchControl) -> Option<BigUint> {
+    while !control.stopped() {
+        let prime = (gen_plain_prime(rng, bits - 1) << 1) + 1u8;
+        control.record_tested(1);
+        if probably_prime(&prime) {
+            return Some(prime);
+        }
+    }
+    None
+}
+
+fn gen_plain_prime<R: Rng>(rng: &mut R, bits: usize) -> BigUint {
+    rng.gen_prime_exact(bits, Some(PrimalityTestConfig::strict()))
+}
+
+// Gordon's algorithm. Returns (p, r, s, t) with r | p - 1, s | p + 1 and t | r - 1.
+fn gen_strong_prime_parts<R: Rng>(
+    rng: &mut R,
+    bits: usize,
+    congruence: PrimeCongruence,
+    control: &SearchControl,
+) -> Option<(BigUint, BigUint, BigUint, BigUint)> {
+    // Leave enough room below p for the search over j to find a prime of exactly `bits` bits
+    let t_bits = (bits / 2).saturating_sub(16).max(8);
+    while !control.stopped() {
+        let t = gen_plain_prime(rng, t_bits);
+        // r = 2it + 1
+        let step = &t << 1;
+        let mut r = &step + 1u8;
+        while !probably_prime(&r) {
+            r += &step;
+        }
+        let s_bits = bits.saturating_sub(r.bits() as usize + 16).max(8);
+        let s = gen_plain_prime(rng, s_bits);
+
+        // p0 ≡ 1 (mod r) and p0 ≡ -1 (mod s)
+        let s_inverse = s.modinv(&r).expect("distinct primes are coprime");
+        let p0 = ((s_inverse * &s) << 1) - 1u8;
+        let modulus = (&r * &s) << 1;
+
+        let low = BigUint::one() << (bits - 1);
+        let high = BigUint::one() << bits;
+        let mut p = if p0 >= low {
+            p0
+        } else {
+            let steps = Integer::div_ceil(&(&low - &p0), &modulus);
+            p0 + steps * &modulus
+        };
+        while p < high && !control.stopped() {
+            if congruence.accepts(&p) {
+                control.record_tested(1);
+                if probably_prime(&p) {
+                    return Some((p, r, s, t));
+                }
+            }
+            p += &modulus;
+        }
+    }
+    None
+}
+
+fn gen_prime_of_kind<R: Rng>(rng: &mut R, config: &KeygenConfig, control: &SearchControl) -> Option<BigUint> {
+    match config.primes {
+        PrimeKind::Random => gen_random_prime(rng, config.bits, config.congruence, control),
+        PrimeKind::Safe => gen_safe_prime(rng, config.bits, control),
+        PrimeKind::Strong => gen_strong_prime_parts(rng, config.bits, config.congruence, control).map(|parts| parts.0),
+    }
+}
+
+impl KeygenConfig {
+    fn gen_prime(&self, control: &SearchControl) -> Option<BigUint> {
+        match &self.entropy {
+            EntropySource::Thread => gen_prime_of_kind(&mut thread_rng(), self, control),
+            EntropySource::Os => gen_prime_of_kind(&mut OsRng, self, control),
+            EntropySource::Getrandom => gen_prime_of_kind(&mut GetrandomRng, self, control),
+            EntropySource::Custom(rng) => {
+                let mut rng = lock(rng);
+                let mut rng: &mut (dyn RngCore + Send) = &mut *rng;
+                gen_prime_of_kind(&mut rng, self, control)
+            }
+        }
+    }
+}
+
+impl KeygenConfig {
+    // Self-tests the entropy source, then draws prime_count distinct primes
+    pub(crate) fn gen_distinct_primes(&self) -> Result<(Vec<BigUint>, KeygenReport), RabinError> {
+        self.gen_distinct_primes_with(&SearchControl::new())
+    }
+
+    // The same, reporting to and stopping at the given control; fails with Cancelled if the
+    // control's cancel flag is set before all primes are in
+    pub(crate) fn gen_distinct_primes_with(
+        &self,
+        control: &SearchControl,
+    ) -> Result<(Vec<BigUint>, KeygenReport), RabinError> {
+        if self.prime_count < 2 {
+            return Err(RabinError::InvalidKey("a key needs at least two primes"));
+        }
+        if self.is_demo() {
+            if self.insecure.is_none() {
+                return Err(RabinError::InsecureKey((self.bits * self.prime_count) as u64));
+            }
+            warn!("Generating an insecure demo key with bit size {}", self.bits);
+        }
+        self.entropy.self_test()?;
+        info!("Starting {} key generation with bit size {}", self.primes, self.bits);
+        let started = Instant::now();
+
+        let primes = match &self.entropy {
+            // One generator, drawn from in order, so a seeded generator gives the same primes
+            EntropySource::Custom(_) => {
+                let mut primes: Vec<BigUint> = Vec::with_capacity(self.prime_count);
+                while primes.len() < self.prime_count {
+                    let Some(prime) = self.gen_prime(control) else {
+                        break;
+                    };
+                    if primes.iter().all(|other| far_apart(other, &prime)) {
+                        primes.push(prime);
+                    }
+                }
+                primes
+            }
+            _ => race_for_primes(self.prime_count, self.workers, control, |control| self.gen_prime(control)),
+        };
+        if primes.len() < self.prime_count {
+            info!("Key generation cancelled after {} candidates", control.tested());
+            return Err(RabinError::Cancelled);
+        }
+
+        let report = KeygenReport {
+            primes: self.primes,
+            candidates: control.tested(),
+            elapsed: started.elapsed(),
+            demo: self.is_demo(),
+        };
+        info!("Generated {}", report);
+        Ok((primes, report))
+    }
+}
+
+impl PrivateKey {
+    pub fn generate_with(config: &KeygenConfig) -> Result<(Self, KeygenReport), RabinError> {
+        PrivateKey::generate_with_control(config, &SearchControl::new())
+    }
+
+    fn generate_with_control(
+        config: &KeygenConfig,
+        control: &SearchControl,
+    ) -> Result<(Self, KeygenReport), RabinError> {
+        if config.prime_count != 2 {
+            return Err(RabinError::InvalidKey("use MultiPrimeKey for more than two primes"));
+        }
+        if config.squarings != 1 {
+            return Err(RabinError::InvalidKey("use PowerKey for exponents other than 2"));
+        }
+        let (primes, report) = config.gen_distinct_primes_with(control)?;
+        let [p, q]: [BigUint; 2] = primes.try_into().expect("exactly two primes were drawn");
+        let mut key = PrivateKey::from_primes(BigInt::from(p), BigInt::from(q))
+            .expect("generated primes are greater than 1")
+            .with_metadata(KeyMetadata::new(Some(config.bits)));
+        if let Some(opt_in) = config.insecure {
+            key = key.allow_insecure(opt_in);
+        }
+        Ok((key, report))
+    }
+}
+
+type KeygenResult = Result<(PrivateKey, KeygenReport), RabinError>;
+
+// Filled in by the background job; the waker is whichever task polled last
+struct PendingKey {
+    result: Option<thread::Result<KeygenResult>>,
+    waker: Option<Waker>,
+}
+
+// Resolves to the result of PrivateKey::generate_with. Works with any executor: the search runs
+// on rayon's pool and wakes the task when it is done. A panic in the search is re-raised from
+// poll. Dropping the future does not stop the search, only discards its result.
+pub struct KeygenFuture {
+    shared: Arc<Mutex<PendingKey>>,
+}
+
+impl Future for KeygenFuture {
+    type Output = KeygenResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<KeygenResult> {
+        let mut pending = self.shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match pending.result.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(panic)) => panic::resume_unwind(panic),
+            None => {
+                pending.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// Key generation for async code: hands the CPU-heavy search to rayon's pool instead of
+// blocking the executor thread
+pub fn generate_keypair_async(config: KeygenConfig) -> KeygenFuture {
+    let shared = Arc::new(Mutex::new(PendingKey {
+        result: None,
+        waker: None,
+    }));
+    let job = Arc::clone(&shared);
+    rayon::spawn(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| PrivateKey::generate_with(&config)));
+        let waker = {
+            let mut pending = job.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            pending.result = Some(result);
+            pending.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+    KeygenFuture { shared }
+}
+
+// Progress of a key generation running behind a KeygenHandle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeygenProgress {
+    // Candidates put through the full primality test so far, across all workers
+    pub candidates: u64,
+    // Distinct primes found so far, out of the configured prime count
+    pub primes: usize,
+}
+
+// A key generation running on its own thread. cancel() makes every worker stop at its next
+// candidate, and wait() then returns Err(Cancelled); dropping the handle cancels as well.
+pub struct KeygenHandle {
+    cancelled: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<KeygenResult>>,
+}
+
+impl KeygenHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|thread| thread.is_finished())
+    }
+
+    // Blocks until the search ends; a panic in the search is re-raised here
+    pub fn wait(mut self) -> KeygenResult {
+        let thread = self.thread.take().expect("only wait() takes the thread");
+        thread.join().unwrap_or_else(|panic| panic::resume_unwind(panic))
+    }
+}
+
+impl Drop for KeygenHandle {
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            self.cancel();
+        }
+    }
+}
+
+// Starts PrivateKey::generate_with on a background thread. The callback runs on the worker
+// threads after every candidate tested and every prime found, so it should be quick (e.g.
+// update a counter or a progress bar).
+pub fn generate_keypair_with_progress<F>(config: KeygenConfig, progress: F) -> KeygenHandle
+where
+    F: Fn(KeygenProgress) + Send + Sync + 'static,
+{
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&cancelled);
+    let thread = thread::Builder::new()
+        .name("keygen".into())
+        .spawn(move || {
+            let report = |candidates, primes| progress(KeygenProgress { candidates, primes });
+            let control = SearchControl::new().with_cancel(&flag).with_progress(&report);
+            PrivateKey::generate_with_control(&config, &control)
+        })
+        .expect("failed to spawn the key generation thread");
+    KeygenHandle {
+        cancelled,
+        thread: Some(thread),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+    use crate::rabin::{decrypt, encrypt};
+    use rand::rngs::mock::StepRng;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    // Minimal executor: polls on the current thread and parks between wake-ups
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct Unpark(thread::Thread);
+        impl std::task::Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_async_generation() {
+        let (key, report) = block_on(generate_keypair_async(KeygenConfig::new(128).allow_insecure(InsecureDemo))).unwrap();
+        assert_eq!(key.validate(), vec![]);
+        assert!(report.candidates >= 2);
+
+        let refused = block_on(generate_keypair_async(KeygenConfig::new(128).allow_insecure(InsecureDemo).with_prime_count(3)));
+        assert!(matches!(refused, Err(RabinError::InvalidKey(_))), "Errors come back through the future");
+    }
+
+    #[test]
+    fn test_progress_and_cancellation() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let handle = generate_keypair_with_progress(KeygenConfig::new(128).allow_insecure(InsecureDemo), move |progress| {
+            log.lock().unwrap().push(progress);
+        });
+        let (key, report) = handle.wait().unwrap();
+        assert_eq!(key.validate(), vec![]);
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.iter().map(|progress| progress.primes).max(), Some(2), "Both primes are reported");
+        assert_eq!(seen.iter().map(|progress| progress.candidates).max(), Some(report.candidates));
+
+        // Far too large to finish before the cancel arrives
+        let (started, signal) = std::sync::mpsc::channel();
+        let handle = generate_keypair_with_progress(KeygenConfig::new(8192).allow_insecure(InsecureDemo).with_workers(4), move |_| {
+            let _ = started.send(());
+        });
+        signal.recv().unwrap();
+        handle.cancel();
+        let started = Instant::now();
+        assert_eq!(handle.wait().map(|_| ()), Err(RabinError::Cancelled));
+        assert!(started.elapsed() < Duration::from_secs(10), "Workers should stop at their next candidate");
+    }
+
+    #[test]
+    fn test_safe_primes() {
+        let p = gen_safe_prime(&mut thread_rng(), 64, &SearchControl::new()).unwrap();
+        assert_eq!(p.bits(), 64);
+        assert!(is_three_mod_four(&p));
+        assert!(probably_prime(&p) && probably_prime(&(&p >> 1)), "(p - 1) / 2 should be prime");
+    }
+
+    #[test]
+    fn test_strong_prime_structure() {
+        let (p, r, s, t) =
+            gen_strong_prime_parts(&mut thread_rng(), 256, PrimeCongruence::ThreeModFour, &SearchControl::new()).unwrap();
+        assert_eq!(p.bits(), 256);
+        assert!(probably_prime(&p) && is_three_mod_four(&p));
+        assert!((&p - 1u8).is_multiple_of(&r), "r should divide p - 1");
+        assert!((&p + 1u8).is_multiple_of(&s), "s should divide p + 1");
+        assert!((&r - 1u8).is_multiple_of(&t), "t should divide r - 1");
+        assert!(r.bits() >= 100 && s.bits() >= 100, "The factors should be large");
+    }
+
+    #[test]
+    fn test_generate_with_config() {
+        let config = KeygenConfig::new(128).allow_insecure(InsecureDemo).with_primes(PrimeKind::Safe);
+        let (key, report) = PrivateKey::generate_with(&config).unwrap();
+        assert_eq!(key.validate(), vec![]);
+        assert_eq!(report.primes, PrimeKind::Safe);
+        assert!(report.candidates >= 2);
+    }
+
+    #[test]
+    fn test_worker_counts() {
+        for workers in [1, 2, 8] {
+            let config = KeygenConfig::new(64).allow_insecure(InsecureDemo).with_prime_count(4).with_workers(workers);
+            let (primes, report) = config.gen_distinct_primes().unwrap();
+            assert_eq!(primes.len(), 4);
+            assert!(primes.iter().all(|p| p.bits() == 64 && is_three_mod_four(p)));
+            for (i, p) in primes.iter().enumerate() {
+                assert!(!primes[..i].contains(p), "{} workers returned {} twice", workers, p);
+            }
+            assert!(report.candidates >= 4);
+        }
+    }
+
+    #[test]
+    fn test_any_congruence_keys_decrypt() {
+        // With a fixed seed this reliably includes primes ≡ 1 (mod 4)
+        let config = KeygenConfig::new(64).allow_insecure(InsecureDemo)
+            .with_congruence(PrimeCongruence::Any)
+            .with_rng(ChaCha20Rng::seed_from_u64(1));
+        let mut saw_one_mod_four = false;
+        for _ in 0..8 {
+            let (key, _) = PrivateKey::generate_with(&config).unwrap();
+            assert_eq!(key.validate_with(PrimeCongruence::Any), vec![]);
+            saw_one_mod_four |= key.p() % 4 == BigInt::one() || key.q() % 4 == BigInt::one();
+
+            let message = BigInt::from(0xC0FFEEu32);
+            let candidates = decrypt(&encrypt(&message, key.n()).unwrap(), key.p(), key.q()).unwrap();
+            assert!(candidates.contains(&message));
+        }
+        assert!(saw_one_mod_four, "Expected at least one prime ≡ 1 (mod 4)");
+    }
+
+    #[test]
+    fn test_entropy_sources() {
+        for source in [EntropySource::Thread, EntropySource::Os, EntropySource::Getrandom] {
+            assert_eq!(source.self_test(), Ok(()), "{:?} should pass the self-test", source);
+        }
+
+        // A seeded custom generator makes generation reproducible
+        let config = || KeygenConfig::new(64).allow_insecure(InsecureDemo).with_rng(ChaCha20Rng::seed_from_u64(7));
+        let (first, _) = PrivateKey::generate_with(&config()).unwrap();
+        let (second, _) = PrivateKey::generate_with(&config()).unwrap();
+        assert_eq!(first.n(), second.n());
+    }
+
+    #[test]
+    fn test_broken_rng_is_rejected() {
+        let config = KeygenConfig::new(64).allow_insecure(InsecureDemo).with_rng(StepRng::new(42, 0));
+        assert_eq!(
+            PrivateKey::generate_with(&config).unwrap_err(),
+            RabinError::RngFailure("repeated output")
+        );
+        assert_eq!(
+            check_rng(&mut StepRng::new(0, 1)),
+            Err(RabinError::RngFailure("biased output")),
+            "Distinct words pass the repeat check, but are mostly zero bits"
+        );
+
+        let mut sample = vec![0u8; SELF_TEST_BYTES];
+        ChaCha20Rng::seed_from_u64(1).fill_bytes(&mut sample);
+        assert!(!has_repeated_block(&sample) && passes_monobit(&sample));
+        sample.copy_within(..64, 1024);
+        assert!(has_repeated_block(&sample), "A replayed stretch of output is caught");
+        sample.iter_mut().step_by(5).for_each(|byte| *byte |= 0x0f);
+        assert!(!passes_monobit(&sample), "So is a source biased towards ones");
+    }
+}
@@ -0,0 +1,210 @@
+// A plain-text list of public keys in the spirit of OpenSSH's authorized_keys:
+//
+//     rabin-pk <base64 of n, big-endian> [comment]
+//
+// Blank lines and lines starting with '#' are ignored. The comment is free text (usually a
+// name or an email address) and may contain spaces.
+
+use crate::encoding::{bytes2num, num2bytes, ByteOrder};
+use crate::error::RabinError;
+use crate::fingerprint::Fingerprint;
+use crate::keys::PublicKey;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use num_traits::Zero;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+pub const KEY_TYPE: &str = "rabin-pk";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyringEntry {
+    pub key: PublicKey,
+    pub comment: String,
+}
+
+impl KeyringEntry {
+    pub fn new(key: PublicKey, comment: &str) -> Self {
+        KeyringEntry {
+            key,
+            comment: comment.trim().to_string(),
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        let n = num2bytes(self.key.n(), ByteOrder::BigEndian, None).expect("moduli are positive");
+        let mut line = format!("{} {}", KEY_TYPE, STANDARD.encode(n));
+        if !self.comment.is_empty() {
+            line.push(' ');
+            line.push_str(&self.comment);
+        }
+        line
+    }
+
+    pub fn from_line(line: &str) -> Result<Self, &'static str> {
+        let mut fields = line.trim().splitn(3, char::is_whitespace);
+        if fields.next() != Some(KEY_TYPE) {
+            return Err("expected the rabin-pk key type");
+        }
+        let encoded = fields.next().ok_or("missing key data")?;
+        let n = bytes2num(
+            &STANDARD.decode(encoded).map_err(|_| "invalid base64 key data")?,
+            ByteOrder::BigEndian,
+        );
+        if n.is_zero() {
+            return Err("modulus must be positive");
+        }
+        Ok(KeyringEntry::new(PublicKey::new(n), fields.next().unwrap_or("")))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Keyring {
+    entries: Vec<KeyringEntry>,
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Keyring::default()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, RabinError> {
+        let mut keyring = Keyring::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = KeyringEntry::from_line(line)
+                .map_err(|reason| RabinError::MalformedKeyring(number + 1, reason))?;
+            keyring.entries.push(entry);
+        }
+        Ok(keyring)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RabinError> {
+        Keyring::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), RabinError> {
+        Ok(fs::write(path, self.to_string())?)
+    }
+
+    // Adding a key that is already present only updates its comment
+    pub fn add(&mut self, key: PublicKey, comment: &str) {
+        let fingerprint = key.fingerprint();
+        match self.entries.iter_mut().find(|e| e.key.fingerprint() == fingerprint) {
+            Some(entry) => entry.comment = comment.trim().to_string(),
+            None => self.entries.push(KeyringEntry::new(key, comment)),
+        }
+    }
+
+    pub fn remove(&mut self, fingerprint: &Fingerprint) -> Option<KeyringEntry> {
+        let position = self.entries.iter().position(|e| e.key.fingerprint() == *fingerprint)?;
+        Some(self.entries.remove(position))
+    }
+
+    pub fn entries(&self) -> &[KeyringEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn find_by_fingerprint(&self, fingerprint: &Fingerprint) -> Option<&KeyringEntry> {
+        self.entries.iter().find(|e| e.key.fingerprint() == *fingerprint)
+    }
+
+    // Comments are not unique, so every match is returned
+    pub fn find_by_comment(&self, comment: &str) -> Vec<&KeyringEntry> {
+        self.entries.iter().filter(|e| e.comment == comment.trim()).collect()
+    }
+
+    // Resolves a user-supplied query: a full fingerprint, a short fingerprint, or a comment.
+    // Ambiguous queries resolve to nothing rather than to an arbitrary key.
+    pub fn find(&self, query: &str) -> Option<&KeyringEntry> {
+        if let Some(fingerprint) = Fingerprint::from_hex(query) {
+            return self.find_by_fingerprint(&fingerprint);
+        }
+        let by_short: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|e| e.key.fingerprint().short() == query)
+            .collect();
+        let matches = if by_short.is_empty() { self.find_by_comment(query) } else { by_short };
+        match matches.as_slice() {
+            [single] => Some(single),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry.to_line())?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigInt;
+
+    #[test]
+    fn test_line_format() {
+        let entry = KeyringEntry::new(PublicKey::new(BigInt::from(3233)), "alice@example.org");
+        assert_eq!(entry.to_line(), "rabin-pk DKE= alice@example.org");
+        assert_eq!(KeyringEntry::from_line(&entry.to_line()).unwrap(), entry);
+
+        // Comments may contain spaces; a missing comment is allowed
+        let spaced = KeyringEntry::from_line("rabin-pk DKE= Alice Liddell (laptop)").unwrap();
+        assert_eq!(spaced.comment, "Alice Liddell (laptop)");
+        assert_eq!(KeyringEntry::from_line("rabin-pk DKE=").unwrap().comment, "");
+    }
+
+    #[test]
+    fn test_parse_skips_comments_and_reports_bad_lines() {
+        let text = "# team keys\n\nrabin-pk DKE= alice\nrabin-pk TQ== bob\n";
+        let keyring = Keyring::parse(text).unwrap();
+        assert_eq!(keyring.len(), 2);
+        assert_eq!(Keyring::parse(&keyring.to_string()).unwrap(), keyring);
+
+        assert_eq!(
+            Keyring::parse("rabin-pk DKE= alice\nssh-rsa AAAA bob\n"),
+            Err(RabinError::MalformedKeyring(2, "expected the rabin-pk key type"))
+        );
+    }
+
+    #[test]
+    fn test_lookup_by_fingerprint_and_comment() {
+        let alice = PublicKey::new(BigInt::from(3233));
+        let bob = PublicKey::new(BigInt::from(77));
+        let mut keyring = Keyring::new();
+        keyring.add(alice.clone(), "alice");
+        keyring.add(bob.clone(), "bob");
+        keyring.add(bob.clone(), "bob (work)");
+        assert_eq!(keyring.len(), 2, "Re-adding a key should not duplicate it");
+
+        assert_eq!(keyring.find("alice").unwrap().key, alice);
+        assert_eq!(keyring.find(&bob.fingerprint().to_string()).unwrap().key, bob);
+        assert_eq!(keyring.find(&bob.fingerprint().short()).unwrap().comment, "bob (work)");
+        assert!(keyring.find("carol").is_none());
+
+        keyring.add(PublicKey::new(BigInt::from(221)), "alice");
+        assert!(keyring.find("alice").is_none(), "An ambiguous comment should not match");
+        assert_eq!(keyring.find_by_comment("alice").len(), 2);
+
+        assert!(keyring.remove(&alice.fingerprint()).is_some());
+        assert_eq!(keyring.find_by_comment("alice").len(), 1);
+    }
+}
@@ -0,0 +1,506 @@
+use crate::barrett::Barrett;
+use crate::der::{encode_integer, encode_sequence, DerReader};
+use crate::error::RabinError;
+use crate::metadata::{KeyMetadata, KeyUsage};
+use crate::pem;
+use log::warn;
+use crate::rabin::{
+    compute_candidates, compute_candidates_with, decrypt_blinded, encrypt, generate_keypair,
+    generate_keypair_from_seed, DecryptionParams,
+};
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use rand::rngs::OsRng;
+
+pub const PUBLIC_KEY_PEM_LABEL: &str = "RABIN PUBLIC KEY";
+
+// Moduli shorter than this are demo keys: fine for exercises and tests, within reach of
+// factoring in practice. Encrypting, decrypting or signing with one through the key API is
+// refused unless the caller opts in with InsecureDemo.
+pub const MIN_SECURE_BITS: u64 = 2048;
+
+// The opt-in for demo keys. It is a property of the caller, not of the key, so it is never
+// serialized: a demo key read back from disk has to be allowed again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InsecureDemo;
+
+// Two 1024-bit primes can multiply to a 2047-bit n, so one bit of slack is allowed
+fn is_demo_modulus(n: &BigInt) -> bool {
+    n.bits() + 1 < MIN_SECURE_BITS
+}
+
+// Demo keys are refused without the opt-in, and every allowed use of one is logged
+fn check_modulus_size(n: &BigInt, insecure: Option<InsecureDemo>) -> Result<(), RabinError> {
+    if !is_demo_modulus(n) {
+        return Ok(());
+    }
+    if insecure.is_none() {
+        return Err(RabinError::InsecureKey(n.bits()));
+    }
+    warn!("Using an insecure {}-bit demo key", n.bits());
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct PublicKey {
+    n: BigInt,
+    metadata: Option<KeyMetadata>,
+    insecure: Option<InsecureDemo>,
+}
+
+// The demo opt-in belongs to the caller, so it does not take part in comparisons
+impl PartialEq for PublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.n == other.n && self.metadata == other.metadata
+    }
+}
+
+impl Eq for PublicKey {}
+
+// Appends the metadata SEQUENCE, when there is one, to a key structure's fields
+fn with_metadata_field(mut fields: Vec<Vec<u8>>, metadata: &Option<KeyMetadata>) -> Vec<u8> {
+    if let Some(metadata) = metadata {
+        fields.push(metadata.to_der());
+    }
+    encode_sequence(&fields)
+}
+
+impl PublicKey {
+    pub fn new(n: BigInt) -> Self {
+        PublicKey {
+            n,
+            metadata: None,
+            insecure: None,
+        }
+    }
+
+    pub fn n(&self) -> &BigInt {
+        &self.n
+    }
+
+    pub fn metadata(&self) -> Option<&KeyMetadata> {
+        self.metadata.as_ref()
+    }
+
+    pub fn with_metadata(mut self, metadata: KeyMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    // Shorter than MIN_SECURE_BITS, give or take the bit a product of two primes can lose
+    pub fn is_demo(&self) -> bool {
+        is_demo_modulus(&self.n)
+    }
+
+    // Lets a demo key be used; has no effect on full-size keys
+    pub fn allow_insecure(mut self, opt_in: InsecureDemo) -> Self {
+        self.insecure = Some(opt_in);
+        self
+    }
+
+    pub(crate) fn check_size(&self) -> Result<(), RabinError> {
+        check_modulus_size(&self.n, self.insecure)
+    }
+
+    // rabin::encrypt under this key, refused if its metadata does not allow encryption
+    pub fn encrypt(&self, message: &BigInt) -> Result<BigInt, RabinError> {
+        self.check_usage(KeyUsage::Encrypt)?;
+        encrypt(message, &self.n)
+    }
+
+    // RabinPublicKey ::= SEQUENCE { n INTEGER, metadata KeyMetadata OPTIONAL }
+    pub fn to_der(&self) -> Vec<u8> {
+        with_metadata_field(vec![encode_integer(&self.n)], &self.metadata)
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, RabinError> {
+        let mut outer = DerReader::new(der);
+        let mut seq = outer.read_sequence()?;
+        outer.finish()?;
+        let n = seq.read_integer()?;
+        let metadata = read_optional_metadata(&mut seq)?;
+        seq.finish()?;
+
+        if n <= BigInt::zero() {
+            return Err(RabinError::InvalidKey("modulus must be positive"));
+        }
+        Ok(PublicKey {
+            n,
+            metadata,
+            insecure: None,
+        })
+    }
+
+    // Reduction context for encrypting many messages under this key; build it once and reuse
+    pub fn barrett(&self) -> Result<Barrett, RabinError> {
+        if self.n.sign() != Sign::Plus {
+            return Err(RabinError::InvalidKey("modulus must be positive"));
+        }
+        Ok(Barrett::new(self.n.magnitude()).expect("positive modulus"))
+    }
+
+    pub fn to_pem(&self) -> String {
+        pem::encode(PUBLIC_KEY_PEM_LABEL, &self.to_der())
+    }
+
+    pub fn from_pem(pem_text: &str) -> Result<Self, RabinError> {
+        PublicKey::from_der(&pem::decode(PUBLIC_KEY_PEM_LABEL, pem_text)?)
+    }
+}
+
+fn read_optional_metadata(seq: &mut DerReader) -> Result<Option<KeyMetadata>, RabinError> {
+    if seq.is_empty() {
+        Ok(None)
+    } else {
+        KeyMetadata::read_der(seq).map(Some)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrivateKey {
+    n: BigInt,
+    p: BigInt,
+    q: BigInt,
+    metadata: Option<KeyMetadata>,
+    insecure: Option<InsecureDemo>,
+    // Filled in when the key is created; keys read back from DER compute it per decryption
+    // unless precompute() is called
+    params: Option<DecryptionParams>,
+}
+
+// The cached parameters follow from p and q, so they do not take part in comparisons
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.n == other.n && self.p == other.p && self.q == other.q && self.metadata == other.metadata
+    }
+}
+
+impl Eq for PrivateKey {}
+
+impl PrivateKey {
+    // A key with fresh decryption parameters; primes sharing a factor leave them empty, and
+    // decryption then reports the problem
+    fn from_parts(n: BigInt, p: BigInt, q: BigInt) -> Self {
+        PrivateKey {
+            n,
+            p,
+            q,
+            metadata: None,
+            insecure: None,
+            params: None,
+        }
+        .precompute()
+    }
+
+    pub fn generate(bit_size: usize) -> Self {
+        let (n, p, q) = generate_keypair(bit_size);
+        PrivateKey::from_parts(n, p, q)
+    }
+
+    // Regenerates the same key for the same seed and bit size
+    pub fn from_seed(seed: &[u8], bit_size: usize) -> Self {
+        let (n, p, q) = generate_keypair_from_seed(seed, bit_size);
+        PrivateKey::from_parts(n, p, q)
+    }
+
+    // Builds a key from its two primes, e.g. the ones handed out with an exercise.
+    // Only the shape is checked here; use validate() to test primality.
+    pub fn from_primes(p: BigInt, q: BigInt) -> Result<Self, RabinError> {
+        if p <= BigInt::one() || q <= BigInt::one() {
+            return Err(RabinError::InvalidKey("primes must be greater than 1"));
+        }
+        let n = &p * &q;
+        Ok(PrivateKey::from_parts(n, p, q))
+    }
+
+    // Builds a key from the public modulus and one of its factors
+    pub fn from_modulus_and_prime(n: BigInt, p: BigInt) -> Result<Self, RabinError> {
+        if p <= BigInt::one() || p >= n {
+            return Err(RabinError::InvalidKey("prime must lie strictly between 1 and n"));
+        }
+        let (q, remainder) = n.div_rem(&p);
+        if !remainder.is_zero() {
+            return Err(RabinError::InvalidKey("prime does not divide the modulus"));
+        }
+        Ok(PrivateKey::from_parts(n, p, q))
+    }
+
+    pub fn n(&self) -> &BigInt {
+        &self.n
+    }
+
+    pub fn p(&self) -> &BigInt {
+        &self.p
+    }
+
+    pub fn q(&self) -> &BigInt {
+        &self.q
+    }
+
+    pub fn metadata(&self) -> Option<&KeyMetadata> {
+        self.metadata.as_ref()
+    }
+
+    pub fn with_metadata(mut self, metadata: KeyMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    pub fn is_demo(&self) -> bool {
+        is_demo_modulus(&self.n)
+    }
+
+    pub fn allow_insecure(mut self, opt_in: InsecureDemo) -> Self {
+        self.insecure = Some(opt_in);
+        self
+    }
+
+    pub(crate) fn check_size(&self) -> Result<(), RabinError> {
+        check_modulus_size(&self.n, self.insecure)
+    }
+
+    pub(crate) fn insecure(&self) -> Option<InsecureDemo> {
+        self.insecure
+    }
+
+    // Computes and caches the per-key decryption parameters, if they are not cached yet
+    pub fn precompute(mut self) -> Self {
+        if self.params.is_none() {
+            self.params = DecryptionParams::new(&self.p, &self.q).ok();
+        }
+        self
+    }
+
+    pub fn is_precomputed(&self) -> bool {
+        self.params.is_some()
+    }
+
+    // All four square roots of the ciphertext, refused if the key may not decrypt
+    pub fn decrypt(&self, ciphertext: &BigInt) -> Result<Vec<BigInt>, RabinError> {
+        self.check_usage(KeyUsage::Encrypt)?;
+        self.square_roots(ciphertext)
+    }
+
+    // decrypt without the usage check, using the cached parameters when present. Signing takes
+    // the same roots, and callers that already checked the key use it to avoid doing so twice.
+    pub(crate) fn square_roots(&self, value: &BigInt) -> Result<Vec<BigInt>, RabinError> {
+        match &self.params {
+            Some(params) => compute_candidates_with(value, &self.p, &self.q, &self.n, params),
+            None => compute_candidates(value, &self.p, &self.q, &self.n),
+        }
+    }
+
+    // decrypt with the ciphertext blinded by a random square first; see rabin::decrypt_blinded
+    pub fn decrypt_blinded(&self, ciphertext: &BigInt) -> Result<Vec<BigInt>, RabinError> {
+        self.check_usage(KeyUsage::Encrypt)?;
+        let computed;
+        let params = match &self.params {
+            Some(params) => params,
+            None => {
+                computed = DecryptionParams::new(&self.p, &self.q)?;
+                &computed
+            }
+        };
+        decrypt_blinded(&mut OsRng, ciphertext, &self.p, &self.q, params)
+    }
+
+    // The public half carries the same metadata and demo opt-in
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey {
+            n: self.n.clone(),
+            metadata: self.metadata.clone(),
+            insecure: self.insecure,
+        }
+    }
+
+    // RabinPrivateKey ::= SEQUENCE {
+    //     version INTEGER (0), n INTEGER, p INTEGER, q INTEGER, metadata KeyMetadata OPTIONAL
+    // }
+    pub fn to_der(&self) -> Vec<u8> {
+        let fields = vec![
+            encode_integer(&BigInt::zero()),
+            encode_integer(&self.n),
+            encode_integer(&self.p),
+            encode_integer(&self.q),
+        ];
+        with_metadata_field(fields, &self.metadata)
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, RabinError> {
+        let mut outer = DerReader::new(der);
+        let mut seq = outer.read_sequence()?;
+        outer.finish()?;
+
+        if !seq.read_integer()?.is_zero() {
+            return Err(RabinError::UnsupportedVersion);
+        }
+        let n = seq.read_integer()?;
+        let p = seq.read_integer()?;
+        let q = seq.read_integer()?;
+        let metadata = read_optional_metadata(&mut seq)?;
+        seq.finish()?;
+
+        check_factors(&n, &p, &q)?;
+        Ok(PrivateKey {
+            n,
+            p,
+            q,
+            metadata,
+            insecure: None,
+            params: None,
+        })
+    }
+}
+
+// The shape of a key read from outside: two distinct odd factors above 1 whose product is n,
+// which rules out a zero or negative modulus. Primality is left to validate(), and so is the
+// p ≡ q ≡ 3 (mod 4) rule, since keys made with --any-congruence are stored in this form too.
+fn check_factors(n: &BigInt, p: &BigInt, q: &BigInt) -> Result<(), RabinError> {
+    if *p <= BigInt::one() || *q <= BigInt::one() {
+        return Err(RabinError::InvalidKey("primes must be greater than 1"));
+    }
+    if p.is_even() || q.is_even() {
+        return Err(RabinError::InvalidKey("primes must be odd"));
+    }
+    if p == q {
+        return Err(RabinError::InvalidKey("p and q must be distinct"));
+    }
+    if &(p * q) != n {
+        return Err(RabinError::InvalidKey("modulus does not equal p * q"));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_key_der_round_trip() {
+        let key = PublicKey::new(BigInt::from(3233));
+        assert_eq!(key.to_der(), vec![0x30, 0x04, 0x02, 0x02, 0x0c, 0xa1]);
+        assert_eq!(PublicKey::from_der(&key.to_der()).unwrap(), key);
+    }
+
+    #[test]
+    fn test_private_key_der_round_trip() {
+        let key = PrivateKey::generate(256);
+        let der = key.to_der();
+        let decoded = PrivateKey::from_der(&der).expect("Failed to decode private key");
+        assert_eq!(decoded, key, "DER round-trip should preserve the private key");
+    }
+
+    #[test]
+    fn test_loaded_key_decrypts_without_cached_parameters() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        assert!(key.is_precomputed(), "Generated keys cache their decryption parameters");
+        let loaded = PrivateKey::from_der(&key.to_der()).unwrap().allow_insecure(InsecureDemo);
+        assert!(!loaded.is_precomputed());
+
+        let ciphertext = crate::rabin::encrypt(&BigInt::from(123_456_789), key.n()).unwrap();
+        let mut expected = key.decrypt(&ciphertext).unwrap();
+        let mut actual = loaded.decrypt(&ciphertext).unwrap();
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected, "Both paths should find the same four roots");
+
+        let loaded = loaded.precompute();
+        assert!(loaded.is_precomputed());
+        assert!(loaded.decrypt(&ciphertext).unwrap().contains(&BigInt::from(123_456_789)));
+        assert!(loaded.decrypt_blinded(&ciphertext).unwrap().contains(&BigInt::from(123_456_789)));
+    }
+
+    #[test]
+    fn test_barrett_context_encrypts_like_rabin() {
+        let key = PrivateKey::generate(256).public_key();
+        let context = key.barrett().unwrap();
+        let message = BigInt::from(987_654_321u64);
+        assert_eq!(context.encrypt(&message), crate::rabin::encrypt(&message, key.n()).unwrap());
+        assert!(PublicKey::new(BigInt::zero()).barrett().is_err());
+    }
+
+    #[test]
+    fn test_public_key_from_private_key() {
+        let key = PrivateKey::from_primes(BigInt::from(7), BigInt::from(11)).unwrap();
+        assert_eq!(key.public_key(), PublicKey::new(BigInt::from(77)));
+    }
+
+    #[test]
+    fn test_private_key_from_partial_components() {
+        let from_primes = PrivateKey::from_primes(BigInt::from(7), BigInt::from(11)).unwrap();
+        assert_eq!(from_primes.n(), &BigInt::from(77));
+
+        let from_modulus = PrivateKey::from_modulus_and_prime(BigInt::from(77), BigInt::from(7)).unwrap();
+        assert_eq!(from_modulus, from_primes);
+
+        assert_eq!(
+            PrivateKey::from_modulus_and_prime(BigInt::from(77), BigInt::from(5)),
+            Err(RabinError::InvalidKey("prime does not divide the modulus"))
+        );
+        assert!(PrivateKey::from_modulus_and_prime(BigInt::from(77), BigInt::from(77)).is_err());
+        assert!(PrivateKey::from_primes(BigInt::one(), BigInt::from(11)).is_err());
+    }
+
+    #[test]
+    fn test_private_key_der_rejects_inconsistent_modulus() {
+        let der = encode_sequence(&[
+            encode_integer(&BigInt::zero()),
+            encode_integer(&BigInt::from(22)),
+            encode_integer(&BigInt::from(3)),
+            encode_integer(&BigInt::from(7)),
+        ]);
+        assert_eq!(
+            PrivateKey::from_der(&der),
+            Err(RabinError::InvalidKey("modulus does not equal p * q"))
+        );
+    }
+
+    #[test]
+    fn test_private_key_der_rejects_degenerate_factors() {
+        let der = |n: i64, p: i64, q: i64| {
+            encode_sequence(&[
+                encode_integer(&BigInt::zero()),
+                encode_integer(&BigInt::from(n)),
+                encode_integer(&BigInt::from(p)),
+                encode_integer(&BigInt::from(q)),
+            ])
+        };
+        let too_small = Err(RabinError::InvalidKey("primes must be greater than 1"));
+        assert_eq!(PrivateKey::from_der(&der(77, -7, -11)), too_small, "Negative factors");
+        assert_eq!(PrivateKey::from_der(&der(77, 1, 77)), too_small, "Trivial factors");
+        assert_eq!(PrivateKey::from_der(&der(0, 0, 7)), too_small, "Zero modulus");
+        assert_eq!(PrivateKey::from_der(&der(-77, -7, 11)), too_small, "Negative modulus");
+        assert_eq!(PrivateKey::from_der(&der(22, 2, 11)), Err(RabinError::InvalidKey("primes must be odd")));
+        assert_eq!(PrivateKey::from_der(&der(49, 7, 7)), Err(RabinError::InvalidKey("p and q must be distinct")));
+        assert!(PrivateKey::from_der(&der(77, 7, 11)).is_ok());
+    }
+
+    #[test]
+    fn test_demo_keys_need_the_opt_in() {
+        use crate::keygen::KeygenConfig;
+        use crate::metadata::KeyUsage;
+
+        let key = PrivateKey::generate(256);
+        assert!(key.is_demo() && key.public_key().is_demo());
+        assert_eq!(key.check_usage(KeyUsage::Encrypt), Err(RabinError::InsecureKey(key.n().bits())));
+        assert_eq!(key.public_key().check_usage(KeyUsage::Encrypt), Err(RabinError::InsecureKey(key.n().bits())));
+
+        let allowed = key.clone().allow_insecure(InsecureDemo);
+        assert_eq!(allowed.check_usage(KeyUsage::Sign), Ok(()));
+        assert_eq!(allowed.public_key().check_usage(KeyUsage::Encrypt), Ok(()), "The opt-in carries over");
+        assert_eq!(allowed, key, "The opt-in is not part of the key");
+        let reloaded = PrivateKey::from_der(&allowed.to_der()).unwrap();
+        assert!(reloaded.check_usage(KeyUsage::Encrypt).is_err(), "Nor is it serialized");
+
+        assert_eq!(
+            PrivateKey::generate_with(&KeygenConfig::new(128)).map(|_| ()),
+            Err(RabinError::InsecureKey(256))
+        );
+        let (key, report) = PrivateKey::generate_with(&KeygenConfig::new(128).allow_insecure(InsecureDemo)).unwrap();
+        assert!(report.demo && report.to_string().ends_with("(insecure demo key)"));
+        assert_eq!(key.check_usage(KeyUsage::Encrypt), Ok(()));
+        assert!(!KeygenConfig::new(1024).is_demo());
+    }
+}
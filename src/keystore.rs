@@ -0,0 +1,310 @@
+use crate::error::RabinError;
+use crate::fingerprint::Fingerprint;
+use crate::keygen::{KeygenConfig, KeygenReport};
+use crate::keys::{PrivateKey, PublicKey};
+use crate::metadata::KeyMetadata;
+use log::info;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Layout under the keystore root:
+//   keys/<name>.pub   public key, PEM
+//   keys/<name>.priv  private key, PKCS#8 PEM
+//   default           name of the default key
+const KEYS_DIR: &str = "keys";
+const DEFAULT_FILE: &str = "default";
+const PUBLIC_EXT: &str = "pub";
+const PRIVATE_EXT: &str = "priv";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyEntry {
+    pub name: String,
+    pub fingerprint: Fingerprint,
+    pub has_private: bool,
+    pub is_default: bool,
+    pub metadata: Option<KeyMetadata>,
+    // The modulus is shorter than MIN_SECURE_BITS
+    pub demo: bool,
+}
+
+pub struct Keystore {
+    root: PathBuf,
+}
+
+fn validate_name(name: &str) -> Result<(), RabinError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if valid {
+        Ok(())
+    } else {
+        Err(RabinError::InvalidKeyName(name.to_string()))
+    }
+}
+
+fn write_private_file(path: &Path, contents: &str) -> Result<(), RabinError> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    // Private keys are only readable by the owner
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+impl Keystore {
+    // $RABIN_HOME if set, otherwise ~/.rabin
+    pub fn default_root() -> Option<PathBuf> {
+        if let Some(home) = std::env::var_os("RABIN_HOME") {
+            return Some(PathBuf::from(home));
+        }
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".rabin"))
+    }
+
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, RabinError> {
+        let root = root.into();
+        fs::create_dir_all(root.join(KEYS_DIR))?;
+        Ok(Keystore { root })
+    }
+
+    pub fn open_default() -> Result<Self, RabinError> {
+        let root = Keystore::default_root()
+            .ok_or_else(|| RabinError::Io("cannot locate home directory".to_string()))?;
+        Keystore::open(root)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn key_path(&self, name: &str, ext: &str) -> PathBuf {
+        self.root.join(KEYS_DIR).join(format!("{}.{}", name, ext))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.key_path(name, PUBLIC_EXT).exists()
+    }
+
+    // New keys record their creation time and size; see create_with_metadata for more
+    pub fn create(&self, name: &str, bit_size: usize) -> Result<PrivateKey, RabinError> {
+        self.create_with_metadata(name, bit_size, KeyMetadata::new(Some(bit_size)))
+    }
+
+    pub fn create_with_metadata(
+        &self,
+        name: &str,
+        bit_size: usize,
+        metadata: KeyMetadata,
+    ) -> Result<PrivateKey, RabinError> {
+        let (key, _) = self.create_with_config(name, &KeygenConfig::new(bit_size), metadata)?;
+        Ok(key)
+    }
+
+    // The name is checked before generating, since structured primes can take a while
+    pub fn create_with_config(
+        &self,
+        name: &str,
+        config: &KeygenConfig,
+        metadata: KeyMetadata,
+    ) -> Result<(PrivateKey, KeygenReport), RabinError> {
+        validate_name(name)?;
+        if self.contains(name) {
+            return Err(RabinError::KeyExists(name.to_string()));
+        }
+        let (key, report) = PrivateKey::generate_with(config)?;
+        let key = key.with_metadata(metadata);
+        self.import_private(name, &key)?;
+        Ok((key, report))
+    }
+
+    pub fn import_private(&self, name: &str, key: &PrivateKey) -> Result<(), RabinError> {
+        validate_name(name)?;
+        if self.contains(name) {
+            return Err(RabinError::KeyExists(name.to_string()));
+        }
+        // Write the private half first so a failure never leaves a public key without it
+        write_private_file(&self.key_path(name, PRIVATE_EXT), &key.to_pkcs8_pem())?;
+        let public = key.public_key();
+        fs::write(self.key_path(name, PUBLIC_EXT), public.to_pem())?;
+        info!("Stored key '{}' ({})", name, public.fingerprint());
+        Ok(())
+    }
+
+    pub fn import_public(&self, name: &str, key: &PublicKey) -> Result<(), RabinError> {
+        validate_name(name)?;
+        if self.contains(name) {
+            return Err(RabinError::KeyExists(name.to_string()));
+        }
+        fs::write(self.key_path(name, PUBLIC_EXT), key.to_pem())?;
+        info!("Stored public key '{}' ({})", name, key.fingerprint());
+        Ok(())
+    }
+
+    pub fn load_public(&self, name: &str) -> Result<PublicKey, RabinError> {
+        validate_name(name)?;
+        let path = self.key_path(name, PUBLIC_EXT);
+        if !path.exists() {
+            return Err(RabinError::KeyNotFound(name.to_string()));
+        }
+        PublicKey::from_pem(&fs::read_to_string(path)?)
+    }
+
+    pub fn load_private(&self, name: &str) -> Result<PrivateKey, RabinError> {
+        validate_name(name)?;
+        let path = self.key_path(name, PRIVATE_EXT);
+        if !path.exists() {
+            return Err(RabinError::KeyNotFound(name.to_string()));
+        }
+        PrivateKey::from_pkcs8_pem(&fs::read_to_string(path)?)
+    }
+
+    pub fn list(&self) -> Result<Vec<KeyEntry>, RabinError> {
+        let default = self.default_name()?;
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(self.root.join(KEYS_DIR))? {
+            let path = dir_entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(PUBLIC_EXT) {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let public = PublicKey::from_pem(&fs::read_to_string(&path)?)?;
+            entries.push(KeyEntry {
+                name: name.to_string(),
+                fingerprint: public.fingerprint(),
+                has_private: self.key_path(name, PRIVATE_EXT).exists(),
+                is_default: default.as_deref() == Some(name),
+                metadata: public.metadata().cloned(),
+                demo: public.is_demo(),
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), RabinError> {
+        validate_name(name)?;
+        if !self.contains(name) {
+            return Err(RabinError::KeyNotFound(name.to_string()));
+        }
+        let private = self.key_path(name, PRIVATE_EXT);
+        if private.exists() {
+            fs::remove_file(private)?;
+        }
+        fs::remove_file(self.key_path(name, PUBLIC_EXT))?;
+
+        if self.default_name()?.as_deref() == Some(name) {
+            fs::remove_file(self.root.join(DEFAULT_FILE))?;
+        }
+        info!("Deleted key '{}'", name);
+        Ok(())
+    }
+
+    pub fn set_default(&self, name: &str) -> Result<(), RabinError> {
+        validate_name(name)?;
+        if !self.contains(name) {
+            return Err(RabinError::KeyNotFound(name.to_string()));
+        }
+        fs::write(self.root.join(DEFAULT_FILE), name)?;
+        Ok(())
+    }
+
+    pub fn default_name(&self) -> Result<Option<String>, RabinError> {
+        let path = self.root.join(DEFAULT_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let name = fs::read_to_string(path)?.trim().to_string();
+        Ok(if name.is_empty() { None } else { Some(name) })
+    }
+
+    // Resolves a `--key` argument: an explicit name, or the default key when none is given
+    pub fn resolve_name(&self, name: Option<&str>) -> Result<String, RabinError> {
+        match name {
+            Some(name) => Ok(name.to_string()),
+            None => self
+                .default_name()?
+                .ok_or_else(|| RabinError::KeyNotFound("<default>".to_string())),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+
+    fn temp_keystore(label: &str) -> Keystore {
+        let root = std::env::temp_dir().join(format!("rabin-keystore-{}-{}", label, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        Keystore::open(root).unwrap()
+    }
+
+    fn create_demo(store: &Keystore, name: &str) -> Result<PrivateKey, RabinError> {
+        let config = KeygenConfig::new(256).allow_insecure(InsecureDemo);
+        let (key, _) = store.create_with_config(name, &config, KeyMetadata::new(Some(256)))?;
+        Ok(key)
+    }
+
+    #[test]
+    fn test_create_list_load_delete() {
+        let store = temp_keystore("lifecycle");
+        let key = create_demo(&store, "alice").expect("Failed to create key");
+
+        let entries = store.list().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "alice");
+        assert!(entries[0].has_private);
+        assert!(entries[0].demo);
+        assert_eq!(entries[0].fingerprint, key.public_key().fingerprint());
+
+        assert_eq!(store.load_private("alice").unwrap(), key);
+        assert_eq!(store.load_public("alice").unwrap().n(), key.n());
+
+        store.delete("alice").unwrap();
+        assert!(store.list().unwrap().is_empty());
+        assert_eq!(store.load_public("alice"), Err(RabinError::KeyNotFound("alice".to_string())));
+
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn test_default_key() {
+        let store = temp_keystore("default");
+        create_demo(&store, "bob").unwrap();
+        create_demo(&store, "carol").unwrap();
+
+        assert_eq!(store.default_name().unwrap(), None);
+        store.set_default("carol").unwrap();
+        assert_eq!(store.resolve_name(None).unwrap(), "carol");
+        assert_eq!(store.resolve_name(Some("bob")).unwrap(), "bob");
+        assert!(store.list().unwrap().iter().any(|e| e.name == "carol" && e.is_default));
+
+        // Deleting the default key clears the default
+        store.delete("carol").unwrap();
+        assert_eq!(store.default_name().unwrap(), None);
+
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_duplicate_and_invalid_names() {
+        let store = temp_keystore("names");
+        create_demo(&store, "dave").unwrap();
+        assert_eq!(create_demo(&store, "dave"), Err(RabinError::KeyExists("dave".to_string())));
+        assert_eq!(
+            create_demo(&store, "../escape"),
+            Err(RabinError::InvalidKeyName("../escape".to_string()))
+        );
+        fs::remove_dir_all(store.root()).unwrap();
+    }
+}
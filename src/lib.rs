@@ -0,0 +1,66 @@
+pub mod aead;
+#[cfg(feature = "age")]
+pub mod age;
+pub mod barrett;
+pub mod bbs;
+#[cfg(any(feature = "wasm", feature = "ffi", feature = "uniffi"))]
+mod bindings;
+pub mod blind;
+pub mod blocks;
+pub mod compat;
+pub mod ct;
+pub mod der;
+pub mod encoding;
+pub mod envelope;
+pub mod error;
+pub mod ffs;
+pub mod fiat_shamir;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fingerprint;
+#[cfg(feature = "fast-math")]
+pub mod gmp;
+pub mod goldwasser_micali;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod hash;
+pub mod kdf;
+pub mod kem;
+pub mod keygen;
+pub mod keyring;
+pub mod keys;
+pub mod keystore;
+pub mod locked;
+pub mod math;
+pub mod metadata;
+pub mod mnemonic;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+pub mod montgomery;
+pub mod multiprime;
+pub mod pem;
+pub mod pkcs8;
+pub mod power;
+pub mod primality;
+#[cfg(feature = "qr")]
+pub mod qr;
+pub mod rabin;
+pub mod rabin_williams;
+pub mod redact;
+pub mod residue;
+#[cfg(feature = "rustcrypto")]
+pub mod rustcrypto;
+pub mod seal;
+pub mod secret;
+pub mod shamir;
+pub mod signature;
+pub mod stream;
+pub mod threshold;
+pub mod trapdoor;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+// The UniFFI component for src/mobile.rs
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!("rabin");
@@ -0,0 +1,151 @@
+// Private keys kept in page-locked memory, so the operating system never writes them to swap or
+// to a hibernation image. Locking needs the locked-memory feature and an OS limit that allows
+// it (RLIMIT_MEMLOCK on Linux, the working set quota on Windows). Where either is missing the
+// key is still held and usable, and status() says why it is not locked.
+//
+// The key is stored as its DER encoding, in pages no other allocation shares: unlocking on
+// drop then cannot unlock someone else's data, since locks do not nest. with_key decodes it
+// into ordinary memory for the duration of one call.
+
+use crate::error::RabinError;
+use crate::keys::{InsecureDemo, PrivateKey};
+use crate::secret::Wipe;
+use log::warn;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockStatus {
+    Locked,
+    // Built without the locked-memory feature
+    Disabled,
+    // The operating system refused, typically because the lock limit is too low
+    Failed(String),
+}
+
+impl fmt::Display for LockStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockStatus::Locked => write!(f, "locked in memory"),
+            LockStatus::Disabled => write!(f, "not locked: built without the locked-memory feature"),
+            LockStatus::Failed(reason) => write!(f, "not locked: {}", reason),
+        }
+    }
+}
+
+pub struct LockedKey {
+    buffer: Vec<u8>,
+    // Where the DER sits inside buffer, starting on a page boundary
+    range: Range<usize>,
+    status: LockStatus,
+    // Not part of the DER, so kept alongside it
+    insecure: Option<InsecureDemo>,
+    #[cfg(feature = "locked-memory")]
+    _guard: Option<region::LockGuard>,
+}
+
+#[cfg(feature = "locked-memory")]
+fn page_size() -> usize {
+    region::page::size()
+}
+
+// Only used for the layout when nothing is locked
+#[cfg(not(feature = "locked-memory"))]
+fn page_size() -> usize {
+    4096
+}
+
+impl LockedKey {
+    pub fn new(key: &PrivateKey) -> Self {
+        let mut der = key.to_der();
+        let page = page_size();
+        let pages = der.len().div_ceil(page).max(1) * page;
+        // One spare page on each side, so the locked pages hold nothing but the key
+        let mut buffer = vec![0u8; pages + 2 * page];
+        let start = page - buffer.as_ptr() as usize % page;
+        let range = start..start + der.len();
+        buffer[range.clone()].copy_from_slice(&der);
+        der.wipe();
+
+        #[cfg(feature = "locked-memory")]
+        let (status, guard) = match region::lock(buffer[start..].as_ptr(), pages) {
+            Ok(guard) => (LockStatus::Locked, Some(guard)),
+            Err(err) => (LockStatus::Failed(err.to_string()), None),
+        };
+        #[cfg(not(feature = "locked-memory"))]
+        let status = LockStatus::Disabled;
+        if let LockStatus::Failed(reason) = &status {
+            warn!("Private key could not be locked in memory: {}", reason);
+        }
+
+        LockedKey {
+            buffer,
+            range,
+            status,
+            insecure: key.insecure(),
+            #[cfg(feature = "locked-memory")]
+            _guard: guard,
+        }
+    }
+
+    pub fn status(&self) -> &LockStatus {
+        &self.status
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.status == LockStatus::Locked
+    }
+
+    // Runs `f` on the decoded key. The decoded copy is ordinary memory and lives only until
+    // `f` returns; it has no cached decryption parameters.
+    pub fn with_key<T>(&self, f: impl FnOnce(&PrivateKey) -> T) -> Result<T, RabinError> {
+        let mut key = PrivateKey::from_der(&self.buffer[self.range.clone()])?;
+        if let Some(opt_in) = self.insecure {
+            key = key.allow_insecure(opt_in);
+        }
+        Ok(f(&key))
+    }
+}
+
+// The key bytes are wiped before the pages are unlocked and freed. Fields drop in declaration
+// order after this, so the guard is released here rather than after buffer is freed.
+impl Drop for LockedKey {
+    fn drop(&mut self) {
+        self.buffer.wipe();
+        #[cfg(feature = "locked-memory")]
+        drop(self._guard.take());
+    }
+}
+
+// Never prints the key
+impl fmt::Debug for LockedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LockedKey").field("status", &self.status).finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rabin::encrypt;
+    use num_bigint::BigInt;
+
+    #[test]
+    fn test_locked_key_decrypts() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let locked = LockedKey::new(&key);
+        if cfg!(feature = "locked-memory") {
+            assert_ne!(locked.status(), &LockStatus::Disabled);
+        } else {
+            assert_eq!(locked.status(), &LockStatus::Disabled);
+        }
+        assert_eq!(locked.is_locked(), locked.status() == &LockStatus::Locked);
+
+        let message = BigInt::from(0x10c4ed_u32);
+        let ciphertext = encrypt(&message, key.n()).unwrap();
+        let candidates = locked.with_key(|key| key.decrypt(&ciphertext)).unwrap().unwrap();
+        assert!(candidates.contains(&message));
+        assert!(locked.with_key(|inner| inner == &key).unwrap());
+        assert!(!format!("{:?}", locked).contains(&key.p().to_string()));
+    }
+}
@@ -0,0 +1,302 @@
+// Number-theoretic helpers shared by key generation, decryption and the protocol modules.
+// Everything here is iterative, so no input can exhaust the stack.
+
+use crate::error::RabinError;
+use crate::montgomery::modpow;
+use num_bigint::{BigInt, BigUint};
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+
+// Euclid's algorithm. The result is always non-negative.
+pub fn gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while !b.is_zero() {
+        let remainder = &a % &b;
+        a = std::mem::replace(&mut b, remainder);
+    }
+    a.abs()
+}
+
+// Stein's algorithm: only shifts and subtractions, no division
+pub fn binary_gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    let (mut a, mut b): (BigUint, BigUint) = (a.magnitude().clone(), b.magnitude().clone());
+    if a.is_zero() {
+        return b.into();
+    }
+    if b.is_zero() {
+        return a.into();
+    }
+
+    // Both are non-zero here, so trailing_zeros always returns Some
+    let a_twos = a.trailing_zeros().unwrap_or(0);
+    let b_twos = b.trailing_zeros().unwrap_or(0);
+    let shared_twos = a_twos.min(b_twos);
+    a >>= a_twos;
+    b >>= b_twos;
+
+    // Invariant: a and b are both odd
+    loop {
+        if a > b {
+            std::mem::swap(&mut a, &mut b);
+        }
+        b -= &a;
+        if b.is_zero() {
+            return (a << shared_twos).into();
+        }
+        b >>= b.trailing_zeros().unwrap_or(0);
+    }
+}
+
+// Inverse of a modulo m via the extended Euclidean algorithm, in [0, m). Works for any modulus,
+// prime or not, and fails when gcd(a, m) != 1.
+pub fn mod_inverse(a: &BigInt, m: &BigInt) -> Result<BigInt, RabinError> {
+    if *m <= BigInt::one() {
+        return Err(RabinError::NotInvertible);
+    }
+    // Invariant: old_s * a ≡ old_r (mod m), and likewise for s and r
+    let (mut old_r, mut r) = (a.mod_floor(m), m.clone());
+    let (mut old_s, mut s) = (BigInt::one(), BigInt::zero());
+    while !r.is_zero() {
+        let quotient = &old_r / &r;
+        let next_r = &old_r - &quotient * &r;
+        old_r = std::mem::replace(&mut r, next_r);
+        let next_s = &old_s - &quotient * &s;
+        old_s = std::mem::replace(&mut s, next_s);
+    }
+    if !old_r.is_one() {
+        return Err(RabinError::NotInvertible);
+    }
+    Ok(old_s.mod_floor(m))
+}
+
+// Jacobi symbol (a / n) for odd positive n, via quadratic reciprocity. It is 0 when a and n
+// share a factor; +1 does not imply a is a square mod n, but -1 proves it is not.
+pub fn jacobi(a: &BigInt, n: &BigInt) -> i8 {
+    assert!(n.is_positive() && n.is_odd(), "the Jacobi symbol needs an odd positive modulus");
+    let mut a = a.mod_floor(n);
+    let mut n = n.clone();
+    let mut result = 1i8;
+    let three = BigInt::from(3);
+    let five = BigInt::from(5);
+    let eight = BigInt::from(8);
+    let four = BigInt::from(4);
+
+    while !a.is_zero() {
+        while a.is_even() {
+            a >>= 1;
+            let r = n.mod_floor(&eight);
+            if r == three || r == five {
+                result = -result;
+            }
+        }
+        std::mem::swap(&mut a, &mut n);
+        if a.mod_floor(&four) == three && n.mod_floor(&four) == three {
+            result = -result;
+        }
+        a = a.mod_floor(&n);
+    }
+    if n.is_one() {
+        result
+    } else {
+        0
+    }
+}
+
+// Legendre symbol (a / p) for an odd prime p, by Euler's criterion: a^((p - 1) / 2) mod p is
+// 1 for non-zero squares and p - 1 for non-squares. Same value as jacobi, computed the slow,
+// textbook way; p is not checked for primality.
+pub fn legendre(a: &BigInt, p: &BigInt) -> i8 {
+    assert!(*p > BigInt::from(2) && p.is_odd(), "the Legendre symbol needs an odd prime");
+    let power = modpow(a, &(p >> 1), p);
+    if power.is_zero() {
+        0
+    } else if power.is_one() {
+        1
+    } else {
+        -1
+    }
+}
+
+// The unique x in [0, m1 * m2 * ...) with x ≡ residues[i] (mod moduli[i]) for every i.
+// The moduli must be pairwise coprime; otherwise there is no unique answer and this fails.
+// Builds the solution one modulus at a time (Garner's method), so any number of moduli works.
+pub fn crt(residues: &[BigInt], moduli: &[BigInt]) -> Result<BigInt, RabinError> {
+    assert_eq!(residues.len(), moduli.len(), "crt needs one residue per modulus");
+    let mut x = BigInt::zero();
+    let mut product = BigInt::one();
+    for (residue, modulus) in residues.iter().zip(moduli) {
+        // Choose t so that x + product * t ≡ residue (mod modulus)
+        let t = ((residue - &x) * mod_inverse(&product, modulus)?).mod_floor(modulus);
+        x += &product * t;
+        product *= modulus;
+    }
+    Ok(x)
+}
+
+// Square root of a modulo an odd prime p. Uses a^((p + 1) / 4) when p ≡ 3 (mod 4) and
+// Tonelli-Shanks otherwise; either way the result is checked by Euler's criterion first.
+pub fn sqrt_mod_prime(a: &BigInt, p: &BigInt) -> Result<BigInt, RabinError> {
+    let a = a.mod_floor(p);
+    match legendre(&a, p) {
+        0 => return Ok(BigInt::zero()),
+        -1 => return Err(RabinError::NotQuadraticResidue),
+        _ => {}
+    }
+    if p.mod_floor(&BigInt::from(4)) == BigInt::from(3) {
+        return Ok(modpow(&a, &((p + 1) >> 2), p));
+    }
+    Ok(tonelli_shanks(&a, p))
+}
+
+// a must be a non-zero quadratic residue modulo the odd prime p
+fn tonelli_shanks(a: &BigInt, p: &BigInt) -> BigInt {
+    // p - 1 = odd * 2^twos
+    let p_minus_one: BigInt = p - 1;
+    let twos = p_minus_one.trailing_zeros().unwrap_or(0);
+    let odd = &p_minus_one >> twos;
+
+    // Any non-residue will do; half of all values are one, so this ends quickly
+    let mut z = BigInt::from(2);
+    while legendre(&z, p) != -1 {
+        z += 1;
+    }
+
+    let mut m = twos;
+    let mut c = z.modpow(&odd, p);
+    let mut t = a.modpow(&odd, p);
+    let mut root = a.modpow(&((&odd + 1) >> 1), p);
+    // Invariant: root^2 ≡ a * t (mod p), and t has order dividing 2^(m - 1)
+    while !t.is_one() {
+        // Least i with t^(2^i) ≡ 1; always below m for a residue
+        let mut i = 0;
+        let mut power = t.clone();
+        while !power.is_one() {
+            power = &power * &power % p;
+            i += 1;
+        }
+        let b = c.modpow(&(BigInt::one() << (m - i - 1)), p);
+        m = i;
+        c = &b * &b % p;
+        t = t * &c % p;
+        root = root * b % p;
+    }
+    root
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gcd_variants_agree() {
+        let cases = [
+            (0, 0, 0),
+            (0, 12, 12),
+            (12, 0, 12),
+            (48, 18, 6),
+            (-48, 18, 6),
+            (17, 5, 1),
+            (1 << 20, 3 << 12, 1 << 12),
+        ];
+        for (a, b, want) in cases {
+            let (a, b, want) = (BigInt::from(a), BigInt::from(b), BigInt::from(want));
+            assert_eq!(gcd(&a, &b), want, "gcd({}, {})", a, b);
+            assert_eq!(binary_gcd(&a, &b), want, "binary_gcd({}, {})", a, b);
+        }
+    }
+
+    #[test]
+    fn test_gcd_on_large_fibonacci_numbers() {
+        // Consecutive Fibonacci numbers are the worst case for Euclid: one step per term
+        let (mut a, mut b) = (BigInt::one(), BigInt::one());
+        for _ in 0..20_000 {
+            let next = &a + &b;
+            a = std::mem::replace(&mut b, next);
+        }
+        assert!(gcd(&a, &b).is_one(), "Consecutive Fibonacci numbers are coprime");
+        assert!(binary_gcd(&a, &b).is_one());
+        assert_eq!(binary_gcd(&(&a * 6u8), &(&a * 4u8)), &a * 2u8);
+    }
+
+    #[test]
+    fn test_jacobi_small_values() {
+        // (a / 15) for a = 1..=14, from the standard table
+        let expected = [1, 1, 0, 1, 0, 0, -1, 1, 0, 0, -1, 0, -1, -1];
+        for (a, want) in (1..=14).zip(expected) {
+            assert_eq!(jacobi(&BigInt::from(a), &BigInt::from(15)), want, "jacobi({}, 15)", a);
+        }
+    }
+
+    #[test]
+    fn test_legendre_matches_jacobi_for_primes() {
+        // Squares mod 7 are 1, 2 and 4
+        let expected = [0, 1, 1, -1, 1, -1, -1];
+        for (a, want) in (0..7).zip(expected) {
+            assert_eq!(legendre(&BigInt::from(a), &BigInt::from(7)), want, "legendre({}, 7)", a);
+        }
+
+        let p = BigInt::parse_bytes(b"5081134225938911632501879835073274182691064608067531203259", 10).unwrap();
+        for a in [-5i64, 2, 3, 10, 12345, 987654321] {
+            let a = BigInt::from(a);
+            assert_eq!(legendre(&a, &p), jacobi(&a, &p), "symbols disagree for {}", a);
+        }
+    }
+
+    #[test]
+    fn test_sqrt_mod_prime() {
+        // 17 ≡ 1 (mod 16) is the hardest small case for Tonelli-Shanks; 19 uses the shortcut
+        for p in [5u32, 13, 17, 19, 41, 97, 257, 65537] {
+            let p = BigInt::from(p);
+            for a in 0..40u32 {
+                let a = BigInt::from(a).mod_floor(&p);
+                match sqrt_mod_prime(&a, &p) {
+                    Ok(root) => assert_eq!(&root * &root % &p, a, "bad root of {} mod {}", a, p),
+                    Err(err) => {
+                        assert_eq!(err, RabinError::NotQuadraticResidue);
+                        assert_eq!(legendre(&a, &p), -1, "{} is a square mod {}", a, p);
+                    }
+                }
+            }
+        }
+
+        // A 129-bit prime with p - 1 divisible by 2^7
+        let p = BigInt::parse_bytes(b"340282366920938463463374607431768211841", 10).unwrap();
+        let a = BigInt::from(123456789u64).pow(2) % &p;
+        let root = sqrt_mod_prime(&a, &p).unwrap();
+        assert_eq!(&root * &root % &p, a);
+    }
+
+    #[test]
+    fn test_crt() {
+        let to_big = |values: &[i64]| values.iter().map(|&v| BigInt::from(v)).collect::<Vec<_>>();
+
+        // Sunzi's original puzzle: x ≡ 2 (mod 3), x ≡ 3 (mod 5), x ≡ 2 (mod 7)
+        assert_eq!(crt(&to_big(&[2, 3, 2]), &to_big(&[3, 5, 7])).unwrap(), BigInt::from(23));
+
+        // Negative and oversized residues are reduced; a single modulus is just a reduction
+        assert_eq!(crt(&to_big(&[-1, 12]), &to_big(&[4, 9])).unwrap(), BigInt::from(3));
+        assert_eq!(crt(&to_big(&[100]), &to_big(&[7])).unwrap(), BigInt::from(2));
+        assert_eq!(crt(&[], &[]).unwrap(), BigInt::zero());
+
+        let moduli = to_big(&[11, 13, 17, 19, 23]);
+        let x = BigInt::from(1_000_003);
+        let residues: Vec<_> = moduli.iter().map(|m| &x % m).collect();
+        assert_eq!(crt(&residues, &moduli).unwrap(), x);
+
+        assert_eq!(crt(&to_big(&[1, 2]), &to_big(&[6, 9])), Err(RabinError::NotInvertible));
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        let inverse = mod_inverse(&BigInt::from(3), &BigInt::from(11)).unwrap();
+        assert_eq!(inverse, BigInt::from(4));
+
+        // Composite moduli and negative inputs work too
+        assert_eq!(mod_inverse(&BigInt::from(7), &BigInt::from(40)).unwrap(), BigInt::from(23));
+        assert_eq!(mod_inverse(&BigInt::from(-3), &BigInt::from(11)).unwrap(), BigInt::from(7));
+
+        assert_eq!(mod_inverse(&BigInt::from(6), &BigInt::from(9)), Err(RabinError::NotInvertible));
+        assert_eq!(mod_inverse(&BigInt::from(5), &BigInt::one()), Err(RabinError::NotInvertible));
+    }
+}
@@ -0,0 +1,236 @@
+// Optional key metadata: when the key was made, when it stops being valid, what it may be
+// used for, and the prime size it was generated with. Stored as a trailing element of the key
+// structures; fingerprints cover only the key material, so adding metadata never changes them.
+
+use crate::der::{encode_integer, encode_sequence, DerReader};
+use crate::error::RabinError;
+use crate::keys::{PrivateKey, PublicKey};
+use log::warn;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const USAGE_ENCRYPT: u8 = 1;
+const USAGE_SIGN: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyUsage {
+    Encrypt,
+    Sign,
+}
+
+impl KeyUsage {
+    fn bit(self) -> u8 {
+        match self {
+            KeyUsage::Encrypt => USAGE_ENCRYPT,
+            KeyUsage::Sign => USAGE_SIGN,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMetadata {
+    // Seconds since the Unix epoch
+    pub created: u64,
+    pub expires: Option<u64>,
+    usage: u8,
+    // Size of each prime at generation time, if known
+    pub bits: Option<usize>,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+impl KeyMetadata {
+    // Created now, never expires, allowed for every usage
+    pub fn new(bits: Option<usize>) -> Self {
+        KeyMetadata {
+            created: now(),
+            expires: None,
+            usage: USAGE_ENCRYPT | USAGE_SIGN,
+            bits,
+        }
+    }
+
+    pub fn expires_after(mut self, lifetime: Duration) -> Self {
+        self.expires = Some(self.created.saturating_add(lifetime.as_secs()));
+        self
+    }
+
+    // Restricts the key to the given usages
+    pub fn restrict_to(mut self, usages: &[KeyUsage]) -> Self {
+        self.usage = usages.iter().fold(0, |acc, usage| acc | usage.bit());
+        self
+    }
+
+    pub fn allows(&self, usage: KeyUsage) -> bool {
+        self.usage & usage.bit() != 0
+    }
+
+    pub fn is_expired_at(&self, time: u64) -> bool {
+        self.expires.is_some_and(|expires| time >= expires)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(now())
+    }
+
+    pub fn check_at(&self, usage: KeyUsage, time: u64) -> Result<(), RabinError> {
+        if !self.allows(usage) {
+            return Err(RabinError::UsageViolation(usage));
+        }
+        if self.is_expired_at(time) {
+            return Err(RabinError::KeyExpired);
+        }
+        Ok(())
+    }
+
+    pub fn check(&self, usage: KeyUsage) -> Result<(), RabinError> {
+        self.check_at(usage, now())
+    }
+
+    // KeyMetadata ::= SEQUENCE {
+    //     created INTEGER,
+    //     expires INTEGER (0 = never),
+    //     usage   INTEGER (bit 0 encrypt, bit 1 sign),
+    //     bits    INTEGER (0 = unknown)
+    // }
+    pub fn to_der(&self) -> Vec<u8> {
+        encode_sequence(&[
+            encode_integer(&BigInt::from(self.created)),
+            encode_integer(&BigInt::from(self.expires.unwrap_or(0))),
+            encode_integer(&BigInt::from(self.usage)),
+            encode_integer(&BigInt::from(self.bits.unwrap_or(0))),
+        ])
+    }
+
+    // Reads the metadata SEQUENCE from inside an enclosing structure
+    pub fn read_der(reader: &mut DerReader) -> Result<Self, RabinError> {
+        const OUT_OF_RANGE: RabinError = RabinError::MalformedDer("metadata field out of range");
+        let mut seq = reader.read_sequence()?;
+        let created = seq.read_integer()?.to_u64().ok_or(OUT_OF_RANGE)?;
+        let expires = seq.read_integer()?.to_u64().ok_or(OUT_OF_RANGE)?;
+        let usage = seq.read_integer()?.to_u8().ok_or(OUT_OF_RANGE)?;
+        let bits = seq.read_integer()?.to_usize().ok_or(OUT_OF_RANGE)?;
+        seq.finish()?;
+        Ok(KeyMetadata {
+            created,
+            expires: (expires != 0).then_some(expires),
+            usage,
+            bits: (bits != 0).then_some(bits),
+        })
+    }
+}
+
+impl PublicKey {
+    // Keys without metadata are unrestricted, apart from the demo key opt-in
+    pub fn check_usage(&self, usage: KeyUsage) -> Result<(), RabinError> {
+        self.check_size()?;
+        self.metadata().map_or(Ok(()), |metadata| metadata.check(usage))
+    }
+}
+
+impl PrivateKey {
+    // Signing with an expired key is refused, but decrypting only warns: messages sent while
+    // the key was valid must stay readable
+    pub fn check_usage(&self, usage: KeyUsage) -> Result<(), RabinError> {
+        self.check_size()?;
+        let Some(metadata) = self.metadata() else {
+            return Ok(());
+        };
+        match (usage, metadata.check(usage)) {
+            (KeyUsage::Encrypt, Err(RabinError::KeyExpired)) => {
+                warn!("Decrypting with an expired key");
+                Ok(())
+            }
+            (_, result) => result,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+
+    #[test]
+    fn test_metadata_der_round_trip() {
+        let metadata = KeyMetadata::new(Some(512))
+            .expires_after(Duration::from_secs(86400))
+            .restrict_to(&[KeyUsage::Encrypt]);
+        let der = metadata.to_der();
+        let decoded = KeyMetadata::read_der(&mut DerReader::new(&der)).unwrap();
+        assert_eq!(decoded, metadata);
+        assert_eq!(decoded.expires, Some(decoded.created + 86400));
+    }
+
+    #[test]
+    fn test_expiry_and_usage_checks() {
+        let metadata = KeyMetadata::new(None)
+            .expires_after(Duration::from_secs(100))
+            .restrict_to(&[KeyUsage::Encrypt]);
+        let created = metadata.created;
+
+        assert_eq!(metadata.check_at(KeyUsage::Encrypt, created + 99), Ok(()));
+        assert_eq!(metadata.check_at(KeyUsage::Encrypt, created + 100), Err(RabinError::KeyExpired));
+        assert_eq!(
+            metadata.check_at(KeyUsage::Sign, created),
+            Err(RabinError::UsageViolation(KeyUsage::Sign))
+        );
+        assert!(!KeyMetadata::new(None).is_expired(), "Keys without expiry never expire");
+    }
+
+    #[test]
+    fn test_key_metadata_survives_serialization() {
+        let key = PrivateKey::from_primes(BigInt::from(7), BigInt::from(11))
+            .unwrap().allow_insecure(InsecureDemo)
+            .with_metadata(KeyMetadata::new(Some(4)).restrict_to(&[KeyUsage::Sign]));
+        let public = key.public_key();
+
+        assert_eq!(PrivateKey::from_der(&key.to_der()).unwrap(), key);
+        assert_eq!(PublicKey::from_der(&public.to_der()).unwrap(), public);
+        assert_eq!(
+            public.fingerprint(),
+            PublicKey::new(BigInt::from(77)).fingerprint(),
+            "Metadata must not change the fingerprint"
+        );
+        assert_eq!(
+            public.check_usage(KeyUsage::Encrypt),
+            Err(RabinError::UsageViolation(KeyUsage::Encrypt))
+        );
+    }
+
+    #[test]
+    fn test_key_operations_enforce_usage() {
+        let key = PrivateKey::from_primes(BigInt::from(7), BigInt::from(11)).unwrap().allow_insecure(InsecureDemo);
+        let signing = key.clone().with_metadata(KeyMetadata::new(None).restrict_to(&[KeyUsage::Sign]));
+        let violation = RabinError::UsageViolation(KeyUsage::Encrypt);
+
+        assert_eq!(signing.public_key().encrypt(&BigInt::from(9)).unwrap_err(), violation);
+        assert_eq!(signing.decrypt(&BigInt::from(4)).unwrap_err(), violation);
+        assert_eq!(signing.decrypt_blinded(&BigInt::from(4)).unwrap_err(), violation);
+        assert_eq!(violation.to_string(), "key is not allowed to encrypt");
+
+        let encrypting = key.with_metadata(KeyMetadata::new(None).restrict_to(&[KeyUsage::Encrypt]));
+        let ciphertext = encrypting.public_key().encrypt(&BigInt::from(9)).unwrap();
+        assert!(encrypting.decrypt(&ciphertext).unwrap().contains(&BigInt::from(9)));
+    }
+
+    #[test]
+    fn test_expired_private_key_still_decrypts() {
+        let mut metadata = KeyMetadata::new(None);
+        metadata.expires = Some(1);
+        let key = PrivateKey::from_primes(BigInt::from(7), BigInt::from(11))
+            .unwrap().allow_insecure(InsecureDemo)
+            .with_metadata(metadata);
+
+        assert_eq!(key.check_usage(KeyUsage::Encrypt), Ok(()));
+        assert_eq!(key.check_usage(KeyUsage::Sign), Err(RabinError::KeyExpired));
+        assert_eq!(key.public_key().check_usage(KeyUsage::Encrypt), Err(RabinError::KeyExpired));
+    }
+}
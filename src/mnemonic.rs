@@ -0,0 +1,138 @@
+// Recovery phrases for seeded keys, in the spirit of BIP39 but with a smaller, self-contained
+// list: 256 words, so every word carries exactly one byte of entropy, followed by one checksum
+// word taken from the first byte of SHA-256(entropy). Words have distinct four-letter prefixes.
+
+use crate::error::RabinError;
+use crate::hash::sha256;
+use crate::keys::PrivateKey;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+// 128 bits of entropy, matching the shortest BIP39 phrase
+pub const DEFAULT_ENTROPY_LEN: usize = 16;
+
+const WORDLIST: [&str; 256] = [
+    "able", "acid", "acorn", "actor", "adapt", "admit", "adult", "agent",
+    "alarm", "album", "alert", "alley", "alpha", "amber", "angle", "ankle",
+    "apple", "april", "arena", "argue", "armor", "arrow", "atlas", "audio",
+    "autumn", "avoid", "awake", "bacon", "badge", "baker", "bamboo", "banner",
+    "barrel", "basket", "beach", "beard", "bench", "berry", "birch", "blossom",
+    "board", "bonus", "border", "bottle", "bounce", "brave", "bread", "brick",
+    "bridge", "bronze", "brush", "bubble", "bucket", "bundle", "butter", "cabin",
+    "cactus", "camera", "canal", "candle", "canvas", "carbon", "cargo", "carpet",
+    "castle", "cattle", "cedar", "cellar", "cement", "cereal", "chalk", "cherry",
+    "chess", "circle", "citrus", "clever", "cliff", "clock", "cloud", "cobalt",
+    "coffee", "comet", "copper", "coral", "cotton", "couch", "cousin", "cradle",
+    "crater", "cricket", "cube", "curtain", "dagger", "dawn", "debate", "decade",
+    "delta", "denim", "desert", "diamond", "dinner", "dolphin", "domain", "donkey",
+    "dragon", "drama", "drift", "drum", "eagle", "earth", "echo", "eclipse",
+    "elbow", "elder", "ember", "empire", "engine", "epic", "escape", "fabric",
+    "falcon", "famous", "fence", "ferry", "fiber", "filter", "finger", "flame",
+    "flavor", "fleet", "forest", "fossil", "fox", "frost", "galaxy", "garden",
+    "garlic", "gentle", "ginger", "glacier", "globe", "gravel", "guitar", "hammer",
+    "harbor", "harvest", "hazel", "helmet", "hockey", "honey", "horizon", "hotel",
+    "hunter", "igloo", "impact", "indoor", "island", "ivory", "jacket", "jaguar",
+    "jelly", "jewel", "journey", "jungle", "kettle", "kingdom", "kitten", "ladder",
+    "lagoon", "lantern", "laptop", "lemon", "letter", "lizard", "lobster", "locket",
+    "lumber", "magnet", "mango", "marble", "meadow", "melody", "mirror", "monkey",
+    "mosaic", "motor", "muffin", "museum", "napkin", "nectar", "needle", "nickel",
+    "noodle", "novel", "oasis", "ocean", "olive", "onion", "orbit", "orchid",
+    "oven", "oyster", "paddle", "palace", "panda", "paper", "parrot", "pebble",
+    "pepper", "piano", "pigeon", "pilot", "planet", "pocket", "pony", "puzzle",
+    "quartz", "quiver", "rabbit", "radar", "raven", "recipe", "ribbon", "rocket",
+    "saddle", "salmon", "sandal", "scarf", "shadow", "shelter", "silver", "sketch",
+    "spider", "sponge", "statue", "summit", "sunset", "tablet", "tiger", "timber",
+    "tomato", "tunnel", "turtle", "valley", "velvet", "violin", "walnut", "whistle",
+    "willow", "window", "winter", "wizard", "yogurt", "zebra", "zenith", "zipper",
+];
+
+fn word_index(word: &str) -> Option<u8> {
+    WORDLIST.binary_search(&word).ok().map(|index| index as u8)
+}
+
+pub fn entropy_to_mnemonic(entropy: &[u8]) -> String {
+    let checksum = sha256(entropy)[0];
+    entropy
+        .iter()
+        .chain(std::iter::once(&checksum))
+        .map(|&byte| WORDLIST[byte as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn mnemonic_to_entropy(phrase: &str) -> Result<Vec<u8>, RabinError> {
+    let mut bytes = phrase
+        .split_whitespace()
+        .map(|word| {
+            word_index(&word.to_lowercase())
+                .ok_or_else(|| RabinError::InvalidMnemonic(format!("unknown word '{}'", word)))
+        })
+        .collect::<Result<Vec<u8>, RabinError>>()?;
+
+    let checksum = bytes
+        .pop()
+        .filter(|_| !bytes.is_empty())
+        .ok_or_else(|| RabinError::InvalidMnemonic("phrase is too short".to_string()))?;
+    if sha256(&bytes)[0] != checksum {
+        return Err(RabinError::InvalidMnemonic("checksum word does not match".to_string()));
+    }
+    Ok(bytes)
+}
+
+pub fn generate_mnemonic(entropy_len: usize) -> String {
+    let mut entropy = vec![0u8; entropy_len];
+    OsRng.fill_bytes(&mut entropy);
+    entropy_to_mnemonic(&entropy)
+}
+
+impl PrivateKey {
+    // The bit size is not part of the phrase, so it has to be supplied again on recovery
+    pub fn from_mnemonic(phrase: &str, bit_size: usize) -> Result<Self, RabinError> {
+        Ok(PrivateKey::from_seed(&mnemonic_to_entropy(phrase)?, bit_size))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wordlist_is_sorted_and_unique() {
+        assert!(
+            WORDLIST.windows(2).all(|pair| pair[0] < pair[1]),
+            "Binary search needs a strictly sorted word list"
+        );
+    }
+
+    #[test]
+    fn test_mnemonic_round_trip() {
+        let entropy: Vec<u8> = (0..16).map(|i| i * 17).collect();
+        let phrase = entropy_to_mnemonic(&entropy);
+        assert_eq!(phrase.split(' ').count(), 17);
+        assert_eq!(mnemonic_to_entropy(&phrase).unwrap(), entropy);
+        // Case and spacing are not significant
+        let shouty = phrase.to_uppercase().replace(' ', "  \n");
+        assert_eq!(mnemonic_to_entropy(&shouty).unwrap(), entropy);
+    }
+
+    #[test]
+    fn test_mnemonic_rejects_typos() {
+        let phrase = entropy_to_mnemonic(&[1, 2, 3, 4]);
+        let mut words: Vec<&str> = phrase.split(' ').collect();
+
+        words.swap(0, 1);
+        assert_eq!(
+            mnemonic_to_entropy(&words.join(" ")),
+            Err(RabinError::InvalidMnemonic("checksum word does not match".to_string()))
+        );
+        assert!(mnemonic_to_entropy("able notaword").is_err());
+    }
+
+    #[test]
+    fn test_private_key_from_mnemonic_is_reproducible() {
+        let phrase = generate_mnemonic(DEFAULT_ENTROPY_LEN);
+        let key = PrivateKey::from_mnemonic(&phrase, 256).unwrap();
+        assert_eq!(PrivateKey::from_mnemonic(&phrase, 256).unwrap(), key);
+    }
+}
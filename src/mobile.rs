@@ -0,0 +1,110 @@
+// Swift and Kotlin bindings for iOS and Android teaching apps, through UniFFI, built with the
+// uniffi feature. Build the library for the target, then generate the sources from it with the
+// bundled uniffi-bindgen (the same uniffi version, which the generated code checks):
+//
+//     cargo build --release --features uniffi
+//     cargo run --features uniffi-bindgen --bin uniffi-bindgen -- generate \
+//         --library target/release/libnaive_rabin_cryptosystem.so --language kotlin --out-dir out
+//
+// with --language swift for iOS, and the .dylib or .a of an Apple target. uniffi.toml names
+// the Kotlin package (rabin) and the Swift module (Rabin).
+//
+// The API is the one the browser gets (src/wasm.rs): keys are PEM strings (PKCS#8 for private
+// keys, as in the CLI), messages and ciphertexts big-endian bytes, and encode and decode convert
+// text through an alphabet. Encryption is tagged and decryption strict. Failures are thrown as
+// RabinError (RabinException in Kotlin), one case per variant with its message. Demo-sized keys
+// are refused until the app calls allowInsecureDemo().
+
+use crate::bindings::{alphabet, OptIn};
+use crate::encoding::{bytes2num, modulus_len, num2bytes, num2str, str2num, to_fixed_be_bytes, ByteOrder};
+use crate::error::RabinError;
+use crate::keygen::KeygenConfig;
+use crate::keys::PrivateKey;
+use crate::metadata::KeyUsage;
+use crate::rabin::{decrypt_tagged, encrypt_tagged};
+
+static INSECURE_DEMO: OptIn = OptIn::new();
+
+// Lets demo-sized keys be generated and used for the rest of the process's life
+#[uniffi::export]
+pub fn allow_insecure_demo() {
+    INSECURE_DEMO.allow();
+}
+
+// Blocks until a key is found, which takes seconds for full-size keys on a phone; call it from
+// a background thread or coroutine, never the UI thread
+#[uniffi::export]
+pub fn keygen(bits: u32) -> Result<String, RabinError> {
+    let config = KeygenConfig::new(bits as usize);
+    let config = match INSECURE_DEMO.get() {
+        Some(opt_in) => config.allow_insecure(opt_in),
+        None => config,
+    };
+    Ok(PrivateKey::generate_with(&config)?.0.to_pkcs8_pem())
+}
+
+#[uniffi::export]
+pub fn public_key(private_pem: String) -> Result<String, RabinError> {
+    Ok(INSECURE_DEMO.load_private(&private_pem)?.public_key().to_pem())
+}
+
+// The short form apps show for comparing keys out of band
+#[uniffi::export]
+pub fn fingerprint(public_pem: String) -> Result<String, RabinError> {
+    Ok(INSECURE_DEMO.load_public(&public_pem)?.fingerprint().to_string())
+}
+
+// Fails with MessageTooLarge when the message and its tag do not fit below the modulus
+#[uniffi::export]
+pub fn encrypt(public_pem: String, message: Vec<u8>) -> Result<Vec<u8>, RabinError> {
+    let key = INSECURE_DEMO.load_public(&public_pem)?;
+    key.check_usage(KeyUsage::Encrypt)?;
+    let ciphertext = encrypt_tagged(&bytes2num(&message, ByteOrder::BigEndian), key.n())?;
+    Ok(to_fixed_be_bytes(&ciphertext, modulus_len(key.n())).expect("the ciphertext is below n"))
+}
+
+#[uniffi::export]
+pub fn decrypt(private_pem: String, ciphertext: Vec<u8>) -> Result<Vec<u8>, RabinError> {
+    let key = INSECURE_DEMO.load_private(&private_pem)?;
+    key.check_usage(KeyUsage::Encrypt)?;
+    let message = decrypt_tagged(&bytes2num(&ciphertext, ByteOrder::BigEndian), key.p(), key.q())?;
+    Ok(num2bytes(&message, ByteOrder::BigEndian, None).expect("the message is not negative"))
+}
+
+// alphabet_digits lists the symbols in digit order (nil or null for the default set); text
+// with any other symbol fails with RabinError.Encoding
+#[uniffi::export]
+pub fn encode(text: String, alphabet_digits: Option<String>) -> Result<Vec<u8>, RabinError> {
+    let number = str2num(&text, &alphabet(alphabet_digits)?)?;
+    Ok(num2bytes(&number, ByteOrder::BigEndian, None).expect("encoded text is not negative"))
+}
+
+#[uniffi::export]
+pub fn decode(number: Vec<u8>, alphabet_digits: Option<String>) -> Result<String, RabinError> {
+    Ok(num2str(&bytes2num(&number, ByteOrder::BigEndian), &alphabet(alphabet_digits)?)?)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mobile_round_trip() {
+        assert_eq!(keygen(256), Err(RabinError::InsecureKey(512)));
+        allow_insecure_demo();
+        let private_pem = keygen(256).unwrap();
+        let public_pem = public_key(private_pem.clone()).unwrap();
+        let expected = INSECURE_DEMO.load_private(&private_pem).unwrap().public_key().fingerprint();
+        assert_eq!(fingerprint(public_pem.clone()).unwrap(), expected.to_string());
+
+        let message = encode("Hello, phone".to_string(), None).unwrap();
+        let mut ciphertext = encrypt(public_pem, message).unwrap();
+        let decrypted = decrypt(private_pem.clone(), ciphertext.clone()).unwrap();
+        assert_eq!(decode(decrypted, None).unwrap(), "Hello, phone");
+
+        ciphertext[1] ^= 1;
+        assert!(decrypt(private_pem, ciphertext).is_err(), "A tampered ciphertext should not decrypt");
+        assert!(matches!(encode("~".to_string(), None), Err(RabinError::Encoding(_))));
+    }
+}
@@ -0,0 +1,349 @@
+// Montgomery arithmetic for odd moduli. Numbers are kept as a*R mod n with R = 2^(64 * limbs),
+// which turns every modular multiplication into two multiply-accumulate passes and a shift,
+// with no division. Limb buffers are allocated once per exponentiation and reused, and the
+// exponent is consumed in sliding windows: a window always starts and ends at a one bit, so only
+// odd powers of the base need a table entry, and runs of zeros cost nothing but squarings. The
+// window width grows with the exponent, up to 6 bits.
+//
+// num-bigint's own modpow also uses Montgomery reduction for odd moduli, but allocates on every
+// multiplication and uses fixed 4-bit windows; benches/modpow.rs measures the difference.
+
+use num_bigint::{BigInt, BigUint, Sign};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Montgomery {
+    modulus: BigUint,
+    limbs: Vec<u64>,
+    // -n^-1 mod 2^64
+    n0_inverse: u64,
+    // R^2 mod n, used to convert into Montgomery form
+    r_squared: Vec<u64>,
+}
+
+pub(crate) fn to_limbs(value: &BigUint, len: usize) -> Vec<u64> {
+    let mut limbs = value.to_u64_digits();
+    limbs.resize(len, 0);
+    limbs
+}
+
+pub(crate) fn from_limbs(limbs: &[u64]) -> BigUint {
+    let bytes: Vec<u8> = limbs.iter().flat_map(|limb| limb.to_le_bytes()).collect();
+    BigUint::from_bytes_le(&bytes)
+}
+
+// Newton iteration for n0^-1 mod 2^64; each step doubles the number of correct low bits
+fn inverse_mod_word(n0: u64) -> u64 {
+    let mut inverse = 1u64;
+    for _ in 0..6 {
+        inverse = inverse.wrapping_mul(2u64.wrapping_sub(n0.wrapping_mul(inverse)));
+    }
+    inverse
+}
+
+impl Montgomery {
+    // None for even moduli (and 1), which have no Montgomery form
+    pub fn new(modulus: &BigUint) -> Option<Self> {
+        if modulus.is_even() || modulus.is_one() {
+            return None;
+        }
+        let limbs = modulus.to_u64_digits();
+        let r_squared = (BigUint::one() << (128 * limbs.len())) % modulus;
+        Some(Montgomery {
+            modulus: modulus.clone(),
+            n0_inverse: inverse_mod_word(limbs[0]).wrapping_neg(),
+            r_squared: to_limbs(&r_squared, limbs.len()),
+            limbs,
+        })
+    }
+
+    pub fn modulus(&self) -> &BigUint {
+        &self.modulus
+    }
+
+    // Reduces the double-width value in t (2 * limbs words, below n * R) to t / R mod n
+    // and writes it to out (REDC)
+    fn reduce(&self, t: &mut [u64], out: &mut [u64]) {
+        let s = self.limbs.len();
+        let mut overflow = false;
+        for i in 0..s {
+            // Adding m * n clears word i, so the final value is divisible by R
+            let m = t[i].wrapping_mul(self.n0_inverse);
+            let carry = mul_add_row(&mut t[i..i + s], &self.limbs, m);
+            let (sum, o1) = t[i + s].overflowing_add(carry);
+            let (sum, o2) = sum.overflowing_add(u64::from(overflow));
+            t[i + s] = sum;
+            overflow = o1 || o2;
+        }
+        // The result is below 2n; one conditional subtraction brings it below n
+        let high = &mut t[s..2 * s];
+        if overflow || !less_than(high, &self.limbs) {
+            sub_in_place(high, &self.limbs);
+        }
+        out.copy_from_slice(high);
+    }
+
+    // out = a * b / R mod n with inputs below n (CIOS: the product and reduction rows are
+    // interleaved, so every pass over t does both). `scratch` needs 2 * limbs words.
+    fn multiply(&self, a: &[u64], b: &[u64], out: &mut [u64], scratch: &mut [u64]) {
+        let s = self.limbs.len();
+        let n = &self.limbs[..s];
+        let (a, t) = (&a[..s], &mut scratch[..s + 1]);
+        t.fill(0);
+        for &b_i in b {
+            let x = t[0] as u128 + a[0] as u128 * b_i as u128;
+            let m = (x as u64).wrapping_mul(self.n0_inverse);
+            let y = (x as u64) as u128 + m as u128 * n[0] as u128;
+            let (mut c1, mut c2) = ((x >> 64) as u64, (y >> 64) as u64);
+            for j in 1..s {
+                let x = t[j] as u128 + a[j] as u128 * b_i as u128 + c1 as u128;
+                let y = (x as u64) as u128 + m as u128 * n[j] as u128 + c2 as u128;
+                c1 = (x >> 64) as u64;
+                c2 = (y >> 64) as u64;
+                t[j - 1] = y as u64;
+            }
+            let x = t[s] as u128 + c1 as u128 + c2 as u128;
+            t[s - 1] = x as u64;
+            t[s] = (x >> 64) as u64;
+        }
+        // t is below 2n; one conditional subtraction brings it below n
+        let (low, top) = t.split_at_mut(s);
+        if top[0] != 0 || !less_than(low, n) {
+            sub_in_place(low, n);
+        }
+        out.copy_from_slice(low);
+    }
+
+    // out = a^2 / R mod n
+    fn square(&self, a: &[u64], out: &mut [u64], scratch: &mut [u64]) {
+        square_limbs(&a[..self.limbs.len()], scratch);
+        self.reduce(scratch, out);
+    }
+
+    pub fn pow(&self, base: &BigUint, exponent: &BigUint) -> BigUint {
+        let s = self.limbs.len();
+        let k = window_bits(exponent.bits());
+        let mut scratch = vec![0u64; 2 * s];
+        let mut one = vec![0u64; s];
+        one[0] = 1;
+
+        // Table of the odd powers base^1, base^3, ..., base^(2^k - 1) in Montgomery form, one
+        // entry of s limbs after the other in a single buffer
+        let entries = 1 << (k - 1);
+        let mut table = vec![0u64; entries * s];
+        let reduced = to_limbs(&(base % &self.modulus), s);
+        self.multiply(&reduced, &self.r_squared, &mut table[..s], &mut scratch);
+        let mut base_squared = vec![0u64; s];
+        if entries > 1 {
+            self.square(&table[..s], &mut base_squared, &mut scratch);
+        }
+        for i in 1..entries {
+            let (done, rest) = table.split_at_mut(i * s);
+            self.multiply(&done[(i - 1) * s..], &base_squared, &mut rest[..s], &mut scratch);
+        }
+        let entry = |window: usize| &table[(window >> 1) * s..][..s];
+
+        // acc stays None (that is, 1) until the first window, which saves squaring a one
+        let mut acc: Option<Vec<u64>> = None;
+        let mut tmp = vec![0u64; s];
+        let mut position = exponent.bits();
+        while position > 0 {
+            if !exponent.bit(position - 1) {
+                if let Some(acc) = acc.as_mut() {
+                    self.square(acc, &mut tmp, &mut scratch);
+                    std::mem::swap(acc, &mut tmp);
+                }
+                position -= 1;
+                continue;
+            }
+            // The longest window of at most k bits that starts at a one and ends at a one
+            let mut low = position.saturating_sub(k);
+            while !exponent.bit(low) {
+                low += 1;
+            }
+            let window = (low..position).rev().fold(0usize, |window, bit| {
+                (window << 1) | usize::from(exponent.bit(bit))
+            });
+            match acc.as_mut() {
+                Some(acc) => {
+                    for _ in low..position {
+                        self.square(acc, &mut tmp, &mut scratch);
+                        std::mem::swap(acc, &mut tmp);
+                    }
+                    self.multiply(acc, entry(window), &mut tmp, &mut scratch);
+                    std::mem::swap(acc, &mut tmp);
+                }
+                None => acc = Some(entry(window).to_vec()),
+            }
+            position = low;
+        }
+
+        match acc {
+            // Multiplying by plain 1 divides out the last R
+            Some(acc) => {
+                self.multiply(&acc, &one, &mut tmp, &mut scratch);
+                from_limbs(&tmp)
+            }
+            None => BigUint::one() % &self.modulus,
+        }
+    }
+}
+
+// Sliding-window width for an exponent of the given length: each extra bit doubles the table
+// but saves multiplications, and these are the break-even points (the same ones OpenSSL uses)
+fn window_bits(exponent_bits: u64) -> u64 {
+    match exponent_bits {
+        672.. => 6,
+        240.. => 5,
+        80.. => 4,
+        24.. => 3,
+        _ => 1,
+    }
+}
+
+// out[..2 * a.len()] = a^2. Each cross product a_i * a_j is computed once and doubled, which
+// saves almost half the word multiplications of a general product.
+pub(crate) fn square_limbs(a: &[u64], out: &mut [u64]) {
+    let s = a.len();
+    out.fill(0);
+    for i in 0..s - 1 {
+        out[i + s] = mul_add_row(&mut out[2 * i + 1..i + s], &a[i + 1..], a[i]);
+    }
+    let mut carry = 0u64;
+    for word in out[..2 * s].iter_mut() {
+        let next = *word >> 63;
+        *word = (*word << 1) | carry;
+        carry = next;
+    }
+    let mut carry = 0u128;
+    for (i, &a_i) in a.iter().enumerate() {
+        let square = a_i as u128 * a_i as u128;
+        let low = out[2 * i] as u128 + (square as u64) as u128 + carry;
+        out[2 * i] = low as u64;
+        let high = out[2 * i + 1] as u128 + (square >> 64) + (low >> 64);
+        out[2 * i + 1] = high as u64;
+        carry = high >> 64;
+    }
+}
+
+// acc += a * b, returning the carry out of the top word
+#[inline(always)]
+pub(crate) fn mul_add_row(acc: &mut [u64], a: &[u64], b: u64) -> u64 {
+    let mut carry = 0u64;
+    for (x, &y) in acc.iter_mut().zip(a) {
+        let sum = *x as u128 + y as u128 * b as u128 + carry as u128;
+        *x = sum as u64;
+        carry = (sum >> 64) as u64;
+    }
+    carry
+}
+
+// a -= b over the words of b, returning the borrow out of the last one
+pub(crate) fn sub_in_place(a: &mut [u64], b: &[u64]) -> bool {
+    let mut borrow = false;
+    for (x, &y) in a.iter_mut().zip(b) {
+        let (difference, b1) = x.overflowing_sub(y);
+        let (difference, b2) = difference.overflowing_sub(u64::from(borrow));
+        *x = difference;
+        borrow = b1 || b2;
+    }
+    borrow
+}
+
+pub(crate) fn less_than(a: &[u64], b: &[u64]) -> bool {
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return x < y;
+        }
+    }
+    false
+}
+
+// Drop-in for BigInt::modpow with a positive modulus and non-negative exponent. The result is
+// in [0, modulus). With the fast-math feature this is GMP, otherwise modpow_portable.
+pub fn modpow(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> BigInt {
+    #[cfg(feature = "fast-math")]
+    let result = crate::gmp::modpow(base, exponent, modulus);
+    #[cfg(not(feature = "fast-math"))]
+    let result = modpow_portable(base, exponent, modulus);
+    result
+}
+
+// Odd moduli go through Montgomery, anything else falls back to num-bigint
+pub fn modpow_portable(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> BigInt {
+    assert!(exponent.sign() != Sign::Minus, "negative exponent");
+    match modulus.to_biguint().as_ref().and_then(Montgomery::new) {
+        Some(context) => {
+            let base = base.mod_floor(modulus).to_biguint().expect("reduced base is non-negative");
+            BigInt::from(context.pow(&base, exponent.magnitude()))
+        }
+        None if modulus.is_zero() => panic!("zero modulus"),
+        None => base.modpow(exponent, modulus).mod_floor(modulus),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::RandBigInt;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_matches_num_bigint() {
+        let mut rng = thread_rng();
+        for bits in [3u64, 64, 65, 127, 512, 1000, 2048] {
+            let modulus = rng.gen_biguint(bits) | BigUint::from(1u8) | (BigUint::one() << (bits - 1));
+            let context = Montgomery::new(&modulus).unwrap();
+            for exponent_bits in [0u64, 1, 5, 63, bits] {
+                let base = rng.gen_biguint(bits + 8);
+                let exponent = rng.gen_biguint(exponent_bits);
+                assert_eq!(
+                    context.pow(&base, &exponent),
+                    base.modpow(&exponent, &modulus),
+                    "{}-bit modulus, {}-bit exponent",
+                    bits,
+                    exponent_bits
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_window_width() {
+        let mut rng = thread_rng();
+        let modulus = rng.gen_biguint(256) | BigUint::one();
+        let context = Montgomery::new(&modulus).unwrap();
+        // Both sides of each width threshold, and exponents with long runs of zeros and ones
+        for exponent_bits in [23u64, 24, 79, 80, 239, 240, 671, 672, 1500] {
+            let base = rng.gen_biguint(256);
+            let top = BigUint::one() << (exponent_bits - 1);
+            for exponent in [rng.gen_biguint(exponent_bits) | &top, &top + 1u8, (&top << 1) - 1u8] {
+                assert_eq!(
+                    context.pow(&base, &exponent),
+                    base.modpow(&exponent, &modulus),
+                    "{}-bit exponent",
+                    exponent_bits
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        let context = Montgomery::new(&BigUint::from(u64::MAX)).unwrap();
+        assert_eq!(context.pow(&BigUint::from(u64::MAX - 1), &BigUint::from(2u8)), BigUint::one());
+        assert_eq!(context.pow(&BigUint::from(5u8), &BigUint::zero()), BigUint::one());
+        assert!(Montgomery::new(&BigUint::from(10u8)).is_none());
+        assert!(Montgomery::new(&BigUint::one()).is_none());
+    }
+
+    #[test]
+    fn test_modpow_wrapper() {
+        let n = BigInt::from(253);
+        assert_eq!(modpow(&BigInt::from(-3), &BigInt::from(3), &n), BigInt::from(226));
+        // Even moduli use the fallback and still return a reduced, non-negative value
+        assert_eq!(modpow(&BigInt::from(-3), &BigInt::from(3), &BigInt::from(10)), BigInt::from(3));
+    }
+}
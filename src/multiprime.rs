@@ -0,0 +1,129 @@
+// Multi-prime Rabin: n = p * q * r (or more primes). Each prime contributes two square roots,
+// so k primes give 2^k decryption candidates: eight for three primes, against four for two.
+// In exchange the primes are smaller for a given modulus size, and decryption only does
+// exponentiations modulo those smaller primes before the CRT recombination.
+//
+// Keys of this kind live in memory only; the key file formats hold exactly two primes.
+
+use crate::error::RabinError;
+use crate::keygen::{KeygenConfig, KeygenReport};
+use crate::keys::PublicKey;
+use crate::math::crt;
+use crate::rabin::root_mod_prime;
+use crate::secret::Secret;
+use num_bigint::BigInt;
+use num_traits::One;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiPrimeKey {
+    n: BigInt,
+    primes: Vec<BigInt>,
+}
+
+impl MultiPrimeKey {
+    // Draws config.prime_count primes of config.bits bits each
+    pub fn generate_with(config: &KeygenConfig) -> Result<(Self, KeygenReport), RabinError> {
+        if config.squarings != 1 {
+            return Err(RabinError::InvalidKey("multi-prime keys only square once"));
+        }
+        let (primes, report) = config.gen_distinct_primes()?;
+        let key = MultiPrimeKey::from_primes(primes.into_iter().map(BigInt::from).collect())?;
+        Ok((key, report))
+    }
+
+    // Only the shape is checked; primality and coprimality are up to the caller
+    pub fn from_primes(primes: Vec<BigInt>) -> Result<Self, RabinError> {
+        if primes.len() < 2 {
+            return Err(RabinError::InvalidKey("a key needs at least two primes"));
+        }
+        if primes.iter().any(|prime| *prime <= BigInt::one()) {
+            return Err(RabinError::InvalidKey("primes must be greater than 1"));
+        }
+        let n = primes.iter().product();
+        Ok(MultiPrimeKey { n, primes })
+    }
+
+    pub fn n(&self) -> &BigInt {
+        &self.n
+    }
+
+    pub fn primes(&self) -> &[BigInt] {
+        &self.primes
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey::new(self.n.clone())
+    }
+
+    // All 2^k square roots of the ciphertext. Bit i of a candidate's index selects the negated
+    // root modulo the i-th prime, so candidates i and (2^k - 1 - i) are negations of each other.
+    pub fn decrypt(&self, ciphertext: &BigInt) -> Result<Vec<BigInt>, RabinError> {
+        let roots = Secret::new(
+            self.primes
+                .iter()
+                .map(|prime| root_mod_prime(ciphertext, prime))
+                .collect::<Result<Vec<_>, _>>()?,
+        );
+
+        (0..1usize << roots.len())
+            .map(|signs| {
+                let residues = Secret::new(
+                    roots
+                        .iter()
+                        .enumerate()
+                        .map(|(i, root)| if (signs >> i) & 1 == 1 { -root } else { root.clone() })
+                        .collect::<Vec<_>>(),
+                );
+                crt(&residues, &self.primes)
+            })
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+    use crate::keys::PrivateKey;
+    use crate::rabin::{decrypt, encrypt};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_three_prime_key_gives_eight_candidates() {
+        let config = KeygenConfig::new(128).allow_insecure(InsecureDemo).with_prime_count(3);
+        let (key, _) = MultiPrimeKey::generate_with(&config).unwrap();
+        assert_eq!(key.primes().len(), 3);
+        assert!((382..=384).contains(&key.n().bits()), "Three 128-bit primes make a 382- to 384-bit modulus");
+
+        let message = BigInt::from(987654321u64);
+        let ciphertext = encrypt(&message, key.n()).unwrap();
+        let candidates = key.decrypt(&ciphertext).unwrap();
+
+        assert_eq!(candidates.len(), 8);
+        assert_eq!(candidates.iter().collect::<HashSet<_>>().len(), 8, "Candidates should be unique");
+        assert!(candidates.contains(&message));
+        for (i, candidate) in candidates.iter().enumerate() {
+            assert_eq!(candidate * candidate % key.n(), ciphertext, "candidate {} is not a root", i);
+            assert_eq!(candidate + &candidates[7 - i], *key.n(), "candidate {} should negate {}", i, 7 - i);
+        }
+    }
+
+    #[test]
+    fn test_two_primes_match_the_classic_scheme() {
+        let (p, q) = (BigInt::from(43), BigInt::from(47));
+        let key = MultiPrimeKey::from_primes(vec![p.clone(), q.clone()]).unwrap();
+        let ciphertext = encrypt(&BigInt::from(1000), key.n()).unwrap();
+
+        let multi: HashSet<_> = key.decrypt(&ciphertext).unwrap().into_iter().collect();
+        let classic: HashSet<_> = decrypt(&ciphertext, &p, &q).unwrap().into_iter().collect();
+        assert_eq!(multi, classic);
+    }
+
+    #[test]
+    fn test_prime_count_is_checked() {
+        let config = KeygenConfig::new(64).allow_insecure(InsecureDemo).with_prime_count(3);
+        assert!(PrivateKey::generate_with(&config).is_err(), "PrivateKey holds exactly two primes");
+        assert!(MultiPrimeKey::from_primes(vec![BigInt::from(7)]).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use crate::error::RabinError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+// RFC 7468 wraps the base64 body at 64 columns
+const LINE_WIDTH: usize = 64;
+
+pub fn encode(label: &str, der: &[u8]) -> String {
+    let body = STANDARD.encode(der);
+    let mut out = format!("-----BEGIN {}-----\n", label);
+    for chunk in body.as_bytes().chunks(LINE_WIDTH) {
+        // base64 output is pure ASCII, so chunking on bytes cannot split a character
+        out.push_str(std::str::from_utf8(chunk).unwrap());
+        out.push('\n');
+    }
+    out.push_str(&format!("-----END {}-----\n", label));
+    out
+}
+
+pub fn decode(label: &str, pem: &str) -> Result<Vec<u8>, RabinError> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+
+    let start = pem
+        .find(&begin)
+        .ok_or(RabinError::MalformedPem("missing BEGIN line"))?
+        + begin.len();
+    let stop = pem[start..]
+        .find(&end)
+        .ok_or(RabinError::MalformedPem("missing END line"))?
+        + start;
+
+    let body: String = pem[start..stop].chars().filter(|c| !c.is_whitespace()).collect();
+    STANDARD
+        .decode(body)
+        .map_err(|_| RabinError::MalformedPem("invalid base64 body"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pem_round_trip() {
+        let der: Vec<u8> = (0..=255).collect();
+        let pem = encode("TEST DATA", &der);
+
+        assert!(pem.starts_with("-----BEGIN TEST DATA-----\n"));
+        assert!(pem.lines().all(|line| line.len() <= LINE_WIDTH));
+        assert_eq!(decode("TEST DATA", &pem).unwrap(), der);
+    }
+
+    #[test]
+    fn test_pem_tolerates_rewrapping() {
+        let der: Vec<u8> = (0..200).collect();
+        let pem = encode("TEST DATA", &der);
+        let body: String = pem.lines().filter(|line| !line.starts_with("-----")).collect();
+        let mut rewrapped = String::from("  -----BEGIN TEST DATA-----\r\n");
+        for chunk in body.as_bytes().chunks(72) {
+            rewrapped.push('\t');
+            rewrapped.push_str(std::str::from_utf8(chunk).unwrap());
+            rewrapped.push_str(" \r\n");
+        }
+        rewrapped.push_str("-----END TEST DATA-----");
+        assert_eq!(decode("TEST DATA", &rewrapped).unwrap(), der);
+    }
+
+    #[test]
+    fn test_pem_rejects_wrong_label() {
+        let pem = encode("PUBLIC KEY", &[1, 2, 3]);
+        assert_eq!(
+            decode("PRIVATE KEY", &pem),
+            Err(RabinError::MalformedPem("missing BEGIN line"))
+        );
+    }
+}
@@ -0,0 +1,163 @@
+// Generalized Rabin: encryption squares k times, c = m^(2^k) mod n, and decryption peels the
+// squarings off one at a time by taking square roots modulo each prime.
+//
+// For Blum primes (p ≡ 3 mod 4) squaring permutes the quadratic residues, so only one of the
+// two roots at every intermediate step is itself a square and can be rooted again. The search
+// therefore never widens and there are still exactly four candidates, whatever k is. This is
+// the trapdoor behind Blum-Goldwasser: its keystream is x, x^2, x^4, ... mod n, and the
+// receiver recovers the seed from the last state by exactly this iterated root extraction.
+//
+// For p ≡ 1 (mod 4), -1 is a square, so both roots can keep going and m^(2^k) has
+// gcd(2^k, p - 1) roots modulo p. Those are the higher-degree (2^k-th power) residues, and the
+// candidate list grows with k until it hits that bound.
+
+use crate::error::RabinError;
+use crate::keygen::{KeygenConfig, KeygenReport};
+use crate::keys::{PrivateKey, PublicKey};
+use crate::math::{crt, sqrt_mod_prime};
+use num_bigint::BigInt;
+use num_integer::Integer;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerKey {
+    key: PrivateKey,
+    squarings: u32,
+}
+
+// m^(2^squarings) mod n
+pub fn encrypt(message: &BigInt, n: &BigInt, squarings: u32) -> BigInt {
+    (0..squarings).fold(message.mod_floor(n), |value, _| &value * &value % n)
+}
+
+// Every x modulo the odd prime p with x^(2^squarings) ≡ value, found one square root at a time
+fn roots_mod_prime(value: &BigInt, p: &BigInt, squarings: u32) -> Result<Vec<BigInt>, RabinError> {
+    let mut roots = vec![value.mod_floor(p)];
+    for _ in 0..squarings {
+        let mut next = Vec::with_capacity(roots.len() * 2);
+        for root in &roots {
+            match sqrt_mod_prime(root, p) {
+                Ok(r) => {
+                    let negated = (p - &r).mod_floor(p);
+                    if negated != r {
+                        next.push(negated);
+                    }
+                    next.push(r);
+                }
+                // Dead end: this branch is not a square, so it cannot be an earlier step
+                Err(RabinError::NotQuadraticResidue) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        roots = next;
+    }
+    if roots.is_empty() {
+        return Err(RabinError::NotQuadraticResidue);
+    }
+    roots.sort();
+    Ok(roots)
+}
+
+impl PowerKey {
+    pub fn new(key: PrivateKey, squarings: u32) -> Result<Self, RabinError> {
+        if squarings == 0 {
+            return Err(RabinError::InvalidKey("the exponent needs at least one squaring"));
+        }
+        Ok(PowerKey { key, squarings })
+    }
+
+    // Draws an ordinary two-prime key and takes the exponent from config.squarings
+    pub fn generate_with(config: &KeygenConfig) -> Result<(Self, KeygenReport), RabinError> {
+        let (key, report) = PrivateKey::generate_with(&config.clone().with_squarings(1))?;
+        Ok((PowerKey::new(key, config.squarings)?, report))
+    }
+
+    pub fn key(&self) -> &PrivateKey {
+        &self.key
+    }
+
+    pub fn squarings(&self) -> u32 {
+        self.squarings
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.key.public_key()
+    }
+
+    pub fn encrypt(&self, message: &BigInt) -> BigInt {
+        encrypt(message, self.key.n(), self.squarings)
+    }
+
+    // All 2^k-th roots of the ciphertext modulo n, in ascending order
+    pub fn decrypt(&self, ciphertext: &BigInt) -> Result<Vec<BigInt>, RabinError> {
+        let (p, q) = (self.key.p(), self.key.q());
+        let roots_p = roots_mod_prime(ciphertext, p, self.squarings)?;
+        let roots_q = roots_mod_prime(ciphertext, q, self.squarings)?;
+
+        let moduli = [p.clone(), q.clone()];
+        let mut candidates = Vec::with_capacity(roots_p.len() * roots_q.len());
+        for mp in &roots_p {
+            for mq in &roots_q {
+                candidates.push(crt(&[mp.clone(), mq.clone()], &moduli)?);
+            }
+        }
+        candidates.sort();
+        Ok(candidates)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_blum_key_keeps_four_candidates() {
+        let config = KeygenConfig::new(128).allow_insecure(InsecureDemo).with_squarings(5);
+        let (key, _) = PowerKey::generate_with(&config).unwrap();
+        assert_eq!(key.squarings(), 5);
+
+        let message = BigInt::from(31415926535u64);
+        let ciphertext = key.encrypt(&message);
+        let candidates = key.decrypt(&ciphertext).unwrap();
+
+        assert_eq!(candidates.len(), 4, "Blum primes give four candidates for any k");
+        assert!(candidates.contains(&message));
+        for candidate in &candidates {
+            assert_eq!(encrypt(candidate, key.key().n(), 5), ciphertext);
+        }
+    }
+
+    #[test]
+    fn test_one_mod_four_primes_give_higher_degree_roots() {
+        // gcd(4, 12) = 4 fourth roots modulo 13 and gcd(4, 16) = 4 modulo 17
+        let key = PowerKey::new(PrivateKey::from_primes(BigInt::from(13), BigInt::from(17)).unwrap().allow_insecure(InsecureDemo), 2).unwrap();
+        let ciphertext = key.encrypt(&BigInt::from(5));
+        let candidates = key.decrypt(&ciphertext).unwrap();
+
+        assert_eq!(candidates.len(), 16);
+        assert_eq!(candidates.iter().collect::<HashSet<_>>().len(), 16, "Candidates should be unique");
+        assert!(candidates.contains(&BigInt::from(5)));
+        assert!(candidates.iter().all(|c| encrypt(c, key.key().n(), 2) == ciphertext));
+    }
+
+    #[test]
+    fn test_one_squaring_matches_the_classic_scheme() {
+        let (p, q) = (BigInt::from(43), BigInt::from(47));
+        let key = PowerKey::new(PrivateKey::from_primes(p.clone(), q.clone()).unwrap().allow_insecure(InsecureDemo), 1).unwrap();
+        let ciphertext = crate::rabin::encrypt(&BigInt::from(1000), key.key().n()).unwrap();
+
+        let mut classic = crate::rabin::decrypt(&ciphertext, &p, &q).unwrap();
+        classic.sort();
+        assert_eq!(key.decrypt(&ciphertext).unwrap(), classic);
+    }
+
+    #[test]
+    fn test_squarings_are_checked() {
+        let key = PrivateKey::from_primes(BigInt::from(43), BigInt::from(47)).unwrap().allow_insecure(InsecureDemo);
+        assert!(PowerKey::new(key, 0).is_err());
+        let config = KeygenConfig::new(64).allow_insecure(InsecureDemo).with_squarings(3);
+        assert!(PrivateKey::generate_with(&config).is_err(), "PrivateKey only squares once");
+    }
+}
@@ -0,0 +1,179 @@
+// Probable-prime testing with a configurable strategy. Every test starts with trial division
+// by the primes below 256, which settles everything below 65536 exactly. After that:
+//
+// - numbers below 2^64 are settled exactly by Miller-Rabin on the first twelve prime bases,
+//   which has no counterexample below 3.3 * 10^24 (unless deterministic_small is off)
+// - larger numbers get a base-2 Miller-Rabin round, `rounds` more with random bases (a
+//   composite survives each with probability below 1/4), and optionally a strong Lucas test.
+//   Base 2 plus strong Lucas is Baillie-PSW, for which no counterexample is known at all.
+//
+// The individual tests come from num-prime; this module only decides which ones run.
+
+use num_bigint::{BigInt, BigUint, RandBigInt};
+use num_prime::PrimalityUtils;
+use num_traits::ToPrimitive;
+use rand::thread_rng;
+
+const SMALL_PRIMES: [u8; 54] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251,
+];
+// Miller-Rabin with these bases is exact for n < 3,317,044,064,679,887,385,961,981
+const DETERMINISTIC_BASES: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrimalityConfig {
+    // Miller-Rabin rounds with random bases, on top of the fixed base-2 round
+    pub rounds: usize,
+    // Strong Lucas probable-prime test (Selfridge parameters)
+    pub lucas: bool,
+    // Exact answers below 2^64 instead of the probabilistic path
+    pub deterministic_small: bool,
+}
+
+impl PrimalityConfig {
+    // Miller-Rabin only: base 2 plus `rounds` random bases
+    pub fn miller_rabin(rounds: usize) -> Self {
+        PrimalityConfig {
+            rounds,
+            lucas: false,
+            deterministic_small: true,
+        }
+    }
+
+    pub fn bpsw() -> Self {
+        PrimalityConfig {
+            rounds: 0,
+            lucas: true,
+            deterministic_small: true,
+        }
+    }
+
+    // Baillie-PSW plus one random-base round; what key generation and validation use
+    pub fn strict() -> Self {
+        PrimalityConfig {
+            rounds: 1,
+            ..PrimalityConfig::bpsw()
+        }
+    }
+
+    pub fn with_rounds(mut self, rounds: usize) -> Self {
+        self.rounds = rounds;
+        self
+    }
+
+    pub fn with_lucas(mut self, lucas: bool) -> Self {
+        self.lucas = lucas;
+        self
+    }
+
+    pub fn with_deterministic_small(mut self, deterministic_small: bool) -> Self {
+        self.deterministic_small = deterministic_small;
+        self
+    }
+}
+
+impl Default for PrimalityConfig {
+    fn default() -> Self {
+        PrimalityConfig::strict()
+    }
+}
+
+pub fn is_probable_prime(n: &BigUint, config: &PrimalityConfig) -> bool {
+    if let Some(small) = n.to_u8() {
+        return SMALL_PRIMES.contains(&small);
+    }
+    for &p in &SMALL_PRIMES {
+        if (n % p).to_u8() == Some(0) {
+            return false;
+        }
+    }
+    // No factor below 256, so anything below 256^2 is prime
+    if n.bits() <= 16 {
+        return true;
+    }
+
+    if config.deterministic_small && n.bits() <= 64 {
+        return SMALL_PRIMES[..DETERMINISTIC_BASES]
+            .iter()
+            .all(|&base| n.is_sprp(BigUint::from(base)));
+    }
+
+    if !n.is_sprp(BigUint::from(2u8)) {
+        return false;
+    }
+    let mut rng = thread_rng();
+    let upper = n - 1u8;
+    for _ in 0..config.rounds {
+        let base = rng.gen_biguint_range(&BigUint::from(2u8), &upper);
+        if !n.is_sprp(base) {
+            return false;
+        }
+    }
+    !config.lucas || n.is_slprp(None, None)
+}
+
+// Convenience for callers holding a BigInt; negative numbers are never prime
+pub fn is_probable_prime_int(n: &BigInt, config: &PrimalityConfig) -> bool {
+    n.to_biguint().is_some_and(|n| is_probable_prime(&n, config))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_traits::One;
+
+    fn sieve(limit: usize) -> Vec<bool> {
+        let mut prime = vec![true; limit];
+        prime[0] = false;
+        prime[1] = false;
+        for i in 2..limit {
+            if prime[i] {
+                for multiple in (i * i..limit).step_by(i) {
+                    prime[multiple] = false;
+                }
+            }
+        }
+        prime
+    }
+
+    #[test]
+    fn test_matches_sieve_for_small_numbers() {
+        let expected = sieve(100_000);
+        let configs = [
+            PrimalityConfig::strict(),
+            PrimalityConfig::miller_rabin(0),
+            // Base 2 plus Lucas, with nothing random in it
+            PrimalityConfig::bpsw().with_deterministic_small(false),
+        ];
+        for config in &configs {
+            for (n, &is_prime) in expected.iter().enumerate() {
+                assert_eq!(is_probable_prime(&BigUint::from(n), config), is_prime, "{} with {:?}", n, config);
+            }
+        }
+    }
+
+    #[test]
+    fn test_strong_pseudoprime_needs_more_than_base_two() {
+        // 829 * 1657: passes Miller-Rabin to bases 2 and 3, with no factor below 256
+        let pseudoprime = BigUint::from(1_373_653u32);
+        let base_two_only = PrimalityConfig::miller_rabin(0).with_deterministic_small(false);
+        assert!(is_probable_prime(&pseudoprime, &base_two_only), "A single base-2 round is fooled");
+        assert!(!is_probable_prime(&pseudoprime, &base_two_only.with_lucas(true)), "Lucas catches it");
+        assert!(!is_probable_prime(&pseudoprime, &PrimalityConfig::miller_rabin(0)), "Fixed bases catch it");
+    }
+
+    #[test]
+    fn test_large_numbers() {
+        let mersenne = |e: usize| (BigUint::one() << e) - 1u8;
+        for config in [PrimalityConfig::strict(), PrimalityConfig::miller_rabin(20), PrimalityConfig::bpsw()] {
+            assert!(is_probable_prime(&mersenne(127), &config));
+            assert!(is_probable_prime(&mersenne(61), &config));
+            assert!(!is_probable_prime(&(mersenne(61) * mersenne(89)), &config));
+            assert!(!is_probable_prime(&mersenne(67), &config), "2^67 - 1 = 193707721 * 761838257287");
+        }
+        assert!(!is_probable_prime_int(&BigInt::from(-7), &PrimalityConfig::strict()));
+    }
+}
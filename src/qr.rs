@@ -0,0 +1,169 @@
+// QR codes for exchanging armored public keys in person. The qrcode crate encodes at error
+// correction level M in the smallest version that fits, rqrr finds and decodes codes in
+// images (photos as well as screenshots), and the png crate reads and writes the images. This
+// module only converts between them and the key types.
+
+use crate::error::RabinError;
+use crate::keys::PublicKey;
+use qrcode::{Color, EcLevel};
+
+// Light border around the symbol, in modules
+const QUIET_ZONE: usize = 4;
+// Largest image decode_png accepts, in bytes of decoded pixel data
+const MAX_IMAGE_BYTES: usize = 1 << 26;
+
+pub struct QrCode(qrcode::QrCode);
+
+pub fn encode(data: &[u8]) -> Result<QrCode, RabinError> {
+    qrcode::QrCode::with_error_correction_level(data, EcLevel::M)
+        .map(QrCode)
+        .map_err(|_| RabinError::InvalidQrCode("data too long for a QR code"))
+}
+
+impl QrCode {
+    pub fn size(&self) -> usize {
+        self.0.width()
+    }
+
+    // Coordinates outside the symbol read as light, like the quiet zone
+    pub fn is_dark(&self, x: isize, y: isize) -> bool {
+        let size = self.size() as isize;
+        (0..size).contains(&x) && (0..size).contains(&y) && self.0[(x as usize, y as usize)] == Color::Dark
+    }
+
+    // Two modules per character cell using half blocks. Light modules are the ones drawn, so
+    // the code scans on the usual dark terminal background.
+    pub fn to_text(&self) -> String {
+        let quiet = QUIET_ZONE as isize;
+        let size = self.size() as isize;
+        let mut out = String::new();
+        for y in (-quiet..size + quiet).step_by(2) {
+            for x in -quiet..size + quiet {
+                out.push(match (!self.is_dark(x, y), !self.is_dark(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    // 8-bit grayscale, `scale` pixels per module, with the quiet zone
+    pub fn to_png(&self, scale: usize) -> Vec<u8> {
+        let side = (self.size() + 2 * QUIET_ZONE) * scale;
+        let mut pixels = Vec::with_capacity(side * side);
+        for py in 0..side {
+            for px in 0..side {
+                let x = (px / scale) as isize - QUIET_ZONE as isize;
+                let y = (py / scale) as isize - QUIET_ZONE as isize;
+                pixels.push(if self.is_dark(x, y) { 0 } else { 255 });
+            }
+        }
+
+        let mut out = Vec::new();
+        let mut encoder = png::Encoder::new(&mut out, side as u32, side as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().expect("writing to memory cannot fail");
+        writer.write_image_data(&pixels).expect("the pixels match the header");
+        writer.finish().expect("writing to memory cannot fail");
+        out
+    }
+}
+
+// Luminance of each pixel of a PNG, 0 = black, after expanding palettes and low bit depths
+fn read_gray(bytes: &[u8]) -> Result<(usize, usize, Vec<u8>), RabinError> {
+    let mut decoder = png::Decoder::new_with_limits(bytes, png::Limits { bytes: MAX_IMAGE_BYTES });
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|_| RabinError::MalformedPng("unreadable PNG"))?;
+    let mut buffer = vec![0u8; reader.output_buffer_size()];
+    let frame = reader
+        .next_frame(&mut buffer)
+        .map_err(|_| RabinError::MalformedPng("unreadable image data"))?;
+    let channels = frame.color_type.samples();
+    // Transparent pixels are read against a white background
+    let luminance = |pixel: &[u8]| -> u8 {
+        let (value, alpha) = match pixel {
+            [gray] => (*gray as u32, 255),
+            [gray, alpha] => (*gray as u32, *alpha as u32),
+            [r, g, b] => ((*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000, 255),
+            [r, g, b, alpha] => ((*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000, *alpha as u32),
+            _ => unreachable!("PNG pixels have one to four samples"),
+        };
+        ((value * alpha + 255 * (255 - alpha)) / 255) as u8
+    };
+    let pixels = buffer[..frame.buffer_size()].chunks_exact(channels).map(luminance).collect();
+    Ok((frame.width as usize, frame.height as usize, pixels))
+}
+
+// The payload of the first QR code found in a PNG image
+pub fn decode_png(bytes: &[u8]) -> Result<Vec<u8>, RabinError> {
+    let (width, height, pixels) = read_gray(bytes)?;
+    let mut image = rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| pixels[y * width + x]);
+    let grid = image
+        .detect_grids()
+        .into_iter()
+        .next()
+        .ok_or(RabinError::InvalidQrCode("no QR code found in the image"))?;
+    let mut payload = Vec::new();
+    grid.decode_to(&mut payload)
+        .map_err(|_| RabinError::InvalidQrCode("unreadable QR code"))?;
+    Ok(payload)
+}
+
+impl PublicKey {
+    pub fn to_qr(&self) -> Result<QrCode, RabinError> {
+        encode(self.to_pem().as_bytes())
+    }
+
+    pub fn from_qr_png(bytes: &[u8]) -> Result<Self, RabinError> {
+        let text = String::from_utf8(decode_png(bytes)?)
+            .map_err(|_| RabinError::InvalidQrCode("payload is not UTF-8"))?;
+        PublicKey::from_pem(&text)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::PrivateKey;
+
+    #[test]
+    fn test_qr_round_trip_through_png() {
+        for len in [1, 14, 15, 300, 1200] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 7 % 256) as u8).collect();
+            let code = encode(&data).unwrap();
+            assert_eq!(decode_png(&code.to_png(3)).unwrap(), data, "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn test_public_key_qr_round_trip() {
+        let key = PrivateKey::generate(256).public_key();
+        let png = key.to_qr().unwrap().to_png(4);
+        assert_eq!(PublicKey::from_qr_png(&png).unwrap(), key);
+    }
+
+    #[test]
+    fn test_rejects_images_without_a_code() {
+        let mut blank = Vec::new();
+        let mut encoder = png::Encoder::new(&mut blank, 64, 64);
+        encoder.set_color(png::ColorType::Grayscale);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[255; 64 * 64]).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(decode_png(&blank), Err(RabinError::InvalidQrCode("no QR code found in the image")));
+        assert_eq!(decode_png(b"not a png"), Err(RabinError::MalformedPng("unreadable PNG")));
+    }
+
+    #[test]
+    fn test_rejects_data_too_long_for_a_code() {
+        // Level M holds at most 2331 bytes
+        assert!(encode(&[0u8; 2331]).is_ok());
+        assert!(encode(&[0u8; 2332]).is_err());
+    }
+}
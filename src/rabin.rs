@@ -0,0 +1,945 @@
+use log::info;
+use num_bigint::{BigInt, Sign};
+use num_bigint::BigUint;
+use num_bigint::RandBigInt;
+use num_integer::Integer;
+use num_traits::{One, ToPrimitive, Zero};
+use rand::rngs::OsRng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::hint::black_box;
+use std::sync::{Mutex, OnceLock};
+
+use crate::ct::ct_eq;
+use crate::encoding::{bytes2num, modulus_len, num2bytes, ByteOrder, Codec};
+use crate::error::RabinError;
+use crate::hash::sha256;
+
+// Kept here for existing callers; the implementations live in the math module
+pub use crate::math::{gcd, mod_inverse};
+use crate::math::sqrt_mod_prime;
+use crate::montgomery::modpow;
+use crate::primality::{is_probable_prime, PrimalityConfig};
+use crate::redact::secret;
+use crate::secret::{Secret, Wipe};
+
+pub fn gen_prime(bit_size: usize) -> BigUint {
+    gen_prime_with_rng(&mut OsRng, bit_size)
+}
+
+// Same as gen_prime, but draws every candidate from the given generator
+pub fn gen_prime_with_rng<R: Rng>(rng: &mut R, bit_size: usize) -> BigUint {
+    search_prime(rng, bit_size).0
+}
+
+// Odd primes below this bound are used to weed out candidates before the full test
+const SIEVE_LIMIT: u32 = 2048;
+
+fn sieve_primes() -> &'static [u32] {
+    static PRIMES: OnceLock<Vec<u32>> = OnceLock::new();
+    PRIMES.get_or_init(|| {
+        (3..SIEVE_LIMIT)
+            .step_by(2)
+            .filter(|&c| (3..).step_by(2).take_while(|d| d * d <= c).all(|d| c % d != 0))
+            .collect()
+    })
+}
+
+// Shared by everything taking part in one prime search: a flag that ends it once enough primes
+// are in, an optional flag the caller sets to cancel it, the running count of candidates put
+// through the full primality test, and an optional callback that hears about every change to
+// that count or to the number of primes found.
+#[derive(Default)]
+pub(crate) struct SearchControl<'a> {
+    done: AtomicBool,
+    cancelled: Option<&'a AtomicBool>,
+    tested: AtomicU64,
+    found: AtomicUsize,
+    progress: Option<&'a (dyn Fn(u64, usize) + Sync)>,
+}
+
+impl<'a> SearchControl<'a> {
+    pub(crate) fn new() -> Self {
+        SearchControl::default()
+    }
+
+    pub(crate) fn with_cancel(mut self, cancelled: &'a AtomicBool) -> Self {
+        self.cancelled = Some(cancelled);
+        self
+    }
+
+    // Called with (candidates tested, primes found)
+    pub(crate) fn with_progress(mut self, progress: &'a (dyn Fn(u64, usize) + Sync)) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub(crate) fn stopped(&self) -> bool {
+        self.done.load(Ordering::Relaxed) || self.is_cancelled()
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.is_some_and(|cancelled| cancelled.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn tested(&self) -> u64 {
+        self.tested.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_tested(&self, candidates: u64) {
+        let tested = self.tested.fetch_add(candidates, Ordering::Relaxed) + candidates;
+        if let Some(progress) = self.progress {
+            progress(tested, self.found.load(Ordering::Relaxed));
+        }
+    }
+
+    fn record_prime(&self) {
+        let found = self.found.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some(progress) = self.progress {
+            progress(self.tested(), found);
+        }
+    }
+}
+
+// Incremental search for a prime ≡ 3 (mod 4) of exactly bit_size bits. The starting point has
+// its top bit and its two low bits set, so every candidate has the right size and congruence
+// by construction. From there the search walks up in steps of 4, keeping the remainders by the
+// small primes up to date with word arithmetic; only candidates none of them divides reach
+// the full primality test. If the walk leaves the bit size, a fresh start is drawn.
+//
+// Returns the prime and the number of candidates that went through the full test.
+pub(crate) fn search_prime<R: Rng + ?Sized>(rng: &mut R, bit_size: usize) -> (BigUint, u64) {
+    let control = SearchControl::new();
+    let prime = search_prime_until(rng, bit_size, &control).expect("the search was not stopped");
+    (prime, control.tested())
+}
+
+// search_prime that counts its candidates in `control` and gives up, returning None, once the
+// control says stop; that is checked between candidates
+pub(crate) fn search_prime_until<R: Rng + ?Sized>(
+    rng: &mut R,
+    bit_size: usize,
+    control: &SearchControl,
+) -> Option<BigUint> {
+    assert!(bit_size >= 2, "no prime ≡ 3 (mod 4) has fewer than 2 bits");
+    let config = PrimalityConfig::strict();
+    let primes = sieve_primes();
+    loop {
+        let mut candidate = rng.gen_biguint(bit_size as u64);
+        candidate.set_bit(bit_size as u64 - 1, true);
+        candidate.set_bit(1, true);
+        candidate.set_bit(0, true);
+        let mut remainders: Vec<u32> = primes
+            .iter()
+            .map(|&p| (&candidate % p).to_u32().unwrap())
+            .collect();
+
+        while candidate.bits() as usize == bit_size {
+            if control.stopped() {
+                return None;
+            }
+            // A candidate below the limit may be one of the sieving primes itself
+            let survives = remainders.iter().all(|&r| r != 0) || candidate.bits() <= SIEVE_LIMIT.ilog2() as u64;
+            if survives {
+                control.record_tested(1);
+                if is_probable_prime(&candidate, &config) {
+                    return Some(candidate);
+                }
+            }
+            candidate += 4u8;
+            for (r, &p) in remainders.iter_mut().zip(primes) {
+                *r = (*r + 4) % p;
+            }
+        }
+    }
+}
+
+// One search per thread in rayon's pool, and at least two
+pub fn default_workers() -> usize {
+    rayon::current_num_threads().max(2)
+}
+
+// Runs `workers` prime searches side by side on rayon's pool until `count` distinct primes are
+// in, then stops every search that is still going. `find` gets the shared control and returns
+// None once it says stop. Nothing waits for an unlucky search that happens to draw a long run
+// of composites: whichever workers finish first supply the primes. The gain depends on the
+// pool having threads to spare; benches/keygen_workers.rs measures it.
+//
+// Returns the primes in the order they were found; fewer than `count` only if the search was
+// cancelled.
+pub(crate) fn race_for_primes<F>(count: usize, workers: usize, control: &SearchControl, find: F) -> Vec<BigUint>
+where
+    F: Fn(&SearchControl) -> Option<BigUint> + Sync,
+{
+    let found = Mutex::new(Vec::with_capacity(count));
+    rayon::scope(|scope| {
+        for _ in 0..workers.max(1) {
+            scope.spawn(|_| {
+                while !control.stopped() {
+                    let Some(prime) = find(control) else {
+                        break;
+                    };
+                    let mut primes = found.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                    if primes.len() < count && primes.iter().all(|other| far_apart(other, &prime)) {
+                        primes.push(prime);
+                        control.record_prime();
+                        if primes.len() == count {
+                            control.done.store(true, Ordering::Relaxed);
+                        }
+                    }
+                }
+            });
+        }
+    });
+    found.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// FIPS 186-4 asks for |p - q| > 2^(bits - 100) between primes of `bits` bits. Closer primes
+// sit near sqrt(n), where Fermat's method finds them in a few steps, and equal ones make n a
+// perfect square. Teaching-sized primes get half their bits as the bound instead.
+pub(crate) fn min_prime_distance_bits(bits: u64) -> u64 {
+    if bits > 200 {
+        bits - 100
+    } else {
+        bits / 2
+    }
+}
+
+// Whether two primes may share a key. Generation redraws the newer prime until this holds; the
+// chance of that ever happening with random primes is negligible, but it costs nothing to rule out.
+pub(crate) fn far_apart(p: &BigUint, q: &BigUint) -> bool {
+    let distance = if p > q { p - q } else { q - p };
+    distance.bits() > min_prime_distance_bits(p.bits().max(q.bits()))
+}
+
+pub fn generate_keypair(bit_size: usize) -> (BigInt, BigInt, BigInt) {
+    info!("Starting key generation with bit size {}", bit_size);
+
+    // Search for the two primes on all workers at once
+    let primes = race_for_primes(2, default_workers(), &SearchControl::new(), |control| {
+        search_prime_until(&mut OsRng, bit_size, control)
+    });
+    let [p, q]: [BigUint; 2] = primes.try_into().expect("exactly two primes were found");
+    let (p, q) = (BigInt::from(p), BigInt::from(q));
+
+    let n = &p * &q; // Compute modulus n
+    (n, p, q)
+}
+
+// Deterministic variant: the seed is hashed into a ChaCha20 key, and p and q are drawn one
+// after the other from that stream, so the same seed and bit size always give the same key.
+// Only as strong as the seed; never feed it anything guessable.
+pub fn generate_keypair_from_seed(seed: &[u8], bit_size: usize) -> (BigInt, BigInt, BigInt) {
+    info!("Starting seeded key generation with bit size {}", bit_size);
+
+    let mut rng = ChaCha20Rng::from_seed(sha256(seed));
+    let p = gen_prime_with_rng(&mut rng, bit_size);
+    let mut q = gen_prime_with_rng(&mut rng, bit_size);
+    while !far_apart(&p, &q) {
+        q = gen_prime_with_rng(&mut rng, bit_size);
+    }
+    let (p, q) = (BigInt::from(p), BigInt::from(q));
+
+    let n = &p * &q;
+    (n, p, q)
+}
+
+// A plaintext must satisfy 0 <= m < n: anything larger comes back from decryption reduced
+// modulo n, as some other number. With `require_unit` the message must also be coprime to n;
+// one that is not reveals the key, since gcd(m, n) is then p or q.
+pub fn check_message(message: &BigInt, n: &BigInt, require_unit: bool) -> Result<(), RabinError> {
+    if message.sign() == Sign::Minus {
+        return Err(RabinError::MessageOutOfRange);
+    }
+    if message >= n {
+        return Err(RabinError::MessageTooLarge { bits: message.bits(), modulus_bits: n.bits() });
+    }
+    if require_unit && !gcd(message, n).is_one() {
+        return Err(RabinError::NotInvertible);
+    }
+    Ok(())
+}
+
+pub fn encrypt(message: &BigInt, n: &BigInt) -> Result<BigInt, RabinError> {
+    check_message(message, n, false)?;
+    #[cfg(feature = "fast-math")]
+    let ciphertext = crate::gmp::square_mod(message, n);
+    #[cfg(not(feature = "fast-math"))]
+    let ciphertext = (message * message) % n;
+    Ok(ciphertext)
+}
+
+pub fn decrypt(ciphertext: &BigInt, p: &BigInt, q: &BigInt) -> Result<Vec<BigInt>, RabinError> {
+    let n = p * q;
+    let candidates = compute_candidates(ciphertext, p, q, &n)?;
+
+    // just return the candidates for now, later we could experiment with padding
+    Ok(candidates)
+}
+
+// Text in and out through any codec. The encoded message must stay below n, or it would come
+// back reduced mod n as some other text.
+pub fn encrypt_str<C: Codec + ?Sized>(text: &str, n: &BigInt, codec: &C) -> Result<BigInt, RabinError> {
+    encrypt(&codec.encode(text)?, n)
+}
+
+// The candidates that decode under the codec, in root order; the others are dropped
+pub fn decrypt_str<C: Codec + ?Sized>(
+    ciphertext: &BigInt,
+    p: &BigInt,
+    q: &BigInt,
+    codec: &C,
+) -> Result<Vec<String>, RabinError> {
+    let candidates = decrypt(ciphertext, p, q)?;
+    Ok(candidates.iter().filter_map(|candidate| codec.decode(candidate).ok()).collect())
+}
+
+// Bytes of SHA-256 carried in the low end of a tagged message
+pub const TAG_LEN: usize = 8;
+
+// Textbook decryption is a chosen-ciphertext oracle: square a random x, ask for a root, and a
+// root other than ±x factors n half of the time. Tagged messages close that off. The message
+// is followed by TAG_LEN bytes of SHA-256 over it, and strict decryption releases a root only
+// if its tag verifies. The square of a random x has no root with a valid tag, so nothing is
+// released. The tag is computed over the message at a fixed width (that of n, less the tag),
+// so that checking a candidate does not depend on the value's length.
+fn message_tag(message_bytes: &[u8]) -> [u8; TAG_LEN] {
+    sha256(message_bytes)[..TAG_LEN].try_into().unwrap()
+}
+
+pub fn tag_message(message: &BigInt, n: &BigInt) -> Result<BigInt, RabinError> {
+    let width = modulus_len(n).saturating_sub(TAG_LEN);
+    let bytes = num2bytes(message, ByteOrder::BigEndian, Some(width)).ok_or_else(|| match message.sign() {
+        Sign::Minus => RabinError::MessageOutOfRange,
+        _ => RabinError::MessageTooLarge {
+            bits: message.bits() + 8 * TAG_LEN as u64,
+            modulus_bits: n.bits(),
+        },
+    })?;
+    let tagged = bytes2num(&[bytes.as_slice(), &message_tag(&bytes)].concat(), ByteOrder::BigEndian);
+    check_message(&tagged, n, false)?;
+    Ok(tagged)
+}
+
+fn tag_verifies(candidate: &BigInt, n: &BigInt) -> bool {
+    let Some(bytes) = num2bytes(candidate, ByteOrder::BigEndian, Some(modulus_len(n))) else {
+        return false;
+    };
+    let Some(split) = bytes.len().checked_sub(TAG_LEN) else {
+        return false;
+    };
+    let (message, tag) = bytes.split_at(split);
+    ct_eq(&message_tag(message), tag)
+}
+
+pub fn encrypt_tagged(message: &BigInt, n: &BigInt) -> Result<BigInt, RabinError> {
+    encrypt(&tag_message(message, n)?, n)
+}
+
+// Strict decryption: the one root whose tag verifies, with the tag removed. Every other root
+// is withheld, and a ciphertext with no such root fails with DecryptionFailed.
+pub fn decrypt_tagged(ciphertext: &BigInt, p: &BigInt, q: &BigInt) -> Result<BigInt, RabinError> {
+    let n = p * q;
+    let candidates = Secret::new(decrypt(ciphertext, p, q)?);
+    let tagged = select_candidate(&candidates, &n, |candidate| tag_verifies(candidate, &n))
+        .ok_or(RabinError::DecryptionFailed)?;
+    Ok(tagged >> (8 * TAG_LEN))
+}
+
+pub fn encrypt_str_tagged<C: Codec + ?Sized>(text: &str, n: &BigInt, codec: &C) -> Result<BigInt, RabinError> {
+    encrypt_tagged(&codec.encode(text)?, n)
+}
+
+pub fn decrypt_str_tagged<C: Codec + ?Sized>(
+    ciphertext: &BigInt,
+    p: &BigInt,
+    q: &BigInt,
+    codec: &C,
+) -> Result<String, RabinError> {
+    Ok(codec.decode(&decrypt_tagged(ciphertext, p, q)?)?)
+}
+
+// For p ≡ 3 (mod 4) the root is ciphertext^((p + 1) / 4) mod p, taken without checking that
+// the ciphertext is a square. Other primes need Tonelli-Shanks, which fails on non-squares.
+pub(crate) fn root_mod_prime(ciphertext: &BigInt, p: &BigInt) -> Result<BigInt, RabinError> {
+    root_with(ciphertext, p, &root_exponent(p))
+}
+
+// Per-key constants for decryption, worth computing once: the root exponents (p + 1) / 4 and
+// (q + 1) / 4 (only for primes ≡ 3 mod 4; others go through Tonelli-Shanks) and the CRT
+// coefficient yq = q^-1 mod p.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecryptionParams {
+    dp: Option<BigInt>,
+    dq: Option<BigInt>,
+    yq: BigInt,
+}
+
+impl Drop for DecryptionParams {
+    fn drop(&mut self) {
+        self.dp.wipe();
+        self.dq.wipe();
+        self.yq.wipe();
+    }
+}
+
+// Primes are positive, so the two low bits give the residue modulo 4
+fn root_exponent(p: &BigInt) -> Option<BigInt> {
+    (p.bit(0) && p.bit(1)).then(|| (p + 1u8) >> 2)
+}
+
+impl DecryptionParams {
+    // Fails only if p and q share a factor (e.g. p == q), in which case no unique CRT
+    // combination exists
+    pub fn new(p: &BigInt, q: &BigInt) -> Result<Self, RabinError> {
+        Ok(DecryptionParams {
+            dp: root_exponent(p),
+            dq: root_exponent(q),
+            yq: mod_inverse(q, p)?,
+        })
+    }
+}
+
+// A ciphertext with no root modulo one prime has none modulo n either, and is reported as such
+fn root_with(ciphertext: &BigInt, p: &BigInt, exponent: &Option<BigInt>) -> Result<BigInt, RabinError> {
+    match exponent {
+        Some(exponent) => Ok(modpow(ciphertext, exponent, p)),
+        None => sqrt_mod_prime(ciphertext, p).map_err(|err| match err {
+            RabinError::NotQuadraticResidue => RabinError::NotAResidue,
+            other => other,
+        }),
+    }
+}
+
+pub fn compute_candidates(
+    ciphertext: &BigInt,
+    p: &BigInt,
+    q: &BigInt,
+    n: &BigInt,
+) -> Result<Vec<BigInt>, RabinError> {
+    compute_candidates_with(ciphertext, p, q, n, &DecryptionParams::new(p, q)?)
+}
+
+pub fn compute_candidates_with(
+    ciphertext: &BigInt,
+    p: &BigInt,
+    q: &BigInt,
+    n: &BigInt,
+    params: &DecryptionParams,
+) -> Result<Vec<BigInt>, RabinError> {
+    check_ciphertext(ciphertext, n)?;
+    // Compute mp and mq, one square root of 'ciphertext' modulo 'p' and 'q' each. The two
+    // exponentiations are independent, so they run side by side on rayon's pool.
+    let (mp, mq) = rayon::join(
+        || root_with(ciphertext, p, &params.dp),
+        || root_with(ciphertext, q, &params.dq),
+    );
+    // Both roots, and everything derived from them below, are wiped on the way out
+    let (mp, mq) = (Secret::new(mp?), Secret::new(mq?));
+
+    // Log the results for debugging; redacted unless secret logging is on
+    log::debug!("mp (mod p): {}", secret(&*mp));
+    log::debug!("mq (mod q): {}", secret(&*mq));
+
+    // Combine results using the Chinese Remainder Theorem (CRT) in Garner's form: the root
+    // that is mp modulo p and mq modulo q is mq + q * ((mp - mq) * yq mod p). Every product
+    // stays at the size of p, and the sum is already below n, so nothing is reduced modulo n.
+    let h = Secret::new(&*mp - &*mq);
+    let h = Secret::new(&*h * &params.yq);
+    let h = Secret::new(h.mod_floor(p));
+    // Compute one possible candidate solution r1
+    let r1 = &*h * q + &*mq;
+    // The root exponent yields a number for any input; only for a square modulo both primes do
+    // the roots square back. Without this check a non-residue would give four candidates that
+    // encrypt to something else.
+    if &(&r1 * &r1 % n) != ciphertext {
+        return Err(RabinError::NotAResidue);
+    }
+    // Compute the second candidate by subtracting r1 from n (zero is its own negative)
+    let r2 = if r1.is_zero() { BigInt::zero() } else { n - &r1 };
+    // Compute third candidate r3 by negating only mp: -mp - mq = -(mp + mq)
+    let h = Secret::new(&*mp + &*mq);
+    let h = Secret::new(&*h * &params.yq);
+    let h = Secret::new(h.mod_floor(p));
+    let h = Secret::new(if h.is_zero() { BigInt::zero() } else { p - &*h });
+    let r3 = &*h * q + &*mq;
+    // r1 was squared back above; r3 comes from a separate CRT combination
+    verify_root(&r3, ciphertext, n)?;
+    // Compute the fourth candidate by subtracting r3 from n
+    let r4 = if r3.is_zero() { BigInt::zero() } else { n - &r3 };
+
+    // Log all four candidates for debugging
+    log::debug!(
+        "Candidates: r1 = {}, r2 = {}, r3 = {}, r4 = {}",
+        secret(&r1),
+        secret(&r2),
+        secret(&r3),
+        secret(&r4)
+    );
+
+    // Return all four potential roots as a vector
+    Ok(vec![r1, r2, r3, r4])
+}
+
+// Fault check for anything computed with the private key. A glitch during the computation (a
+// flipped bit, a skipped step in one half of the CRT) can give a root that is right modulo p
+// but wrong modulo q, and releasing it hands over a factor: gcd(root^2 - square, n). Squaring
+// the result again before it leaves catches that.
+pub fn verify_root(root: &BigInt, square: &BigInt, n: &BigInt) -> Result<(), RabinError> {
+    if &(root * root % n) != square {
+        return Err(RabinError::FaultDetected);
+    }
+    Ok(())
+}
+
+// A ciphertext is a square modulo n, so it lies in [0, n)
+pub fn check_ciphertext(ciphertext: &BigInt, n: &BigInt) -> Result<(), RabinError> {
+    if ciphertext.sign() == Sign::Minus || ciphertext >= n {
+        return Err(RabinError::MessageOutOfRange);
+    }
+    Ok(())
+}
+
+// Hardened decryption. The root exponentiations take time that depends on the value being
+// raised, so timing decrypt on chosen ciphertexts leaks information about p and q. Here the
+// ciphertext is first multiplied by r^2 for a fresh random r coprime to n: the roots of
+// c * r^2 are the roots of c times r, so the modpow work runs on a value the caller cannot
+// predict, and multiplying by r^-1 afterwards gives the same four candidates as decrypt (in
+// an order that depends on r). num-bigint arithmetic is itself not constant-time; this removes
+// the correlation with the input, not every leak.
+pub fn decrypt_blinded<R: Rng + ?Sized>(
+    rng: &mut R,
+    ciphertext: &BigInt,
+    p: &BigInt,
+    q: &BigInt,
+    params: &DecryptionParams,
+) -> Result<Vec<BigInt>, RabinError> {
+    let n = p * q;
+    check_ciphertext(ciphertext, &n)?;
+    let r = loop {
+        let r = rng.gen_bigint_range(&BigInt::from(2u8), &n);
+        if gcd(&r, &n) == BigInt::from(1u8) {
+            break r;
+        }
+    };
+    let r = Secret::new(r);
+    let r_inverse = Secret::new(mod_inverse(&r, &n)?);
+    let blinded = (ciphertext * &*r).mod_floor(&n) * &*r % &n;
+    let roots = Secret::new(compute_candidates_with(&blinded, p, q, &n, params)?);
+    Ok(roots.iter().map(|root| root * &*r_inverse % &n).collect())
+}
+
+// The first candidate the check accepts, chosen without branching on which one it was: every
+// candidate is checked, and the winner is copied out through byte masks, so the running time
+// does not depend on the position of the valid root. The check itself should be branch-free
+// too (a hash comparison over fixed-width bytes, not an early-exit parser).
+pub fn select_candidate<F>(candidates: &[BigInt], n: &BigInt, is_valid: F) -> Option<BigInt>
+where
+    F: Fn(&BigInt) -> bool,
+{
+    let width = modulus_len(n);
+    let mut chosen = vec![0u8; width];
+    let mut found = 0u8;
+    for candidate in candidates {
+        let valid = black_box(u8::from(is_valid(candidate)));
+        // 0xff for the first valid candidate, 0 for the rest
+        let take = (valid & !found).wrapping_neg();
+        let bytes = num2bytes(candidate, ByteOrder::BigEndian, Some(width))?;
+        for (out, byte) in chosen.iter_mut().zip(bytes) {
+            *out |= byte & take;
+        }
+        found |= valid;
+    }
+    (found == 1).then(|| bytes2num(&chosen, ByteOrder::BigEndian))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_compute_candidates() {
+        let (n, p, q) = generate_keypair(512);
+        // Any square will do; other numbers are not ciphertexts
+        let ciphertext = BigInt::from(123456u32).pow(2);
+
+        // Generate decryption candidates
+        let candidates = compute_candidates(&ciphertext, &p, &q, &n).unwrap();
+
+        assert_eq!(
+            candidates.len(),
+            4,
+            "Compute_candidates should return exactly four candidates"
+        );
+
+        // Ensure candidates are unique
+        let unique_candidates: std::collections::HashSet<_> = candidates.iter().collect();
+        assert_eq!(
+            unique_candidates.len(),
+            4,
+            "Decryption candidates should be unique"
+        );
+    }
+
+    #[test]
+    fn test_decrypt_with_primes_one_mod_four() {
+        // 13 ≡ 17 ≡ 1 (mod 4), so both roots need Tonelli-Shanks
+        let (p, q) = (BigInt::from(13), BigInt::from(17));
+        let n = &p * &q;
+        let message = BigInt::from(100);
+        let candidates = decrypt(&encrypt(&message, &n).unwrap(), &p, &q).unwrap();
+        assert!(candidates.contains(&message), "The message should be among {:?}", candidates);
+
+        assert_eq!(decrypt(&BigInt::from(5), &p, &q), Err(RabinError::NotAResidue));
+    }
+
+    #[test]
+    fn test_decrypt_rejects_equal_primes() {
+        let p = BigInt::from(7);
+        assert_eq!(decrypt(&BigInt::from(4), &p, &p), Err(RabinError::NotInvertible));
+    }
+
+    #[test]
+    fn test_encrypt_checks_the_message_range() {
+        let n = BigInt::from(77);
+        assert_eq!(encrypt(&BigInt::from(76), &n), Ok(BigInt::from(1)));
+        assert_eq!(encrypt(&BigInt::from(-1), &n), Err(RabinError::MessageOutOfRange));
+        assert_eq!(encrypt(&n, &n), Err(RabinError::MessageTooLarge { bits: 7, modulus_bits: 7 }));
+        assert_eq!(encrypt(&BigInt::from(7), &n), Ok(BigInt::from(49)), "Units are only checked on request");
+        assert_eq!(check_message(&BigInt::from(7), &n, true), Err(RabinError::NotInvertible));
+        assert_eq!(check_message(&BigInt::from(8), &n, true), Ok(()));
+        let message = RabinError::MessageTooLarge { bits: 1024, modulus_bits: 512 }.to_string();
+        assert!(message.contains("block mode"), "{}", message);
+    }
+
+    #[test]
+    fn test_decrypt_rejects_bad_ciphertexts() {
+        // p = 7, q = 11: 3 is a square modulo 11 (5^2) but not modulo 7
+        let (p, q, n) = (BigInt::from(7), BigInt::from(11), BigInt::from(77));
+        assert_eq!(decrypt(&BigInt::from(-4), &p, &q), Err(RabinError::MessageOutOfRange));
+        assert_eq!(decrypt(&n, &p, &q), Err(RabinError::MessageOutOfRange));
+        assert_eq!(decrypt(&BigInt::from(3), &p, &q), Err(RabinError::NotAResidue));
+        // 2 is a square modulo 7 (3^2) but not modulo 11
+        assert_eq!(decrypt(&BigInt::from(2), &p, &q), Err(RabinError::NotAResidue));
+        let params = DecryptionParams::new(&p, &q).unwrap();
+        assert_eq!(
+            decrypt_blinded(&mut thread_rng(), &BigInt::from(3), &p, &q, &params),
+            Err(RabinError::NotAResidue)
+        );
+        assert!(decrypt(&BigInt::zero(), &p, &q).unwrap().contains(&BigInt::zero()));
+        assert!(decrypt(&BigInt::from(14), &p, &q).unwrap().contains(&BigInt::from(28)), "Shares a factor with n");
+    }
+
+    #[test]
+    fn test_faulty_roots_are_caught() {
+        let key = crate::keys::PrivateKey::generate(256);
+        let (p, q, n) = (key.p(), key.q(), key.n());
+        let ciphertext = encrypt(&BigInt::from(0xfa017u32), n).unwrap();
+        let root = decrypt(&ciphertext, p, q).unwrap().swap_remove(0);
+        assert_eq!(verify_root(&root, &ciphertext, n), Ok(()));
+
+        // A glitch in the half modulo q: still right modulo p, and that gives p away
+        let faulty = crate::math::crt(&[root.clone(), &root + 1], &[p.clone(), q.clone()]).unwrap();
+        assert_eq!(verify_root(&faulty, &ciphertext, n), Err(RabinError::FaultDetected));
+        assert_eq!(&gcd(&(&faulty * &faulty - &ciphertext), n), p);
+    }
+
+    #[test]
+    fn test_search_prime_size_and_congruence() {
+        let mut rng = thread_rng();
+        // Small sizes land among or below the sieving primes themselves
+        for bits in [2, 3, 5, 11, 12, 64, 256] {
+            let (prime, tested) = search_prime(&mut rng, bits);
+            assert_eq!(prime.bits() as usize, bits, "{} should have exactly {} bits", prime, bits);
+            assert_eq!(&prime % 4u8, BigUint::from(3u8));
+            assert!(is_probable_prime(&prime, &PrimalityConfig::strict()));
+            assert!(tested >= 1);
+        }
+    }
+
+    #[test]
+    fn test_race_for_primes() {
+        // 19, 23 and 31 are the only 5-bit primes ≡ 3 (mod 4)
+        let control = SearchControl::new();
+        let mut primes = race_for_primes(3, 4, &control, |control| search_prime_until(&mut thread_rng(), 5, control));
+        primes.sort();
+        assert_eq!(primes, [19u8, 23, 31].map(BigUint::from), "The primes should be distinct");
+        assert!(control.tested() >= 3);
+
+        let cancelled = AtomicBool::new(true);
+        let stopped = SearchControl::new().with_cancel(&cancelled);
+        assert_eq!(search_prime_until(&mut thread_rng(), 256, &stopped), None, "A stopped search gives up");
+        assert!(race_for_primes(2, 2, &stopped, |control| search_prime_until(&mut thread_rng(), 256, control)).is_empty());
+    }
+
+    #[test]
+    fn test_primes_must_be_far_apart() {
+        assert_eq!(min_prime_distance_bits(1024), 924);
+        assert_eq!(min_prime_distance_bits(64), 32);
+        let p = BigUint::from(0xffff_ffff_ffff_ffc5u64);
+        assert!(!far_apart(&p, &p));
+        assert!(!far_apart(&p, &BigUint::from(0xffff_ffff_ffff_ff59u64)), "Only 108 apart");
+        assert!(far_apart(&p, &BigUint::from(0xc000_0000_0000_0003u64)));
+
+        // A search that keeps finding the same prime must not fill both slots with it
+        let control = SearchControl::new();
+        let repeats = AtomicUsize::new(0);
+        let primes = race_for_primes(2, 1, &control, |_| {
+            match repeats.fetch_add(1, Ordering::Relaxed) {
+                0..=9 => Some(p.clone()),
+                _ => Some(BigUint::from(0xc000_0000_0000_0003u64)),
+            }
+        });
+        assert_eq!(primes, [p, BigUint::from(0xc000_0000_0000_0003u64)]);
+    }
+
+    #[test]
+    fn test_seeded_keypair_is_reproducible() {
+        let first = generate_keypair_from_seed(b"correct horse battery staple", 256);
+        let second = generate_keypair_from_seed(b"correct horse battery staple", 256);
+        assert_eq!(first, second, "The same seed should regenerate the same keypair");
+
+        let (n, p, q) = first;
+        assert_eq!(n, &p * &q);
+        assert_ne!(p, q);
+
+        let other = generate_keypair_from_seed(b"correct horse battery stapler", 256);
+        assert_ne!(other.0, n, "Different seeds should give different keys");
+    }
+
+    #[test]
+    fn test_decrypt_candidates() {
+        use std::collections::HashSet;
+
+        let (n, p, q) = generate_keypair(512);
+        let message = BigInt::from(123u32); // Arbitrary message for testing
+        let ciphertext = encrypt(&message, &n).unwrap();
+
+        // Decrypt the ciphertext
+        let candidates = decrypt(&ciphertext, &p, &q).unwrap();
+
+        // Verify the number of candidates
+        assert_eq!(
+            candidates.len(),
+            4,
+            "Decrypt should return exactly 4 candidates"
+        );
+
+        // Ensure all candidates are unique
+        let unique_candidates: HashSet<_> = candidates.iter().collect();
+        assert_eq!(
+            unique_candidates.len(),
+            4,
+            "Decryption candidates should be unique"
+        );
+
+        // Verify that each candidate squared modulo n equals the ciphertext
+        for candidate in &candidates {
+            let squared = (candidate * candidate) % &n;
+            assert_eq!(
+                squared, ciphertext,
+                "Each candidate squared modulo n should equal the ciphertext"
+            );
+        }
+    }
+
+    #[test]
+    fn test_encrypt_produces_expected_ciphertext() {
+        // Generate a keypair
+        let (n, _, _) = generate_keypair(512);
+
+        // Define a known message
+        let message = BigInt::from(123u32);
+
+        // Perform encryption
+        let ciphertext = encrypt(&message, &n).unwrap();
+
+        // Manually compute the expected ciphertext
+        let expected_ciphertext = (&message * &message) % &n;
+
+        // Verify that the produced ciphertext matches the expected value
+        assert_eq!(
+            ciphertext, expected_ciphertext,
+            "The ciphertext produced by encryption does not match the expected value"
+        );
+    }
+
+    #[test]
+    fn test_encrypt_with_string_encoding() {
+        use crate::encoding::str2num; // Ensure str2num is accessible
+        use crate::encoding::Alphabet;
+
+        // Generate a keypair
+        let (n, _, _) = generate_keypair(512);
+
+        // Define a known string message
+        let message_str = "TestMessage123";
+
+        // Encode the string into a number
+        let message_num = str2num(message_str, Alphabet::default_symbols())
+            .expect("Failed to convert string to number");
+
+        // Encrypt the encoded number
+        let ciphertext = encrypt(&message_num, &n).unwrap();
+
+        // Manually compute the expected ciphertext
+        let expected_ciphertext = (&message_num * &message_num) % &n;
+
+        // Verify that the produced ciphertext matches the expected value
+        assert_eq!(
+            ciphertext, expected_ciphertext,
+            "The ciphertext does not match the expected value after encoding"
+        );
+    }
+
+    #[test]
+    fn test_decrypt_exercise_message() {
+        use crate::encoding::{num2str, str2num, Alphabet};
+        use num_bigint::BigInt;
+
+        // Provided private key components
+        let p = BigInt::parse_bytes(
+            b"5081134225938911632501879835073274182691064608067531203259",
+            10,
+        )
+            .unwrap();
+        let q = BigInt::parse_bytes(
+            b"5258660163169151701715131756224662568205137498312501937487",
+            10,
+        )
+            .unwrap();
+        let n = &p * &q;
+
+        // Define the plaintext and encode it into a number
+        let expected_plaintext = "recommended website";
+        let plaintext_num = str2num(expected_plaintext, Alphabet::default_symbols())
+            .expect("Failed to convert plaintext to number");
+
+        // Encrypt the plaintext number to generate the ciphertext
+        let ciphertext = encrypt(&plaintext_num, &n).unwrap();
+
+        // Decrypt the ciphertext using the private key
+        let candidates = decrypt(&ciphertext, &p, &q).unwrap();
+
+        // Check if one of the decrypted candidates matches the original plaintext
+        let mut found_match = false;
+        for candidate in &candidates {
+            let decoded_text = num2str(candidate, Alphabet::default_symbols()).unwrap();
+            println!("Decrypted candidate: {}", decoded_text);
+
+            if decoded_text == expected_plaintext {
+                found_match = true;
+                break;
+            }
+        }
+
+        // Assert that at least one candidate matches the expected plaintext
+        assert!(
+            found_match,
+            "None of the decrypted candidates matched the expected plaintext"
+        );
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_message() {
+        use crate::encoding::{num2str, str2num, Alphabet};
+
+        // Generate keypair
+        let (n, p, q) = generate_keypair(512);
+
+        // Original plaintext message
+        let message_str = "Hello, Rabin!";
+        let message_num = str2num(message_str, Alphabet::default_symbols()).expect("Failed to convert string to number");
+
+        // Encrypt the message
+        let ciphertext = encrypt(&message_num, &n).unwrap();
+
+        // Decrypt the message
+        let candidates = decrypt(&ciphertext, &p, &q).unwrap();
+
+        // Check if one candidate matches the original message
+        let mut found_match = false;
+        for candidate in &candidates {
+            let decoded_text = num2str(candidate, Alphabet::default_symbols()).unwrap();
+            println!("Decrypted candidate: {}", decoded_text);
+            if decoded_text == message_str {
+                found_match = true;
+                break;
+            }
+        }
+
+        assert!(
+            found_match,
+            "None of the decrypted candidates matched the original message"
+        );
+    }
+
+    #[test]
+    fn test_encrypt_str_with_any_codec() {
+        use crate::encoding::{Alphabet, Codec, Hex, Utf8};
+
+        let (n, p, q) = generate_keypair(256);
+        let codecs: [(&dyn Codec, &str); 3] =
+            [(Alphabet::default_symbols(), "Hello, Rabin!"), (&Utf8, "Grüße 🦀"), (&Hex(None), "c0ffee")];
+        for (codec, text) in codecs {
+            let ciphertext = encrypt_str(text, &n, codec).unwrap();
+            let decoded = decrypt_str(&ciphertext, &p, &q, codec).unwrap();
+            assert!(decoded.iter().any(|candidate| candidate == text), "{:?} not among {:?}", text, decoded);
+        }
+
+        let too_long = "x".repeat(200);
+        assert!(matches!(encrypt_str(&too_long, &n, &Utf8), Err(RabinError::MessageTooLarge { .. })));
+        assert!(matches!(encrypt_str("$", &n, Alphabet::default_symbols()), Err(RabinError::Encoding(_))));
+    }
+
+    #[test]
+    fn test_tagged_decryption_releases_only_the_tagged_root() {
+        use crate::encoding::Utf8;
+
+        let (n, p, q) = generate_keypair(256);
+        let message = BigInt::from(0x7a66_ed00_u32);
+        let ciphertext = encrypt_tagged(&message, &n).unwrap();
+        assert_eq!(decrypt_tagged(&ciphertext, &p, &q), Ok(message.clone()));
+        assert!(!decrypt(&ciphertext, &p, &q).unwrap().contains(&message), "Textbook roots still carry the tag");
+
+        // The chosen-ciphertext attack: a random square has no root with a valid tag
+        let x = thread_rng().gen_bigint_range(&BigInt::from(2), &n);
+        assert_eq!(decrypt_tagged(&encrypt(&x, &n).unwrap(), &p, &q), Err(RabinError::DecryptionFailed));
+        assert_eq!(decrypt_tagged(&encrypt(&message, &n).unwrap(), &p, &q), Err(RabinError::DecryptionFailed));
+
+        let ciphertext = encrypt_str_tagged("tagged text", &n, &Utf8).unwrap();
+        assert_eq!(decrypt_str_tagged(&ciphertext, &p, &q, &Utf8), Ok("tagged text".to_string()));
+        let room = modulus_len(&n) - 1 - TAG_LEN;
+        assert!(encrypt_str_tagged(&"x".repeat(room), &n, &Utf8).is_ok());
+        assert!(matches!(
+            encrypt_str_tagged(&"x".repeat(room + 2), &n, &Utf8),
+            Err(RabinError::MessageTooLarge { .. })
+        ));
+        assert_eq!(tag_message(&BigInt::from(-1), &n), Err(RabinError::MessageOutOfRange));
+        assert!(tag_message(&BigInt::one(), &BigInt::from(77)).is_err(), "No room for the tag");
+    }
+
+    #[test]
+    fn test_blinded_decryption_and_selection() {
+        let (n, p, q) = generate_keypair(256);
+        let params = DecryptionParams::new(&p, &q).unwrap();
+        let message = BigInt::from(0x5eed_cafe_u64);
+        let ciphertext = encrypt(&message, &n).unwrap();
+
+        let mut expected = decrypt(&ciphertext, &p, &q).unwrap();
+        expected.sort();
+        for _ in 0..4 {
+            let mut roots = decrypt_blinded(&mut thread_rng(), &ciphertext, &p, &q, &params).unwrap();
+            roots.sort();
+            assert_eq!(roots, expected);
+        }
+
+        let is_message = |candidate: &BigInt| candidate == &message;
+        assert_eq!(select_candidate(&expected, &n, is_message), Some(message.clone()));
+        assert_eq!(select_candidate(&expected, &n, |_| false), None);
+        assert_eq!(select_candidate(&expected, &n, |_| true), Some(expected[0].clone()), "The first one wins");
+    }
+}
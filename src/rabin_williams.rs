@@ -0,0 +1,222 @@
+// Rabin-Williams signatures. With p ≡ 3 (mod 8) and q ≡ 7 (mod 8), -1 is a non-square modulo
+// both primes and 2 is a non-square modulo p only, so for every hash h exactly one choice of
+// e ∈ {1, -1} and f ∈ {1, 2} makes e * f * h a square modulo n. Signing therefore never has
+// to retry, needs no salt, and is fully deterministic:
+//
+//     sign:   pick (e, f), then s = the principal square root of e * f * h mod n
+//     verify: s^2 ≡ e * f * h (mod n)
+//
+// The principal root is the one of the four that is itself a square modulo both primes.
+
+use crate::ct::ct_eq_int;
+use crate::der::{encode_integer, encode_sequence, DerReader};
+use crate::encoding::modulus_len;
+use crate::error::RabinError;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::math::{crt, legendre};
+use crate::metadata::KeyUsage;
+use crate::montgomery::modpow;
+use crate::rabin::{gen_prime, verify_root};
+use crate::secret::Secret;
+use crate::signature::full_domain_hash;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{ToPrimitive, Zero};
+
+const DOMAIN: &[u8] = b"naive-rabin rabin-williams v0";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RwSignature {
+    // Tweak flags: e is 1 or -1, f is 1 or 2
+    e: i8,
+    f: u8,
+    root: BigInt,
+}
+
+fn residue_mod_eight(value: &BigInt) -> u8 {
+    value.mod_floor(&BigInt::from(8)).to_u8().expect("reduced modulo 8")
+}
+
+// Returns (p, q) ordered so that p ≡ 3 and q ≡ 7 (mod 8), if the key has that shape
+fn williams_primes(key: &PrivateKey) -> Option<(&BigInt, &BigInt)> {
+    match (residue_mod_eight(key.p()), residue_mod_eight(key.q())) {
+        (3, 7) => Some((key.p(), key.q())),
+        (7, 3) => Some((key.q(), key.p())),
+        _ => None,
+    }
+}
+
+fn tweak(hash: &BigInt, e: i8, f: u8, n: &BigInt) -> BigInt {
+    (hash * BigInt::from(e) * BigInt::from(f)).mod_floor(n)
+}
+
+// The square root of a modulo the prime p ≡ 3 (mod 4) that is itself a square
+fn principal_root(a: &BigInt, p: &BigInt) -> BigInt {
+    let root = modpow(a, &((p + 1) >> 2), p);
+    if legendre(&root, p) == 1 {
+        root
+    } else {
+        p - root
+    }
+}
+
+impl PrivateKey {
+    // Draws Blum primes until one is ≡ 3 and another ≡ 7 (mod 8); about four draws on average
+    pub fn generate_williams(bit_size: usize) -> Self {
+        let (mut three, mut seven) = (None, None);
+        while three.is_none() || seven.is_none() {
+            let prime = BigInt::from(gen_prime(bit_size));
+            match residue_mod_eight(&prime) {
+                3 => three = Some(prime),
+                _ => seven = Some(prime),
+            }
+        }
+        PrivateKey::from_primes(three.unwrap(), seven.unwrap()).expect("generated primes are greater than 1")
+    }
+
+    pub fn is_williams(&self) -> bool {
+        williams_primes(self).is_some()
+    }
+}
+
+impl RwSignature {
+    pub fn sign(key: &PrivateKey, message: &[u8]) -> Result<Self, RabinError> {
+        key.check_usage(KeyUsage::Sign)?;
+        let (p, q) = williams_primes(key)
+            .ok_or(RabinError::InvalidKey("Rabin-Williams needs p ≡ 3 and q ≡ 7 (mod 8)"))?;
+        let n = key.n();
+        let hash = full_domain_hash(n, DOMAIN, &[], message)?;
+
+        let (legendre_p, legendre_q) = (legendre(&hash, p), legendre(&hash, q));
+        if legendre_p == 0 || legendre_q == 0 {
+            // The hash shares a factor with n: as likely as guessing p outright
+            return Err(RabinError::MessageOutOfRange);
+        }
+        // (2 / q) = 1, so only e can fix the symbol modulo q; then f fixes it modulo p
+        let e = legendre_q;
+        let f = if e * legendre_p == 1 { 1 } else { 2 };
+
+        let tweaked = tweak(&hash, e, f, n);
+        let roots = Secret::new(vec![principal_root(&tweaked, p), principal_root(&tweaked, q)]);
+        let root = crt(&roots, &[p.clone(), q.clone()])?;
+        verify_root(&root, &tweaked, n)?;
+        Ok(RwSignature { e, f, root })
+    }
+
+    // Expiry is not checked: signatures made while the key was valid stay verifiable
+    pub fn verify(&self, key: &PublicKey, message: &[u8]) -> Result<(), RabinError> {
+        if key.metadata().is_some_and(|metadata| !metadata.allows(KeyUsage::Sign)) {
+            return Err(RabinError::UsageViolation(KeyUsage::Sign));
+        }
+        if !matches!(self.e, 1 | -1) || !matches!(self.f, 1 | 2) {
+            return Err(RabinError::InvalidSignature);
+        }
+        let n = key.n();
+        if self.root <= BigInt::zero() || self.root >= *n {
+            return Err(RabinError::InvalidSignature);
+        }
+        let hash = full_domain_hash(n, DOMAIN, &[], message)?;
+        let expected = tweak(&hash, self.e, self.f, n);
+        if !ct_eq_int(&(&self.root * &self.root % n), &expected, modulus_len(n)) {
+            return Err(RabinError::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    pub fn tweaks(&self) -> (i8, u8) {
+        (self.e, self.f)
+    }
+
+    pub fn root(&self) -> &BigInt {
+        &self.root
+    }
+
+    // RabinWilliamsSignature ::= SEQUENCE {
+    //     version INTEGER (0),
+    //     e       INTEGER (1 or -1),
+    //     f       INTEGER (1 or 2),
+    //     root    INTEGER
+    // }
+    pub fn to_der(&self) -> Vec<u8> {
+        encode_sequence(&[
+            encode_integer(&BigInt::zero()),
+            encode_integer(&BigInt::from(self.e)),
+            encode_integer(&BigInt::from(self.f)),
+            encode_integer(&self.root),
+        ])
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, RabinError> {
+        let mut outer = DerReader::new(der);
+        let mut seq = outer.read_sequence()?;
+        outer.finish()?;
+
+        if !seq.read_integer()?.is_zero() {
+            return Err(RabinError::UnsupportedVersion);
+        }
+        let e = seq.read_integer()?.to_i8().filter(|e| matches!(e, 1 | -1));
+        let f = seq.read_integer()?.to_u8().filter(|f| matches!(f, 1 | 2));
+        let root = seq.read_integer()?;
+        seq.finish()?;
+        match (e, f) {
+            (Some(e), Some(f)) => Ok(RwSignature { e, f, root }),
+            _ => Err(RabinError::MalformedDer("tweak flags out of range")),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+
+    #[test]
+    fn test_every_message_signs_without_retries() {
+        let key = PrivateKey::generate_williams(256).allow_insecure(InsecureDemo);
+        assert!(key.is_williams());
+        let public = key.public_key();
+
+        let mut tweaks = std::collections::HashSet::new();
+        for i in 0..64u32 {
+            let message = i.to_be_bytes();
+            let signature = RwSignature::sign(&key, &message).unwrap();
+            assert_eq!(signature.verify(&public, &message), Ok(()), "message {}", i);
+            tweaks.insert(signature.tweaks());
+
+            // The chosen root is the principal one
+            assert_eq!(legendre(signature.root(), key.p()), 1);
+            assert_eq!(legendre(signature.root(), key.q()), 1);
+        }
+        assert_eq!(tweaks.len(), 4, "All four tweak combinations should occur in 64 messages");
+    }
+
+    #[test]
+    fn test_signatures_are_deterministic() {
+        let key = PrivateKey::generate_williams(256).allow_insecure(InsecureDemo);
+        let first = RwSignature::sign(&key, b"message").unwrap();
+        assert_eq!(RwSignature::sign(&key, b"message").unwrap(), first);
+        assert_eq!(RwSignature::from_der(&first.to_der()).unwrap(), first);
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let key = PrivateKey::generate_williams(256).allow_insecure(InsecureDemo);
+        let public = key.public_key();
+        let signature = RwSignature::sign(&key, b"message").unwrap();
+        assert_eq!(signature.verify(&public, b"massage"), Err(RabinError::InvalidSignature));
+
+        let flipped = RwSignature { e: -signature.e, ..signature.clone() };
+        assert_eq!(flipped.verify(&public, b"message"), Err(RabinError::InvalidSignature));
+        let bogus = RwSignature { f: 3, ..signature.clone() };
+        assert_eq!(bogus.verify(&public, b"message"), Err(RabinError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_requires_williams_primes() {
+        // 7 and 23 are both ≡ 7 (mod 8)
+        let key = PrivateKey::from_primes(BigInt::from(7), BigInt::from(23)).unwrap().allow_insecure(InsecureDemo);
+        assert!(!key.is_williams());
+        assert!(matches!(RwSignature::sign(&key, b"message"), Err(RabinError::InvalidKey(_))));
+    }
+}
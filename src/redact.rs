@@ -0,0 +1,56 @@
+// Logging of values derived from private keys or plaintexts (roots modulo p and q, decryption
+// candidates, decoded messages). Wrapped in secret(), such a value is written to the log as
+// "<redacted>", and is not even formatted, unless secret logging was switched on for the
+// process: the CLI does that for --log-secrets, a developer aid that puts private key material
+// in the log.
+
+use log::warn;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static LOG_SECRETS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_log_secrets(enabled: bool) {
+    if enabled {
+        warn!("Secret values will be written to the log");
+    }
+    LOG_SECRETS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn log_secrets() -> bool {
+    LOG_SECRETS.load(Ordering::Relaxed)
+}
+
+pub struct Redacted<'a, T: fmt::Display + ?Sized>(&'a T);
+
+pub fn secret<T: fmt::Display + ?Sized>(value: &T) -> Redacted<'_, T> {
+    Redacted(value)
+}
+
+impl<T: fmt::Display + ?Sized> fmt::Display for Redacted<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if log_secrets() {
+            self.0.fmt(f)
+        } else {
+            f.write_str("<redacted>")
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigInt;
+
+    #[test]
+    fn test_secrets_are_redacted_unless_enabled() {
+        let root = BigInt::from(1234567);
+        assert_eq!(format!("mp = {}", secret(&root)), "mp = <redacted>");
+        set_log_secrets(true);
+        let shown = format!("mp = {}", secret(&root));
+        set_log_secrets(false);
+        assert_eq!(shown, "mp = 1234567");
+        assert_eq!(format!("{}", secret("plaintext")), "<redacted>");
+    }
+}
@@ -0,0 +1,101 @@
+// Quadratic residuosity: which units are squares. Modulo a prime that is one Legendre symbol;
+// modulo n = p * q a unit is a square exactly when it is a square modulo both primes, which
+// takes the factors to decide. Units with Jacobi symbol +1 that are squares modulo neither
+// prime are pseudosquares, and telling them from real squares without p and q is the quadratic
+// residuosity problem that Goldwasser-Micali rests on.
+//
+// Residues here are units: 0 and multiples of p or q are never counted as residues.
+
+use crate::keys::PrivateKey;
+use crate::math::{jacobi, legendre};
+use num_bigint::{BigInt, RandBigInt};
+use num_integer::Integer;
+use num_traits::One;
+use rand::Rng;
+
+// p must be an odd prime
+pub fn is_quadratic_residue_mod_prime(a: &BigInt, p: &BigInt) -> bool {
+    legendre(a, p) == 1
+}
+
+pub fn is_quadratic_residue_mod_n(a: &BigInt, key: &PrivateKey) -> bool {
+    is_quadratic_residue_mod_prime(a, key.p()) && is_quadratic_residue_mod_prime(a, key.q())
+}
+
+fn random_unit<R: Rng + ?Sized>(rng: &mut R, n: &BigInt) -> BigInt {
+    loop {
+        let x = rng.gen_bigint_range(&BigInt::one(), n);
+        if x.gcd(n).is_one() {
+            return x;
+        }
+    }
+}
+
+// The square of a random unit; needs no private key, and every residue is equally likely
+pub fn random_residue<R: Rng + ?Sized>(rng: &mut R, n: &BigInt) -> BigInt {
+    let x = random_unit(rng, n);
+    &x * &x % n
+}
+
+// A unit that is not a square modulo n: a non-square modulo p, q, or both
+pub fn random_non_residue<R: Rng + ?Sized>(rng: &mut R, key: &PrivateKey) -> BigInt {
+    loop {
+        let y = random_unit(rng, key.n());
+        if !is_quadratic_residue_mod_n(&y, key) {
+            return y;
+        }
+    }
+}
+
+// A non-residue with Jacobi symbol +1, i.e. a non-square modulo both primes. About a quarter
+// of all units qualify.
+pub fn random_pseudosquare<R: Rng + ?Sized>(rng: &mut R, key: &PrivateKey) -> BigInt {
+    loop {
+        let y = random_unit(rng, key.n());
+        if legendre(&y, key.p()) == -1 && legendre(&y, key.q()) == -1 {
+            debug_assert_eq!(jacobi(&y, key.n()), 1);
+            return y;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_residues_mod_prime() {
+        let p = BigInt::from(23);
+        let residues = (1..23).filter(|a| is_quadratic_residue_mod_prime(&BigInt::from(*a), &p)).count();
+        assert_eq!(residues, 11, "Half of the units modulo a prime are squares");
+        assert!(is_quadratic_residue_mod_prime(&BigInt::from(2), &p), "5^2 = 2 (mod 23)");
+        assert!(!is_quadratic_residue_mod_prime(&BigInt::from(5), &p));
+        assert!(!is_quadratic_residue_mod_prime(&BigInt::from(0), &p), "0 is not a unit");
+    }
+
+    #[test]
+    fn test_residues_mod_n() {
+        let key = PrivateKey::from_primes(BigInt::from(11), BigInt::from(23)).unwrap();
+        let residues = (1..253)
+            .filter(|a| is_quadratic_residue_mod_n(&BigInt::from(*a), &key))
+            .count();
+        assert_eq!(residues, 55, "A quarter of the 220 units modulo 253 are squares");
+        assert!(!is_quadratic_residue_mod_n(&BigInt::from(11), &key), "Multiples of p are not units");
+    }
+
+    #[test]
+    fn test_sampling() {
+        let mut rng = thread_rng();
+        let key = PrivateKey::generate(128);
+        for _ in 0..20 {
+            assert!(is_quadratic_residue_mod_n(&random_residue(&mut rng, key.n()), &key));
+            assert!(!is_quadratic_residue_mod_n(&random_non_residue(&mut rng, &key), &key));
+
+            let pseudosquare = random_pseudosquare(&mut rng, &key);
+            assert_eq!(jacobi(&pseudosquare, key.n()), 1);
+            assert!(!is_quadratic_residue_mod_n(&pseudosquare, &key));
+        }
+    }
+}
@@ -0,0 +1,113 @@
+// The RustCrypto traits, for code written against them rather than against this crate: with the
+// rustcrypto feature, PrivateKey is a signature::Signer and PublicKey a signature::Verifier for
+// both Rabin and Rabin-Williams signatures, and the signatures encode to and from their DER.
+// Failures come back as signature::Error with the RabinError as the source.
+
+use crate::error::RabinError;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::rabin_williams::RwSignature;
+use crate::signature::Signature;
+use ::signature::{Error, SignatureEncoding, Signer, Verifier};
+
+fn to_error(err: RabinError) -> Error {
+    Error::from_source(err)
+}
+
+impl Signer<Signature> for PrivateKey {
+    fn try_sign(&self, message: &[u8]) -> Result<Signature, Error> {
+        Signature::sign(self, message).map_err(to_error)
+    }
+}
+
+impl Verifier<Signature> for PublicKey {
+    fn verify(&self, message: &[u8], signature: &Signature) -> Result<(), Error> {
+        signature.verify(self, message).map_err(to_error)
+    }
+}
+
+impl TryFrom<&[u8]> for Signature {
+    type Error = Error;
+
+    fn try_from(der: &[u8]) -> Result<Self, Error> {
+        Signature::from_der(der).map_err(to_error)
+    }
+}
+
+impl From<Signature> for Vec<u8> {
+    fn from(signature: Signature) -> Self {
+        signature.to_der()
+    }
+}
+
+impl SignatureEncoding for Signature {
+    type Repr = Vec<u8>;
+}
+
+impl Signer<RwSignature> for PrivateKey {
+    fn try_sign(&self, message: &[u8]) -> Result<RwSignature, Error> {
+        RwSignature::sign(self, message).map_err(to_error)
+    }
+}
+
+impl Verifier<RwSignature> for PublicKey {
+    fn verify(&self, message: &[u8], signature: &RwSignature) -> Result<(), Error> {
+        signature.verify(self, message).map_err(to_error)
+    }
+}
+
+impl TryFrom<&[u8]> for RwSignature {
+    type Error = Error;
+
+    fn try_from(der: &[u8]) -> Result<Self, Error> {
+        RwSignature::from_der(der).map_err(to_error)
+    }
+}
+
+impl From<RwSignature> for Vec<u8> {
+    fn from(signature: RwSignature) -> Self {
+        signature.to_der()
+    }
+}
+
+impl SignatureEncoding for RwSignature {
+    type Repr = Vec<u8>;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+    use std::error::Error as _;
+
+    // Written the way generic code would use it, knowing nothing of Rabin
+    fn sign_and_check<S, K, V>(signer: &K, verifier: &V) -> S
+    where
+        S: SignatureEncoding,
+        K: Signer<S>,
+        V: Verifier<S>,
+    {
+        let signature = signer.sign(b"generic message");
+        let Ok(decoded) = S::try_from(signature.to_bytes().as_ref()) else {
+            panic!("a signature should decode from its own encoding");
+        };
+        assert!(verifier.verify(b"generic message", &decoded).is_ok());
+        assert!(verifier.verify(b"another message", &decoded).is_err());
+        decoded
+    }
+
+    #[test]
+    fn test_keys_work_through_the_signature_traits() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let public = key.public_key();
+        let signature: Signature = sign_and_check(&key, &public);
+        assert_eq!(signature.verify(&public, b"generic message"), Ok(()));
+
+        let williams = PrivateKey::generate_williams(256).allow_insecure(InsecureDemo);
+        let _: RwSignature = sign_and_check(&williams, &williams.public_key());
+
+        let err = Verifier::<Signature>::verify(&public, b"another message", &signature).unwrap_err();
+        let source = err.source().and_then(|source| source.downcast_ref::<RabinError>());
+        assert_eq!(source, Some(&RabinError::InvalidSignature));
+    }
+}
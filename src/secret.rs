@@ -0,0 +1,101 @@
+// Wiping of values derived from the private key (roots modulo p and q, CRT coefficients, the
+// roots of a ciphertext that were not picked) once they are no longer needed. num-bigint has no
+// zeroize support, so a number is overwritten through its own API: clearing its bits rewrites
+// the digits in place, and black_box keeps those stores from being dropped as dead before the
+// buffer is freed. Temporaries inside num-bigint's arithmetic, and spare capacity past the
+// digits, are out of reach; this shortens how long secrets linger in memory, it does not
+// guarantee they are gone.
+
+use num_bigint::{BigInt, Sign};
+use num_traits::Zero;
+use std::hint::black_box;
+use std::ops::{Deref, DerefMut};
+
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+impl Wipe for BigInt {
+    fn wipe(&mut self) {
+        if self.sign() == Sign::Minus {
+            *self = -std::mem::take(self);
+        }
+        // Clearing from the bottom up keeps every digit in place until the top bit goes, and
+        // needs no scratch buffer, so wiping never allocates
+        let bits = self.bits();
+        for bit in 0..bits.saturating_sub(1) {
+            self.set_bit(bit, false);
+        }
+        black_box(&*self);
+        self.set_zero();
+    }
+}
+
+impl<T: Wipe> Wipe for Option<T> {
+    fn wipe(&mut self) {
+        if let Some(value) = self {
+            value.wipe();
+        }
+    }
+}
+
+impl Wipe for u8 {
+    fn wipe(&mut self) {
+        *self = 0;
+    }
+}
+
+impl<T: Wipe> Wipe for Vec<T> {
+    fn wipe(&mut self) {
+        self.iter_mut().for_each(Wipe::wipe);
+        black_box(&*self);
+    }
+}
+
+// A value that is wiped when it goes out of scope
+#[derive(Debug, Default)]
+pub struct Secret<T: Wipe>(T);
+
+impl<T: Wipe> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+impl<T: Wipe> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Wipe> DerefMut for Secret<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Wipe> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wipe_overwrites_in_place() {
+        let mut value: BigInt = BigInt::from(0xdead_beef_u32) << 200;
+        value.wipe();
+        assert!(value.is_zero());
+        let mut roots = vec![BigInt::from(-7), BigInt::zero(), BigInt::from(u64::MAX)];
+        roots.wipe();
+        assert!(roots.iter().all(Zero::is_zero), "Signs and zeros are handled too");
+
+        let secret = Secret::new(Some(BigInt::from(42)));
+        assert_eq!(secret.as_ref().map(|value| value + 1), Some(BigInt::from(43)));
+    }
+}
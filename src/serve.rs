@@ -0,0 +1,181 @@
+// `rabin serve --http PORT`: a small JSON-over-HTTP front end for web demos and curl exercises,
+// built with the http feature. The server holds one private key (--key, or the default key) and
+// answers three endpoints:
+//
+//     GET  /public-key   {"public_key": PEM, "fingerprint": ..., "demo": bool}
+//     POST /encrypt      {"plaintext": text} or {"plaintext_base64": ...}
+//                        -> {"ciphertext": base64 of the envelope}
+//     POST /decrypt      {"ciphertext": base64 of an envelope}
+//                        -> {"plaintext_base64": ..., "plaintext": text when it is UTF-8}
+//
+// Ciphertexts are the same envelopes `rabin encrypt` writes, so the two can be mixed. Failures
+// come back as {"error": message} with a 4xx status. Requests are handled one at a time, and
+// the server listens on 127.0.0.1 unless --bind says otherwise: decryption with a server-held
+// key is an oracle, so do not expose it beyond the classroom.
+
+use crate::cli::{load_private_key, Args, CliResult};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::{info, warn};
+use naive_rabin_cryptosystem::envelope::Envelope;
+use naive_rabin_cryptosystem::keys::PrivateKey;
+use serde_json::{json, Map, Value};
+use std::io::Read;
+use tiny_http::{Header, Method, Response, Server};
+
+const DEFAULT_BIND: &str = "127.0.0.1";
+// Larger request bodies are refused
+const MAX_BODY_LEN: u64 = 1 << 20;
+
+struct Reply {
+    status: u16,
+    body: Value,
+}
+
+impl Reply {
+    fn ok(body: Value) -> Self {
+        Reply { status: 200, body }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Self {
+        Reply {
+            status,
+            body: json!({ "error": message.to_string() }),
+        }
+    }
+}
+
+fn parse_object(body: &[u8]) -> Result<Map<String, Value>, Reply> {
+    match serde_json::from_slice(body) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(Reply::error(400, "the request body must be a JSON object")),
+        Err(err) => Err(Reply::error(400, format!("invalid JSON: {}", err))),
+    }
+}
+
+fn string_field<'a>(object: &'a Map<String, Value>, name: &str) -> Result<Option<&'a str>, Reply> {
+    match object.get(name) {
+        None => Ok(None),
+        Some(Value::String(text)) => Ok(Some(text)),
+        Some(_) => Err(Reply::error(400, format!("\"{}\" must be a string", name))),
+    }
+}
+
+fn base64_field(object: &Map<String, Value>, name: &str) -> Result<Option<Vec<u8>>, Reply> {
+    string_field(object, name)?
+        .map(|text| STANDARD.decode(text).map_err(|_| Reply::error(400, format!("\"{}\" is not valid base64", name))))
+        .transpose()
+}
+
+fn public_key(key: &PrivateKey) -> Reply {
+    let public = key.public_key();
+    Reply::ok(json!({
+        "public_key": public.to_pem(),
+        "fingerprint": public.fingerprint().to_string(),
+        "demo": public.is_demo(),
+    }))
+}
+
+fn encrypt(key: &PrivateKey, body: &[u8]) -> Result<Reply, Reply> {
+    let request = parse_object(body)?;
+    let plaintext = match (string_field(&request, "plaintext")?, base64_field(&request, "plaintext_base64")?) {
+        (Some(text), None) => text.as_bytes().to_vec(),
+        (None, Some(bytes)) => bytes,
+        _ => return Err(Reply::error(400, "give exactly one of \"plaintext\" and \"plaintext_base64\"")),
+    };
+    let envelope = Envelope::seal(&key.public_key(), &plaintext).map_err(|err| Reply::error(400, err))?;
+    Ok(Reply::ok(json!({ "ciphertext": STANDARD.encode(envelope.to_der()) })))
+}
+
+fn decrypt(key: &PrivateKey, body: &[u8]) -> Result<Reply, Reply> {
+    let request = parse_object(body)?;
+    let ciphertext = base64_field(&request, "ciphertext")?.ok_or_else(|| Reply::error(400, "missing \"ciphertext\""))?;
+    let envelope = Envelope::from_bytes(&ciphertext).map_err(|err| Reply::error(400, err))?;
+    let plaintext = envelope.open(key).map_err(|err| Reply::error(422, err))?;
+    let mut reply = json!({ "plaintext_base64": STANDARD.encode(&plaintext) });
+    if let Ok(text) = String::from_utf8(plaintext) {
+        reply["plaintext"] = Value::String(text);
+    }
+    Ok(Reply::ok(reply))
+}
+
+fn handle(key: &PrivateKey, method: &Method, path: &str, body: &[u8]) -> Reply {
+    let result = match (method, path) {
+        (Method::Get, "/public-key") => Ok(public_key(key)),
+        (Method::Post, "/encrypt") => encrypt(key, body),
+        (Method::Post, "/decrypt") => decrypt(key, body),
+        (_, "/public-key" | "/encrypt" | "/decrypt") => Err(Reply::error(405, "method not allowed")),
+        _ => Err(Reply::error(404, "no such endpoint")),
+    };
+    result.unwrap_or_else(|reply| reply)
+}
+
+pub fn run_serve(mut args: Args) -> CliResult {
+    let port: u16 = match args.option("http")? {
+        Some(port) => port.parse().map_err(|_| format!("invalid port '{}'", port))?,
+        None => return Err("missing --http PORT".into()),
+    };
+    let bind = args.option("bind")?.unwrap_or_else(|| DEFAULT_BIND.to_string());
+    let key_spec = args.option("key")?;
+    let key = load_private_key(&args, key_spec)?;
+    args.finish()?;
+
+    let server = Server::http((bind.as_str(), port)).map_err(|err| format!("cannot listen on {}:{}: {}", bind, port, err))?;
+    info!("Serving key {} on http://{}:{}", key.public_key().fingerprint(), bind, port);
+    let content_type = Header::from_bytes("Content-Type", "application/json").expect("the header is valid");
+    for mut request in server.incoming_requests() {
+        let mut body = Vec::new();
+        let read = request.as_reader().take(MAX_BODY_LEN + 1).read_to_end(&mut body);
+        let reply = match read {
+            Ok(_) if body.len() as u64 > MAX_BODY_LEN => Reply::error(413, "request body too large"),
+            Ok(_) => handle(&key, request.method(), request.url(), &body),
+            Err(err) => Reply::error(400, format!("could not read the request: {}", err)),
+        };
+        info!("{} {} -> {}", request.method(), request.url(), reply.status);
+        let response = Response::from_string(reply.body.to_string())
+            .with_status_code(reply.status)
+            .with_header(content_type.clone());
+        if let Err(err) = request.respond(response) {
+            warn!("could not send the response: {}", err);
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use naive_rabin_cryptosystem::keys::InsecureDemo;
+
+    #[test]
+    fn test_endpoints() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let reply = handle(&key, &Method::Get, "/public-key", b"");
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.body["public_key"], key.public_key().to_pem());
+
+        let reply = handle(&key, &Method::Post, "/encrypt", br#"{"plaintext": "hello, curl"}"#);
+        assert_eq!(reply.status, 200);
+        let request = json!({ "ciphertext": reply.body["ciphertext"] }).to_string();
+        let reply = handle(&key, &Method::Post, "/decrypt", request.as_bytes());
+        assert_eq!(reply.status, 200);
+        assert_eq!(reply.body["plaintext"], "hello, curl");
+
+        let reply = handle(&key, &Method::Post, "/encrypt", br#"{"plaintext_base64": "/wA="}"#);
+        let request = json!({ "ciphertext": reply.body["ciphertext"] }).to_string();
+        let reply = handle(&key, &Method::Post, "/decrypt", request.as_bytes());
+        assert_eq!(reply.body["plaintext_base64"], "/wA=");
+        assert!(reply.body.get("plaintext").is_none(), "Bytes that are not UTF-8 only come back as base64");
+
+        for (method, path, body, status) in [
+            (Method::Post, "/encrypt", &b"[1]"[..], 400),
+            (Method::Post, "/encrypt", br#"{"plaintext": "a", "plaintext_base64": "YQ=="}"#, 400),
+            (Method::Post, "/decrypt", br#"{"ciphertext": "not base64!"}"#, 400),
+            (Method::Get, "/encrypt", b"", 405),
+            (Method::Get, "/private-key", b"", 404),
+        ] {
+            assert_eq!(handle(&key, &method, path, body).status, status, "{} {}", method, path);
+        }
+    }
+}
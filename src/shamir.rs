@@ -0,0 +1,226 @@
+// Shamir secret sharing over GF(2^8), applied bytewise to the serialized private key.
+// Each byte of the secret is the constant term of its own random polynomial of degree k - 1.
+
+use crate::der::{encode_integer, encode_octet_string, encode_sequence, DerReader};
+use crate::error::RabinError;
+use crate::keys::PrivateKey;
+use crate::pem;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+pub const SHARE_PEM_LABEL: &str = "RABIN KEY SHARE";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    // Evaluation point x (never 0, which would reveal the secret)
+    pub index: u8,
+    pub threshold: u8,
+    pub data: Vec<u8>,
+}
+
+// Multiplication modulo the AES polynomial x^8 + x^4 + x^3 + x + 1
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+// a^254 = a^-1 in GF(2^8)
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 != 0 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+pub fn split(secret: &[u8], threshold: u8, share_count: u8) -> Result<Vec<Share>, RabinError> {
+    if threshold == 0 || threshold > share_count {
+        return Err(RabinError::InvalidShares("threshold must be between 1 and the number of shares"));
+    }
+
+    let mut rng = OsRng;
+    let mut shares: Vec<Share> = (1..=share_count)
+        .map(|index| Share {
+            index,
+            threshold,
+            data: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+
+    let mut coefficients = vec![0u8; threshold as usize];
+    for &byte in secret {
+        coefficients[0] = byte;
+        rng.fill_bytes(&mut coefficients[1..]);
+        for share in shares.iter_mut() {
+            // Horner evaluation at x = index
+            let value = coefficients
+                .iter()
+                .rev()
+                .fold(0u8, |acc, &c| gf_mul(acc, share.index) ^ c);
+            share.data.push(value);
+        }
+    }
+    Ok(shares)
+}
+
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>, RabinError> {
+    let first = shares.first().ok_or(RabinError::InvalidShares("no shares given"))?;
+    let threshold = first.threshold as usize;
+    if shares.iter().any(|s| s.threshold != first.threshold || s.data.len() != first.data.len()) {
+        return Err(RabinError::InvalidShares("shares come from different splits"));
+    }
+    if shares.iter().any(|s| s.index == 0) {
+        return Err(RabinError::InvalidShares("share index 0 is not allowed"));
+    }
+
+    // Use the first `threshold` distinct shares
+    let mut selected: Vec<&Share> = Vec::with_capacity(threshold);
+    for share in shares {
+        if let Some(existing) = selected.iter().find(|s| s.index == share.index) {
+            if existing.data != share.data {
+                return Err(RabinError::InvalidShares("conflicting shares with the same index"));
+            }
+            continue;
+        }
+        if selected.len() < threshold {
+            selected.push(share);
+        }
+    }
+    if selected.len() < threshold {
+        return Err(RabinError::InvalidShares("not enough shares to reach the threshold"));
+    }
+
+    // Lagrange interpolation at x = 0; subtraction is XOR in characteristic 2
+    let weights: Vec<u8> = selected
+        .iter()
+        .map(|share_i| {
+            selected
+                .iter()
+                .filter(|share_j| share_j.index != share_i.index)
+                .fold(1u8, |acc, share_j| {
+                    gf_mul(acc, gf_mul(share_j.index, gf_inv(share_j.index ^ share_i.index)))
+                })
+        })
+        .collect();
+
+    Ok((0..first.data.len())
+        .map(|position| {
+            selected
+                .iter()
+                .zip(&weights)
+                .fold(0u8, |acc, (share, &weight)| acc ^ gf_mul(share.data[position], weight))
+        })
+        .collect())
+}
+
+impl Share {
+    // KeyShare ::= SEQUENCE { threshold INTEGER, index INTEGER, data OCTET STRING }
+    pub fn to_der(&self) -> Vec<u8> {
+        encode_sequence(&[
+            encode_integer(&BigInt::from(self.threshold)),
+            encode_integer(&BigInt::from(self.index)),
+            encode_octet_string(&self.data),
+        ])
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, RabinError> {
+        let mut outer = DerReader::new(der);
+        let mut seq = outer.read_sequence()?;
+        outer.finish()?;
+        let threshold = seq.read_integer()?.to_u8();
+        let index = seq.read_integer()?.to_u8();
+        let data = seq.read_octet_string()?.to_vec();
+        seq.finish()?;
+
+        match (threshold, index) {
+            (Some(threshold), Some(index)) => Ok(Share { index, threshold, data }),
+            _ => Err(RabinError::MalformedDer("share header out of range")),
+        }
+    }
+
+    pub fn to_pem(&self) -> String {
+        pem::encode(SHARE_PEM_LABEL, &self.to_der())
+    }
+
+    pub fn from_pem(pem_text: &str) -> Result<Self, RabinError> {
+        Share::from_der(&pem::decode(SHARE_PEM_LABEL, pem_text)?)
+    }
+}
+
+impl PrivateKey {
+    // Shards the PKCS#8 encoding so the reassembled key loads like any other key file
+    pub fn split_shares(&self, threshold: u8, share_count: u8) -> Result<Vec<Share>, RabinError> {
+        split(&self.to_pkcs8_der(), threshold, share_count)
+    }
+
+    pub fn from_shares(shares: &[Share]) -> Result<Self, RabinError> {
+        PrivateKey::from_pkcs8_der(&combine(shares)?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gf_inverse() {
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1, "{} * {}^-1 should be 1", a, a);
+        }
+    }
+
+    #[test]
+    fn test_any_threshold_subset_recovers_secret() {
+        let secret = b"recommended website".to_vec();
+        let shares = split(&secret, 3, 5).unwrap();
+
+        for (a, b, c) in [(0, 1, 2), (0, 2, 4), (1, 3, 4), (4, 2, 0)] {
+            let subset = vec![shares[a].clone(), shares[b].clone(), shares[c].clone()];
+            assert_eq!(combine(&subset).unwrap(), secret);
+        }
+    }
+
+    #[test]
+    fn test_too_few_shares_are_rejected() {
+        let shares = split(b"secret", 3, 5).unwrap();
+        assert_eq!(
+            combine(&shares[..2]),
+            Err(RabinError::InvalidShares("not enough shares to reach the threshold"))
+        );
+        // Repeating a share does not count twice
+        let repeated = vec![shares[0].clone(), shares[0].clone(), shares[1].clone()];
+        assert!(combine(&repeated).is_err());
+    }
+
+    #[test]
+    fn test_private_key_shares_round_trip() {
+        let key = PrivateKey::generate(256);
+        let shares = key.split_shares(2, 3).unwrap();
+
+        let parsed: Vec<Share> = shares
+            .iter()
+            .map(|share| Share::from_pem(&share.to_pem()).unwrap())
+            .collect();
+        assert_eq!(PrivateKey::from_shares(&parsed[1..]).unwrap(), key);
+    }
+}
@@ -0,0 +1,236 @@
+// Rabin signatures. The message is hashed together with a random salt into a number below n;
+// roughly one salt in four makes that number a square modulo both primes, and the signature is
+// one of its square roots. Verification squares the signature and compares.
+//
+//     h = FDH(salt, SHA-256(message)), a full-width hash one byte shorter than n
+//     sign:   retry salts until h is a square mod p and mod q, then s = sqrt(h) mod n
+//     verify: s^2 mod n == h
+
+use crate::ct::ct_eq_int;
+use crate::der::{encode_integer, encode_octet_string, encode_sequence, DerReader};
+use crate::encoding::{bytes2num, modulus_len, ByteOrder};
+use crate::error::RabinError;
+use crate::hash::sha256;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::math::legendre;
+use crate::metadata::KeyUsage;
+use crate::pem;
+use crate::rabin::verify_root;
+use crate::secret::Secret;
+use num_bigint::BigInt;
+use num_traits::Zero;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+pub const SIGNATURE_PEM_LABEL: &str = "RABIN SIGNATURE";
+pub const SALT_LEN: usize = 16;
+
+const DOMAIN: &[u8] = b"naive-rabin signature v0";
+// Each salt succeeds with probability about 1/4, so running out means the key is broken
+const MAX_SALT_ATTEMPTS: usize = 256;
+// The hash must be at least as wide as SHA-256 itself
+const MIN_HASH_LEN: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    salt: [u8; SALT_LEN],
+    root: BigInt,
+}
+
+fn hash_len(n: &BigInt) -> Result<usize, RabinError> {
+    let len = (n.bits().div_ceil(8) as usize).saturating_sub(1);
+    if len < MIN_HASH_LEN {
+        return Err(RabinError::ModulusTooSmall);
+    }
+    Ok(len)
+}
+
+// Full-domain hash: SHA-256 in counter mode over (domain, salt, message digest), truncated to
+// one byte less than n so the result is always below it. Shared with Rabin-Williams, which
+// uses its own domain and no salt.
+pub(crate) fn full_domain_hash(
+    n: &BigInt,
+    domain: &[u8],
+    salt: &[u8],
+    message: &[u8],
+) -> Result<BigInt, RabinError> {
+    let len = hash_len(n)?;
+    let digest = sha256(message);
+    let mut out = Vec::with_capacity(len + 32);
+    let mut counter = 0u32;
+    while out.len() < len {
+        out.extend_from_slice(&sha256(&[domain, salt, &digest, &counter.to_be_bytes()].concat()));
+        counter += 1;
+    }
+    out.truncate(len);
+    Ok(bytes2num(&out, ByteOrder::BigEndian))
+}
+
+pub(crate) fn message_hash(n: &BigInt, salt: &[u8; SALT_LEN], message: &[u8]) -> Result<BigInt, RabinError> {
+    full_domain_hash(n, DOMAIN, salt, message)
+}
+
+impl Signature {
+    pub fn sign(key: &PrivateKey, message: &[u8]) -> Result<Self, RabinError> {
+        key.check_usage(KeyUsage::Sign)?;
+        let mut rng = OsRng;
+        for _ in 0..MAX_SALT_ATTEMPTS {
+            let mut salt = [0u8; SALT_LEN];
+            rng.fill_bytes(&mut salt);
+            let hash = message_hash(key.n(), &salt, message)?;
+            if legendre(&hash, key.p()) != 1 || legendre(&hash, key.q()) != 1 {
+                continue;
+            }
+            // Any of the four roots is a valid signature; the other three are wiped, since two
+            // roots that are not each other's negation give away the factors
+            let roots = Secret::new(key.square_roots(&hash)?);
+            let root = roots[0].clone();
+            verify_root(&root, &hash, key.n())?;
+            return Ok(Signature { salt, root });
+        }
+        Err(RabinError::InvalidKey("no salt gave a square; p and q are probably not prime"))
+    }
+
+    // Expiry is not checked: signatures made while the key was valid stay verifiable
+    pub fn verify(&self, key: &PublicKey, message: &[u8]) -> Result<(), RabinError> {
+        if key.metadata().is_some_and(|metadata| !metadata.allows(KeyUsage::Sign)) {
+            return Err(RabinError::UsageViolation(KeyUsage::Sign));
+        }
+        let n = key.n();
+        if self.root <= BigInt::zero() || self.root >= *n {
+            return Err(RabinError::InvalidSignature);
+        }
+        let hash = message_hash(n, &self.salt, message)?;
+        if !ct_eq_int(&(&self.root * &self.root % n), &hash, modulus_len(n)) {
+            return Err(RabinError::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    // For protocols that assemble the root themselves; verify before trusting the result
+    pub(crate) fn from_parts(salt: [u8; SALT_LEN], root: BigInt) -> Self {
+        Signature { salt, root }
+    }
+
+    pub fn salt(&self) -> &[u8; SALT_LEN] {
+        &self.salt
+    }
+
+    pub fn root(&self) -> &BigInt {
+        &self.root
+    }
+
+    // RabinSignature ::= SEQUENCE {
+    //     version INTEGER (0),
+    //     salt    OCTET STRING,
+    //     root    INTEGER
+    // }
+    pub fn to_der(&self) -> Vec<u8> {
+        encode_sequence(&[
+            encode_integer(&BigInt::zero()),
+            encode_octet_string(&self.salt),
+            encode_integer(&self.root),
+        ])
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, RabinError> {
+        let mut outer = DerReader::new(der);
+        let mut seq = outer.read_sequence()?;
+        outer.finish()?;
+
+        if !seq.read_integer()?.is_zero() {
+            return Err(RabinError::UnsupportedVersion);
+        }
+        let salt: [u8; SALT_LEN] = seq
+            .read_octet_string()?
+            .try_into()
+            .map_err(|_| RabinError::MalformedDer("salt has the wrong length"))?;
+        let root = seq.read_integer()?;
+        seq.finish()?;
+        Ok(Signature { salt, root })
+    }
+
+    pub fn to_pem(&self) -> String {
+        pem::encode(SIGNATURE_PEM_LABEL, &self.to_der())
+    }
+
+    pub fn from_pem(pem_text: &str) -> Result<Self, RabinError> {
+        Signature::from_der(&pem::decode(SIGNATURE_PEM_LABEL, pem_text)?)
+    }
+
+    // Accepts both the binary DER form and the PEM armor
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RabinError> {
+        if bytes.starts_with(b"-----BEGIN") {
+            let text = std::str::from_utf8(bytes).map_err(|_| RabinError::MalformedPem("not UTF-8"))?;
+            Signature::from_pem(text)
+        } else {
+            Signature::from_der(bytes)
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+    use crate::metadata::KeyMetadata;
+    use num_traits::One;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let public = key.public_key();
+        let signature = Signature::sign(&key, b"attack at dawn").unwrap();
+
+        assert_eq!(signature.verify(&public, b"attack at dawn"), Ok(()));
+        assert_eq!(signature.verify(&public, b"attack at dusk"), Err(RabinError::InvalidSignature));
+
+        let other = PrivateKey::generate(256).allow_insecure(InsecureDemo).public_key();
+        assert_eq!(signature.verify(&other, b"attack at dawn"), Err(RabinError::InvalidSignature));
+
+        // Salts differ, so signing twice gives different signatures that both verify
+        let again = Signature::sign(&key, b"attack at dawn").unwrap();
+        assert_ne!(again, signature);
+        assert_eq!(again.verify(&public, b"attack at dawn"), Ok(()));
+    }
+
+    #[test]
+    fn test_tampered_signatures_fail() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let public = key.public_key();
+        let signature = Signature::sign(&key, b"message").unwrap();
+
+        let mut salted = signature.clone();
+        salted.salt[0] ^= 1;
+        assert_eq!(salted.verify(&public, b"message"), Err(RabinError::InvalidSignature));
+
+        let shifted = Signature { salt: signature.salt, root: &signature.root + BigInt::one() };
+        assert_eq!(shifted.verify(&public, b"message"), Err(RabinError::InvalidSignature));
+
+        let oversized = Signature { salt: signature.salt, root: &signature.root + public.n() };
+        assert_eq!(oversized.verify(&public, b"message"), Err(RabinError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_signature_encodings_round_trip() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let signature = Signature::sign(&key, b"message").unwrap();
+        assert_eq!(Signature::from_der(&signature.to_der()).unwrap(), signature);
+        assert_eq!(Signature::from_bytes(signature.to_pem().as_bytes()).unwrap(), signature);
+        assert_eq!(Signature::from_bytes(&signature.to_der()).unwrap(), signature);
+    }
+
+    #[test]
+    fn test_usage_restrictions() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo)
+            .with_metadata(KeyMetadata::new(None).restrict_to(&[KeyUsage::Encrypt]));
+        assert_eq!(
+            Signature::sign(&key, b"message"),
+            Err(RabinError::UsageViolation(KeyUsage::Sign))
+        );
+
+        let small = PrivateKey::from_primes(BigInt::from(7), BigInt::from(11)).unwrap().allow_insecure(InsecureDemo);
+        assert_eq!(Signature::sign(&small, b"message"), Err(RabinError::ModulusTooSmall));
+    }
+}
@@ -0,0 +1,465 @@
+// Streaming file encryption: the Envelope scheme cut into independently sealed chunks, so
+// files of any size go through in constant memory and on every core.
+//
+//   magic (8 bytes) || header length (4 bytes) || header DER || frames
+//   frame = length (4 bytes) || AES-256-GCM(chunk) with the tag appended
+//
+// The header carries the Rabin-encrypted session key exactly as an Envelope does. Chunk i is
+// sealed under the base nonce with i XORed into its last 8 bytes, and its associated data is
+// the header DER, i and a last-chunk flag, so reordered, dropped or truncated frames fail
+// to open.
+//
+// Both directions run as a pipeline: a reader thread cuts the input into chunks, a pool of
+// workers seals or opens them, and the calling thread writes the results back in order.
+// The reader waits for a credit before each chunk and the writer hands one back after each
+// write, so no more than a fixed window of chunks is ever held in memory.
+
+use crate::aead::{aes256_gcm_decrypt, aes256_gcm_encrypt, NONCE_LEN, TAG_LEN};
+use crate::der::{encode_integer, encode_octet_string, encode_sequence, DerReader};
+use crate::error::RabinError;
+use crate::fingerprint::Fingerprint;
+use crate::kem::{decapsulate, encapsulate, EncapsulatedKey};
+use crate::keys::{PrivateKey, PublicKey};
+use crate::rabin::default_workers;
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::mpsc::sync_channel;
+use std::sync::Mutex;
+use std::thread;
+
+pub const STREAM_MAGIC: &[u8; 8] = b"RABINSTM";
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+// Upper bound on the chunk size a header may declare, which also bounds reader memory
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+// Headers are a few hundred bytes even for very large moduli
+const MAX_HEADER_LEN: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamConfig {
+    chunk_size: usize,
+    workers: usize,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            workers: default_workers(),
+        }
+    }
+}
+
+impl StreamConfig {
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.clamp(1, MAX_CHUNK_SIZE);
+        self
+    }
+
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    // Chunks in flight between the reader and the writer
+    fn window(&self) -> usize {
+        self.workers * 2
+    }
+}
+
+struct StreamHeader {
+    recipient: Fingerprint,
+    encrypted_key: BigInt,
+    nonce: [u8; NONCE_LEN],
+    chunk_size: usize,
+}
+
+impl StreamHeader {
+    // RabinStream ::= SEQUENCE {
+    //     version      INTEGER (0),
+    //     recipient    OCTET STRING (SHA-256 fingerprint of the public key),
+    //     encryptedKey INTEGER,
+    //     nonce        OCTET STRING,
+    //     chunkSize    INTEGER
+    // }
+    fn to_der(&self) -> Vec<u8> {
+        encode_sequence(&[
+            encode_integer(&BigInt::zero()),
+            encode_octet_string(self.recipient.as_bytes()),
+            encode_integer(&self.encrypted_key),
+            encode_octet_string(&self.nonce),
+            encode_integer(&BigInt::from(self.chunk_size)),
+        ])
+    }
+
+    fn from_der(der: &[u8]) -> Result<Self, RabinError> {
+        let mut outer = DerReader::new(der);
+        let mut seq = outer.read_sequence()?;
+        outer.finish()?;
+
+        if !seq.read_integer()?.is_zero() {
+            return Err(RabinError::UnsupportedVersion);
+        }
+        let recipient: [u8; 32] = seq
+            .read_octet_string()?
+            .try_into()
+            .map_err(|_| RabinError::MalformedDer("recipient fingerprint has the wrong length"))?;
+        let encrypted_key = seq.read_integer()?;
+        let nonce: [u8; NONCE_LEN] = seq
+            .read_octet_string()?
+            .try_into()
+            .map_err(|_| RabinError::MalformedDer("nonce has the wrong length"))?;
+        let chunk_size = seq
+            .read_integer()?
+            .to_usize()
+            .filter(|size| (1..=MAX_CHUNK_SIZE).contains(size))
+            .ok_or(RabinError::MalformedDer("chunk size out of range"))?;
+        seq.finish()?;
+
+        Ok(StreamHeader {
+            recipient: Fingerprint::from_bytes(recipient),
+            encrypted_key,
+            nonce,
+            chunk_size,
+        })
+    }
+}
+
+fn chunk_nonce(base: &[u8; NONCE_LEN], index: u64) -> [u8; NONCE_LEN] {
+    let mut nonce = *base;
+    for (byte, counter) in nonce[NONCE_LEN - 8..].iter_mut().zip(index.to_be_bytes()) {
+        *byte ^= counter;
+    }
+    nonce
+}
+
+fn chunk_aad(header: &[u8], index: u64, last: bool) -> Vec<u8> {
+    [header, &index.to_be_bytes()[..], &[u8::from(last)]].concat()
+}
+
+// Fills as much of buf as the input allows; short only at end of input
+fn read_full<R: Read>(input: &mut R, buf: &mut [u8]) -> Result<usize, RabinError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match input.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(filled)
+}
+
+// Turns a source of pieces into (piece, last) pairs by reading one piece ahead. An empty
+// source still yields a single empty piece, so every stream has a last chunk.
+struct LookAhead<F> {
+    read: F,
+    ahead: Option<Vec<u8>>,
+    started: bool,
+    finished: bool,
+}
+
+impl<F: FnMut() -> Result<Option<Vec<u8>>, RabinError>> LookAhead<F> {
+    fn new(read: F) -> Self {
+        LookAhead {
+            read,
+            ahead: None,
+            started: false,
+            finished: false,
+        }
+    }
+
+    fn advance(&mut self) -> Result<Option<(Vec<u8>, bool)>, RabinError> {
+        let current = match self.ahead.take() {
+            Some(piece) => piece,
+            None => match (self.read)()? {
+                Some(piece) => piece,
+                None if self.started => return Ok(None),
+                None => Vec::new(),
+            },
+        };
+        self.started = true;
+        self.ahead = (self.read)()?;
+        Ok(Some((current, self.ahead.is_none())))
+    }
+}
+
+impl<F: FnMut() -> Result<Option<Vec<u8>>, RabinError>> Iterator for LookAhead<F> {
+    type Item = Result<(Vec<u8>, bool), RabinError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let item = self.advance().transpose();
+        self.finished = !matches!(item, Some(Ok((_, false))));
+        item
+    }
+}
+
+// Runs transform over every (chunk, last) pair on `workers` threads and hands the results to
+// sink in their original order. At most `window` chunks are between the reader and the sink
+// at any time. The first error, from any stage, stops the pipeline and is returned.
+fn pipeline<I, T, S>(source: I, workers: usize, window: usize, transform: T, mut sink: S) -> Result<(), RabinError>
+where
+    I: Iterator<Item = Result<(Vec<u8>, bool), RabinError>> + Send,
+    T: Fn(u64, bool, Vec<u8>) -> Result<Vec<u8>, RabinError> + Sync,
+    S: FnMut(Vec<u8>) -> Result<(), RabinError>,
+{
+    let (credit_tx, credit_rx) = sync_channel::<()>(window);
+    let (job_tx, job_rx) = sync_channel::<(u64, bool, Vec<u8>)>(window);
+    let (done_tx, done_rx) = sync_channel::<(u64, Result<Vec<u8>, RabinError>)>(window);
+    for _ in 0..window {
+        credit_tx.send(()).unwrap();
+    }
+    let job_rx = Mutex::new(job_rx);
+    let transform = &transform;
+
+    thread::scope(|scope| {
+        let reader_done = done_tx.clone();
+        scope.spawn(move || {
+            for (index, item) in (0u64..).zip(source) {
+                if credit_rx.recv().is_err() {
+                    return;
+                }
+                match item {
+                    Ok((chunk, last)) => {
+                        if job_tx.send((index, last, chunk)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(err) => {
+                        let _ = reader_done.send((index, Err(err)));
+                        return;
+                    }
+                }
+            }
+        });
+
+        for _ in 0..workers {
+            let done_tx = done_tx.clone();
+            let job_rx = &job_rx;
+            scope.spawn(move || loop {
+                // The lock is released before the chunk is processed
+                let job = job_rx.lock().unwrap().recv();
+                let Ok((index, last, chunk)) = job else { return };
+                if done_tx.send((index, transform(index, last, chunk))).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(done_tx);
+
+        // Returning early drops the credits and results channels, which winds down the reader
+        // and the workers before the scope joins them
+        let credit_tx = credit_tx;
+        let mut pending = BTreeMap::new();
+        let mut next = 0u64;
+        for (index, result) in done_rx {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&next) {
+                sink(result?)?;
+                next += 1;
+                let _ = credit_tx.send(());
+            }
+        }
+        Ok(())
+    })
+}
+
+// Encrypts everything read from input for the recipient, writing the stream to output
+pub fn encrypt_stream<R, W>(recipient: &PublicKey, input: &mut R, output: &mut W, config: &StreamConfig) -> Result<(), RabinError>
+where
+    R: Read + Send,
+    W: Write + ?Sized,
+{
+    let mut rng = OsRng;
+    let (encrypted_key, session_key) = encapsulate(&mut rng, recipient)?;
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut nonce);
+
+    let header = StreamHeader {
+        recipient: recipient.fingerprint(),
+        encrypted_key: encrypted_key.value().clone(),
+        nonce,
+        chunk_size: config.chunk_size,
+    }
+    .to_der();
+    output.write_all(STREAM_MAGIC)?;
+    output.write_all(&(header.len() as u32).to_be_bytes())?;
+    output.write_all(&header)?;
+
+    let chunk_size = config.chunk_size;
+    let chunks = LookAhead::new(|| {
+        let mut chunk = vec![0u8; chunk_size];
+        let read = read_full(input, &mut chunk)?;
+        chunk.truncate(read);
+        Ok((read > 0).then_some(chunk))
+    });
+    let seal = |index, last, chunk: Vec<u8>| {
+        let aad = chunk_aad(&header, index, last);
+        Ok(aes256_gcm_encrypt(&session_key, &chunk_nonce(&nonce, index), &chunk, &aad))
+    };
+    pipeline(chunks, config.workers, config.window(), seal, |sealed| {
+        output.write_all(&(sealed.len() as u32).to_be_bytes())?;
+        Ok(output.write_all(&sealed)?)
+    })?;
+    Ok(output.flush()?)
+}
+
+// Decrypts a stream written by encrypt_stream. Chunks are authenticated one at a time, so on
+// failure output may already hold the chunks before the damaged one; write to a temporary
+// file and discard it on error when that matters.
+pub fn decrypt_stream<R, W>(key: &PrivateKey, input: &mut R, output: &mut W, config: &StreamConfig) -> Result<(), RabinError>
+where
+    R: Read + Send,
+    W: Write + ?Sized,
+{
+    let mut magic = [0u8; 8];
+    if read_full(input, &mut magic)? != magic.len() || &magic != STREAM_MAGIC {
+        return Err(RabinError::MalformedDer("not a Rabin stream"));
+    }
+    decrypt_stream_body(key, input, output, config)
+}
+
+// Decrypts a stream whose magic bytes the caller has already consumed
+pub fn decrypt_stream_body<R, W>(key: &PrivateKey, input: &mut R, output: &mut W, config: &StreamConfig) -> Result<(), RabinError>
+where
+    R: Read + Send,
+    W: Write + ?Sized,
+{
+    let mut length = [0u8; 4];
+    if read_full(input, &mut length)? != length.len() {
+        return Err(RabinError::MalformedDer("truncated stream header"));
+    }
+    let header_len = u32::from_be_bytes(length) as usize;
+    if header_len > MAX_HEADER_LEN {
+        return Err(RabinError::MalformedDer("stream header too long"));
+    }
+    let mut header = vec![0u8; header_len];
+    if read_full(input, &mut header)? != header_len {
+        return Err(RabinError::MalformedDer("truncated stream header"));
+    }
+    let parsed = StreamHeader::from_der(&header)?;
+
+    if !key.public_key().fingerprint().ct_eq(&parsed.recipient) {
+        return Err(RabinError::WrongRecipient);
+    }
+    let session_key = decapsulate(key, &EncapsulatedKey::new(parsed.encrypted_key.clone()))?;
+
+    let max_frame = parsed.chunk_size + TAG_LEN;
+    let frames = LookAhead::new(|| {
+        let mut length = [0u8; 4];
+        match read_full(input, &mut length)? {
+            0 => return Ok(None),
+            4 => {}
+            _ => return Err(RabinError::DecryptionFailed),
+        }
+        let frame_len = u32::from_be_bytes(length) as usize;
+        if frame_len > max_frame {
+            return Err(RabinError::DecryptionFailed);
+        }
+        let mut frame = vec![0u8; frame_len];
+        if read_full(input, &mut frame)? != frame_len {
+            return Err(RabinError::DecryptionFailed);
+        }
+        Ok(Some(frame))
+    });
+    let open = |index, last, sealed: Vec<u8>| {
+        let aad = chunk_aad(&header, index, last);
+        aes256_gcm_decrypt(&session_key, &chunk_nonce(&parsed.nonce, index), &sealed, &aad)
+    };
+    pipeline(frames, config.workers, config.window(), open, |chunk| Ok(output.write_all(&chunk)?))?;
+    Ok(output.flush()?)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+
+    fn round_trip(key: &PrivateKey, message: &[u8], config: &StreamConfig) -> Vec<u8> {
+        let mut sealed = Vec::new();
+        encrypt_stream(&key.public_key(), &mut &message[..], &mut sealed, config).unwrap();
+        let mut opened = Vec::new();
+        decrypt_stream(key, &mut &sealed[..], &mut opened, config).unwrap();
+        opened
+    }
+
+    #[test]
+    fn test_stream_round_trip() {
+        let key = PrivateKey::generate(512).allow_insecure(InsecureDemo);
+        let message: Vec<u8> = (0..10_000u32).map(|i| (i * 31) as u8).collect();
+        for (chunk_size, workers) in [(1000, 4), (999, 1), (10_000, 3), (64, 8)] {
+            let config = StreamConfig::default().with_chunk_size(chunk_size).with_workers(workers);
+            assert_eq!(round_trip(&key, &message, &config), message, "chunk size {}", chunk_size);
+        }
+        assert!(round_trip(&key, b"", &StreamConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn test_tampered_streams_fail() {
+        let key = PrivateKey::generate(512).allow_insecure(InsecureDemo);
+        let config = StreamConfig::default().with_chunk_size(100).with_workers(3);
+        let message = vec![0x42u8; 1000];
+        let mut sealed = Vec::new();
+        encrypt_stream(&key.public_key(), &mut &message[..], &mut sealed, &config).unwrap();
+
+        let frame_len = 4 + 100 + TAG_LEN;
+        let decrypt = |data: &[u8]| decrypt_stream(&key, &mut &data[..], &mut Vec::new(), &config);
+
+        let truncated = &sealed[..sealed.len() - frame_len];
+        assert_eq!(decrypt(truncated), Err(RabinError::DecryptionFailed), "Dropping the last frame");
+
+        let mut swapped = sealed.clone();
+        let first = sealed.len() - 10 * frame_len;
+        swapped[first..first + 2 * frame_len].rotate_left(frame_len);
+        assert_eq!(decrypt(&swapped), Err(RabinError::DecryptionFailed), "Reordered frames");
+
+        let mut flipped = sealed.clone();
+        let last = flipped.len() - 1;
+        flipped[last] ^= 1;
+        assert_eq!(decrypt(&flipped), Err(RabinError::DecryptionFailed), "Modified tag");
+
+        let other = PrivateKey::generate(512).allow_insecure(InsecureDemo);
+        assert_eq!(
+            decrypt_stream(&other, &mut &sealed[..], &mut Vec::new(), &config),
+            Err(RabinError::WrongRecipient)
+        );
+        assert!(decrypt(b"RABINENV").is_err());
+    }
+
+    #[test]
+    fn test_read_errors_stop_the_pipeline() {
+        struct Failing(usize);
+        impl Read for Failing {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0 == 0 {
+                    return Err(std::io::Error::other("disk on fire"));
+                }
+                let read = buf.len().min(self.0);
+                self.0 -= read;
+                Ok(read)
+            }
+        }
+
+        let key = PrivateKey::generate(512).allow_insecure(InsecureDemo);
+        let config = StreamConfig::default().with_chunk_size(16).with_workers(2);
+        let mut sealed = Vec::new();
+        let result = encrypt_stream(&key.public_key(), &mut Failing(1000), &mut sealed, &config);
+        assert_eq!(result, Err(RabinError::Io("disk on fire".to_string())));
+    }
+}
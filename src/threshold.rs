@@ -0,0 +1,159 @@
+// Threshold decryption: the private key is dealt out as additive shares of a decryption
+// exponent, each party raises the ciphertext to its share, and a combiner multiplies the
+// partial results. No party (including the combiner) ever sees p, q, or the full exponent.
+//
+// Simply giving p to one party and q to another does not work for Rabin: n is public, so the
+// holder of p can compute q = n / p and decrypt alone. Instead we use that for a Blum integer
+// n = p * q the principal square root of a quadratic residue c is c^d mod n with
+// d = (phi(n) / 4 + 1) / 2. Only the principal root (and its negation) is ever produced, which
+// also matters for safety: handing out two "different" roots of the same c reveals the factors.
+
+use crate::error::RabinError;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::math::jacobi;
+use crate::metadata::KeyUsage;
+use crate::montgomery::modpow;
+use crate::secret::{Secret, Wipe};
+use num_bigint::{BigInt, RandBigInt};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use rand::rngs::OsRng;
+
+// Messages are shifted left by one byte; the low byte is a counter chosen so the encoded value
+// has Jacobi symbol +1, which makes the principal root the message itself or its negation
+const COUNTER_BITS: usize = 8;
+// Extra bits on the random exponent shares so they statistically hide the real exponent
+const SHARE_SLACK_BITS: u64 = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecryptionShare {
+    n: BigInt,
+    exponent: BigInt,
+}
+
+impl Drop for DecryptionShare {
+    fn drop(&mut self) {
+        self.exponent.wipe();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialDecryption(BigInt);
+
+// Trusted dealer: splits the decryption exponent into `parties` additive shares.
+// The dealer must discard the private key afterwards for the scheme to mean anything.
+pub fn deal_shares(key: &PrivateKey, parties: usize) -> Result<Vec<DecryptionShare>, RabinError> {
+    if parties < 2 {
+        return Err(RabinError::InvalidShares("threshold decryption needs at least two parties"));
+    }
+    let four = BigInt::from(4);
+    if key.p().mod_floor(&four) != BigInt::from(3) || key.q().mod_floor(&four) != BigInt::from(3) {
+        return Err(RabinError::InvalidKey("threshold decryption requires p ≡ q ≡ 3 (mod 4)"));
+    }
+
+    let order: Secret<BigInt> = Secret::new((key.p() - 1) * (key.q() - 1) / &four);
+    let exponent: Secret<BigInt> = Secret::new((&*order + 1) / 2);
+
+    // Quadratic residues satisfy c^order = 1, so shares only need to sum to d modulo order
+    let mut rng = OsRng;
+    let bound = BigInt::one() << (key.n().bits() + SHARE_SLACK_BITS);
+    let mut shares = Vec::with_capacity(parties);
+    let mut sum = Secret::new(BigInt::zero());
+    for _ in 0..parties - 1 {
+        let share = rng.gen_bigint_range(&BigInt::zero(), &bound);
+        *sum += &share;
+        shares.push(DecryptionShare {
+            n: key.n().clone(),
+            exponent: share,
+        });
+    }
+    shares.push(DecryptionShare {
+        n: key.n().clone(),
+        exponent: (&*exponent - &*sum).mod_floor(&order),
+    });
+    Ok(shares)
+}
+
+// Encrypts a message in the form the combiner can disambiguate: m must be below n / 512
+pub fn encrypt(key: &PublicKey, message: &BigInt) -> Result<BigInt, RabinError> {
+    key.check_usage(KeyUsage::Encrypt)?;
+    let n = key.n();
+    if message.sign() == num_bigint::Sign::Minus {
+        return Err(RabinError::MessageOutOfRange);
+    }
+    let half = n >> 1;
+    for counter in 0..(1u32 << COUNTER_BITS) {
+        let encoded = (message << COUNTER_BITS) + counter;
+        if encoded >= half {
+            return Err(RabinError::MessageOutOfRange);
+        }
+        if jacobi(&encoded, n) == 1 {
+            return Ok(&encoded * &encoded % n);
+        }
+    }
+    // Each counter has roughly even odds, so running out means n is not a Blum integer
+    Err(RabinError::MessageOutOfRange)
+}
+
+impl DecryptionShare {
+    pub fn partial_decrypt(&self, ciphertext: &BigInt) -> Result<PartialDecryption, RabinError> {
+        if ciphertext.sign() == num_bigint::Sign::Minus || ciphertext >= &self.n {
+            return Err(RabinError::MessageOutOfRange);
+        }
+        Ok(PartialDecryption(modpow(ciphertext, &self.exponent, &self.n)))
+    }
+}
+
+pub fn combine(
+    key: &PublicKey,
+    ciphertext: &BigInt,
+    partials: &[PartialDecryption],
+) -> Result<BigInt, RabinError> {
+    let n = key.n();
+    let root = partials
+        .iter()
+        .fold(BigInt::one(), |acc, partial| acc * &partial.0 % n);
+
+    // A missing or corrupted partial yields a value that does not square to the ciphertext
+    if &root * &root % n != *ciphertext {
+        return Err(RabinError::InvalidShares("partial decryptions do not combine to a square root"));
+    }
+
+    // The message was encoded below n / 2, so exactly one of ±root qualifies
+    let encoded = if root <= (n >> 1) { root } else { n - root };
+    Ok(encoded >> COUNTER_BITS)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::InsecureDemo;
+
+    #[test]
+    fn test_threshold_round_trip() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let public = key.public_key();
+        let shares = deal_shares(&key, 3).unwrap();
+
+        let message = BigInt::from(123456789u64);
+        let ciphertext = encrypt(&public, &message).unwrap();
+        let partials: Vec<_> = shares
+            .iter()
+            .map(|share| share.partial_decrypt(&ciphertext).unwrap())
+            .collect();
+
+        assert_eq!(combine(&public, &ciphertext, &partials).unwrap(), message);
+    }
+
+    #[test]
+    fn test_threshold_needs_every_partial() {
+        let key = PrivateKey::generate(256).allow_insecure(InsecureDemo);
+        let public = key.public_key();
+        let shares = deal_shares(&key, 2).unwrap();
+
+        let ciphertext = encrypt(&public, &BigInt::from(42)).unwrap();
+        let partial = shares[0].partial_decrypt(&ciphertext).unwrap();
+        assert!(combine(&public, &ciphertext, &[partial]).is_err());
+    }
+}
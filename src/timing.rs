@@ -0,0 +1,186 @@
+// `rabin bench --timing`: a dudect-style check that decryption time does not depend on the
+// data being decrypted (Reparaz, Balasch and Verbauwhede, "Dude, is my code constant time?",
+// 2017). Each target runs on inputs from two classes, interleaved in random order: one fixed
+// input, and fresh random ones. If the running time is independent of the input, both classes
+// have the same distribution of times and Welch's t-test finds no difference between their
+// means; |t| above T_THRESHOLD is reported as a leak. As in dudect, the test is repeated on the
+// measurements below a few percentiles, since a leak often only shows once the slow outliers
+// (interrupts, migrations) are cut off.
+//
+// Passing is evidence, not proof: a leak smaller than the noise, or one the inputs never
+// trigger, goes unnoticed. Run it from a release build on an otherwise idle machine.
+
+use crate::cli::CliResult;
+use naive_rabin_cryptosystem::keys::PrivateKey;
+use naive_rabin_cryptosystem::rabin::{decrypt_tagged, encrypt, encrypt_tagged, TAG_LEN};
+use num_bigint::{BigInt, RandBigInt};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use std::hint::black_box;
+use std::time::Instant;
+
+// dudect's threshold for "definitely not constant time"
+const T_THRESHOLD: f64 = 4.5;
+// Measurements above these percentiles are dropped for the cropped tests
+const CROP_PERCENTILES: [f64; 3] = [0.5, 0.75, 0.9];
+const SEED: [u8; 32] = *b"rabin timing harness fixed seed!";
+
+// Running mean and variance of one class (Welford's algorithm)
+#[derive(Debug, Clone, Copy, Default)]
+struct Moments {
+    count: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Moments {
+    fn push(&mut self, value: f64) {
+        self.count += 1.0;
+        let delta = value - self.mean;
+        self.mean += delta / self.count;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    fn variance(&self) -> f64 {
+        if self.count < 2.0 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1.0)
+        }
+    }
+}
+
+// Welch's t statistic for the difference between the two class means
+fn welch_t(fixed: &Moments, random: &Moments) -> f64 {
+    let error = (fixed.variance() / fixed.count + random.variance() / random.count).sqrt();
+    if error > 0.0 {
+        (fixed.mean - random.mean) / error
+    } else {
+        0.0
+    }
+}
+
+// The largest |t| over the uncropped samples and each crop; samples are (is_fixed, nanoseconds)
+fn max_t(samples: &[(bool, f64)]) -> f64 {
+    let mut times: Vec<f64> = samples.iter().map(|(_, ns)| *ns).collect();
+    times.sort_by(f64::total_cmp);
+    let mut limits = vec![f64::INFINITY];
+    limits.extend(CROP_PERCENTILES.iter().map(|p| times[((times.len() - 1) as f64 * p) as usize]));
+
+    limits
+        .iter()
+        .map(|limit| {
+            let (mut fixed, mut random) = (Moments::default(), Moments::default());
+            for (is_fixed, ns) in samples.iter().filter(|(_, ns)| ns <= limit) {
+                if *is_fixed {
+                    fixed.push(*ns);
+                } else {
+                    random.push(*ns);
+                }
+            }
+            welch_t(&fixed, &random).abs()
+        })
+        .fold(0.0, f64::max)
+}
+
+struct Target {
+    name: &'static str,
+    fixed: BigInt,
+    random: Box<dyn FnMut(&mut ChaCha20Rng) -> BigInt>,
+    run: Box<dyn Fn(&BigInt)>,
+}
+
+fn targets() -> Vec<Target> {
+    let key = PrivateKey::from_seed(&SEED, 1024);
+    let (n, p, q) = (key.n().clone(), key.p().clone(), key.q().clone());
+    let mut rng = ChaCha20Rng::from_seed(SEED);
+    // Full-size inputs in both classes, so that only their values differ
+    let full_size = |rng: &mut ChaCha20Rng, n: &BigInt| rng.gen_bigint_range(&(n >> 8u8), n);
+
+    let random_n = n.clone();
+    let message_limit: BigInt = &n >> (8 * (TAG_LEN + 1));
+    let tagged_n = n.clone();
+    vec![
+        // Taking the square roots: exponentiation and CRT on the ciphertext
+        Target {
+            name: "decrypt-2048",
+            fixed: encrypt(&full_size(&mut rng, &n), &n).unwrap(),
+            random: Box::new(move |rng| encrypt(&full_size(rng, &random_n), &random_n).unwrap()),
+            run: Box::new(move |ciphertext| {
+                black_box(key.decrypt(ciphertext).unwrap());
+            }),
+        },
+        // Strict decryption also checks four tags and picks the valid root, whose position
+        // among the candidates differs from one random message to the next
+        Target {
+            name: "decrypt-tagged-2048",
+            fixed: encrypt_tagged(&rng.gen_bigint_range(&BigInt::from(0), &message_limit), &n).unwrap(),
+            random: Box::new(move |rng| {
+                let message = rng.gen_bigint_range(&BigInt::from(0), &message_limit);
+                encrypt_tagged(&message, &tagged_n).unwrap()
+            }),
+            run: Box::new(move |ciphertext| {
+                black_box(decrypt_tagged(ciphertext, &p, &q).unwrap());
+            }),
+        },
+    ]
+}
+
+fn measure(target: &mut Target, samples: usize, rng: &mut ChaCha20Rng) -> Vec<(bool, f64)> {
+    (0..samples)
+        .map(|_| {
+            // The input is prepared outside the timed region
+            let is_fixed = rng.gen::<bool>();
+            let input = if is_fixed { target.fixed.clone() } else { (target.random)(rng) };
+            let started = Instant::now();
+            (target.run)(&input);
+            (is_fixed, started.elapsed().as_nanos() as f64)
+        })
+        .collect()
+}
+
+pub fn run_timing(samples: usize) -> CliResult {
+    if cfg!(debug_assertions) {
+        println!("note: this is a debug build; timings from a release build are more meaningful");
+    }
+    println!("{:<22} {:>8} {:>8}  verdict", "target", "samples", "max |t|");
+    let mut rng = ChaCha20Rng::from_seed(SEED);
+    let mut leaks = Vec::new();
+    for mut target in targets() {
+        let t = max_t(&measure(&mut target, samples, &mut rng));
+        let verdict = if t > T_THRESHOLD {
+            leaks.push(target.name);
+            "leak: timing depends on the input"
+        } else {
+            "no leak detected"
+        };
+        println!("{:<22} {:>8} {:>8.2}  {}", target.name, samples, t, verdict);
+    }
+    if !leaks.is_empty() {
+        return Err(format!("{} failed the timing test (|t| > {})", leaks.join(", "), T_THRESHOLD).into());
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_welch_t_separates_shifted_classes() {
+        let mut rng = ChaCha20Rng::from_seed(SEED);
+        let noise = |rng: &mut ChaCha20Rng| rng.gen_range(0.0..10.0);
+        let same: Vec<(bool, f64)> = (0..4000).map(|i| (i % 2 == 0, 100.0 + noise(&mut rng))).collect();
+        assert!(max_t(&same) < T_THRESHOLD, "Identical distributions pass");
+
+        let shifted: Vec<(bool, f64)> =
+            (0..4000).map(|i| (i % 2 == 0, 100.0 + noise(&mut rng) + if i % 2 == 0 { 2.0 } else { 0.0 })).collect();
+        assert!(max_t(&shifted) > T_THRESHOLD, "A shift of a fifth of the noise is caught");
+
+        let mut moments = Moments::default();
+        [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0].iter().for_each(|value| moments.push(*value));
+        assert_eq!(moments.mean, 5.0);
+        assert!((moments.variance() - 32.0 / 7.0).abs() < 1e-12);
+    }
+}
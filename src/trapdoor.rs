@@ -0,0 +1,102 @@
+// The Rabin trapdoor permutation. For a Blum integer n (p ≡ q ≡ 3 mod 4) squaring is a
+// permutation of the quadratic residues modulo n: of the four square roots of a residue,
+// exactly one, the principal root, is itself a residue. Anyone can evaluate x -> x^2; inverting
+// it is as hard as factoring n, and easy with p and q.
+//
+// This is the primitive under Blum-Blum-Shub, Blum-Goldwasser and Rabin signatures, stripped of
+// any encoding. Only the Jacobi symbol of an input can be checked publicly, so eval accepts
+// every unit with Jacobi symbol +1; half of those are pseudosquares outside the domain, and
+// the caller must produce real residues (for example by squaring a random unit).
+
+use crate::error::RabinError;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::math::{crt, jacobi};
+use crate::montgomery::modpow;
+use crate::residue::is_quadratic_residue_mod_n;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Zero};
+
+fn check_blum(key: &PrivateKey) -> Result<(), RabinError> {
+    let four = BigInt::from(4);
+    let three = BigInt::from(3);
+    if key.p().mod_floor(&four) != three || key.q().mod_floor(&four) != three {
+        return Err(RabinError::InvalidKey("the trapdoor permutation needs p ≡ q ≡ 3 (mod 4)"));
+    }
+    Ok(())
+}
+
+// x^2 mod n, for x in the domain as far as the public key can tell
+pub fn eval(key: &PublicKey, x: &BigInt) -> Result<BigInt, RabinError> {
+    let n = key.n();
+    if *x <= BigInt::zero() || x >= n || !x.gcd(n).is_one() || jacobi(x, n) != 1 {
+        return Err(RabinError::MessageOutOfRange);
+    }
+    Ok(x * x % n)
+}
+
+// The principal square root of y: the one root that is itself a quadratic residue
+pub fn invert(key: &PrivateKey, y: &BigInt) -> Result<BigInt, RabinError> {
+    check_blum(key)?;
+    let n = key.n();
+    if *y <= BigInt::zero() || y >= n {
+        return Err(RabinError::MessageOutOfRange);
+    }
+    if !is_quadratic_residue_mod_n(y, key) {
+        return Err(RabinError::NotQuadraticResidue);
+    }
+    // y^((p + 1) / 4) squares to y, and as a power of a residue it is a residue itself
+    let (p, q) = (key.p(), key.q());
+    let root_p = modpow(y, &((p + 1) >> 2), p);
+    let root_q = modpow(y, &((q + 1) >> 2), q);
+    crt(&[root_p, root_q], &[p.clone(), q.clone()])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::residue::random_residue;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_invert_undoes_eval() {
+        let key = PrivateKey::generate(256);
+        let public = key.public_key();
+        let mut rng = thread_rng();
+        for _ in 0..10 {
+            let x = random_residue(&mut rng, key.n());
+            let y = eval(&public, &x).unwrap();
+            assert_eq!(invert(&key, &y).unwrap(), x, "The principal root should be the residue we squared");
+            assert_eq!(eval(&public, &invert(&key, &x).unwrap()).unwrap(), x);
+        }
+    }
+
+    #[test]
+    fn test_permutation_of_small_residues() {
+        // n = 77: the 15 residues are permuted by squaring
+        let key = PrivateKey::from_primes(BigInt::from(7), BigInt::from(11)).unwrap();
+        let public = key.public_key();
+        let residues: Vec<BigInt> = (1..77)
+            .map(BigInt::from)
+            .filter(|x| is_quadratic_residue_mod_n(x, &key))
+            .collect();
+        assert_eq!(residues.len(), 15);
+        let mut images: Vec<BigInt> = residues.iter().map(|x| eval(&public, x).unwrap()).collect();
+        images.sort();
+        assert_eq!(images, residues);
+    }
+
+    #[test]
+    fn test_rejects_values_outside_the_domain() {
+        let key = PrivateKey::from_primes(BigInt::from(7), BigInt::from(11)).unwrap();
+        let public = key.public_key();
+        assert_eq!(eval(&public, &BigInt::from(7)), Err(RabinError::MessageOutOfRange), "Not a unit");
+        assert_eq!(eval(&public, &BigInt::from(77)), Err(RabinError::MessageOutOfRange));
+        // -1 is a pseudosquare modulo a Blum integer
+        assert_eq!(invert(&key, &BigInt::from(76)), Err(RabinError::NotQuadraticResidue));
+
+        let not_blum = PrivateKey::from_primes(BigInt::from(13), BigInt::from(17)).unwrap();
+        assert!(matches!(invert(&not_blum, &BigInt::from(4)), Err(RabinError::InvalidKey(_))));
+    }
+}
@@ -0,0 +1,149 @@
+// Consistency checks for private keys loaded from outside (files, exercises, other tools).
+// Every failed check is reported, so a broken key can be diagnosed in one go.
+
+use crate::keygen::PrimeCongruence;
+use crate::keys::PrivateKey;
+use crate::primality::{is_probable_prime_int, PrimalityConfig};
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyViolation {
+    PNotPrime,
+    QNotPrime,
+    // The fast (p + 1) / 4 square root needs p ≡ q ≡ 3 (mod 4); see validate_with
+    PNotThreeModFour,
+    QNotThreeModFour,
+    // p = q makes n a perfect square, which anyone can factor
+    EqualPrimes,
+    ModulusMismatch,
+    BitLengthMismatch { expected: usize, p_bits: u64, q_bits: u64 },
+}
+
+impl fmt::Display for KeyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyViolation::PNotPrime => write!(f, "p is not prime"),
+            KeyViolation::QNotPrime => write!(f, "q is not prime"),
+            KeyViolation::PNotThreeModFour => write!(f, "p is not congruent to 3 mod 4"),
+            KeyViolation::QNotThreeModFour => write!(f, "q is not congruent to 3 mod 4"),
+            KeyViolation::EqualPrimes => write!(f, "p and q are equal"),
+            KeyViolation::ModulusMismatch => write!(f, "n does not equal p * q"),
+            KeyViolation::BitLengthMismatch { expected, p_bits, q_bits } => write!(
+                f,
+                "expected {}-bit primes, found p with {} bits and q with {} bits",
+                expected, p_bits, q_bits
+            ),
+        }
+    }
+}
+
+fn is_three_mod_four(value: &BigInt) -> bool {
+    value.mod_floor(&BigInt::from(4)) == BigInt::from(3)
+}
+
+impl PrivateKey {
+    // Returns every violated property; an empty list means the key is sound
+    pub fn validate(&self) -> Vec<KeyViolation> {
+        self.validate_with(PrimeCongruence::ThreeModFour)
+    }
+
+    // Like validate, for keys whose primes may have any odd congruence
+    pub fn validate_with(&self, congruence: PrimeCongruence) -> Vec<KeyViolation> {
+        let mut violations = Vec::new();
+        if !is_probable_prime_int(self.p(), &PrimalityConfig::strict()) {
+            violations.push(KeyViolation::PNotPrime);
+        }
+        if !is_probable_prime_int(self.q(), &PrimalityConfig::strict()) {
+            violations.push(KeyViolation::QNotPrime);
+        }
+        if congruence == PrimeCongruence::ThreeModFour {
+            if !is_three_mod_four(self.p()) {
+                violations.push(KeyViolation::PNotThreeModFour);
+            }
+            if !is_three_mod_four(self.q()) {
+                violations.push(KeyViolation::QNotThreeModFour);
+            }
+        }
+        if self.p() == self.q() {
+            violations.push(KeyViolation::EqualPrimes);
+        }
+        if self.p() * self.q() != *self.n() || self.n().sign() != Sign::Plus {
+            violations.push(KeyViolation::ModulusMismatch);
+        }
+        if let Some(bits) = self.metadata().and_then(|metadata| metadata.bits) {
+            self.check_bit_size(bits, &mut violations);
+        }
+        violations
+    }
+
+    // Like validate, and also checks that both primes have the requested size
+    pub fn validate_bit_size(&self, bit_size: usize) -> Vec<KeyViolation> {
+        let mut violations = self.validate();
+        if !violations.iter().any(|v| matches!(v, KeyViolation::BitLengthMismatch { .. })) {
+            self.check_bit_size(bit_size, &mut violations);
+        }
+        violations
+    }
+
+    fn check_bit_size(&self, bit_size: usize, violations: &mut Vec<KeyViolation>) {
+        let (p_bits, q_bits) = (self.p().bits(), self.q().bits());
+        if p_bits != bit_size as u64 || q_bits != bit_size as u64 {
+            violations.push(KeyViolation::BitLengthMismatch {
+                expected: bit_size,
+                p_bits,
+                q_bits,
+            });
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // from_primes does not test primality, which lets the tests build deliberately bad keys
+    fn key_from_parts(p: u64, q: u64) -> PrivateKey {
+        PrivateKey::from_primes(BigInt::from(p), BigInt::from(q)).unwrap()
+    }
+
+    #[test]
+    fn test_generated_key_is_valid() {
+        let key = PrivateKey::generate(256);
+        assert_eq!(key.validate_bit_size(256), vec![], "A fresh key should pass every check");
+    }
+
+    #[test]
+    fn test_validate_reports_every_violation() {
+        // 15 is composite and 13 ≡ 1 (mod 4)
+        assert_eq!(
+            key_from_parts(15, 13).validate(),
+            vec![KeyViolation::PNotPrime, KeyViolation::QNotThreeModFour]
+        );
+        assert_eq!(key_from_parts(7, 7).validate(), vec![KeyViolation::EqualPrimes]);
+        assert_eq!(key_from_parts(7, 11).validate(), vec![]);
+    }
+
+    #[test]
+    fn test_exercise_key_is_valid() {
+        let p = BigInt::parse_bytes(b"5081134225938911632501879835073274182691064608067531203259", 10).unwrap();
+        let q = BigInt::parse_bytes(b"5258660163169151701715131756224662568205137498312501937487", 10).unwrap();
+        let key = PrivateKey::from_modulus_and_prime(&p * &q, p).unwrap();
+        assert_eq!(key.q(), &q);
+        assert_eq!(key.validate(), vec![]);
+    }
+
+    #[test]
+    fn test_validate_bit_size() {
+        assert_eq!(
+            key_from_parts(7, 11).validate_bit_size(3),
+            vec![KeyViolation::BitLengthMismatch {
+                expected: 3,
+                p_bits: 3,
+                q_bits: 4
+            }]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+// Browser bindings for teaching pages, built with the wasm feature:
+//
+//     wasm-pack build --target web --features wasm
+//
+// Keys cross the boundary as PEM strings (PKCS#8 for private keys, as in the CLI) and numbers
+// as big-endian Uint8Arrays, so page scripts need no bignum library: encode turns text into a
+// number, encrypt and decrypt work on numbers, and decode turns the result back into text.
+// Errors are thrown as JS Errors carrying the RabinError message.
+//
+// Encryption is tagged and decryption strict, as in the CLI. Demo-sized keys are refused until
+// the page calls allowInsecureDemo(), the counterpart of the CLI's --insecure-demo.
+//
+// Key generation goes through PrivateKey::generate rather than KeygenConfig: the latter times
+// itself and stamps metadata with the current time, and std has no clock on
+// wasm32-unknown-unknown. Randomness comes from crypto.getRandomValues through getrandom.
+
+use crate::bindings::{alphabet, OptIn};
+use crate::encoding::{bytes2num, modulus_len, num2bytes, num2str, str2num, to_fixed_be_bytes, ByteOrder};
+use crate::error::RabinError;
+use crate::keygen::KeygenConfig;
+use crate::keys::PrivateKey;
+use crate::metadata::KeyUsage;
+use crate::rabin::{decrypt_tagged, encrypt_tagged};
+use wasm_bindgen::prelude::*;
+
+static INSECURE_DEMO: OptIn = OptIn::new();
+
+// Lets demo-sized keys be generated and used for the rest of the page's life
+#[wasm_bindgen(js_name = allowInsecureDemo)]
+pub fn allow_insecure_demo() {
+    INSECURE_DEMO.allow();
+}
+
+// Generation runs on the calling thread, so a full-size key blocks the page for a while; call
+// it from a Web Worker to keep the page responsive
+#[wasm_bindgen]
+pub fn keygen(bits: usize) -> Result<String, JsError> {
+    if KeygenConfig::new(bits).is_demo() && INSECURE_DEMO.get().is_none() {
+        return Err(RabinError::InsecureKey(2 * bits as u64).into());
+    }
+    Ok(PrivateKey::generate(bits).to_pkcs8_pem())
+}
+
+#[wasm_bindgen(js_name = publicKey)]
+pub fn public_key(private_pem: &str) -> Result<String, JsError> {
+    Ok(INSECURE_DEMO.load_private(private_pem)?.public_key().to_pem())
+}
+
+// Leading zero bytes are kept, so every ciphertext under one key has the same length
+#[wasm_bindgen]
+pub fn encrypt(public_pem: &str, message: &[u8]) -> Result<Vec<u8>, JsError> {
+    let key = INSECURE_DEMO.load_public(public_pem)?;
+    key.check_usage(KeyUsage::Encrypt)?;
+    let ciphertext = encrypt_tagged(&bytes2num(message, ByteOrder::BigEndian), key.n())?;
+    Ok(to_fixed_be_bytes(&ciphertext, modulus_len(key.n())).expect("the ciphertext is below n"))
+}
+
+#[wasm_bindgen]
+pub fn decrypt(private_pem: &str, ciphertext: &[u8]) -> Result<Vec<u8>, JsError> {
+    let key = INSECURE_DEMO.load_private(private_pem)?;
+    key.check_usage(KeyUsage::Encrypt)?;
+    let message = decrypt_tagged(&bytes2num(ciphertext, ByteOrder::BigEndian), key.p(), key.q())?;
+    Ok(num2bytes(&message, ByteOrder::BigEndian, None).expect("the message is not negative"))
+}
+
+// alphabetDigits is a string of symbols in digit order; pass undefined for the default set.
+// decode must be given the same one.
+#[wasm_bindgen]
+pub fn encode(text: &str, alphabet_digits: Option<String>) -> Result<Vec<u8>, JsError> {
+    let number = str2num(text, &alphabet(alphabet_digits)?)?;
+    Ok(num2bytes(&number, ByteOrder::BigEndian, None).expect("encoded text is not negative"))
+}
+
+#[wasm_bindgen]
+pub fn decode(number: &[u8], alphabet_digits: Option<String>) -> Result<String, JsError> {
+    Ok(num2str(&bytes2num(number, ByteOrder::BigEndian), &alphabet(alphabet_digits)?)?)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Only the success paths run natively: building a JsError needs a JS host
+    #[test]
+    fn test_browser_round_trip() {
+        allow_insecure_demo();
+        let private_pem = keygen(256).unwrap();
+        let public_pem = public_key(&private_pem).unwrap();
+
+        let message = encode("Hello, browser", None).unwrap();
+        let ciphertext = encrypt(&public_pem, &message).unwrap();
+        assert_eq!(ciphertext.len(), modulus_len(INSECURE_DEMO.load_public(&public_pem).unwrap().n()));
+        assert_eq!(decode(&decrypt(&private_pem, &ciphertext).unwrap(), None).unwrap(), "Hello, browser");
+    }
+}
@@ -0,0 +1,55 @@
+// Allocation count of one decryption. This lives in its own test binary because it installs a
+// counting global allocator, which would otherwise sit under every library unit test.
+
+use naive_rabin_cryptosystem::keys::PrivateKey;
+use naive_rabin_cryptosystem::rabin::{compute_candidates_with, encrypt, DecryptionParams};
+use num_bigint::BigInt;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+// Counts the allocations made by each thread
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[test]
+fn test_candidate_allocations() {
+    let key = PrivateKey::generate(512);
+    let (p, q, n) = (key.p(), key.q(), key.n());
+    let params = DecryptionParams::new(p, q).unwrap();
+    let ciphertext = encrypt(&BigInt::from(0xC0FFEEu32), n).unwrap();
+    // On a one-thread pool both halves of the join run on the thread doing the counting
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let allocations = pool.install(|| {
+        let before = ALLOCATIONS.with(Cell::get);
+        compute_candidates_with(&ciphertext, p, q, n, &params).unwrap();
+        ALLOCATIONS.with(Cell::get) - before
+    });
+    // The bound keeps per-term and per-entry allocations from creeping back into decryption
+    assert!(allocations <= 72, "compute_candidates_with made {} allocations", allocations);
+}